@@ -0,0 +1,14 @@
+-- Match history. Accounts are referenced without foreign keys so history
+-- survives account deletion.
+CREATE TABLE matches (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    outcome TEXT NOT NULL CHECK (outcome IN ('finished', 'aborted')),
+    winner_id INTEGER,
+    ended_at DATETIME NOT NULL
+);
+
+CREATE TABLE match_players (
+    match_id INTEGER NOT NULL REFERENCES matches(id) ON DELETE CASCADE,
+    account_id INTEGER NOT NULL,
+    PRIMARY KEY (match_id, account_id)
+);
@@ -0,0 +1,3 @@
+-- Operators allowed to use the /api/admin routes. Granted with
+-- `server --grant-admin <username>`.
+ALTER TABLE accounts ADD COLUMN is_admin BOOLEAN NOT NULL DEFAULT FALSE;
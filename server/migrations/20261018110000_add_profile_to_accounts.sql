@@ -0,0 +1,5 @@
+-- Public profile shown to other players. `avatar` indexes the client's
+-- built-in avatar set; `favourite_family` is a `Family` name.
+ALTER TABLE accounts ADD COLUMN display_name TEXT;
+ALTER TABLE accounts ADD COLUMN avatar INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE accounts ADD COLUMN favourite_family TEXT;
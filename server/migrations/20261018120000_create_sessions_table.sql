@@ -0,0 +1,17 @@
+-- One row per signed-in device. `id` is the `sid` claim of that device's
+-- access tokens; the refresh token itself is only stored as a SHA-256 hash.
+CREATE TABLE sessions (
+    id TEXT PRIMARY KEY NOT NULL,
+    account_id INTEGER NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    refresh_token_hash TEXT NOT NULL,
+    device TEXT,
+    created_at DATETIME NOT NULL,
+    last_used_at DATETIME NOT NULL,
+    expires_at DATETIME NOT NULL
+);
+
+CREATE INDEX sessions_account_id ON sessions (account_id);
+
+-- Replaced by `sessions`; everyone signs in again once.
+ALTER TABLE accounts DROP COLUMN session_id;
+ALTER TABLE accounts DROP COLUMN session_expires_at;
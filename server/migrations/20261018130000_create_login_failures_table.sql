@@ -0,0 +1,11 @@
+-- Audit trail of failed logins. `username` is whatever was typed, so it may
+-- not name an account; `ip` is NULL when the peer address is unknown.
+CREATE TABLE login_failures (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    username TEXT NOT NULL,
+    ip TEXT,
+    reason TEXT NOT NULL CHECK (reason IN ('unknown_user', 'bad_password', 'throttled')),
+    attempted_at DATETIME NOT NULL
+);
+
+CREATE INDEX login_failures_username ON login_failures (username, attempted_at);
@@ -0,0 +1,3 @@
+-- Accounts created by `POST /api/auth/guest`. They have no usable password
+-- until upgraded, and are purged at startup once no session remains.
+ALTER TABLE accounts ADD COLUMN is_guest BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,12 @@
+-- One row per friend request, from `requester_id` to `addressee_id`. It is
+-- pending until the addressee accepts; an accepted row is the friendship.
+-- At most one row exists per pair, in whichever direction it was first sent.
+CREATE TABLE friendships (
+    requester_id INTEGER NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    addressee_id INTEGER NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    accepted BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY (requester_id, addressee_id)
+);
+
+CREATE INDEX friendships_addressee_id ON friendships (addressee_id);
@@ -0,0 +1,3 @@
+-- Each player's post-game `MatchSummary` line as JSON. NULL for matches
+-- recorded before stats were kept.
+ALTER TABLE match_players ADD COLUMN stats TEXT;
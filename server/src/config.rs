@@ -0,0 +1,550 @@
+use crate::handler::rate_limit::RateLimitConfig;
+use serde::Deserialize;
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+/// Path of the optional TOML file, read before env overrides are applied.
+pub const CONFIG_PATH_VAR: &str = "NICKTD_CONFIG";
+
+/// Only ever used in development; production refuses to start without a
+/// real secret.
+const DEV_JWT_SECRET: &str = "nicktd-development-secret";
+
+/// HS256 keys shorter than this are trivially brute-forced offline.
+const MIN_JWT_SECRET_BYTES: usize = 32;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Environment {
+    #[default]
+    Development,
+    Production,
+}
+
+/// What the game loop does when it falls behind the tick clock. Both keep a
+/// fixed `dt` per tick, so the simulation stays deterministic either way.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissedTickPolicy {
+    /// Drop missed ticks: the match runs slower than wall time under load.
+    #[default]
+    Skip,
+    /// Run missed ticks back to back, up to `max_catch_up_ticks` behind.
+    CatchUp,
+}
+
+/// What happens when an account opens a game socket while another device
+/// already has one. There is no read-only spectator view of a match yet, so a
+/// second device either takes over or is turned away.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecondDevicePolicy {
+    /// The new socket wins; the old one is closed with `SessionReplaced`.
+    #[default]
+    Replace,
+    /// The new socket is refused with `AlreadyConnected` while the old one lives.
+    Reject,
+}
+
+/// Everything that used to be hard-coded in `server`, `database` and `jwt`.
+/// Sources, lowest to highest precedence: `Config::default()`, the TOML file
+/// named by `NICKTD_CONFIG`, then individual `NICKTD_*` env vars.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub environment: Environment,
+    pub bind_addr: SocketAddr,
+    pub database_path: PathBuf,
+    pub db_pool_size: u32,
+    pub jwt_secret: Option<String>,
+    /// How long a device stays signed in without refreshing. Each refresh
+    /// pushes the deadline back by this much.
+    pub session_lifetime_hours: i64,
+    /// Lifetime of the access JWTs handed out by login and refresh.
+    pub access_token_minutes: i64,
+    pub second_device: SecondDevicePolicy,
+    /// Origins allowed by CORS. Empty or `["*"]` allows any origin.
+    pub cors_origins: Vec<String>,
+    /// Simulation ticks per second for every match.
+    pub tick_rate: f32,
+    pub missed_tick_policy: MissedTickPolicy,
+    /// Under `CatchUp`, a loop further behind than this many ticks drops the
+    /// backlog instead of bursting through it.
+    pub max_catch_up_ticks: u32,
+    /// Simulation threads matches are spread over; 0 means one per core.
+    pub sim_shards: usize,
+    /// How long a shutdown waits for running matches to finish before
+    /// recording them as aborted.
+    pub shutdown_grace_secs: u64,
+    /// How long both players of a queue pairing have to accept it.
+    pub ready_check_secs: u64,
+    /// How long a player who declines or ignores a ready check is kept out
+    /// of the queue.
+    pub decline_penalty_secs: u64,
+    /// Longest the first Build timer waits for both players to load and pick
+    /// a family before starting anyway.
+    pub setup_timeout_secs: u64,
+    /// Mask profanity in in-match chat.
+    pub chat_filter: bool,
+    /// Per-connection message rate and size limits.
+    pub rate_limits: RateLimitConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            environment: Environment::Development,
+            bind_addr: SocketAddr::from(([0, 0, 0, 0], 9001)),
+            database_path: PathBuf::from("data/nicktd.db"),
+            db_pool_size: 5,
+            jwt_secret: None,
+            session_lifetime_hours: 24,
+            access_token_minutes: 15,
+            second_device: SecondDevicePolicy::Replace,
+            cors_origins: vec!["*".to_string()],
+            tick_rate: crate::handler::game_loop::TICK_RATE,
+            missed_tick_policy: MissedTickPolicy::Skip,
+            max_catch_up_ticks: 5,
+            sim_shards: 0,
+            shutdown_grace_secs: 120,
+            ready_check_secs: 15,
+            decline_penalty_secs: 60,
+            setup_timeout_secs: 90,
+            chat_filter: true,
+            rate_limits: RateLimitConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, toml::de::Error),
+    InvalidVar { var: &'static str, value: String },
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "cannot read {}: {e}", path.display()),
+            ConfigError::Parse(path, e) => write!(f, "invalid config in {}: {e}", path.display()),
+            ConfigError::InvalidVar { var, value } => {
+                write!(f, "{var} has an invalid value: {value:?}")
+            }
+            ConfigError::Invalid(reason) => write!(f, "invalid configuration: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Loads and validates the process configuration. Called once at startup;
+    /// any error should abort the server.
+    pub fn load() -> Result<Self, ConfigError> {
+        let mut config = match std::env::var_os(CONFIG_PATH_VAR) {
+            Some(path) => Self::from_file(Path::new(&path))?,
+            None => Self::default(),
+        };
+        config.apply_env(|var| std::env::var(var).ok())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+        toml::from_str(&text).map_err(|e| ConfigError::Parse(path.to_path_buf(), e))
+    }
+
+    /// Overrides fields from `NICKTD_*` variables. `lookup` is `std::env::var`
+    /// in production and a map in tests.
+    pub fn apply_env(
+        &mut self,
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Result<(), ConfigError> {
+        fn parse<T: std::str::FromStr>(var: &'static str, value: String) -> Result<T, ConfigError> {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| ConfigError::InvalidVar { var, value })
+        }
+
+        if let Some(v) = lookup("NICKTD_ENV") {
+            self.environment = match v.trim().to_ascii_lowercase().as_str() {
+                "development" | "dev" => Environment::Development,
+                "production" | "prod" => Environment::Production,
+                _ => {
+                    return Err(ConfigError::InvalidVar {
+                        var: "NICKTD_ENV",
+                        value: v,
+                    });
+                }
+            };
+        }
+        if let Some(v) = lookup("NICKTD_BIND_ADDR") {
+            self.bind_addr = parse("NICKTD_BIND_ADDR", v)?;
+        }
+        if let Some(v) = lookup("NICKTD_DATABASE_PATH") {
+            self.database_path = PathBuf::from(v);
+        }
+        if let Some(v) = lookup("NICKTD_DB_POOL_SIZE") {
+            self.db_pool_size = parse("NICKTD_DB_POOL_SIZE", v)?;
+        }
+        if let Some(v) = lookup("NICKTD_JWT_SECRET") {
+            self.jwt_secret = Some(v);
+        }
+        if let Some(v) = lookup("NICKTD_SESSION_LIFETIME_HOURS") {
+            self.session_lifetime_hours = parse("NICKTD_SESSION_LIFETIME_HOURS", v)?;
+        }
+        if let Some(v) = lookup("NICKTD_ACCESS_TOKEN_MINUTES") {
+            self.access_token_minutes = parse("NICKTD_ACCESS_TOKEN_MINUTES", v)?;
+        }
+        if let Some(v) = lookup("NICKTD_SECOND_DEVICE") {
+            self.second_device = match v.trim().to_ascii_lowercase().as_str() {
+                "replace" => SecondDevicePolicy::Replace,
+                "reject" => SecondDevicePolicy::Reject,
+                _ => {
+                    return Err(ConfigError::InvalidVar {
+                        var: "NICKTD_SECOND_DEVICE",
+                        value: v,
+                    });
+                }
+            };
+        }
+        if let Some(v) = lookup("NICKTD_CORS_ORIGINS") {
+            self.cors_origins = v
+                .split(',')
+                .map(str::trim)
+                .filter(|origin| !origin.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+        if let Some(v) = lookup("NICKTD_TICK_RATE") {
+            self.tick_rate = parse("NICKTD_TICK_RATE", v)?;
+        }
+        if let Some(v) = lookup("NICKTD_MISSED_TICK_POLICY") {
+            self.missed_tick_policy = match v.trim().to_ascii_lowercase().as_str() {
+                "skip" => MissedTickPolicy::Skip,
+                "catch_up" | "catchup" => MissedTickPolicy::CatchUp,
+                _ => {
+                    return Err(ConfigError::InvalidVar {
+                        var: "NICKTD_MISSED_TICK_POLICY",
+                        value: v,
+                    });
+                }
+            };
+        }
+        if let Some(v) = lookup("NICKTD_MAX_CATCH_UP_TICKS") {
+            self.max_catch_up_ticks = parse("NICKTD_MAX_CATCH_UP_TICKS", v)?;
+        }
+        if let Some(v) = lookup("NICKTD_SIM_SHARDS") {
+            self.sim_shards = parse("NICKTD_SIM_SHARDS", v)?;
+        }
+        if let Some(v) = lookup("NICKTD_SHUTDOWN_GRACE_SECS") {
+            self.shutdown_grace_secs = parse("NICKTD_SHUTDOWN_GRACE_SECS", v)?;
+        }
+        if let Some(v) = lookup("NICKTD_READY_CHECK_SECS") {
+            self.ready_check_secs = parse("NICKTD_READY_CHECK_SECS", v)?;
+        }
+        if let Some(v) = lookup("NICKTD_DECLINE_PENALTY_SECS") {
+            self.decline_penalty_secs = parse("NICKTD_DECLINE_PENALTY_SECS", v)?;
+        }
+        if let Some(v) = lookup("NICKTD_SETUP_TIMEOUT_SECS") {
+            self.setup_timeout_secs = parse("NICKTD_SETUP_TIMEOUT_SECS", v)?;
+        }
+        if let Some(v) = lookup("NICKTD_CHAT_FILTER") {
+            self.chat_filter = parse("NICKTD_CHAT_FILTER", v)?;
+        }
+        if let Some(v) = lookup("NICKTD_RATE_LIMIT_BURST") {
+            self.rate_limits.default.burst = parse("NICKTD_RATE_LIMIT_BURST", v)?;
+        }
+        if let Some(v) = lookup("NICKTD_RATE_LIMIT_PER_SECOND") {
+            self.rate_limits.default.per_second = parse("NICKTD_RATE_LIMIT_PER_SECOND", v)?;
+        }
+        if let Some(v) = lookup("NICKTD_MAX_MESSAGE_BYTES") {
+            self.rate_limits.max_message_bytes = parse("NICKTD_MAX_MESSAGE_BYTES", v)?;
+        }
+        if let Some(v) = lookup("NICKTD_INVALID_MESSAGE_BURST") {
+            self.rate_limits.invalid_frames.burst = parse("NICKTD_INVALID_MESSAGE_BURST", v)?;
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |reason: &str| Err(ConfigError::Invalid(reason.to_string()));
+        if self.database_path.as_os_str().is_empty() {
+            return invalid("database_path must not be empty");
+        }
+        if self.db_pool_size == 0 {
+            return invalid("db_pool_size must be at least 1");
+        }
+        if self.session_lifetime_hours <= 0 {
+            return invalid("session_lifetime_hours must be positive");
+        }
+        if self.access_token_minutes <= 0 {
+            return invalid("access_token_minutes must be positive");
+        }
+        if !(self.tick_rate.is_finite() && (1.0..=240.0).contains(&self.tick_rate)) {
+            return invalid("tick_rate must be between 1 and 240");
+        }
+        if self.max_catch_up_ticks == 0 {
+            return invalid("max_catch_up_ticks must be at least 1");
+        }
+        if self.ready_check_secs == 0 {
+            return invalid("ready_check_secs must be at least 1");
+        }
+        self.rate_limits.validate().map_err(ConfigError::Invalid)?;
+        match (&self.jwt_secret, self.environment) {
+            (None, Environment::Production) => {
+                return invalid("jwt_secret (NICKTD_JWT_SECRET) is required in production");
+            }
+            (Some(secret), Environment::Production) if secret.len() < MIN_JWT_SECRET_BYTES => {
+                return invalid("jwt_secret must be at least 32 bytes in production");
+            }
+            (Some(secret), _) if secret.is_empty() => {
+                return invalid("jwt_secret must not be empty");
+            }
+            _ => {}
+        }
+        if self.environment == Environment::Production
+            && self.cors_origins.iter().any(|origin| origin == "*")
+        {
+            log::warn!("CORS allows any origin in production; set cors_origins to lock it down");
+        }
+        Ok(())
+    }
+
+    pub fn jwt_secret(&self) -> &[u8] {
+        self.jwt_secret
+            .as_deref()
+            .unwrap_or(DEV_JWT_SECRET)
+            .as_bytes()
+    }
+
+    pub fn shutdown_grace(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.shutdown_grace_secs)
+    }
+
+    pub fn ready_check_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.ready_check_secs)
+    }
+
+    pub fn decline_penalty(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.decline_penalty_secs)
+    }
+
+    pub fn session_lifetime(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.session_lifetime_hours)
+    }
+
+    pub fn access_token_lifetime(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.access_token_minutes)
+    }
+
+    /// Value for `Access-Control-Allow-Origin`, or `None` if the request's
+    /// origin is not on the allow-list.
+    pub fn allowed_origin<'a>(&'a self, request_origin: Option<&'a str>) -> Option<&'a str> {
+        if self.cors_origins.is_empty() || self.cors_origins.iter().any(|o| o == "*") {
+            return Some("*");
+        }
+        let origin = request_origin?;
+        self.cors_origins
+            .iter()
+            .any(|allowed| allowed == origin)
+            .then_some(origin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let map: HashMap<String, String> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |key| map.get(key).cloned()
+    }
+
+    #[test]
+    fn defaults_are_valid_in_development() {
+        let config = Config::default();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.jwt_secret(), DEV_JWT_SECRET.as_bytes());
+    }
+
+    #[test]
+    fn env_overrides_file_values() {
+        let mut config: Config = toml::from_str(
+            r#"
+            bind_addr = "127.0.0.1:8000"
+            db_pool_size = 2
+            cors_origins = ["https://a.example"]
+            "#,
+        )
+        .unwrap();
+        config
+            .apply_env(env(&[
+                ("NICKTD_DB_POOL_SIZE", "8"),
+                (
+                    "NICKTD_CORS_ORIGINS",
+                    "https://b.example, https://c.example",
+                ),
+            ]))
+            .unwrap();
+        assert_eq!(config.bind_addr, "127.0.0.1:8000".parse().unwrap());
+        assert_eq!(config.db_pool_size, 8);
+        assert_eq!(
+            config.cors_origins,
+            vec!["https://b.example", "https://c.example"]
+        );
+    }
+
+    #[test]
+    fn missed_tick_policy_reads_from_file_and_env() {
+        let mut config: Config = toml::from_str("missed_tick_policy = \"catch_up\"").unwrap();
+        assert_eq!(config.missed_tick_policy, MissedTickPolicy::CatchUp);
+        config
+            .apply_env(env(&[("NICKTD_MISSED_TICK_POLICY", "skip")]))
+            .unwrap();
+        assert_eq!(config.missed_tick_policy, MissedTickPolicy::Skip);
+    }
+
+    #[test]
+    fn second_device_policy_reads_from_file_and_env() {
+        let mut config: Config = toml::from_str("second_device = \"reject\"").unwrap();
+        assert_eq!(config.second_device, SecondDevicePolicy::Reject);
+        config
+            .apply_env(env(&[("NICKTD_SECOND_DEVICE", "replace")]))
+            .unwrap();
+        assert_eq!(config.second_device, SecondDevicePolicy::Replace);
+        assert!(
+            config
+                .apply_env(env(&[("NICKTD_SECOND_DEVICE", "spectate")]))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn rate_limits_read_from_file_and_env() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [rate_limits]
+            max_message_bytes = 8192
+            invalid_frames = { burst = 2, per_second = 0.1 }
+            [rate_limits.per_kind.chat]
+            burst = 1
+            per_second = 0.25
+            "#,
+        )
+        .unwrap();
+        let defaults = RateLimitConfig::default();
+        assert_eq!(config.rate_limits.max_message_bytes, 8192);
+        assert_eq!(config.rate_limits.invalid_frames.burst, 2);
+        assert_eq!(config.rate_limits.per_kind["chat"].burst, 1);
+        assert_eq!(
+            config.rate_limits.per_kind["ping"], defaults.per_kind["ping"],
+            "kinds the file leaves out keep their built-in limit"
+        );
+        config
+            .apply_env(env(&[
+                ("NICKTD_RATE_LIMIT_BURST", "40"),
+                ("NICKTD_MAX_MESSAGE_BYTES", "2048"),
+            ]))
+            .unwrap();
+        assert_eq!(config.rate_limits.default.burst, 40);
+        assert_eq!(config.rate_limits.max_message_bytes, 2048);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn bad_rate_limits_are_rejected() {
+        let invalid = |toml: &str| {
+            let config: Config = toml::from_str(toml).unwrap();
+            config.validate().is_err()
+        };
+        assert!(invalid(
+            "[rate_limits.per_kind.chatt]\nburst = 1\nper_second = 1.0"
+        ));
+        assert!(invalid(
+            "[rate_limits.per_kind.chat]\nburst = 0\nper_second = 1.0"
+        ));
+        assert!(invalid(
+            "[rate_limits]\ndefault = { burst = 5, per_second = 0.0 }"
+        ));
+        assert!(invalid("[rate_limits]\nmax_message_bytes = 16"));
+    }
+
+    #[test]
+    fn unknown_file_keys_are_rejected() {
+        assert!(toml::from_str::<Config>("bind_adress = \"0.0.0.0:1\"").is_err());
+    }
+
+    #[test]
+    fn malformed_env_var_names_the_variable() {
+        let err = Config::default()
+            .apply_env(env(&[("NICKTD_TICK_RATE", "fast")]))
+            .unwrap_err();
+        assert!(err.to_string().contains("NICKTD_TICK_RATE"));
+    }
+
+    #[test]
+    fn production_requires_a_strong_secret() {
+        let mut config = Config {
+            environment: Environment::Production,
+            ..Config::default()
+        };
+        assert!(config.validate().is_err());
+        config.jwt_secret = Some("short".into());
+        assert!(config.validate().is_err());
+        config.jwt_secret = Some("x".repeat(MIN_JWT_SECRET_BYTES));
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn out_of_range_values_fail_validation() {
+        for config in [
+            Config {
+                db_pool_size: 0,
+                ..Config::default()
+            },
+            Config {
+                tick_rate: 0.0,
+                ..Config::default()
+            },
+            Config {
+                session_lifetime_hours: 0,
+                ..Config::default()
+            },
+            Config {
+                access_token_minutes: 0,
+                ..Config::default()
+            },
+            Config {
+                ready_check_secs: 0,
+                ..Config::default()
+            },
+        ] {
+            assert!(config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn cors_echoes_only_listed_origins() {
+        let config = Config {
+            cors_origins: vec!["https://game.example".into()],
+            ..Config::default()
+        };
+        assert_eq!(
+            config.allowed_origin(Some("https://game.example")),
+            Some("https://game.example")
+        );
+        assert_eq!(config.allowed_origin(Some("https://evil.example")), None);
+        assert_eq!(Config::default().allowed_origin(None), Some("*"));
+    }
+}
@@ -1,22 +1,34 @@
-use crate::model::account::{Account, NewAccount};
+use crate::login_guard::LoginFailure;
+use crate::model::{
+    account::{Account, NewAccount, Profile, ProfileUpdate},
+    family::Family,
+    friend::{FriendLink, FriendRequestOutcome},
+    match_result::MatchResult,
+    session::Session,
+};
 use bcrypt::{DEFAULT_COST, hash, verify};
 use chrono::{DateTime, Utc};
-use sqlx::{SqlitePool, sqlite::SqlitePoolOptions};
+use sqlx::{
+    SqlitePool,
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+};
 use std::fs;
 use std::path::Path;
+use std::sync::OnceLock;
 
-pub async fn init_db() -> Result<SqlitePool, sqlx::Error> {
-    let data_dir = Path::new("./data");
-    if !data_dir.exists() {
-        fs::create_dir(data_dir).expect("Failed to create data directory");
-    }
-    let db_url = "sqlite:data/nicktd.db";
-    if !Path::new("data/nicktd.db").exists() {
-        fs::File::create("data/nicktd.db").expect("Failed to create database file");
+pub async fn init_db(path: &Path, pool_size: u32) -> Result<SqlitePool, sqlx::Error> {
+    if let Some(data_dir) = path.parent()
+        && !data_dir.as_os_str().is_empty()
+        && !data_dir.exists()
+    {
+        fs::create_dir_all(data_dir)?;
     }
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true);
     let pool = SqlitePoolOptions::new()
-        .max_connections(5)
-        .connect(db_url)
+        .max_connections(pool_size)
+        .connect_with(options)
         .await?;
 
     sqlx::migrate!("./migrations").run(&pool).await?;
@@ -34,7 +46,7 @@ pub async fn create_account(
         r#"
         INSERT INTO accounts (username, password_hash) 
         VALUES (?, ?) 
-        RETURNING id, username, password_hash, session_id, session_expires_at
+        RETURNING id, username, password_hash, is_admin, is_guest
         "#,
         new_account.username,
         hashed_password
@@ -45,6 +57,61 @@ pub async fn create_account(
     Ok(account)
 }
 
+/// Creates a passwordless guest account under `username`. The empty hash
+/// never verifies, so a guest cannot log in until it is upgraded.
+pub async fn create_guest_account(
+    pool: &SqlitePool,
+    username: &str,
+) -> Result<Account, sqlx::Error> {
+    sqlx::query_as!(
+        Account,
+        r#"
+        INSERT INTO accounts (username, password_hash, is_guest)
+        VALUES (?, '', TRUE)
+        RETURNING id, username, password_hash, is_admin, is_guest
+        "#,
+        username
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Turns a guest into a full account in place, so its id (and with it match
+/// history and sessions) carries over. Fails if the username is taken.
+pub async fn upgrade_guest(
+    pool: &SqlitePool,
+    account_id: i64,
+    new_account: NewAccount,
+) -> Result<bool, sqlx::Error> {
+    let hashed_password = hash(new_account.password, DEFAULT_COST).unwrap();
+    let result = sqlx::query!(
+        "UPDATE accounts SET username = ?, password_hash = ?, is_guest = FALSE WHERE id = ? AND is_guest",
+        new_account.username,
+        hashed_password,
+        account_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Deletes guest accounts with no unexpired session left; nobody can sign
+/// back into them. Returns how many were removed.
+pub async fn purge_abandoned_guests(pool: &SqlitePool) -> Result<u64, sqlx::Error> {
+    let now = Utc::now();
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM accounts
+        WHERE is_guest
+          AND id NOT IN (SELECT account_id FROM sessions WHERE expires_at > ?)
+        "#,
+        now
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
 pub async fn get_account_by_username(
     pool: &SqlitePool,
     username: &str,
@@ -52,7 +119,7 @@ pub async fn get_account_by_username(
     let account = sqlx::query_as!(
         Account,
         r#"
-        SELECT id, username, password_hash, session_id, session_expires_at
+        SELECT id, username, password_hash, is_admin, is_guest
         FROM accounts
         WHERE username = ?
         "#,
@@ -64,26 +131,181 @@ pub async fn get_account_by_username(
     Ok(account)
 }
 
-pub async fn update_session(
+/// Stores a new signed-in device and drops the account's lapsed sessions.
+pub async fn create_session(
     pool: &SqlitePool,
     account_id: i64,
     session_id: &str,
+    refresh_token_hash: &str,
+    device: Option<&str>,
     expires_at: DateTime<Utc>,
 ) -> Result<(), sqlx::Error> {
+    let now = Utc::now();
+    let mut tx = pool.begin().await?;
     sqlx::query!(
-        "UPDATE accounts SET session_id = ?, session_expires_at = ? WHERE id = ?",
+        "DELETE FROM sessions WHERE account_id = ? AND expires_at <= ?",
+        account_id,
+        now
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO sessions (id, account_id, refresh_token_hash, device, created_at, last_used_at, expires_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?)
+        "#,
         session_id,
+        account_id,
+        refresh_token_hash,
+        device,
+        now,
+        now,
+        expires_at
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn get_session(
+    pool: &SqlitePool,
+    session_id: &str,
+) -> Result<Option<Session>, sqlx::Error> {
+    sqlx::query_as!(
+        Session,
+        r#"
+        SELECT id, account_id, refresh_token_hash, device, created_at, last_used_at, expires_at
+        FROM sessions
+        WHERE id = ?
+        "#,
+        session_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// The account's unexpired sessions, most recently used first.
+pub async fn list_sessions(
+    pool: &SqlitePool,
+    account_id: i64,
+) -> Result<Vec<Session>, sqlx::Error> {
+    let now = Utc::now();
+    sqlx::query_as!(
+        Session,
+        r#"
+        SELECT id, account_id, refresh_token_hash, device, created_at, last_used_at, expires_at
+        FROM sessions
+        WHERE account_id = ? AND expires_at > ?
+        ORDER BY last_used_at DESC
+        "#,
+        account_id,
+        now
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Swaps in a new refresh token hash, only if `old_hash` is still current.
+/// Returns false when another refresh already rotated it.
+pub async fn rotate_refresh_token(
+    pool: &SqlitePool,
+    session_id: &str,
+    old_hash: &str,
+    new_hash: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<bool, sqlx::Error> {
+    let now = Utc::now();
+    let result = sqlx::query!(
+        r#"
+        UPDATE sessions SET refresh_token_hash = ?, last_used_at = ?, expires_at = ?
+        WHERE id = ? AND refresh_token_hash = ?
+        "#,
+        new_hash,
+        now,
         expires_at,
+        session_id,
+        old_hash
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Signs one device out. Returns whether the account had that session.
+pub async fn delete_session(
+    pool: &SqlitePool,
+    account_id: i64,
+    session_id: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "DELETE FROM sessions WHERE id = ? AND account_id = ?",
+        session_id,
         account_id
     )
     .execute(pool)
     .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Signs out every device of the account except `keep`, if given.
+pub async fn delete_other_sessions(
+    pool: &SqlitePool,
+    account_id: i64,
+    keep: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM sessions WHERE account_id = ? AND id IS NOT ?",
+        account_id,
+        keep
+    )
+    .execute(pool)
+    .await?;
     Ok(())
 }
 
-pub async fn clear_session(pool: &SqlitePool, account_id: i64) -> Result<(), sqlx::Error> {
+/// Grants or revokes the admin role. Returns whether the account exists.
+pub async fn set_admin(
+    pool: &SqlitePool,
+    username: &str,
+    is_admin: bool,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "UPDATE accounts SET is_admin = ? WHERE username = ?",
+        is_admin,
+        username
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn get_account_by_id(
+    pool: &SqlitePool,
+    account_id: i64,
+) -> Result<Option<Account>, sqlx::Error> {
+    sqlx::query_as!(
+        Account,
+        r#"
+        SELECT id, username, password_hash, is_admin, is_guest
+        FROM accounts
+        WHERE id = ?
+        "#,
+        account_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn update_password(
+    pool: &SqlitePool,
+    account_id: i64,
+    password: &str,
+) -> Result<(), sqlx::Error> {
+    let hashed_password = hash(password, DEFAULT_COST).unwrap();
     sqlx::query!(
-        "UPDATE accounts SET session_id = NULL, session_expires_at = NULL WHERE id = ?",
+        "UPDATE accounts SET password_hash = ? WHERE id = ?",
+        hashed_password,
         account_id
     )
     .execute(pool)
@@ -91,6 +313,257 @@ pub async fn clear_session(pool: &SqlitePool, account_id: i64) -> Result<(), sql
     Ok(())
 }
 
+/// Removes the account row; its sessions cascade. Match history keeps its
+/// rows.
+pub async fn delete_account(pool: &SqlitePool, account_id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM accounts WHERE id = ?", account_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_profile(
+    pool: &SqlitePool,
+    username: &str,
+) -> Result<Option<Profile>, sqlx::Error> {
+    let row = sqlx::query!(
+        "SELECT username, display_name, avatar, favourite_family FROM accounts WHERE username = ?",
+        username
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|row| Profile {
+        username: row.username,
+        display_name: row.display_name,
+        avatar: u8::try_from(row.avatar).unwrap_or(0),
+        favourite_family: row.favourite_family.as_deref().and_then(Family::from_name),
+    }))
+}
+
+pub async fn update_profile(
+    pool: &SqlitePool,
+    account_id: i64,
+    update: &ProfileUpdate,
+) -> Result<(), sqlx::Error> {
+    let favourite_family = update.favourite_family.map(Family::as_str);
+    sqlx::query!(
+        "UPDATE accounts SET display_name = ?, avatar = ?, favourite_family = ? WHERE id = ?",
+        update.display_name,
+        update.avatar,
+        favourite_family,
+        account_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Asks `to_id` to be friends with `from_id`. If `to_id` already asked
+/// `from_id`, that request is accepted instead.
+pub async fn send_friend_request(
+    pool: &SqlitePool,
+    from_id: i64,
+    to_id: i64,
+) -> Result<FriendRequestOutcome, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let reverse = sqlx::query_scalar!(
+        r#"SELECT accepted AS "accepted: bool" FROM friendships WHERE requester_id = ? AND addressee_id = ?"#,
+        to_id,
+        from_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    let outcome = match reverse {
+        Some(true) => FriendRequestOutcome::AlreadyFriends,
+        Some(false) => {
+            sqlx::query!(
+                "UPDATE friendships SET accepted = TRUE WHERE requester_id = ? AND addressee_id = ?",
+                to_id,
+                from_id
+            )
+            .execute(&mut *tx)
+            .await?;
+            FriendRequestOutcome::Accepted
+        }
+        None => {
+            let now = Utc::now();
+            let inserted = sqlx::query!(
+                "INSERT OR IGNORE INTO friendships (requester_id, addressee_id, created_at) VALUES (?, ?, ?)",
+                from_id,
+                to_id,
+                now
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+            if inserted > 0 {
+                FriendRequestOutcome::Sent
+            } else {
+                let accepted = sqlx::query_scalar!(
+                    r#"SELECT accepted AS "accepted: bool" FROM friendships WHERE requester_id = ? AND addressee_id = ?"#,
+                    from_id,
+                    to_id
+                )
+                .fetch_one(&mut *tx)
+                .await?;
+                if accepted {
+                    FriendRequestOutcome::AlreadyFriends
+                } else {
+                    FriendRequestOutcome::AlreadyPending
+                }
+            }
+        }
+    };
+    tx.commit().await?;
+    Ok(outcome)
+}
+
+/// Accepts the pending request `requester_id` sent to `account_id`. Returns
+/// false if there is none.
+pub async fn accept_friend_request(
+    pool: &SqlitePool,
+    account_id: i64,
+    requester_id: i64,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "UPDATE friendships SET accepted = TRUE WHERE requester_id = ? AND addressee_id = ? AND NOT accepted",
+        requester_id,
+        account_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Ends a friendship, or declines or withdraws a pending request, in either
+/// direction. Returns false if the two had nothing between them.
+pub async fn remove_friend(
+    pool: &SqlitePool,
+    account_id: i64,
+    other_id: i64,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM friendships
+        WHERE (requester_id = ? AND addressee_id = ?) OR (requester_id = ? AND addressee_id = ?)
+        "#,
+        account_id,
+        other_id,
+        other_id,
+        account_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Every friendship and pending request the account is part of.
+pub async fn list_friend_links(
+    pool: &SqlitePool,
+    account_id: i64,
+) -> Result<Vec<FriendLink>, sqlx::Error> {
+    sqlx::query_as!(
+        FriendLink,
+        r#"
+        SELECT a.id AS "account_id!", a.username AS "username!",
+               f.accepted AS "accepted!: bool", (f.requester_id = ?) AS "outgoing!: bool"
+        FROM friendships f
+        JOIN accounts a
+          ON a.id = CASE WHEN f.requester_id = ? THEN f.addressee_id ELSE f.requester_id END
+        WHERE f.requester_id = ? OR f.addressee_id = ?
+        ORDER BY a.username
+        "#,
+        account_id,
+        account_id,
+        account_id,
+        account_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn are_friends(pool: &SqlitePool, a: i64, b: i64) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM friendships
+            WHERE accepted
+              AND ((requester_id = ? AND addressee_id = ?) OR (requester_id = ? AND addressee_id = ?))
+        ) AS "friends!: bool"
+        "#,
+        a,
+        b,
+        b,
+        a
+    )
+    .fetch_one(pool)
+    .await
+}
+
 pub async fn verify_password(password: &str, hashed_password: &str) -> bool {
     verify(password, hashed_password).unwrap_or(false) // TODO: Handle error properly
 }
+
+/// Burns the same bcrypt time as `verify_password` for a username with no
+/// account, so response timing does not reveal which usernames exist.
+pub async fn verify_password_against_nothing(password: &str) {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    let dummy = DUMMY_HASH.get_or_init(|| hash("not-a-real-password", DEFAULT_COST).unwrap());
+    let _ = verify(password, dummy);
+}
+
+/// Appends one row to the failed-login audit log.
+pub async fn record_login_failure(
+    pool: &SqlitePool,
+    username: &str,
+    ip: Option<&str>,
+    reason: LoginFailure,
+) -> Result<(), sqlx::Error> {
+    let reason = reason.as_str();
+    let attempted_at = Utc::now();
+    sqlx::query!(
+        "INSERT INTO login_failures (username, ip, reason, attempted_at) VALUES (?, ?, ?, ?)",
+        username,
+        ip,
+        reason,
+        attempted_at
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Appends one match to the history tables and returns its row id.
+pub async fn record_match_result(
+    pool: &SqlitePool,
+    result: &MatchResult,
+) -> Result<i64, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let outcome = result.outcome.as_str();
+    let match_id = sqlx::query!(
+        "INSERT INTO matches (outcome, winner_id, ended_at) VALUES (?, ?, ?)",
+        outcome,
+        result.winner_id,
+        result.ended_at
+    )
+    .execute(&mut *tx)
+    .await?
+    .last_insert_rowid();
+    for account_id in &result.participant_ids {
+        let stats = result
+            .stats
+            .iter()
+            .find(|s| s.player_id == *account_id)
+            .and_then(|s| serde_json::to_string(s).ok());
+        sqlx::query!(
+            "INSERT OR IGNORE INTO match_players (match_id, account_id, stats) VALUES (?, ?, ?)",
+            match_id,
+            account_id,
+            stats
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(match_id)
+}
@@ -0,0 +1,336 @@
+use crate::database;
+use crate::handler::matchmaking::create_match;
+use crate::model::{
+    game_error::GameError,
+    match_settings::MatchSettings,
+    messages::{ChallengeInfo, ServerMessage},
+};
+use crate::state::{Activity, ServerState};
+use log::error;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+/// How long a challenged friend has to answer.
+pub const CHALLENGE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChallengeStatus {
+    Pending,
+    /// The friend accepted; the match with this id has been created.
+    Started(u64),
+    Declined,
+    /// Timed out, cancelled by the challenger, or closed by the shutdown
+    /// drain.
+    Withdrawn,
+}
+
+/// A challenge waiting for the friend's answer. Lives in
+/// `ServerStateData::challenges` until it is answered or withdrawn.
+pub struct Challenge {
+    info: ChallengeInfo,
+    /// The challenger's loop watches this.
+    status_tx: watch::Sender<ChallengeStatus>,
+}
+
+/// The challenger's handle on their challenge.
+pub struct ChallengeTicket {
+    pub info: ChallengeInfo,
+    pub deadline: Instant,
+    pub status_rx: watch::Receiver<ChallengeStatus>,
+}
+
+/// Challenges the friend `to_id`, who must be online and idle in the menu,
+/// and pushes them a `ChallengeReceived`.
+pub async fn send(
+    state: &ServerState,
+    from_id: i64,
+    from_username: String,
+    to_id: i64,
+) -> Result<ChallengeTicket, GameError> {
+    if to_id == from_id {
+        return Err(GameError::NotFriends);
+    }
+    let friends = database::are_friends(&state.db_pool, from_id, to_id)
+        .await
+        .unwrap_or_else(|e| {
+            error!("Failed to check friendship: {}", e);
+            false
+        });
+    if !friends {
+        return Err(GameError::NotFriends);
+    }
+    let to_username = match database::get_account_by_id(&state.db_pool, to_id).await {
+        Ok(Some(account)) => account.username,
+        Ok(None) => return Err(GameError::NotFriends),
+        Err(e) => {
+            error!("Failed to load challenged account: {}", e);
+            return Err(GameError::NotFriends);
+        }
+    };
+    let notify_tx = match state.active_connections.lock().await.get(&to_id) {
+        None => return Err(GameError::FriendOffline),
+        // Queued or playing friends only read pushed messages later.
+        Some(c) if c.activity.get() != Activity::Idle => return Err(GameError::FriendBusy),
+        Some(c) => c.notify_tx.clone(),
+    };
+
+    let info = ChallengeInfo {
+        challenge_id: state.next_challenge_id.fetch_add(1, Ordering::Relaxed),
+        from_id,
+        from_username,
+        to_id,
+        to_username,
+        timeout_secs: CHALLENGE_TIMEOUT.as_secs(),
+    };
+    let status_tx = watch::Sender::new(ChallengeStatus::Pending);
+    let status_rx = status_tx.subscribe();
+    let mut challenges = state.challenges.lock().await;
+    // Registered first so an instant answer finds it.
+    challenges.insert(
+        info.challenge_id,
+        Challenge {
+            info: info.clone(),
+            status_tx,
+        },
+    );
+    if notify_tx
+        .try_send(ServerMessage::ChallengeReceived(info.clone()))
+        .is_err()
+    {
+        challenges.remove(&info.challenge_id);
+        return Err(GameError::FriendBusy);
+    }
+    Ok(ChallengeTicket {
+        info,
+        deadline: Instant::now() + CHALLENGE_TIMEOUT,
+        status_rx,
+    })
+}
+
+/// The challenged friend's answer. Accepting removes the challenge and
+/// creates the match, whose id is returned.
+pub async fn respond(
+    state: &ServerState,
+    challenge_id: u64,
+    account_id: i64,
+    accept: bool,
+) -> Result<Option<u64>, GameError> {
+    let challenge = {
+        let mut challenges = state.challenges.lock().await;
+        match challenges.get(&challenge_id) {
+            Some(c) if c.info.to_id == account_id => challenges.remove(&challenge_id),
+            _ => None,
+        }
+    };
+    let challenge = challenge.ok_or(GameError::ChallengeNotFound)?;
+    if !accept {
+        challenge.status_tx.send_replace(ChallengeStatus::Declined);
+        return Ok(None);
+    }
+    let info = challenge.info;
+    let match_id = create_match(
+        state,
+        (info.from_id, info.from_username),
+        (info.to_id, info.to_username),
+        MatchSettings::default(),
+    )
+    .await;
+    challenge
+        .status_tx
+        .send_replace(ChallengeStatus::Started(match_id));
+    Ok(Some(match_id))
+}
+
+/// Withdraws the challenge and tells the friend. Returns false if it was
+/// already answered.
+pub async fn withdraw(state: &ServerState, challenge_id: u64) -> bool {
+    let challenge = state.challenges.lock().await.remove(&challenge_id);
+    let Some(challenge) = challenge else {
+        return false;
+    };
+    challenge.status_tx.send_replace(ChallengeStatus::Withdrawn);
+    notify_withdrawn(state, &challenge.info).await;
+    true
+}
+
+/// Withdraws every open challenge; used by the shutdown drain.
+pub async fn withdraw_all(state: &ServerState) {
+    let challenges: Vec<Challenge> = state
+        .challenges
+        .lock()
+        .await
+        .drain()
+        .map(|(_, c)| c)
+        .collect();
+    for challenge in challenges {
+        challenge.status_tx.send_replace(ChallengeStatus::Withdrawn);
+        notify_withdrawn(state, &challenge.info).await;
+    }
+}
+
+async fn notify_withdrawn(state: &ServerState, info: &ChallengeInfo) {
+    let notify_tx = state
+        .active_connections
+        .lock()
+        .await
+        .get(&info.to_id)
+        .map(|c| c.notify_tx.clone());
+    if let Some(notify_tx) = notify_tx {
+        let _ = notify_tx.try_send(ServerMessage::ChallengeWithdrawn {
+            challenge_id: info.challenge_id,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::account::NewAccount;
+    use crate::state::test_support::migrated_test_state;
+    use crate::state::{ActiveConnection, ActivityCell};
+    use tokio::sync::mpsc;
+
+    async fn register(state: &ServerState, username: &str) -> i64 {
+        let new_account = NewAccount {
+            username: username.to_string(),
+            password: "password".to_string(),
+        };
+        database::create_account(&state.db_pool, new_account)
+            .await
+            .unwrap()
+            .id
+    }
+
+    async fn befriend(state: &ServerState, a: i64, b: i64) {
+        database::send_friend_request(&state.db_pool, a, b)
+            .await
+            .unwrap();
+        assert!(
+            database::accept_friend_request(&state.db_pool, b, a)
+                .await
+                .unwrap()
+        );
+    }
+
+    async fn connect(state: &ServerState, account_id: i64) -> mpsc::Receiver<ServerMessage> {
+        let (notify_tx, notify_rx) = mpsc::channel(4);
+        let connection = ActiveConnection {
+            session_id: "sid".into(),
+            kill_tx: mpsc::channel(1).0,
+            notify_tx,
+            activity: ActivityCell::default(),
+        };
+        connection.activity.set(Activity::Idle);
+        state
+            .active_connections
+            .lock()
+            .await
+            .insert(account_id, connection);
+        notify_rx
+    }
+
+    #[tokio::test]
+    async fn accepted_challenge_starts_a_match_for_both() {
+        let state = migrated_test_state().await;
+        let alice = register(&state, "alice").await;
+        let bob = register(&state, "bob").await;
+        befriend(&state, alice, bob).await;
+        let mut bob_inbox = connect(&state, bob).await;
+
+        let ticket = send(&state, alice, "alice".into(), bob).await.unwrap();
+        let Some(ServerMessage::ChallengeReceived(info)) = bob_inbox.recv().await else {
+            panic!("bob must be told about the challenge");
+        };
+        assert_eq!(info, ticket.info);
+        assert_eq!(info.to_username, "bob");
+
+        assert_eq!(
+            respond(&state, info.challenge_id, alice, true).await,
+            Err(GameError::ChallengeNotFound),
+            "only the challenged friend may answer"
+        );
+        let match_id = respond(&state, info.challenge_id, bob, true)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            *ticket.status_rx.borrow(),
+            ChallengeStatus::Started(match_id)
+        );
+        assert!(state.matches.read().await.contains_key(&match_id));
+        assert!(state.challenges.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn only_online_friends_can_be_challenged() {
+        let state = migrated_test_state().await;
+        let alice = register(&state, "alice").await;
+        let bob = register(&state, "bob").await;
+        let _bob_inbox = connect(&state, bob).await;
+
+        assert_eq!(
+            send(&state, alice, "alice".into(), bob).await.err(),
+            Some(GameError::NotFriends)
+        );
+        befriend(&state, alice, bob).await;
+        state.active_connections.lock().await.remove(&bob);
+        assert_eq!(
+            send(&state, alice, "alice".into(), bob).await.err(),
+            Some(GameError::FriendOffline)
+        );
+    }
+
+    #[tokio::test]
+    async fn friends_who_are_not_idle_are_busy() {
+        let state = migrated_test_state().await;
+        let alice = register(&state, "alice").await;
+        let bob = register(&state, "bob").await;
+        befriend(&state, alice, bob).await;
+        let mut bob_inbox = connect(&state, bob).await;
+        let activity = state.active_connections.lock().await[&bob].activity.clone();
+
+        for busy in [Activity::Busy, Activity::InMatch] {
+            activity.set(busy);
+            assert_eq!(
+                send(&state, alice, "alice".into(), bob).await.err(),
+                Some(GameError::FriendBusy)
+            );
+        }
+        assert!(state.challenges.lock().await.is_empty());
+        assert!(bob_inbox.try_recv().is_err());
+
+        activity.set(Activity::Idle);
+        assert!(send(&state, alice, "alice".into(), bob).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn decline_and_withdraw_reach_the_other_side() {
+        let state = migrated_test_state().await;
+        let alice = register(&state, "alice").await;
+        let bob = register(&state, "bob").await;
+        befriend(&state, alice, bob).await;
+        let mut bob_inbox = connect(&state, bob).await;
+
+        let first = send(&state, alice, "alice".into(), bob).await.unwrap();
+        assert_eq!(
+            respond(&state, first.info.challenge_id, bob, false).await,
+            Ok(None)
+        );
+        assert_eq!(*first.status_rx.borrow(), ChallengeStatus::Declined);
+
+        let second = send(&state, alice, "alice".into(), bob).await.unwrap();
+        assert!(withdraw(&state, second.info.challenge_id).await);
+        assert!(!withdraw(&state, second.info.challenge_id).await);
+        let mut notices = Vec::new();
+        while let Ok(msg) = bob_inbox.try_recv() {
+            notices.push(msg);
+        }
+        assert!(matches!(
+            notices.last(),
+            Some(ServerMessage::ChallengeWithdrawn { challenge_id })
+                if *challenge_id == second.info.challenge_id
+        ));
+        assert!(state.matches.read().await.is_empty());
+    }
+}
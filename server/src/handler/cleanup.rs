@@ -1,66 +1,69 @@
 use crate::{
-    database,
-    model::{components::PlayerIdComponent, game_state::GamePhase},
+    model::{components::PlayerIdComponent, game_state::GamePhase, lobby::Lobby},
     state::ServerState,
 };
 use bevy_ecs::prelude::Entity;
 
 pub async fn remove_player_from_match(match_id: u64, player_id: i64, server_state: &ServerState) {
-    let Some(lobby_arc) = server_state.matches.read().await.get(&match_id).cloned() else {
+    let Some(handle) = server_state.matches.read().await.get(&match_id).cloned() else {
         return; // match already torn down
     };
-    let now_empty = {
-        let mut lobby = lobby_arc.lock().await;
-        let game_in_progress = lobby.is_full()
-            && lobby.game_state.phase != GamePhase::GameOver
-            && lobby.game_state.phase != GamePhase::Victory;
-
-        let mut entities_to_despawn = Vec::new();
-        {
-            let mut query = lobby
-                .game_state
-                .world
-                .query::<(Entity, &PlayerIdComponent)>();
-            for (entity, owner) in query.iter(&lobby.game_state.world) {
-                if owner.0 == player_id {
-                    entities_to_despawn.push(entity);
-                }
-            }
-        }
-        for entity in entities_to_despawn {
-            let _ = lobby.game_state.world.despawn(entity);
-        }
-
-        lobby.players.retain(|p| p.id != player_id);
+    let now_empty = handle
+        .with_lobby(move |lobby| remove_player(lobby, player_id))
+        .await;
+
+    // `None`: the shard already dropped the match; clear the stale entry too.
+    if now_empty != Some(false)
+        && let Some(handle) = server_state.matches.write().await.remove(&match_id)
+    {
+        handle.remove();
+    }
+}
 
-        if game_in_progress {
-            // Leaving an active game forfeits: the remaining player wins.
-            lobby.game_state.phase = GamePhase::GameOver;
-            lobby.game_state.world.insert_resource(GamePhase::GameOver);
-            lobby.winner_id = lobby.players.first().map(|p| p.id);
-            lobby.broadcast_gamestate();
+/// Despawns the player's entities and unseats them; leaving a running game
+/// forfeits it. Returns whether the lobby is now empty.
+fn remove_player(lobby: &mut Lobby, player_id: i64) -> bool {
+    let game_in_progress = lobby.is_full()
+        && lobby.game_state.phase != GamePhase::GameOver
+        && lobby.game_state.phase != GamePhase::Victory;
+
+    let mut entities_to_despawn = Vec::new();
+    {
+        let mut query = lobby
+            .game_state
+            .world
+            .query::<(Entity, &PlayerIdComponent)>();
+        for (entity, owner) in query.iter(&lobby.game_state.world) {
+            if owner.0 == player_id {
+                entities_to_despawn.push(entity);
+            }
         }
+    }
+    for entity in entities_to_despawn {
+        let _ = lobby.game_state.world.despawn(entity);
+    }
 
-        lobby.players.is_empty()
-    }; // lobby guard dropped BEFORE taking the matches write lock (rule 3)
+    lobby.sync_gold_stats();
+    lobby.players.retain(|p| p.id != player_id);
 
-    if now_empty {
-        server_state.matches.write().await.remove(&match_id);
+    if game_in_progress {
+        // Leaving an active game forfeits: the remaining player wins.
+        lobby.game_state.phase = GamePhase::GameOver;
+        lobby.game_state.world.insert_resource(GamePhase::GameOver);
+        lobby.winner_id = lobby.players.first().map(|p| p.id);
+        lobby.broadcast_gamestate();
     }
-}
 
-pub async fn cleanup(match_id: u64, player_id: i64, server_state: &ServerState) {
-    remove_player_from_match(match_id, player_id, server_state).await;
-    if let Err(e) = database::clear_session(&server_state.db_pool, player_id).await {
-        log::error!("Failed to clear session for player {}: {}", player_id, e);
-    }
+    lobby.players.is_empty()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::Config;
     use crate::handler::matchmaking::create_match;
     use crate::model::components::Position;
+    use crate::model::match_settings::MatchSettings;
     use crate::state::ServerStateData;
     use sqlx::sqlite::SqlitePoolOptions;
 
@@ -69,16 +72,24 @@ mod tests {
             .connect("sqlite::memory:")
             .await
             .unwrap();
-        let state = ServerStateData::new(db_pool);
-        let match_id = create_match(&state, (p1, "p1".into()), (p2, "p2".into())).await;
+        let state = ServerStateData::new(db_pool, Config::default());
+        let match_id = create_match(
+            &state,
+            (p1, "p1".into()),
+            (p2, "p2".into()),
+            MatchSettings::default(),
+        )
+        .await;
         (state, match_id)
     }
 
-    async fn lock_lobby(
+    async fn with_lobby<R: Send + 'static>(
         state: &crate::state::ServerState,
         match_id: u64,
-    ) -> std::sync::Arc<tokio::sync::Mutex<crate::model::lobby::Lobby>> {
-        state.matches.read().await.get(&match_id).unwrap().clone()
+        f: impl FnOnce(&mut crate::model::lobby::Lobby) -> R + Send + 'static,
+    ) -> R {
+        let handle = state.matches.read().await.get(&match_id).unwrap().clone();
+        handle.with_lobby(f).await.unwrap()
     }
 
     #[tokio::test]
@@ -86,10 +97,7 @@ mod tests {
         let player_id = 123;
         let (state, match_id) = state_with_match(player_id, 456).await;
 
-        {
-            let arc = lock_lobby(&state, match_id).await;
-            let mut lobby = arc.lock().await;
-
+        with_lobby(&state, match_id, move |lobby| {
             // Spawn an entity for the player
             lobby.game_state.world.spawn((
                 Position { x: 100.0, y: 100.0 },
@@ -101,46 +109,43 @@ mod tests {
                 .game_state
                 .world
                 .spawn((Position { x: 200.0, y: 200.0 }, PlayerIdComponent(456)));
-        }
+        })
+        .await;
 
         remove_player_from_match(match_id, player_id, &state).await;
 
-        {
-            let arc = lock_lobby(&state, match_id).await;
-            let mut lobby = arc.lock().await;
-
+        let (owners, player_count) = with_lobby(&state, match_id, |lobby| {
             let mut query = lobby.game_state.world.query::<&PlayerIdComponent>();
             let owners: Vec<i64> = query.iter(&lobby.game_state.world).map(|o| o.0).collect();
+            (owners, lobby.players.len())
+        })
+        .await;
 
-            assert!(
-                !owners.contains(&player_id),
-                "Player's entity should be despawned"
-            );
-            assert!(owners.contains(&456), "Other player's entity should remain");
-            assert_eq!(
-                lobby.players.len(),
-                1,
-                "Player should be removed from lobby list"
-            );
-        }
+        assert!(
+            !owners.contains(&player_id),
+            "Player's entity should be despawned"
+        );
+        assert!(owners.contains(&456), "Other player's entity should remain");
+        assert_eq!(player_count, 1, "Player should be removed from lobby list");
     }
 
     #[tokio::test]
     async fn leaving_mid_game_declares_remaining_player_winner() {
         use crate::model::game_state::GamePhase;
         let (state, match_id) = state_with_match(1, 2).await;
-        {
-            let arc = lock_lobby(&state, match_id).await;
-            let mut lobby = arc.lock().await;
+        with_lobby(&state, match_id, |lobby| {
             lobby.game_state.phase = GamePhase::Combat;
-        }
+        })
+        .await;
 
         remove_player_from_match(match_id, 1, &state).await;
 
-        let arc = lock_lobby(&state, match_id).await;
-        let lobby = arc.lock().await;
-        assert_eq!(lobby.game_state.phase, GamePhase::GameOver);
-        assert_eq!(lobby.winner_id, Some(2));
+        let (phase, winner_id) = with_lobby(&state, match_id, |lobby| {
+            (lobby.game_state.phase, lobby.winner_id)
+        })
+        .await;
+        assert_eq!(phase, GamePhase::GameOver);
+        assert_eq!(winner_id, Some(2));
     }
 
     #[tokio::test]
@@ -153,11 +158,16 @@ mod tests {
             "match must survive while one player remains"
         );
 
+        let handle = state.matches.read().await[&match_id].clone();
         remove_player_from_match(match_id, 2, &state).await;
         assert!(
             !state.matches.read().await.contains_key(&match_id),
             "match must be destroyed when the last player leaves"
         );
+        assert!(
+            handle.with_lobby(|_| ()).await.is_none(),
+            "the shard must drop the lobby too"
+        );
     }
 
     #[tokio::test]
@@ -166,7 +176,7 @@ mod tests {
             .connect("sqlite::memory:")
             .await
             .unwrap();
-        let state = ServerStateData::new(db_pool);
+        let state = ServerStateData::new(db_pool, Config::default());
         // Must not panic.
         remove_player_from_match(999, 1, &state).await;
     }
@@ -178,43 +188,34 @@ mod tests {
         let loser_id = 2;
         let (state, match_id) = state_with_match(winner_id, loser_id).await;
 
-        {
-            let arc = lock_lobby(&state, match_id).await;
-            let mut lobby = arc.lock().await;
+        with_lobby(&state, match_id, move |lobby| {
             lobby.game_state.phase = GamePhase::GameOver;
             lobby.winner_id = Some(winner_id);
-        }
+        })
+        .await;
 
         // The loser leaves after the game already ended.
         remove_player_from_match(match_id, loser_id, &state).await;
 
-        {
-            let arc = lock_lobby(&state, match_id).await;
-            let lobby = arc.lock().await;
-            assert_eq!(
-                lobby.winner_id,
-                Some(winner_id),
-                "Winner must not change when the loser leaves a finished game"
-            );
-        }
+        let winner = with_lobby(&state, match_id, |lobby| lobby.winner_id).await;
+        assert_eq!(
+            winner,
+            Some(winner_id),
+            "Winner must not change when the loser leaves a finished game"
+        );
     }
 
-    #[tokio::test]
-    async fn test_leaving_pre_game_match_declares_no_winner() {
-        use crate::model::game_state::GamePhase;
-        let (state, match_id) = state_with_match(1, 2).await;
-        // Hold the Arc so the Lobby stays inspectable even after the map entry is
-        // removed when the last player leaves.
-        let arc = lock_lobby(&state, match_id).await;
-        {
-            let mut lobby = arc.lock().await;
-            // Simulate a not-yet-full match: only one player seated, no game started.
-            lobby.players.retain(|p| p.id == 1);
-        }
-
-        remove_player_from_match(match_id, 1, &state).await;
+    #[test]
+    fn test_leaving_pre_game_match_declares_no_winner() {
+        use crate::model::{game_state::GamePhase, player::Player};
+        // A not-yet-full match: only one player seated, no game started.
+        let mut lobby = Lobby::new();
+        lobby.add_player(Player::new(1, "p1".into(), 100));
 
-        let lobby = arc.lock().await;
+        assert!(
+            remove_player(&mut lobby, 1),
+            "last player leaving empties it"
+        );
         assert_eq!(lobby.winner_id, None, "No winner without an active game");
         assert_ne!(
             lobby.game_state.phase,
@@ -228,28 +229,27 @@ mod tests {
         let player_id = 789;
         let (state, match_id) = state_with_match(player_id, 2).await;
 
-        {
-            let arc = lock_lobby(&state, match_id).await;
-            let mut lobby = arc.lock().await;
+        with_lobby(&state, match_id, move |lobby| {
             lobby.game_state.world.spawn((
                 Position { x: 100.0, y: 100.0 },
                 PlayerIdComponent(player_id),
             ));
-        }
+        })
+        .await;
 
-        cleanup(match_id, player_id, &state).await;
-
-        {
-            let arc = lock_lobby(&state, match_id).await;
-            let mut lobby = arc.lock().await;
+        remove_player_from_match(match_id, player_id, &state).await;
 
+        let owners = with_lobby(&state, match_id, |lobby| {
             let mut query = lobby.game_state.world.query::<&PlayerIdComponent>();
-            let owners: Vec<i64> = query.iter(&lobby.game_state.world).map(|o| o.0).collect();
-
-            assert!(
-                !owners.contains(&player_id),
-                "Player's entity should be despawned during cleanup"
-            );
-        }
+            query
+                .iter(&lobby.game_state.world)
+                .map(|o| o.0)
+                .collect::<Vec<i64>>()
+        })
+        .await;
+        assert!(
+            !owners.contains(&player_id),
+            "Player's entity should be despawned during cleanup"
+        );
     }
 }
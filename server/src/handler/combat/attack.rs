@@ -2,9 +2,11 @@ use super::apply_damage;
 use super::get_board;
 use crate::model::components::{
     AttackRange, AttackStats, AttackTimer, Bounty, CombatProfile, Dead, DefenseStats, Enemy,
-    Health, InAttackRange, Mana, Position, Target,
+    Health, InAttackRange, Mana, PlayerIdComponent, Position, ShapeComponent, Target, Tower,
 };
-use crate::model::game_state::DeltaTime;
+use crate::model::game_state::{DeltaTime, GameTick};
+use crate::model::gold_ledger::GoldReason;
+use crate::model::match_stats::MatchStats;
 use crate::model::messages::CombatEvent;
 use crate::model::player::Players;
 use bevy_ecs::message::Messages;
@@ -85,20 +87,22 @@ fn execute_combat_round(world: &mut World, tick_delta: f32) -> Vec<CombatEvent>
         let mut new_timer = (timer.0 - tick_delta).max(0.0);
 
         // Try to attack if in range and timer is 0
-        if in_range_opt.is_some() && new_timer <= 0.0 {
-            if let Some(target) = target_opt {
-                // Deduct mana if it was a primary attack with cost
-                if let (Some(profile), Some(mana)) = (profile_opt, mana_opt) {
-                    if profile.mana_cost > 0.0 && mana.current >= profile.mana_cost {
-                        mana_updates.push((attacker_entity, mana.current - profile.mana_cost));
-                    }
-                }
-
-                // Record attack
-                attacks.push((attacker_entity, target.0, stats.damage, stats.damage_type));
-                // Reset timer: 1.0 / rate
-                new_timer = 1.0 / stats.rate;
+        if in_range_opt.is_some()
+            && new_timer <= 0.0
+            && let Some(target) = target_opt
+        {
+            // Deduct mana if it was a primary attack with cost
+            if let (Some(profile), Some(mana)) = (profile_opt, mana_opt)
+                && profile.mana_cost > 0.0
+                && mana.current >= profile.mana_cost
+            {
+                mana_updates.push((attacker_entity, mana.current - profile.mana_cost));
             }
+
+            // Record attack
+            attacks.push((attacker_entity, target.0, stats.damage, stats.damage_type));
+            // Reset timer: 1.0 / rate
+            new_timer = 1.0 / stats.rate;
         }
 
         timer_updates.push((attacker_entity, new_timer));
@@ -146,9 +150,22 @@ fn execute_combat_round(world: &mut World, tick_delta: f32) -> Vec<CombatEvent>
             .unwrap_or_default();
         let mitigated = apply_damage(damage, damage_type, &defense);
 
+        let mut dealt = 0.0;
         if let Some(mut health) = world.get_mut::<Health>(target_entity) {
+            dealt = mitigated.min(health.current.max(0.0));
             health.current -= mitigated;
         }
+
+        // Tower damage counts toward its owner's match stats.
+        if world.get::<Tower>(attacker_entity).is_some()
+            && let (Some(owner), Some(shape)) = (
+                world.get::<PlayerIdComponent>(attacker_entity).map(|p| p.0),
+                world.get::<ShapeComponent>(attacker_entity).map(|s| s.0),
+            )
+            && let Some(mut stats) = world.get_resource_mut::<MatchStats>()
+        {
+            stats.player(owner).add_damage(shape, dealt);
+        }
     }
 
     combat_events
@@ -188,16 +205,27 @@ pub fn cleanup_dead_entities(world: &mut World) {
         }
     }
 
-    // Award bounty gold to the defending player before despawning.
+    // Award bounty gold to the defending player before despawning; every
+    // kill is credited to them in `MatchStats`.
+    let tick = world.get_resource::<GameTick>().map_or(0, |t| t.0);
+    let mut killers = Vec::new();
     if let Some(mut players) = world.get_resource_mut::<Players>() {
         for (_, bounty, board) in &enemies_to_despawn {
-            if let (Some(b), Some(board_idx)) = (bounty, board) {
-                if let Some(player) = players.0.get_mut(*board_idx as usize) {
-                    player.gold += b.0;
+            if let Some(board_idx) = board
+                && let Some(player) = players.0.get_mut(*board_idx as usize)
+            {
+                if let Some(b) = bounty {
+                    player.add_gold(b.0, GoldReason::Bounty, tick);
                 }
+                killers.push(player.id);
             }
         }
     }
+    if let Some(mut stats) = world.get_resource_mut::<MatchStats>() {
+        for player_id in killers {
+            stats.player(player_id).creeps_killed += 1;
+        }
+    }
 
     for (entity, _, _) in enemies_to_despawn {
         world.despawn(entity);
@@ -512,11 +540,14 @@ mod tests {
         );
 
         // 1. Targeting
+        crate::handler::combat::rebuild_spatial_grid(&mut world);
         crate::handler::combat::update_targeting(&mut world);
         assert_eq!(world.entity(unit).get::<Target>().unwrap().0, enemy);
 
         // 2. Movement - multiple ticks until in range
         for _ in 0..100 {
+            crate::handler::combat::rebuild_spatial_grid(&mut world);
+            crate::handler::combat::rebuild_flow_field(&mut world);
             crate::handler::combat::update_combat_movement(&mut world);
             crate::handler::combat::update_attack_range_markers(&mut world);
         }
@@ -540,6 +571,7 @@ mod tests {
         );
 
         // 5. Re-targeting - Unit should lose target
+        crate::handler::combat::rebuild_spatial_grid(&mut world);
         crate::handler::combat::update_targeting(&mut world);
         assert!(
             world.entity(unit).get::<Target>().is_none(),
@@ -840,11 +872,13 @@ mod tests {
 
         let players = world.resource::<Players>();
         assert_eq!(
-            players.0[0].gold, 120,
+            players.0[0].gold(),
+            120,
             "Left board defender should receive 20 bounty gold"
         );
         assert_eq!(
-            players.0[1].gold, 100,
+            players.0[1].gold(),
+            100,
             "Right board defender should not receive bounty gold"
         );
     }
@@ -880,11 +914,13 @@ mod tests {
 
         let players = world.resource::<Players>();
         assert_eq!(
-            players.0[0].gold, 100,
+            players.0[0].gold(),
+            100,
             "Left board defender should not receive bounty gold"
         );
         assert_eq!(
-            players.0[1].gold, 110,
+            players.0[1].gold(),
+            110,
             "Right board defender should receive 10 bounty gold"
         );
     }
@@ -914,7 +950,8 @@ mod tests {
 
         let players = world.resource::<Players>();
         assert_eq!(
-            players.0[0].gold, 100,
+            players.0[0].gold(),
+            100,
             "Regular enemy kill should award no bounty gold"
         );
     }
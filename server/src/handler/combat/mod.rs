@@ -1,11 +1,15 @@
 mod attack;
 mod damage;
 mod movement;
+mod pathing;
+mod spatial;
 mod targeting;
 
 pub use attack::{cleanup_dead_entities, process_combat, update_active_combat_stats, update_mana};
 pub use damage::apply_damage;
 pub use movement::{update_combat_movement, update_combat_reset};
+pub use pathing::{FlowField, placement_blocks_path, rebuild_flow_field};
+pub use spatial::{SpatialGrid, rebuild_spatial_grid};
 pub use targeting::{update_attack_range_markers, update_leaked_creeps, update_targeting};
 
 use crate::model::constants::{LEFT_BOARD_END, RIGHT_BOARD_END, RIGHT_BOARD_START};
@@ -20,7 +24,7 @@ pub const LEAK_PENALTY_WAVE_CAP: u32 = 50;
 pub(super) fn get_board(x: f32) -> Option<u8> {
     if x < LEFT_BOARD_END {
         Some(0)
-    } else if x >= RIGHT_BOARD_START && x < RIGHT_BOARD_END {
+    } else if (RIGHT_BOARD_START..RIGHT_BOARD_END).contains(&x) {
         Some(1)
     } else {
         None
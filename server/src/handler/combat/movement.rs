@@ -1,4 +1,4 @@
-use super::{SPEED, get_board};
+use super::{FlowField, SPEED, SpatialGrid, get_board};
 use crate::model::components::{
     AttackRange, CollisionRadius, Dead, Enemy, Health, HomePosition, King, Mana, Position, Target,
     Worker,
@@ -10,11 +10,6 @@ use bevy_ecs::prelude::{Entity, With, Without, World};
 pub fn update_combat_movement(world: &mut World) {
     let tick_delta = world.resource::<DeltaTime>().0;
     // --- MOVEMENT & COLLISION SYSTEM ---
-    let physical_entities: Vec<(Entity, Position, f32)> = world
-        .query_filtered::<(Entity, &Position, &CollisionRadius), (Without<Worker>, Without<Dead>)>()
-        .iter(world)
-        .map(|(e, p, r)| (e, *p, r.0))
-        .collect();
 
     // Collect king entities for leaked enemy targeting logic
     let king_entities: Vec<Entity> = world
@@ -33,6 +28,20 @@ pub fn update_combat_movement(world: &mut World) {
         &CollisionRadius,
         Option<&Enemy>,
     ), (Without<Worker>, Without<King>, Without<Dead>)>();
+    // Neighbour positions come from the tick's grid, a snapshot taken before
+    // anything moves.
+    let grid = world.resource::<SpatialGrid>();
+    let flow = world.resource::<FlowField>();
+    // A chase target must be a living, non-worker entity that can collide.
+    let physical = |entity: Entity| -> Option<(Position, f32)> {
+        if world.get::<Worker>(entity).is_some() || world.get::<Dead>(entity).is_some() {
+            return None;
+        }
+        Some((
+            *world.get::<Position>(entity)?,
+            world.get::<CollisionRadius>(entity)?.0,
+        ))
+    };
     for (entity, pos, target_opt, attack_range_opt, collision_radius, enemy_opt) in
         query.iter(world)
     {
@@ -41,11 +50,7 @@ pub fn update_combat_movement(world: &mut World) {
 
         // 1. Chasing Force & Range Gating
         if let Some(target) = target_opt {
-            if let Some(target_pos) = physical_entities
-                .iter()
-                .find(|(e, _, _)| *e == target.0)
-                .map(|(_, p, _)| p)
-            {
+            if let Some((target_pos, target_radius)) = physical(target.0) {
                 let dx = target_pos.x - pos.x;
                 let dy = target_pos.y - pos.y;
                 let distance = (dx * dx + dy * dy).sqrt();
@@ -66,11 +71,6 @@ pub fn update_combat_movement(world: &mut World) {
                         range
                     } else {
                         // In-lane enemies chase to contact distance (original behavior)
-                        let target_radius = physical_entities
-                            .iter()
-                            .find(|(e, _, _)| *e == target.0)
-                            .map(|(_, _, r)| *r)
-                            .unwrap_or(0.0);
                         let contact_distance = collision_radius.0 + target_radius;
                         // Chase to contact OR attack range, whichever is closer
                         contact_distance.min(range)
@@ -85,27 +85,36 @@ pub fn update_combat_movement(world: &mut World) {
                 }
             }
         } else {
-            // No target: enemies drift downward towards the opponent's base
+            // No target: enemies follow the flow field around towers towards the
+            // opponent's base.
             if enemy_opt.is_some() {
-                velocity_y += SPEED;
+                let (dir_x, dir_y) = flow.direction(*pos);
+                velocity_x += dir_x * SPEED;
+                velocity_y += dir_y * SPEED;
             }
         }
 
         // 2. Separation Force
         // Leaked enemies targeting the king use reduced separation to allow them to close to attack range.
         // Without this, separation forces cancel out the chasing force and enemies get stuck beyond range.
-        let use_reduced_separation = if enemy_opt.is_some() && target_opt.is_some() {
-            let is_leaked = pos.y >= TOTAL_HEIGHT;
-            let is_targeting_king = king_entities.contains(&target_opt.unwrap().0);
-            is_leaked && is_targeting_king
-        } else {
-            false
+        let use_reduced_separation = match target_opt {
+            Some(target) if enemy_opt.is_some() => {
+                let is_leaked = pos.y >= TOTAL_HEIGHT;
+                let is_targeting_king = king_entities.contains(&target.0);
+                is_leaked && is_targeting_king
+            }
+            _ => false,
         };
 
-        for (other_entity, other_pos, other_radius) in &physical_entities {
-            if entity == *other_entity {
+        let reach = collision_radius.0 + grid.max_radius();
+        for other in grid.candidates_near(*pos, reach) {
+            let Some(other_radius) = other.radius else {
+                continue;
+            };
+            if entity == other.entity || other.dead {
                 continue;
             }
+            let other_pos = other.pos;
 
             let dx = pos.x - other_pos.x;
             let dy = pos.y - other_pos.y;
@@ -211,10 +220,10 @@ pub fn update_combat_reset(world: &mut World) {
 
     // Pass 2: restore position, health, mana and remove Dead marker for each entity
     for entity in entities_to_restore {
-        if let Some(home_pos) = world.get::<HomePosition>(entity).map(|h| h.0) {
-            if let Some(mut pos) = world.get_mut::<Position>(entity) {
-                *pos = home_pos;
-            }
+        if let Some(home_pos) = world.get::<HomePosition>(entity).map(|h| h.0)
+            && let Some(mut pos) = world.get_mut::<Position>(entity)
+        {
+            *pos = home_pos;
         }
 
         if let Some(mut health) = world.get_mut::<Health>(entity) {
@@ -236,7 +245,9 @@ pub fn update_combat_reset(world: &mut World) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::handler::combat::{update_attack_range_markers, update_leaked_creeps};
+    use crate::handler::combat::{
+        rebuild_flow_field, rebuild_spatial_grid, update_attack_range_markers, update_leaked_creeps,
+    };
     use crate::model::components::InAttackRange;
     use crate::model::unit_kind::UnitKind;
 
@@ -271,6 +282,8 @@ mod tests {
         world
             .entity_mut(enemy)
             .insert(Position { x: 100.0, y: 0.0 });
+        rebuild_spatial_grid(&mut world);
+        rebuild_flow_field(&mut world);
         update_combat_movement(&mut world);
         let pos = world.entity(unit).get::<Position>().unwrap();
         assert!(pos.x > 0.0, "Unit should move towards enemy when far away");
@@ -281,6 +294,8 @@ mod tests {
         world
             .entity_mut(enemy)
             .insert(Position { x: 100.0, y: 0.0 });
+        rebuild_spatial_grid(&mut world);
+        rebuild_flow_field(&mut world);
         update_combat_movement(&mut world);
         let pos = world.entity(unit).get::<Position>().unwrap();
         assert_eq!(pos.x, 60.0, "Unit should NOT move when within attack range");
@@ -290,6 +305,8 @@ mod tests {
         world
             .entity_mut(enemy)
             .insert(Position { x: 100.0, y: 0.0 });
+        rebuild_spatial_grid(&mut world);
+        rebuild_flow_field(&mut world);
         update_combat_movement(&mut world);
         let pos = world.entity(unit).get::<Position>().unwrap();
         assert_eq!(
@@ -322,6 +339,8 @@ mod tests {
 
         let tick_delta = 1.0 / 30.0;
         world.insert_resource(DeltaTime(tick_delta));
+        rebuild_spatial_grid(&mut world);
+        rebuild_flow_field(&mut world);
         update_combat_movement(&mut world);
 
         let pos_a = world.entity(unit_a).get::<Position>().unwrap();
@@ -366,6 +385,8 @@ mod tests {
 
         // Run many ticks to let separation force push it
         for _ in 0..100 {
+            rebuild_spatial_grid(&mut world);
+            rebuild_flow_field(&mut world);
             update_combat_movement(&mut world);
         }
 
@@ -472,6 +493,8 @@ mod tests {
             .insert(CollisionRadius(radius));
 
         world.insert_resource(DeltaTime(1.0 / 30.0));
+        rebuild_spatial_grid(&mut world);
+        rebuild_flow_field(&mut world);
         update_combat_movement(&mut world);
 
         // Dead tower should not have moved
@@ -508,6 +531,8 @@ mod tests {
         let _unit = world.spawn((unit_pos, CollisionRadius(10.0))).id();
 
         world.insert_resource(DeltaTime(0.1));
+        rebuild_spatial_grid(&mut world);
+        rebuild_flow_field(&mut world);
         update_combat_movement(&mut world);
 
         // Assert Worker position is UNCHANGED (not clamped to 600, not pushed by unit)
@@ -538,6 +563,8 @@ mod tests {
             .id();
 
         world2.insert_resource(DeltaTime(0.1));
+        rebuild_spatial_grid(&mut world2);
+        rebuild_flow_field(&mut world2);
         update_combat_movement(&mut world2);
 
         let final_unit2_pos = world2.entity(unit2).get::<Position>().unwrap();
@@ -799,6 +826,9 @@ mod tests {
         );
         world.entity_mut(unit).insert(Target(enemy));
 
+        rebuild_spatial_grid(&mut world);
+
+        rebuild_flow_field(&mut world);
         update_combat_movement(&mut world);
 
         let pos = world.entity(unit).get::<Position>().unwrap();
@@ -822,6 +852,8 @@ mod tests {
         );
 
         let y_before = world.entity(enemy).get::<Position>().unwrap().y;
+        rebuild_spatial_grid(&mut world);
+        rebuild_flow_field(&mut world);
         update_combat_movement(&mut world);
         let y_after = world.entity(enemy).get::<Position>().unwrap().y;
 
@@ -860,6 +892,8 @@ mod tests {
 
         // Run movement for several ticks to let enemies approach the king
         for _ in 0..100 {
+            rebuild_spatial_grid(&mut world);
+            rebuild_flow_field(&mut world);
             update_combat_movement(&mut world);
             update_attack_range_markers(&mut world);
         }
@@ -904,6 +938,8 @@ mod tests {
 
         // Run movement for many ticks to let separation forces push enemies apart
         for _ in 0..200 {
+            rebuild_spatial_grid(&mut world);
+            rebuild_flow_field(&mut world);
             update_combat_movement(&mut world);
             update_attack_range_markers(&mut world);
         }
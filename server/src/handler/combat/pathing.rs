@@ -0,0 +1,267 @@
+use super::get_board;
+use crate::model::components::{Dead, HomePosition, Position, Tower};
+use crate::model::constants::{BOARD_SIZE, RIGHT_BOARD_START, SQUARE_SIZE, TOTAL_HEIGHT};
+use bevy_ecs::prelude::{Resource, With, Without, World};
+use std::collections::VecDeque;
+
+const COLS: usize = (BOARD_SIZE / SQUARE_SIZE) as usize;
+const ROWS: usize = (TOTAL_HEIGHT / SQUARE_SIZE) as usize;
+const BOARD_ORIGINS: [f32; 2] = [0.0, RIGHT_BOARD_START];
+/// Cells wave and sent creeps spawn in (top row, either side of the board's
+/// centre line). They must always be able to reach the bottom row.
+const SPAWN_CELLS: [(usize, usize); 2] = [(COLS / 2 - 1, 0), (COLS / 2, 0)];
+/// Neighbour order doubles as the tie-break: prefer heading straight down.
+const STEPS: [(isize, isize); 4] = [(0, 1), (-1, 0), (1, 0), (0, -1)];
+
+type Cells<T> = [[T; COLS]; ROWS];
+
+/// Breadth-first distances (in cells) from every cell of one board to its
+/// bottom row, where creeps leave the lane for the king zone.
+#[derive(Debug)]
+struct BoardPaths {
+    blocked: Cells<bool>,
+    dist: Cells<Option<u16>>,
+}
+
+impl BoardPaths {
+    fn new(blocked: Cells<bool>) -> Self {
+        let mut dist = [[None; COLS]; ROWS];
+        let mut frontier = VecDeque::new();
+        for col in 0..COLS {
+            if !blocked[ROWS - 1][col] {
+                dist[ROWS - 1][col] = Some(0);
+                frontier.push_back((col, ROWS - 1));
+            }
+        }
+        while let Some((col, row)) = frontier.pop_front() {
+            let next = dist[row][col].map(|d| d + 1);
+            for (n_col, n_row) in neighbours(col, row) {
+                if !blocked[n_row][n_col] && dist[n_row][n_col].is_none() {
+                    dist[n_row][n_col] = next;
+                    frontier.push_back((n_col, n_row));
+                }
+            }
+        }
+        Self { blocked, dist }
+    }
+
+    /// Whether every open spawn cell, and at least one, can reach the exit.
+    fn spawn_connected(&self) -> bool {
+        let open: Vec<_> = SPAWN_CELLS
+            .iter()
+            .filter(|&&(col, row)| !self.blocked[row][col])
+            .collect();
+        !open.is_empty()
+            && open
+                .iter()
+                .all(|&&(col, row)| self.dist[row][col].is_some())
+    }
+
+    /// The cell a creep in `(col, row)` should head for next. Creeps pushed
+    /// into a tower's cell step out towards the closest open neighbour.
+    /// `None` in the bottom row (keep going down) and in cells with no route.
+    fn next_cell(&self, col: usize, row: usize) -> Option<(usize, usize)> {
+        if row == ROWS - 1 && !self.blocked[row][col] {
+            return None;
+        }
+        let mut best: Option<((usize, usize), u16)> = None;
+        for (n_col, n_row) in neighbours(col, row) {
+            if let Some(d) = self.dist[n_row][n_col]
+                && best.is_none_or(|(_, best_d)| d < best_d)
+            {
+                best = Some(((n_col, n_row), d));
+            }
+        }
+        let ((n_col, n_row), d) = best?;
+        match self.dist[row][col] {
+            Some(here) if d >= here => None,
+            _ => Some((n_col, n_row)),
+        }
+    }
+}
+
+/// In-bounds 4-neighbours of a cell, in `STEPS` order.
+fn neighbours(col: usize, row: usize) -> impl Iterator<Item = (usize, usize)> {
+    STEPS.iter().filter_map(move |&(dc, dr)| {
+        let n_col = col.checked_add_signed(dc)?;
+        let n_row = row.checked_add_signed(dr)?;
+        (n_col < COLS && n_row < ROWS).then_some((n_col, n_row))
+    })
+}
+
+/// Board-local `(col, row)` of a lane position, or `None` outside the lane
+/// (the gap between boards, the king zone).
+fn cell_of(board: usize, pos: Position) -> Option<(usize, usize)> {
+    let local_x = pos.x - BOARD_ORIGINS[board];
+    if !(0.0..BOARD_SIZE).contains(&local_x) || !(0.0..TOTAL_HEIGHT).contains(&pos.y) {
+        return None;
+    }
+    Some((
+        (local_x / SQUARE_SIZE) as usize,
+        (pos.y / SQUARE_SIZE) as usize,
+    ))
+}
+
+fn cell_center(board: usize, (col, row): (usize, usize)) -> Position {
+    Position {
+        x: BOARD_ORIGINS[board] + (col as f32 + 0.5) * SQUARE_SIZE,
+        y: (row as f32 + 0.5) * SQUARE_SIZE,
+    }
+}
+
+/// Cells anchored by living towers, per board.
+fn tower_cells(world: &mut World) -> [Cells<bool>; 2] {
+    let mut blocked = [[[false; COLS]; ROWS]; 2];
+    let mut query = world.query_filtered::<&HomePosition, (With<Tower>, Without<Dead>)>();
+    for home in query.iter(world) {
+        if let Some(board) = get_board(home.0.x)
+            && let Some((col, row)) = cell_of(board as usize, home.0)
+        {
+            blocked[board as usize][row][col] = true;
+        }
+    }
+    blocked
+}
+
+/// Per-board flow field over the placement grid: towers are walls and every
+/// open cell knows its shortest route to the bottom of the lane. Inserted
+/// empty by `Lobby::new` and rebuilt each combat tick by
+/// [`rebuild_flow_field`] so a tower dying opens its cell.
+#[derive(Resource, Debug)]
+pub struct FlowField {
+    boards: [BoardPaths; 2],
+}
+
+impl Default for FlowField {
+    fn default() -> Self {
+        Self {
+            boards: [0, 1].map(|_| BoardPaths::new([[false; COLS]; ROWS])),
+        }
+    }
+}
+
+impl FlowField {
+    pub fn build(world: &mut World) -> Self {
+        Self {
+            boards: tower_cells(world).map(BoardPaths::new),
+        }
+    }
+
+    /// Unit vector a target-less creep at `pos` should move along. Straight
+    /// down outside the lane, in the bottom row and when walled in.
+    pub fn direction(&self, pos: Position) -> (f32, f32) {
+        let next = get_board(pos.x).and_then(|board| {
+            let board = board as usize;
+            let (col, row) = cell_of(board, pos)?;
+            let next = self.boards[board].next_cell(col, row)?;
+            Some(cell_center(board, next))
+        });
+        match next {
+            // Keep straight-down moves on the creep's own line; only turns
+            // steer towards the next cell's centre.
+            Some(target) if (target.x - pos.x).abs() >= SQUARE_SIZE / 2.0 || target.y < pos.y => {
+                let (dx, dy) = (target.x - pos.x, target.y - pos.y);
+                let len = (dx * dx + dy * dy).sqrt();
+                (dx / len, dy / len)
+            }
+            _ => (0.0, 1.0),
+        }
+    }
+}
+
+/// Rebuilds the [`FlowField`] resource from current tower positions. Runs
+/// before movement each combat tick.
+pub fn rebuild_flow_field(world: &mut World) {
+    let field = FlowField::build(world);
+    world.insert_resource(field);
+}
+
+/// Whether placing a tower in `(col, row)` of `board` would cut the spawn
+/// off from the bottom of the lane.
+pub fn placement_blocks_path(world: &mut World, board: usize, col: usize, row: usize) -> bool {
+    let mut blocked = tower_cells(world)[board];
+    blocked[row][col] = true;
+    !BoardPaths::new(blocked).spawn_connected()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wall(world: &mut World, board: usize, cells: &[(usize, usize)]) {
+        for &cell in cells {
+            world.spawn((Tower, HomePosition(cell_center(board, cell))));
+        }
+    }
+
+    #[test]
+    fn open_board_drifts_straight_down() {
+        let mut world = World::new();
+        let field = FlowField::build(&mut world);
+        for pos in [
+            Position { x: 17.0, y: 30.0 },
+            Position {
+                x: RIGHT_BOARD_START + 333.0,
+                y: 400.0,
+            },
+        ] {
+            assert_eq!(field.direction(pos), (0.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn creeps_route_around_a_wall_through_its_gap() {
+        let mut world = World::new();
+        // Row 3 walled off except column 9.
+        let cells: Vec<_> = (0..9).map(|col| (col, 3)).collect();
+        wall(&mut world, 0, &cells);
+        let field = FlowField::build(&mut world);
+
+        // Directly above the wall: head right, towards the gap.
+        let (dx, dy) = field.direction(cell_center(0, (4, 2)));
+        assert!(dx > 0.9 && dy.abs() < 0.1, "got ({dx}, {dy})");
+        // In the gap column: straight down.
+        assert_eq!(field.direction(cell_center(0, (9, 2))), (0.0, 1.0));
+        // The other board is unaffected.
+        assert_eq!(field.direction(cell_center(1, (4, 2))), (0.0, 1.0));
+    }
+
+    #[test]
+    fn dead_towers_do_not_block() {
+        let mut world = World::new();
+        let cells: Vec<_> = (0..10).map(|col| (col, 3)).collect();
+        wall(&mut world, 0, &cells);
+        let dead = world
+            .spawn((Tower, Dead, HomePosition(cell_center(0, (9, 4)))))
+            .id();
+        let field = FlowField::build(&mut world);
+        assert!(!field.boards[0].spawn_connected());
+        assert!(
+            field.boards[0].dist[4][9].is_some(),
+            "{dead:?} must not block"
+        );
+    }
+
+    #[test]
+    fn placement_that_seals_the_lane_is_rejected() {
+        let mut world = World::new();
+        let cells: Vec<_> = (0..9).map(|col| (col, 3)).collect();
+        wall(&mut world, 1, &cells);
+        assert!(placement_blocks_path(&mut world, 1, 9, 3));
+        assert!(!placement_blocks_path(&mut world, 1, 0, 5));
+        assert!(
+            !placement_blocks_path(&mut world, 0, 9, 3),
+            "other board is open"
+        );
+    }
+
+    #[test]
+    fn placement_that_boxes_in_the_spawn_is_rejected() {
+        let mut world = World::new();
+        // Enclose spawn cells (4,0) and (5,0) from below and the sides.
+        wall(&mut world, 0, &[(3, 0), (6, 0), (4, 1)]);
+        assert!(placement_blocks_path(&mut world, 0, 5, 1));
+        // Covering one spawn cell is fine while the other still has a route.
+        assert!(!placement_blocks_path(&mut world, 0, 4, 0));
+    }
+}
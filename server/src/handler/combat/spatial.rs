@@ -0,0 +1,287 @@
+use super::get_board;
+use crate::model::components::{CollisionRadius, Dead, Enemy, Position, Tower, Worker};
+use crate::model::constants::{BOARD_SIZE, KING_Y, RIGHT_BOARD_START, SQUARE_SIZE};
+use crate::model::king_config::KING_COLLISION_RADIUS;
+use bevy_ecs::prelude::{Entity, Has, Resource, Without, World};
+
+/// Edge length of one grid cell: one placement square.
+const CELL_SIZE: f32 = SQUARE_SIZE;
+const COLS: usize = (BOARD_SIZE / CELL_SIZE) as usize;
+/// Rows cover the lane plus the king zone below it.
+const ROWS: usize = ((KING_Y + KING_COLLISION_RADIUS) / CELL_SIZE) as usize + 1;
+const BOARD_ORIGINS: [f32; 2] = [0.0, RIGHT_BOARD_START];
+
+/// What the combat systems need to know about an indexed entity, copied out
+/// when the grid is rebuilt.
+#[derive(Clone, Copy, Debug)]
+pub struct GridEntry {
+    pub entity: Entity,
+    pub pos: Position,
+    /// `None` for entities without a `CollisionRadius` (they never collide).
+    pub radius: Option<f32>,
+    pub enemy: bool,
+    pub tower: bool,
+    pub dead: bool,
+}
+
+#[derive(Debug)]
+struct BoardGrid {
+    origin_x: f32,
+    cells: Vec<Vec<GridEntry>>,
+}
+
+impl BoardGrid {
+    fn new(origin_x: f32) -> Self {
+        Self {
+            origin_x,
+            cells: vec![Vec::new(); COLS * ROWS],
+        }
+    }
+
+    /// Cell coordinates of `(x, y)`, clamped to the grid. Clamping keeps
+    /// range queries exact: anything past an edge lands in the edge cell,
+    /// which every query reaching that edge also visits.
+    fn cell(&self, x: f32, y: f32) -> (usize, usize) {
+        let col = ((x - self.origin_x) / CELL_SIZE)
+            .floor()
+            .clamp(0.0, (COLS - 1) as f32);
+        let row = (y / CELL_SIZE).floor().clamp(0.0, (ROWS - 1) as f32);
+        (col as usize, row as usize)
+    }
+
+    fn cell_entries(&self, col: usize, row: usize) -> &[GridEntry] {
+        &self.cells[row * COLS + col]
+    }
+}
+
+/// Per-board uniform grid over every non-worker entity with a `Position`.
+/// Inserted empty by `Lobby::new` and rebuilt once per combat tick by
+/// [`rebuild_spatial_grid`] so separation,
+/// closest-target search and area effects only look at nearby cells instead
+/// of every entity on the field.
+#[derive(Resource, Debug)]
+pub struct SpatialGrid {
+    boards: [BoardGrid; 2],
+    /// Entities in the gap between the boards; always visited by range queries.
+    outside: Vec<GridEntry>,
+    /// Largest `CollisionRadius` indexed, so overlap queries know how far to look.
+    max_radius: f32,
+}
+
+impl Default for SpatialGrid {
+    fn default() -> Self {
+        Self {
+            boards: BOARD_ORIGINS.map(BoardGrid::new),
+            outside: Vec::new(),
+            max_radius: 0.0,
+        }
+    }
+}
+
+impl SpatialGrid {
+    pub fn build(world: &mut World) -> Self {
+        let mut grid = Self::default();
+        let mut query = world.query_filtered::<(
+            Entity,
+            &Position,
+            Option<&CollisionRadius>,
+            Has<Enemy>,
+            Has<Tower>,
+            Has<Dead>,
+        ), Without<Worker>>();
+        for (entity, pos, radius, enemy, tower, dead) in query.iter(world) {
+            grid.insert(GridEntry {
+                entity,
+                pos: *pos,
+                radius: radius.map(|r| r.0),
+                enemy,
+                tower,
+                dead,
+            });
+        }
+        grid
+    }
+
+    fn insert(&mut self, entry: GridEntry) {
+        if let Some(radius) = entry.radius {
+            self.max_radius = self.max_radius.max(radius);
+        }
+        match get_board(entry.pos.x) {
+            Some(board) => {
+                let board = &mut self.boards[board as usize];
+                let (col, row) = board.cell(entry.pos.x, entry.pos.y);
+                board.cells[row * COLS + col].push(entry);
+            }
+            None => self.outside.push(entry),
+        }
+    }
+
+    /// The largest collision radius in the grid.
+    pub fn max_radius(&self) -> f32 {
+        self.max_radius
+    }
+
+    /// Every entry whose cell overlaps the square of half-width `radius`
+    /// around `pos`. A superset of the entries within `radius`: callers
+    /// still check the exact distance.
+    pub fn candidates_near(
+        &self,
+        pos: Position,
+        radius: f32,
+    ) -> impl Iterator<Item = &GridEntry> + '_ {
+        let (min_x, max_x) = (pos.x - radius, pos.x + radius);
+        let boards = self
+            .boards
+            .iter()
+            .filter(move |b| max_x >= b.origin_x && min_x < b.origin_x + BOARD_SIZE);
+        boards
+            .flat_map(move |board| {
+                let (col_lo, row_lo) = board.cell(min_x, pos.y - radius);
+                let (col_hi, row_hi) = board.cell(max_x, pos.y + radius);
+                (row_lo..=row_hi).flat_map(move |row| {
+                    (col_lo..=col_hi).flat_map(move |col| board.cell_entries(col, row))
+                })
+            })
+            .chain(&self.outside)
+    }
+
+    /// The entry on `board` closest to `pos` that satisfies `filter`. Searches
+    /// outward ring by ring and stops once no farther ring can beat the best
+    /// hit. Ties go to the lower `Entity`, so results do not depend on
+    /// insertion order.
+    pub fn nearest(
+        &self,
+        board: u8,
+        pos: Position,
+        filter: impl Fn(&GridEntry) -> bool,
+    ) -> Option<Entity> {
+        let grid = &self.boards[board as usize];
+        let (center_col, center_row) = grid.cell(pos.x, pos.y);
+        let mut best: Option<(f32, Entity)> = None;
+        for ring in 0..COLS.max(ROWS) {
+            // Anything in this ring or beyond is at least `ring - 1` cells away.
+            let reach = ring.saturating_sub(1) as f32 * CELL_SIZE;
+            if best.is_some_and(|(d, _)| d < reach * reach) {
+                break;
+            }
+            for (col, row) in ring_cells(center_col, center_row, ring) {
+                for entry in grid.cell_entries(col, row) {
+                    if !filter(entry) {
+                        continue;
+                    }
+                    let candidate = (distance_sq(pos, entry.pos), entry.entity);
+                    if best.is_none_or(|b| candidate < b) {
+                        best = Some(candidate);
+                    }
+                }
+            }
+        }
+        best.map(|(_, entity)| entity)
+    }
+}
+
+/// In-bounds cells at Chebyshev distance exactly `ring` from the center cell.
+fn ring_cells(
+    center_col: usize,
+    center_row: usize,
+    ring: usize,
+) -> impl Iterator<Item = (usize, usize)> {
+    let (col, row, ring) = (center_col as isize, center_row as isize, ring as isize);
+    ((row - ring)..=(row + ring))
+        .flat_map(move |r| ((col - ring)..=(col + ring)).map(move |c| (c, r)))
+        .filter(move |&(c, r)| (c - col).abs().max((r - row).abs()) == ring)
+        .filter(|&(c, r)| c >= 0 && r >= 0 && (c as usize) < COLS && (r as usize) < ROWS)
+        .map(|(c, r)| (c as usize, r as usize))
+}
+
+fn distance_sq(a: Position, b: Position) -> f32 {
+    (a.x - b.x).powi(2) + (a.y - b.y).powi(2)
+}
+
+/// Rebuilds the [`SpatialGrid`] resource from current positions. Runs at the
+/// start of each combat tick, before targeting and movement read it.
+pub fn rebuild_spatial_grid(world: &mut World) {
+    let grid = SpatialGrid::build(world);
+    world.insert_resource(grid);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::constants::RIGHT_BOARD_END;
+
+    fn spawn_enemy(world: &mut World, x: f32, y: f32) -> Entity {
+        world
+            .spawn((Position { x, y }, Enemy, CollisionRadius(10.0)))
+            .id()
+    }
+
+    /// Linear scan the grid must agree with.
+    fn brute_nearest(world: &mut World, board: u8, pos: Position) -> Option<Entity> {
+        let mut query =
+            world.query_filtered::<(Entity, &Position), bevy_ecs::prelude::With<Enemy>>();
+        query
+            .iter(world)
+            .filter(|(_, p)| get_board(p.x) == Some(board))
+            .map(|(e, p)| (distance_sq(pos, *p), e))
+            .min_by(|a, b| a.partial_cmp(b).unwrap())
+            .map(|(_, e)| e)
+    }
+
+    #[test]
+    fn nearest_matches_a_linear_scan() {
+        let mut world = World::new();
+        // Deterministic scatter over both boards, including the king zone.
+        let mut seed = 17u32;
+        let mut next = |max: f32| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (seed >> 8) as f32 / (1u32 << 24) as f32 * max
+        };
+        for _ in 0..200 {
+            let x = next(RIGHT_BOARD_END);
+            let y = next(KING_Y + KING_COLLISION_RADIUS);
+            if get_board(x).is_some() {
+                spawn_enemy(&mut world, x, y);
+            }
+        }
+        let grid = SpatialGrid::build(&mut world);
+        for _ in 0..100 {
+            let pos = Position {
+                x: next(BOARD_SIZE),
+                y: next(KING_Y),
+            };
+            for (board, origin) in BOARD_ORIGINS.iter().enumerate() {
+                let pos = Position {
+                    x: pos.x + origin,
+                    ..pos
+                };
+                assert_eq!(
+                    grid.nearest(board as u8, pos, |e| e.enemy),
+                    brute_nearest(&mut world, board as u8, pos)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_stays_on_the_requested_board() {
+        let mut world = World::new();
+        // Closer in x, but on the other board.
+        spawn_enemy(&mut world, RIGHT_BOARD_START + 5.0, 100.0);
+        let far = spawn_enemy(&mut world, 10.0, 500.0);
+        let grid = SpatialGrid::build(&mut world);
+        let pos = Position { x: 590.0, y: 100.0 };
+        assert_eq!(grid.nearest(0, pos, |e| e.enemy), Some(far));
+        assert_eq!(grid.nearest(0, pos, |e| e.tower), None);
+    }
+
+    #[test]
+    fn build_skips_workers() {
+        let mut world = World::new();
+        let enemy = spawn_enemy(&mut world, 100.0, 300.0);
+        world.spawn((Position { x: 101.0, y: 100.0 }, Worker));
+        let grid = SpatialGrid::build(&mut world);
+        let pos = Position { x: 100.0, y: 100.0 };
+        assert_eq!(grid.nearest(0, pos, |_| true), Some(enemy));
+    }
+}
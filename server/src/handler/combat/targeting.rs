@@ -1,17 +1,24 @@
-use super::{LEAK_GOLD_PENALTY, LEAK_PENALTY_WAVE_CAP, get_board};
+use super::{LEAK_GOLD_PENALTY, LEAK_PENALTY_WAVE_CAP, SpatialGrid, get_board};
 use crate::model::components::{
     AttackRange, Dead, Enemy, InAttackRange, King, Position, Target, Tower,
 };
 use crate::model::constants::TOTAL_HEIGHT;
+use crate::model::game_state::GameTick;
+use crate::model::gold_ledger::GoldReason;
+use crate::model::match_stats::MatchStats;
 use crate::model::player::Players;
 use bevy_ecs::prelude::{Entity, With, Without, World};
 
+/// Drops stale targets, then gives every idle tower the closest in-lane enemy
+/// and every idle enemy the closest living tower within its attack range.
+/// Enemies with nothing in range keep following the [`FlowField`](super::FlowField).
+/// Reads the [`SpatialGrid`] rebuilt at the start of the tick.
 pub fn update_targeting(world: &mut World) {
     // --- 1. VALIDATE AND REMOVE INVALID TARGETS IMMEDIATELY ---
     let mut to_remove = Vec::new();
     {
-        let mut query = world.query::<(Entity, &Target, &Position)>();
-        for (entity, target, pos) in query.iter(world) {
+        let mut query = world.query::<(Entity, &Target, &Position, Option<&AttackRange>)>();
+        for (entity, target, pos, range) in query.iter(world) {
             if !world.entities().contains(target.0) {
                 to_remove.push(entity);
                 continue;
@@ -26,23 +33,35 @@ pub fn update_targeting(world: &mut World) {
             }
 
             // Also check if target is on the same board (but only for alive targets)
-            if let Some(target_pos) = world.get::<Position>(target.0) {
-                if get_board(pos.x) != get_board(target_pos.x) {
-                    to_remove.push(entity);
-                    continue;
-                }
+            if let Some(target_pos) = world.get::<Position>(target.0)
+                && get_board(pos.x) != get_board(target_pos.x)
+            {
+                to_remove.push(entity);
+                continue;
             }
 
             // Remove target if the attacker is a leaked enemy (y >= TOTAL_HEIGHT)
             // but the target is an in-lane entity (y < TOTAL_HEIGHT). Leaked enemies
             // should only target entities in the king zone.
-            if world.get::<Enemy>(entity).is_some() && pos.y >= TOTAL_HEIGHT {
-                if let Some(target_pos) = world.get::<Position>(target.0) {
-                    if target_pos.y < TOTAL_HEIGHT {
-                        to_remove.push(entity);
-                        continue;
-                    }
-                }
+            if world.get::<Enemy>(entity).is_some()
+                && pos.y >= TOTAL_HEIGHT
+                && let Some(target_pos) = world.get::<Position>(target.0)
+                && target_pos.y < TOTAL_HEIGHT
+            {
+                to_remove.push(entity);
+                continue;
+            }
+
+            // In-lane enemies let go of a tower once it is out of their attack
+            // range, and go back to pathing around the rest.
+            if world.get::<Enemy>(entity).is_some()
+                && pos.y < TOTAL_HEIGHT
+                && let Some(range) = range
+                && let Some(target_pos) = world.get::<Position>(target.0)
+                && !within_range(*pos, *target_pos, range.0)
+            {
+                to_remove.push(entity);
+                continue;
             }
         }
     }
@@ -54,73 +73,33 @@ pub fn update_targeting(world: &mut World) {
 
     // --- 2. UNIT TARGETING (Units target closest Enemy, in-lane only) ---
     // Leaked enemies (pos.y >= TOTAL_HEIGHT) are excluded from tower targeting.
-    let enemy_positions: Vec<(Entity, Position)> = world
-        .query_filtered::<(Entity, &Position), With<Enemy>>()
-        .iter(world)
-        .filter(|(_, pos)| pos.y < TOTAL_HEIGHT)
-        .map(|(entity, pos)| (entity, Position { x: pos.x, y: pos.y }))
-        .collect();
-
-    if !enemy_positions.is_empty() {
-        let mut query = world
-            .query_filtered::<(Entity, &Position), (With<Tower>, Without<Target>, Without<Dead>)>();
-        for (unit_entity, unit_pos) in query.iter(world) {
-            let unit_board = get_board(unit_pos.x);
-            if unit_board.is_none() {
-                continue;
-            }
-
-            let mut closest_enemy: Option<(Entity, f32)> = None;
-            for (enemy_entity, enemy_pos) in &enemy_positions {
-                if get_board(enemy_pos.x) != unit_board {
-                    continue;
-                }
-
-                let distance_sq =
-                    (unit_pos.x - enemy_pos.x).powi(2) + (unit_pos.y - enemy_pos.y).powi(2);
-                if closest_enemy.is_none() || distance_sq < closest_enemy.unwrap().1 {
-                    closest_enemy = Some((*enemy_entity, distance_sq));
-                }
-            }
-            if let Some((target_entity, _)) = closest_enemy {
-                commands.push((unit_entity, Target(target_entity)));
-            }
+    let mut tower_query = world
+        .query_filtered::<(Entity, &Position), (With<Tower>, Without<Target>, Without<Dead>)>();
+    let mut enemy_query =
+        world.query_filtered::<(Entity, &Position, &AttackRange), (With<Enemy>, Without<Target>)>();
+    let grid = world.resource::<SpatialGrid>();
+    for (unit_entity, unit_pos) in tower_query.iter(world) {
+        let Some(unit_board) = get_board(unit_pos.x) else {
+            continue;
+        };
+        if let Some(target_entity) =
+            grid.nearest(unit_board, *unit_pos, |e| e.enemy && e.pos.y < TOTAL_HEIGHT)
+        {
+            commands.push((unit_entity, Target(target_entity)));
         }
     }
 
-    // --- ENEMY TARGETING (Enemies target closest non-Worker, non-King Unit) ---
-    // Kings are excluded: in-lane enemies drift downward by default and are routed to the
-    // king zone by update_leaked_creeps once they cross TOTAL_HEIGHT.
-    let unit_positions: Vec<(Entity, Position)> = world
-        .query_filtered::<(Entity, &Position), (With<Tower>, Without<Dead>)>()
-        .iter(world)
-        .map(|(entity, pos)| (entity, Position { x: pos.x, y: pos.y }))
-        .collect();
-
-    if !unit_positions.is_empty() {
-        let mut query =
-            world.query_filtered::<(Entity, &Position), (With<Enemy>, Without<Target>)>();
-        for (enemy_entity, enemy_pos) in query.iter(world) {
-            let enemy_board = get_board(enemy_pos.x);
-            if enemy_board.is_none() {
-                continue;
-            }
-
-            let mut closest_unit: Option<(Entity, f32)> = None;
-            for (unit_entity, unit_pos) in &unit_positions {
-                if get_board(unit_pos.x) != enemy_board {
-                    continue;
-                }
-
-                let distance_sq =
-                    (enemy_pos.x - unit_pos.x).powi(2) + (enemy_pos.y - unit_pos.y).powi(2);
-                if closest_unit.is_none() || distance_sq < closest_unit.unwrap().1 {
-                    closest_unit = Some((*unit_entity, distance_sq));
-                }
-            }
-            if let Some((target_entity, _)) = closest_unit {
-                commands.push((enemy_entity, Target(target_entity)));
-            }
+    // --- ENEMY TARGETING (Enemies target closest non-Worker, non-King Unit in range) ---
+    // Kings are excluded: in-lane enemies follow the flow field by default and are routed to
+    // the king zone by update_leaked_creeps once they cross TOTAL_HEIGHT.
+    for (enemy_entity, enemy_pos, range) in enemy_query.iter(world) {
+        let Some(enemy_board) = get_board(enemy_pos.x) else {
+            continue;
+        };
+        if let Some(target_entity) = grid.nearest(enemy_board, *enemy_pos, |e| {
+            e.tower && !e.dead && within_range(*enemy_pos, e.pos, range.0)
+        }) {
+            commands.push((enemy_entity, Target(target_entity)));
         }
     }
 
@@ -130,6 +109,10 @@ pub fn update_targeting(world: &mut World) {
     }
 }
 
+fn within_range(a: Position, b: Position, range: f32) -> bool {
+    (a.x - b.x).powi(2) + (a.y - b.y).powi(2) <= range * range
+}
+
 /// Validates and updates InAttackRange markers for all entities with targets.
 /// This runs independently of movement to ensure range status is always current,
 /// even when entities are stationary or after other entities die/reposition.
@@ -244,26 +227,36 @@ pub fn update_leaked_creeps(world: &mut World) {
             penalties.push(board);
         }
     }
-    if !penalties.is_empty() {
-        if let Some(mut players) = world.get_resource_mut::<Players>() {
-            for board in penalties {
-                if let Some(player) = players.0.get_mut(board as usize) {
-                    let charged_so_far = player.leaks_this_wave * LEAK_GOLD_PENALTY;
-                    if charged_so_far < LEAK_PENALTY_WAVE_CAP {
-                        player.gold = player.gold.saturating_sub(LEAK_GOLD_PENALTY);
-                    }
-                    player.leaks_this_wave += 1;
+    let tick = world.get_resource::<GameTick>().map_or(0, |t| t.0);
+    let mut leaked_ids = Vec::new();
+    if !penalties.is_empty()
+        && let Some(mut players) = world.get_resource_mut::<Players>()
+    {
+        for board in penalties {
+            if let Some(player) = players.0.get_mut(board as usize) {
+                let charged_so_far = player.leaks_this_wave * LEAK_GOLD_PENALTY;
+                if charged_so_far < LEAK_PENALTY_WAVE_CAP {
+                    player.charge_gold(LEAK_GOLD_PENALTY, GoldReason::LeakPenalty, tick);
                 }
+                player.leaks_this_wave += 1;
+                leaked_ids.push(player.id);
             }
         }
     }
+    if let Some(mut stats) = world.get_resource_mut::<MatchStats>() {
+        for id in leaked_ids {
+            stats.player(id).add_leak();
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::handler::combat::cleanup_dead_entities;
-    use crate::handler::combat::update_combat_movement;
+    use crate::handler::combat::{
+        rebuild_flow_field, rebuild_spatial_grid, update_combat_movement,
+    };
     use crate::handler::worker::{CART_POSITIONS, VEIN_POSITIONS};
     use crate::model::components::TargetPositions;
     use crate::model::components::{AttackStats, AttackTimer, CollisionRadius, DamageType, Health};
@@ -296,6 +289,7 @@ mod tests {
             1,
         );
 
+        rebuild_spatial_grid(&mut world);
         update_targeting(&mut world);
 
         // Assert Worker does NOT have Target
@@ -336,6 +330,7 @@ mod tests {
             1,
         );
 
+        rebuild_spatial_grid(&mut world);
         update_targeting(&mut world);
 
         // Enemy should NOT target the worker, but SHOULD target the unit
@@ -374,6 +369,8 @@ mod tests {
         world.insert_resource(DeltaTime(tick_delta));
 
         // 1. Far away: no marker
+        rebuild_spatial_grid(&mut world);
+        rebuild_flow_field(&mut world);
         update_combat_movement(&mut world);
         update_attack_range_markers(&mut world);
         assert!(
@@ -383,6 +380,8 @@ mod tests {
 
         // 2. Within range: has marker
         world.entity_mut(unit).insert(Position { x: 60.0, y: 0.0 });
+        rebuild_spatial_grid(&mut world);
+        rebuild_flow_field(&mut world);
         update_combat_movement(&mut world);
         update_attack_range_markers(&mut world);
         assert!(
@@ -392,6 +391,8 @@ mod tests {
 
         // 3. Move back out: marker removed
         world.entity_mut(unit).insert(Position { x: 0.0, y: 0.0 });
+        rebuild_spatial_grid(&mut world);
+        rebuild_flow_field(&mut world);
         update_combat_movement(&mut world);
         update_attack_range_markers(&mut world);
         assert!(
@@ -418,6 +419,7 @@ mod tests {
         );
 
         // 1. Initial targeting
+        rebuild_spatial_grid(&mut world);
         update_targeting(&mut world);
         assert_eq!(world.entity(unit).get::<Target>().unwrap().0, enemy);
 
@@ -425,6 +427,7 @@ mod tests {
         world.despawn(enemy);
 
         // 3. Update targeting again - should not crash and should ideally clear or re-target
+        rebuild_spatial_grid(&mut world);
         update_targeting(&mut world);
 
         // The current implementation of update_targeting only ADDS targets to entities WITHOUT targets.
@@ -465,6 +468,7 @@ mod tests {
             .id();
 
         // 1. Update targeting
+        rebuild_spatial_grid(&mut world);
         update_targeting(&mut world);
 
         // 2. Assert NO targeting happens between them
@@ -503,6 +507,7 @@ mod tests {
         );
 
         // 1. Target first enemy (the closest one)
+        rebuild_spatial_grid(&mut world);
         update_targeting(&mut world);
         assert_eq!(world.entity(unit).get::<Target>().unwrap().0, enemy1);
 
@@ -516,6 +521,7 @@ mod tests {
         assert!(!world.entities().contains(enemy1));
 
         // 3. Update targeting - should remove invalid target and find enemy2
+        rebuild_spatial_grid(&mut world);
         update_targeting(&mut world);
         let target = world.entity(unit).get::<Target>();
         assert!(target.is_some(), "Unit should have a new target");
@@ -547,6 +553,7 @@ mod tests {
             1,
         );
 
+        rebuild_spatial_grid(&mut world);
         update_targeting(&mut world);
 
         assert!(
@@ -555,6 +562,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn enemy_ignores_towers_outside_its_attack_range() {
+        let mut world = World::new();
+        let tower = crate::handler::spawn::spawn_unit(
+            &mut world,
+            Position { x: 300.0, y: 300.0 },
+            UnitKind::Square,
+            1,
+        );
+        let enemy = crate::handler::spawn::spawn_enemy(
+            &mut world,
+            Position { x: 300.0, y: 100.0 },
+            UnitKind::Square,
+            1,
+        );
+
+        rebuild_spatial_grid(&mut world);
+        update_targeting(&mut world);
+        assert!(world.entity(enemy).get::<Target>().is_none());
+
+        // In range it engages; pushed back out, it lets go again.
+        world
+            .entity_mut(enemy)
+            .insert(Position { x: 300.0, y: 260.0 });
+        rebuild_spatial_grid(&mut world);
+        update_targeting(&mut world);
+        assert_eq!(
+            world.entity(enemy).get::<Target>().map(|t| t.0),
+            Some(tower)
+        );
+
+        world
+            .entity_mut(enemy)
+            .insert(Position { x: 300.0, y: 100.0 });
+        rebuild_spatial_grid(&mut world);
+        update_targeting(&mut world);
+        assert!(world.entity(enemy).get::<Target>().is_none());
+    }
+
     #[test]
     fn enemy_does_not_target_dead_tower() {
         let mut world = World::new();
@@ -568,10 +614,10 @@ mod tests {
         );
         world.entity_mut(dead_tower).insert(Dead);
 
-        // Spawn a living tower farther from the enemy
+        // Spawn a living tower farther from the enemy, still in its range
         let living_tower = crate::handler::spawn::spawn_unit(
             &mut world,
-            Position { x: 300.0, y: 300.0 },
+            Position { x: 220.0, y: 300.0 },
             UnitKind::Square,
             1,
         );
@@ -584,6 +630,7 @@ mod tests {
             1,
         );
 
+        rebuild_spatial_grid(&mut world);
         update_targeting(&mut world);
 
         let target = world.entity(enemy).get::<Target>();
@@ -620,6 +667,7 @@ mod tests {
             CollisionRadius(10.0),
         ));
 
+        rebuild_spatial_grid(&mut world);
         update_targeting(&mut world);
 
         // Tower should have no target because the only enemy is leaked
@@ -845,9 +893,9 @@ mod tests {
         );
         update_leaked_creeps(&mut world);
         let players = world.resource::<Players>();
-        assert_eq!(players.0[0].gold, 95);
+        assert_eq!(players.0[0].gold(), 95);
         assert_eq!(players.0[0].leaks_this_wave, 1);
-        assert_eq!(players.0[1].gold, 100, "opponent unaffected");
+        assert_eq!(players.0[1].gold(), 100, "opponent unaffected");
     }
 
     #[test]
@@ -874,7 +922,7 @@ mod tests {
         }
         update_leaked_creeps(&mut world);
         let players = world.resource::<Players>();
-        assert_eq!(players.0[0].gold, 7);
+        assert_eq!(players.0[0].gold(), 7);
         assert_eq!(players.0[0].leaks_this_wave, 11);
     }
 
@@ -895,6 +943,6 @@ mod tests {
             1,
         );
         update_leaked_creeps(&mut world);
-        assert_eq!(world.resource::<Players>().0[0].gold, 0);
+        assert_eq!(world.resource::<Players>().0[0].gold(), 0);
     }
 }
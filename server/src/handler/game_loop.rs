@@ -1,34 +1,42 @@
 use crate::{
     handler::{
         combat::{
-            cleanup_dead_entities, process_combat, update_active_combat_stats,
-            update_attack_range_markers, update_combat_movement, update_combat_reset,
-            update_leaked_creeps, update_mana, update_targeting,
+            cleanup_dead_entities, process_combat, rebuild_flow_field, rebuild_spatial_grid,
+            update_active_combat_stats, update_attack_range_markers, update_combat_movement,
+            update_combat_reset, update_leaked_creeps, update_mana, update_targeting,
         },
         king::{apply_king_regen, update_king_attack_range, update_king_targeting},
         worker::update_workers,
     },
     model::{
         components::{Health, King, PlayerIdComponent, Position},
-        game_state::{DeltaTime, GamePhase, NetworkChannel},
+        game_state::{BroadcastBytes, DeltaTime, GamePhase, GameTick, NetworkChannel},
+        gold_ledger::GoldReason,
+        match_stats::MatchStats,
         messages::{CombatEvent, ServerMessage},
         player::Players,
     },
-    state::ServerState,
 };
 use bevy_ecs::message::{MessageReader, Messages};
 use bevy_ecs::prelude::{Res, ResMut, Schedule, SystemSet};
 use bevy_ecs::schedule::IntoScheduleConfigs;
 use bevy_ecs::schedule::common_conditions::resource_equals;
-use std::time::Duration;
+use std::sync::atomic::Ordering;
 
 /// Bevy system: reads buffered [`CombatEvent`] messages and broadcasts them to all
 /// connected clients via the [`NetworkChannel`] resource.
-pub fn broadcast_events(mut reader: MessageReader<CombatEvent>, channel: Res<NetworkChannel>) {
+pub fn broadcast_events(
+    mut reader: MessageReader<CombatEvent>,
+    channel: Res<NetworkChannel>,
+    bytes: Option<Res<BroadcastBytes>>,
+) {
     let events: Vec<CombatEvent> = reader.read().cloned().collect();
     if !events.is_empty() {
         let msg = ServerMessage::CombatEvents(events);
         if let Ok(s) = serde_json::to_string(&msg) {
+            if let Some(bytes) = bytes {
+                bytes.0.fetch_add(s.len() as u64, Ordering::Relaxed);
+            }
             let _ = channel.0.send(s);
         }
     }
@@ -93,8 +101,14 @@ pub fn build_main_schedule() -> Schedule {
     // CombatInit: advance the message double-buffer.
     schedule.add_systems(update_combat_messages.in_set(GameSystemSet::CombatInit));
 
-    // Targeting: acquire targets, then update derived stats, then king targeting pass.
-    schedule.add_systems(update_targeting.in_set(GameSystemSet::Targeting));
+    // Targeting: index positions, acquire targets, then update derived stats, then
+    // king targeting pass.
+    schedule.add_systems(rebuild_spatial_grid.in_set(GameSystemSet::Targeting));
+    schedule.add_systems(
+        update_targeting
+            .in_set(GameSystemSet::Targeting)
+            .after(rebuild_spatial_grid),
+    );
     schedule.add_systems(
         update_active_combat_stats
             .in_set(GameSystemSet::Targeting)
@@ -106,8 +120,14 @@ pub fn build_main_schedule() -> Schedule {
             .after(update_active_combat_stats),
     );
 
-    // Movement: move units, validate attack ranges, then regenerate mana.
-    schedule.add_systems(update_combat_movement.in_set(GameSystemSet::Movement));
+    // Movement: route around towers, move units, validate attack ranges, then
+    // regenerate mana.
+    schedule.add_systems(rebuild_flow_field.in_set(GameSystemSet::Movement));
+    schedule.add_systems(
+        update_combat_movement
+            .in_set(GameSystemSet::Movement)
+            .after(rebuild_flow_field),
+    );
     schedule.add_systems(
         update_attack_range_markers
             .in_set(GameSystemSet::Movement)
@@ -151,155 +171,139 @@ pub fn build_main_schedule() -> Schedule {
     schedule
 }
 
+/// Default ticks per second; overridden by `Config::tick_rate`.
 pub const TICK_RATE: f32 = 30.0;
 
-pub async fn run_game_loop(server_state: ServerState, match_id: u64) {
-    let mut interval = tokio::time::interval(Duration::from_secs_f32(1.0 / TICK_RATE));
-    let tick_delta = 1.0 / TICK_RATE;
-    let mut schedule = build_main_schedule();
-
-    let Some(lobby_arc) = server_state.matches.read().await.get(&match_id).cloned() else {
+/// One synchronous game tick. Driven by the match's simulation shard (see
+/// `handler::shard`); tests call it directly to play full games
+/// deterministically without threads or networking.
+pub fn run_tick(lobby: &mut crate::model::lobby::Lobby, schedule: &mut Schedule, tick_delta: f32) {
+    // A paused match only spends the pauser's budget; clients still get
+    // the countdown.
+    if !lobby.is_finished() && lobby.pause.tick(tick_delta) {
+        lobby.broadcast_changes();
         return;
-    };
-
-    loop {
-        interval.tick().await;
-        // Exit when the match has been torn down (last player left).
-        if !server_state.matches.read().await.contains_key(&match_id) {
-            break;
-        }
-        let mut lobby_guard = lobby_arc.lock().await;
-        let lobby = &mut *lobby_guard;
-        run_tick(lobby, &mut schedule, tick_delta);
     }
-}
 
-/// One synchronous game tick. Extracted from the async loop so tests can
-/// drive full games deterministically without tokio or networking.
-pub fn run_tick(lobby: &mut crate::model::lobby::Lobby, schedule: &mut Schedule, tick_delta: f32) {
     // Insert per-tick resources.
+    lobby.game_state.tick += 1;
     lobby
         .game_state
         .world
         .insert_resource(DeltaTime(tick_delta));
+    lobby
+        .game_state
+        .world
+        .insert_resource(GameTick(lobby.game_state.tick));
     lobby
         .game_state
         .world
         .insert_resource(lobby.game_state.phase);
 
-    // Build phase orchestration: spawn workers and tick the phase timer.
-    if lobby.game_state.phase == GamePhase::Build {
-        if lobby.is_full() {
-            let mut worker_query = lobby
+    // Build phase orchestration: spawn workers and kings when the match
+    // starts and tick the phase timer, once both players are set up (see
+    // `Lobby::wait_for_players`).
+    if lobby.game_state.phase == GamePhase::Build && lobby.is_full() && lobby.tick_setup(tick_delta)
+    {
+        if lobby.start() {
+            for (idx, player) in lobby.players.iter().enumerate() {
+                for _ in 0..crate::model::unit_config::STARTER_WORKERS {
+                    let targets = crate::model::components::TargetPositions {
+                        vein: crate::handler::worker::VEIN_POSITIONS[idx],
+                        cart: crate::handler::worker::CART_POSITIONS[idx],
+                    };
+                    crate::handler::spawn::spawn_worker(
+                        &mut lobby.game_state.world,
+                        player.id,
+                        targets,
+                    );
+                }
+                crate::handler::spawn::spawn_king(&mut lobby.game_state.world, player.id, idx);
+            }
+        }
+
+        lobby.game_state.phase_timer -= tick_delta;
+        if lobby.game_state.phase_timer <= 0.0 {
+            lobby.game_state.phase_timer = 0.0;
+            lobby.game_state.phase = GamePhase::Combat;
+            // Sync the updated phase into the world immediately so combat
+            // systems run on this same tick.
+            lobby
                 .game_state
                 .world
-                .query::<&crate::model::components::Worker>();
-            if worker_query.iter(&lobby.game_state.world).count() == 0 {
-                for (idx, player) in lobby.players.iter().enumerate() {
-                    for _ in 0..3 {
-                        let targets = crate::model::components::TargetPositions {
-                            vein: crate::handler::worker::VEIN_POSITIONS[idx],
-                            cart: crate::handler::worker::CART_POSITIONS[idx],
-                        };
-                        crate::handler::spawn::spawn_worker(
-                            &mut lobby.game_state.world,
-                            player.id,
-                            targets,
-                        );
-                    }
-                }
+                .insert_resource(lobby.game_state.phase);
+            lobby
+                .game_state
+                .world
+                .resource_mut::<MatchStats>()
+                .begin_wave(
+                    lobby.game_state.wave_number,
+                    lobby.players.iter().map(|p| p.id),
+                );
 
-                // Spawn kings once (when workers first appear = game start).
-                let king_count = lobby
-                    .game_state
-                    .world
-                    .query::<&King>()
-                    .iter(&lobby.game_state.world)
-                    .count();
-                if king_count == 0 {
-                    for (idx, player) in lobby.players.iter().enumerate() {
-                        crate::handler::spawn::spawn_king(
-                            &mut lobby.game_state.world,
-                            player.id,
-                            idx,
-                        );
-                    }
-                }
-            }
+            use crate::model::constants::{BOARD_SIZE, RIGHT_BOARD_START};
+            let spawn_x_left = BOARD_SIZE / 2.0;
+            let spawn_x_right = RIGHT_BOARD_START + (BOARD_SIZE / 2.0);
 
-            lobby.game_state.phase_timer -= tick_delta;
-            if lobby.game_state.phase_timer <= 0.0 {
-                lobby.game_state.phase_timer = 0.0;
-                lobby.game_state.phase = GamePhase::Combat;
-                // Sync the updated phase into the world immediately so combat
-                // systems run on this same tick.
-                lobby
-                    .game_state
-                    .world
-                    .insert_resource(lobby.game_state.phase);
-
-                use crate::model::constants::{BOARD_SIZE, RIGHT_BOARD_START};
-                let spawn_x_left = BOARD_SIZE / 2.0;
-                let spawn_x_right = RIGHT_BOARD_START + (BOARD_SIZE / 2.0);
-
-                let wave_config =
-                    crate::handler::wave::get_wave_config(lobby.game_state.wave_number);
-                for x in [spawn_x_left, spawn_x_right] {
-                    let slot_count = wave_config.enemies.len();
-                    for (slot, shape) in wave_config.enemies.iter().enumerate() {
-                        // Deterministic per-slot offset, identical per board slot, so
-                        // mirrored boards spawn creeps with the same relative spread.
-                        // This avoids the exact-overlap collision tiebreaker
-                        // (combat.rs) whose scatter angle is derived from the global
-                        // entity index and is therefore not mirror-symmetric across
-                        // boards.
-                        let offset = (slot as f32) * 12.0 - (slot_count as f32 - 1.0) * 6.0;
-                        crate::handler::spawn::spawn_enemy(
-                            &mut lobby.game_state.world,
-                            Position { x: x + offset, y: 30.0 },
-                            *shape,
-                            lobby.game_state.wave_number,
-                        );
-                    }
+            let wave_config = crate::handler::wave::get_wave_config(lobby.game_state.wave_number);
+            for x in [spawn_x_left, spawn_x_right] {
+                let slot_count = wave_config.enemies.len();
+                for (slot, shape) in wave_config.enemies.iter().enumerate() {
+                    // Deterministic per-slot offset, identical per board slot, so
+                    // mirrored boards spawn creeps with the same relative spread.
+                    // This avoids the exact-overlap collision tiebreaker
+                    // (combat.rs) whose scatter angle is derived from the global
+                    // entity index and is therefore not mirror-symmetric across
+                    // boards.
+                    let offset = (slot as f32) * 12.0 - (slot_count as f32 - 1.0) * 6.0;
+                    crate::handler::spawn::spawn_enemy(
+                        &mut lobby.game_state.world,
+                        Position {
+                            x: x + offset,
+                            y: 30.0,
+                        },
+                        *shape,
+                        lobby.game_state.wave_number,
+                    );
                 }
+            }
 
-                // Drain each player's spawning queue and send their units to the
-                // opponent's board.
-                let queues: Vec<Vec<crate::model::unit_kind::UnitKind>> = lobby
-                    .players
-                    .iter()
-                    .map(|p| p.spawning_queue.clone())
-                    .collect();
-                for (player_idx, queue) in queues.iter().enumerate() {
-                    let opponent_x = if player_idx == 0 {
-                        spawn_x_right
-                    } else {
-                        spawn_x_left
-                    };
-                    let queue_len = queue.len();
-                    for (slot, &shape) in queue.iter().enumerate() {
-                        // Same deterministic per-slot offset idea as wave spawns
-                        // above, so sent units co-located at the opponent's spawn
-                        // x don't trigger the global-index-dependent scatter
-                        // tiebreaker asymmetrically between boards.
-                        let offset = (slot as f32) * 12.0 - (queue_len as f32 - 1.0) * 6.0;
-                        let sent_profile = crate::model::unit_config::get_sent_unit_profile(shape);
-                        crate::handler::spawn::spawn_sent_enemy(
-                            &mut lobby.game_state.world,
-                            Position {
-                                x: opponent_x + offset,
-                                y: 30.0,
-                            },
-                            shape,
-                            lobby.game_state.wave_number,
-                            sent_profile.bounty,
-                        );
-                    }
-                }
-                for player in &mut lobby.players {
-                    player.spawning_queue.clear();
+            // Drain each player's spawning queue and send their units to the
+            // opponent's board.
+            let queues: Vec<Vec<crate::model::unit_kind::UnitKind>> = lobby
+                .players
+                .iter()
+                .map(|p| p.spawning_queue.clone())
+                .collect();
+            for (player_idx, queue) in queues.iter().enumerate() {
+                let opponent_x = if player_idx == 0 {
+                    spawn_x_right
+                } else {
+                    spawn_x_left
+                };
+                let queue_len = queue.len();
+                for (slot, &shape) in queue.iter().enumerate() {
+                    // Same deterministic per-slot offset idea as wave spawns
+                    // above, so sent units co-located at the opponent's spawn
+                    // x don't trigger the global-index-dependent scatter
+                    // tiebreaker asymmetrically between boards.
+                    let offset = (slot as f32) * 12.0 - (queue_len as f32 - 1.0) * 6.0;
+                    let sent_profile = crate::model::unit_config::get_sent_unit_profile(shape);
+                    crate::handler::spawn::spawn_sent_enemy(
+                        &mut lobby.game_state.world,
+                        Position {
+                            x: opponent_x + offset,
+                            y: 30.0,
+                        },
+                        shape,
+                        lobby.game_state.wave_number,
+                        sent_profile.bounty,
+                    );
                 }
             }
+            for player in &mut lobby.players {
+                player.spawning_queue.clear();
+            }
         }
     }
 
@@ -380,14 +384,19 @@ pub fn award_wave_end(lobby: &mut crate::model::lobby::Lobby) {
     let completed_wave = lobby.game_state.wave_number;
     lobby.game_state.phase = GamePhase::Build;
     lobby.game_state.wave_number += 1;
-    lobby.game_state.phase_timer = 30.0;
+    lobby.game_state.phase_timer = lobby.settings.build_time();
     let new_wave = lobby.game_state.wave_number;
 
+    let tick = lobby.game_state.tick;
     for player in &mut lobby.players {
-        player.gold += WAVE_REWARD_BASE + WAVE_REWARD_PER_WAVE * completed_wave;
-        player.gold += player.income;
+        // Interest is on what was banked through the wave, before its rewards.
+        let interest = lobby.settings.interest(player.gold());
+        player.add_gold(interest, GoldReason::Interest, tick);
+        let reward = WAVE_REWARD_BASE + WAVE_REWARD_PER_WAVE * completed_wave;
+        player.add_gold(reward, GoldReason::WaveReward, tick);
+        player.add_gold(player.income, GoldReason::Income, tick);
         if player.leaks_this_wave == 0 {
-            player.gold += PERFECT_CLEAR_BONUS;
+            player.add_gold(PERFECT_CLEAR_BONUS, GoldReason::PerfectClear, tick);
         }
         player.leaks_this_wave = 0;
         player.sends_this_wave = [0; 3];
@@ -543,7 +552,7 @@ mod tests {
 
         assert!(lobby.is_full());
 
-        // We can't easily run run_game_loop in a unit test because it's async and loops forever.
+        // We can't easily run a shard in a unit test because it ticks on a wall clock.
         // But we can extract the logic or test the side effect if we had a "tick" function.
         // For now, I'll simulate a single tick of the Build phase logic here.
 
@@ -555,7 +564,7 @@ mod tests {
             let mut worker_query = lobby.game_state.world.query::<&Worker>();
             if worker_query.iter(&lobby.game_state.world).count() == 0 {
                 for (idx, player) in lobby.players.iter().enumerate() {
-                    for _ in 0..3 {
+                    for _ in 0..crate::model::unit_config::STARTER_WORKERS {
                         let targets = crate::model::components::TargetPositions {
                             vein: crate::handler::worker::VEIN_POSITIONS[idx],
                             cart: crate::handler::worker::CART_POSITIONS[idx],
@@ -623,7 +632,7 @@ mod tests {
         assert_eq!(lobby.game_state.wave_number, 2);
         assert_eq!(lobby.game_state.phase_timer, 30.0);
         // wave 1 clean clear, 0 income = (30 + 3×1) + 20 perfect = 53
-        assert_eq!(lobby.players[0].gold, 153);
+        assert_eq!(lobby.players[0].gold(), 153);
     }
 
     #[test]
@@ -639,12 +648,37 @@ mod tests {
         award_wave_end(&mut lobby); // extracted helper under test
 
         // clean: (30 + 3×4) + 5 income + 20 perfect = 67
-        assert_eq!(lobby.players[0].gold, 67);
+        assert_eq!(lobby.players[0].gold(), 67);
         // leaky: 42 + 0 income, no bonus
-        assert_eq!(lobby.players[1].gold, 42);
+        assert_eq!(lobby.players[1].gold(), 42);
         // counters reset for the new wave
         assert_eq!(lobby.players[1].leaks_this_wave, 0);
         assert_eq!(lobby.players[0].sends_this_wave, [0, 0, 0]);
+
+        let clean = lobby.players[0].ledger.earned();
+        assert_eq!(
+            (clean.wave_reward, clean.income, clean.perfect_clear),
+            (42, 5, 20)
+        );
+        assert_eq!(lobby.players[1].ledger.earned().perfect_clear, 0);
+    }
+
+    #[test]
+    fn wave_end_pays_capped_interest_on_banked_gold() {
+        let mut lobby = Lobby::new();
+        lobby.settings.interest_percent = 10;
+        lobby.settings.interest_cap = 15;
+        lobby.game_state.phase = GamePhase::Combat;
+        lobby.game_state.wave_number = 1;
+        lobby.players.push(Player::new(1, "saver".into(), 120));
+        lobby.players.push(Player::new(2, "hoarder".into(), 400));
+
+        award_wave_end(&mut lobby);
+
+        // 10% of 120 = 12; 10% of 400 = 40, capped at 15. Both then get 33 + 20.
+        assert_eq!(lobby.players[0].gold(), 120 + 12 + 53);
+        assert_eq!(lobby.players[1].gold(), 400 + 15 + 53);
+        assert_eq!(lobby.players[1].ledger.earned().interest, 15);
     }
 
     #[test]
@@ -758,7 +792,7 @@ mod tests {
         assert_eq!(lobby.game_state.phase, GamePhase::Build);
         assert_eq!(lobby.game_state.wave_number, 2);
         // wave 1 clean clear, 0 income = (30 + 3×1) + 20 perfect = 53
-        assert_eq!(lobby.players[0].gold, 153);
+        assert_eq!(lobby.players[0].gold(), 153);
 
         // Jump to Wave 12 Combat
         lobby.game_state.wave_number = 12;
@@ -950,7 +984,7 @@ mod tests {
         let spawn_x_left = BOARD_SIZE / 2.0;
         let spawn_x_right = RIGHT_BOARD_START + (BOARD_SIZE / 2.0);
 
-        // Simulate the queue draining logic from run_game_loop.
+        // Simulate the queue draining logic from the match tick.
         let queues: Vec<Vec<UnitKind>> = lobby
             .players
             .iter()
@@ -1095,13 +1129,13 @@ mod tests {
         // --- STEP 1: Player 0 purchases a Square sent unit ---
         let shape = UnitKind::Square;
         let profile = get_sent_unit_profile(shape);
-        assert!(lobby.players[0].try_spend_gold(profile.send_cost));
+        assert!(lobby.players[0].try_spend_gold(profile.send_cost, GoldReason::Send, 0));
         lobby.players[0].spawning_queue.push(shape);
         lobby.players[0].income += profile.income;
 
         // Verify purchase atomicity
         assert_eq!(
-            lobby.players[0].gold,
+            lobby.players[0].gold(),
             100 - SENT_SQUARE_COST,
             "Gold deducted"
         );
@@ -1214,10 +1248,10 @@ mod tests {
 
         // Simulate two purchases accumulating income
         let sq = get_sent_unit_profile(UnitKind::Square);
-        lobby.players[0].try_spend_gold(sq.send_cost);
+        lobby.players[0].try_spend_gold(sq.send_cost, GoldReason::Send, 0);
         lobby.players[0].income += sq.income;
         let tr = get_sent_unit_profile(UnitKind::Triangle);
-        lobby.players[0].try_spend_gold(tr.send_cost);
+        lobby.players[0].try_spend_gold(tr.send_cost, GoldReason::Send, 0);
         lobby.players[0].income += tr.income;
 
         assert_eq!(
@@ -1229,17 +1263,17 @@ mod tests {
         // Wave ends — award scaled base bonus + accumulated income + perfect-clear bonus
         if lobby.game_state.phase == GamePhase::Combat
             && check_wave_cleared(&mut lobby.game_state.world)
+            && lobby.game_state.wave_number < 6
         {
-            if lobby.game_state.wave_number < 6 {
-                award_wave_end(&mut lobby);
-            }
+            award_wave_end(&mut lobby);
         }
 
         // wave 1 clean clear = (30 + 3×1) + 20 perfect = 53
         let expected =
             200 - sq.send_cost - tr.send_cost + 53 + SENT_SQUARE_INCOME + SENT_TRIANGLE_INCOME;
         assert_eq!(
-            lobby.players[0].gold, expected,
+            lobby.players[0].gold(),
+            expected,
             "Gold should include base wave bonus plus accumulated income"
         );
     }
@@ -1321,7 +1355,7 @@ mod tests {
         lobby.players[0].income = 5;
         lobby.players[1].income = 3;
 
-        // Wave is clear; simulate the phase transition logic from run_game_loop.
+        // Wave is clear; simulate the phase transition logic from the match tick.
         if lobby.game_state.phase == GamePhase::Combat
             && check_wave_cleared(&mut lobby.game_state.world)
         {
@@ -1334,11 +1368,13 @@ mod tests {
 
         // wave 1 clean clear base = (30 + 3×1) + 20 perfect = 53
         assert_eq!(
-            lobby.players[0].gold, 158,
+            lobby.players[0].gold(),
+            158,
             "Player 1 should receive 53 base+bonus + 5 income = 158 total"
         );
         assert_eq!(
-            lobby.players[1].gold, 156,
+            lobby.players[1].gold(),
+            156,
             "Player 2 should receive 53 base+bonus + 3 income = 156 total"
         );
     }
@@ -1353,15 +1389,15 @@ mod tests {
 
         if lobby.game_state.phase == GamePhase::Combat
             && check_wave_cleared(&mut lobby.game_state.world)
+            && lobby.game_state.wave_number < 6
         {
-            if lobby.game_state.wave_number < 6 {
-                award_wave_end(&mut lobby);
-            }
+            award_wave_end(&mut lobby);
         }
 
         // wave 1 clean clear, 0 income = (30 + 3×1) + 20 perfect = 53
         assert_eq!(
-            lobby.players[0].gold, 153,
+            lobby.players[0].gold(),
+            153,
             "Player with 0 income should receive base wave bonus + perfect-clear bonus"
         );
     }
@@ -0,0 +1,115 @@
+use crate::{
+    model::{
+        game_error::GameError,
+        messages::{ClientMessage, PROTOCOL_VERSION, ServerMessage},
+    },
+    routes::ws::send_message,
+    state::UpgradedWebSocket,
+};
+use futures_util::{
+    StreamExt,
+    stream::{SplitSink, SplitStream},
+};
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+
+/// How long a fresh socket may stay silent before it is dropped for never
+/// saying `Hello`.
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub enum HandshakeResult {
+    Accepted,
+    /// An error reply was sent; the caller should close the socket.
+    Rejected,
+    ClientDisconnected,
+}
+
+/// Checks the client's first text frame. Only an exact version match is
+/// accepted: there is a single protocol generation today, so "downgrade"
+/// means telling the client which version to speak via `ProtocolMismatch`.
+pub fn negotiate(text: &str) -> Result<u32, GameError> {
+    match serde_json::from_str::<ClientMessage>(text) {
+        Ok(ClientMessage::Hello { protocol_version }) if protocol_version == PROTOCOL_VERSION => {
+            Ok(protocol_version)
+        }
+        Ok(ClientMessage::Hello { protocol_version }) => Err(GameError::ProtocolMismatch {
+            server: PROTOCOL_VERSION,
+            client: protocol_version,
+        }),
+        _ => Err(GameError::HandshakeRequired),
+    }
+}
+
+/// Waits for the client's `Hello` and replies `Welcome` or an error. Runs
+/// before the connection is registered in `active_connections`, so an
+/// outdated client can never kick a live session of the same account.
+pub async fn handshake(
+    ws_sender: &mut SplitSink<UpgradedWebSocket, Message>,
+    ws_receiver: &mut SplitStream<UpgradedWebSocket>,
+) -> HandshakeResult {
+    let first_text = tokio::time::timeout(HANDSHAKE_TIMEOUT, async {
+        loop {
+            match ws_receiver.next().await {
+                Some(Ok(Message::Text(text))) => return Some(text),
+                Some(Ok(_)) => {} // ping/pong/binary: keep waiting
+                Some(Err(_)) | None => return None,
+            }
+        }
+    })
+    .await;
+
+    let reply = match first_text {
+        Ok(None) => return HandshakeResult::ClientDisconnected,
+        Err(_elapsed) => Err(GameError::HandshakeRequired),
+        Ok(Some(text)) => negotiate(&text),
+    };
+
+    match reply {
+        Ok(protocol_version) => {
+            if send_message(ws_sender, ServerMessage::Welcome { protocol_version })
+                .await
+                .is_err()
+            {
+                return HandshakeResult::ClientDisconnected;
+            }
+            HandshakeResult::Accepted
+        }
+        Err(error) => {
+            let _ = send_message(ws_sender, error.into()).await;
+            HandshakeResult::Rejected
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matching_hello_is_accepted() {
+        let text =
+            format!(r#"{{"action":"hello","payload":{{"protocol_version":{PROTOCOL_VERSION}}}}}"#);
+        assert_eq!(negotiate(&text), Ok(PROTOCOL_VERSION));
+    }
+
+    #[test]
+    fn mismatched_hello_reports_both_versions() {
+        let text = r#"{"action":"hello","payload":{"protocol_version":999}}"#;
+        assert_eq!(
+            negotiate(text),
+            Err(GameError::ProtocolMismatch {
+                server: PROTOCOL_VERSION,
+                client: 999
+            })
+        );
+    }
+
+    #[test]
+    fn non_hello_first_frame_requires_handshake() {
+        assert_eq!(
+            negotiate(r#"{"action":"joinQueue"}"#),
+            Err(GameError::HandshakeRequired)
+        );
+        assert_eq!(negotiate("not json"), Err(GameError::HandshakeRequired));
+    }
+}
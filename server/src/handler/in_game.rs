@@ -1,13 +1,23 @@
 use crate::{
+    handler::{
+        matchmaking::create_match,
+        rate_limit::{ConnectionLimiter, Screened},
+        shutdown::shutdown_notice,
+    },
     model::{
+        chat,
         components::{
-            AttackRange, AttackStats, Boss, DefenseStats, Health, King, PlayerIdComponent,
+            AttackRange, AttackStats, Boss, DefenseStats, Health, King, Mineral, PlayerIdComponent,
             Position, ShapeComponent, TargetPositions, Worker,
         },
         constants::{KING_PLACEMENT_ROW_LIMIT, SQUARE_SIZE},
+        game_error::GameError,
         game_state::GamePhase,
+        gold_ledger::GoldReason,
         king_config::KING_UPGRADE_TIERS,
-        messages::ClientMessage,
+        match_settings::{GameMode, MatchSettings},
+        messages::{ClientMessage, ServerMessage},
+        unit_config::{WORKER_CAP, worker_hire_cost},
     },
     state::{ServerState, UpgradedWebSocket},
 };
@@ -21,8 +31,11 @@ use tokio_tungstenite::tungstenite::Message;
 
 pub enum InGameLoopResult {
     PlayerLeft,
+    /// Both players agreed to a rematch; continue in this new match.
+    Rematch(u64),
     ClientDisconnected,
-    ForceDisconnect,
+    /// Closed from the server side; the error tells the client why.
+    ForceDisconnect(GameError),
 }
 
 /// Returns true if a placed entity already claims the grid cell centred at (x, y).
@@ -38,7 +51,8 @@ pub fn is_cell_occupied(world: &mut bevy_ecs::prelude::World, x: f32, y: f32) ->
 }
 
 /// Sells the entity with the given index if it is a tower owned by `player_id`.
-/// Workers and Kings are never sellable. Returns the refund amount on success.
+/// Workers and Kings are never sellable. Returns the gold refunded on success;
+/// any essence the tower cost is refunded at the same rate.
 pub fn try_sell_entity(
     lobby: &mut crate::model::lobby::Lobby,
     player_id: i64,
@@ -58,13 +72,17 @@ pub fn try_sell_entity(
         .map(|(entity, _, shape)| (entity, shape.0));
 
     let (entity, shape) = found?;
-    let profile = crate::model::unit_config::get_unit_profile(shape);
-    let refund = (profile.gold_cost as f32 * 0.75) as u32;
+    let refund = crate::model::unit_config::sell_refund(crate::model::unit_config::tower_cost(
+        shape,
+        lobby.settings.mode,
+    ));
+    let tick = lobby.game_state.tick;
     if let Some(player) = lobby.players.iter_mut().find(|p| p.id == player_id) {
-        player.gold += refund;
+        player.add_gold(refund.gold, GoldReason::Refund, tick);
+        player.add_essence(refund.essence);
     }
     lobby.game_state.world.despawn(entity);
-    Some(refund)
+    Some(refund.gold)
 }
 
 /// Result of handling one client message. Direct replies are returned (not sent)
@@ -79,6 +97,13 @@ pub enum MessageOutcome {
     LeaveLobby,
     /// Message not applicable in-game (e.g. JoinQueue).
     Ignored,
+    /// Both players asked for a rematch; the caller creates the match,
+    /// which needs the server state, and announces it with
+    /// `Lobby::announce_rematch`.
+    StartRematch {
+        seating: Vec<(i64, String)>,
+        settings: MatchSettings,
+    },
 }
 
 /// Applies a client message to the lobby. Synchronous on purpose: it runs under
@@ -89,7 +114,22 @@ pub fn handle_client_message(
     player_id: i64,
     msg: ClientMessage,
 ) -> MessageOutcome {
-    use crate::model::messages::ServerMessage;
+    let is_gameplay = matches!(
+        msg,
+        ClientMessage::Place(_)
+            | ClientMessage::SellById { .. }
+            | ClientMessage::SkipToCombat
+            | ClientMessage::HireWorker { .. }
+            | ClientMessage::SendUnit { .. }
+            | ClientMessage::UpgradeKing {}
+            | ClientMessage::UpgradeWorkers { .. }
+    );
+    if is_gameplay && lobby.is_waiting_for_players() {
+        return MessageOutcome::Reply(GameError::WaitingForPlayers.into());
+    }
+    if is_gameplay && lobby.pause.is_paused() {
+        return MessageOutcome::Reply(GameError::GamePaused.into());
+    }
 
     match msg {
         ClientMessage::PickFamily { family } => {
@@ -98,29 +138,35 @@ pub fn handle_client_message(
                 return MessageOutcome::Ignored;
             };
             if lobby.players[idx].family.is_some() {
-                return MessageOutcome::Reply(ServerMessage::Error(
-                    "Family already locked for this match.".into(),
-                ));
+                return MessageOutcome::Reply(GameError::FamilyAlreadyLocked.into());
             }
             lobby.players[idx].family = Some(family);
             lobby.broadcast_changes();
             let catalog = crate::model::unit_config::family_catalog(family)
                 .into_iter()
-                .map(|unit_kind| crate::model::messages::BuildCatalogEntry {
-                    unit_kind,
-                    name: crate::model::unit_config::unit_kind_name(unit_kind),
-                    cost: crate::model::unit_config::get_unit_profile(unit_kind).gold_cost,
+                .map(|unit_kind| {
+                    let cost =
+                        crate::model::unit_config::tower_cost(unit_kind, lobby.settings.mode);
+                    crate::model::messages::BuildCatalogEntry {
+                        unit_kind,
+                        name: crate::model::unit_config::unit_kind_name(unit_kind),
+                        cost: cost.gold,
+                        essence_cost: cost.essence,
+                    }
                 })
                 .collect();
             MessageOutcome::Reply(ServerMessage::BuildCatalog(catalog))
         }
         ClientMessage::Place(p) => {
             if lobby.game_state.phase != GamePhase::Build {
-                return MessageOutcome::Reply(ServerMessage::Error(
-                    "Tower placement is only allowed during the build phase.".into(),
-                ));
+                return MessageOutcome::Reply(
+                    GameError::WrongPhase {
+                        required: GamePhase::Build,
+                    }
+                    .into(),
+                );
             }
-            let profile = crate::model::unit_config::get_unit_profile(p.shape);
+            let cost = crate::model::unit_config::tower_cost(p.shape, lobby.settings.mode);
             let player_idx = lobby.players.iter().position(|pl| pl.id == player_id);
 
             let Some(idx) = player_idx else {
@@ -128,22 +174,16 @@ pub fn handle_client_message(
             };
             match lobby.players[idx].family {
                 None => {
-                    return MessageOutcome::Reply(ServerMessage::Error(
-                        "Pick a family before building.".into(),
-                    ));
+                    return MessageOutcome::Reply(GameError::FamilyNotPicked.into());
                 }
                 Some(family) => {
                     if !crate::model::unit_config::family_catalog(family).contains(&p.shape) {
-                        return MessageOutcome::Reply(ServerMessage::Error(
-                            "That unit isn't in your family.".into(),
-                        ));
+                        return MessageOutcome::Reply(GameError::NotInFamily.into());
                     }
                 }
             }
             if p.row >= KING_PLACEMENT_ROW_LIMIT || p.col >= 10 {
-                return MessageOutcome::Reply(ServerMessage::Error(
-                    "Invalid placement coordinates.".into(),
-                ));
+                return MessageOutcome::Reply(GameError::InvalidPlacement.into());
             }
 
             let x = if idx == 0 {
@@ -156,36 +196,44 @@ pub fn handle_client_message(
             let y = (p.row as f32 * SQUARE_SIZE) + (SQUARE_SIZE / 2.0);
 
             if is_cell_occupied(&mut lobby.game_state.world, x, y) {
-                return MessageOutcome::Reply(ServerMessage::Error(
-                    "That square is already occupied.".into(),
-                ));
+                return MessageOutcome::Reply(GameError::CellOccupied.into());
+            }
+            if crate::handler::combat::placement_blocks_path(
+                &mut lobby.game_state.world,
+                idx,
+                p.col as usize,
+                p.row as usize,
+            ) {
+                return MessageOutcome::Reply(GameError::PathBlocked.into());
             }
 
-            if lobby.players[idx].try_spend_gold(profile.gold_cost) {
-                crate::handler::spawn::spawn_unit(
-                    &mut lobby.game_state.world,
-                    Position { x, y },
-                    p.shape,
-                    player_id,
-                );
-                lobby.broadcast_changes();
-                MessageOutcome::Handled
-            } else {
-                MessageOutcome::Reply(ServerMessage::Error(format!(
-                    "Insufficient gold for {:?} (cost: {})",
-                    p.shape, profile.gold_cost
-                )))
+            let tick = lobby.game_state.tick;
+            match lobby.players[idx].try_pay(cost, GoldReason::Tower, tick) {
+                Ok(()) => {
+                    crate::handler::spawn::spawn_unit(
+                        &mut lobby.game_state.world,
+                        Position { x, y },
+                        p.shape,
+                        player_id,
+                    );
+                    lobby.broadcast_changes();
+                    MessageOutcome::Handled
+                }
+                Err(err) => MessageOutcome::Reply(err.into()),
             }
         }
         ClientMessage::SkipToCombat => {
             lobby.game_state.phase_timer = 0.0;
             MessageOutcome::Handled
         }
-        ClientMessage::HireWorker {} => {
+        ClientMessage::HireWorker { mineral } => {
             let player_idx = lobby.players.iter().position(|p| p.id == player_id);
             let Some(idx) = player_idx else {
                 return MessageOutcome::Ignored;
             };
+            if mineral == Mineral::Essence && lobby.settings.mode != GameMode::Essence {
+                return MessageOutcome::Reply(GameError::EssenceDisabled.into());
+            }
             let worker_count = {
                 let mut q = lobby
                     .game_state
@@ -195,27 +243,39 @@ pub fn handle_client_message(
                     .filter(|(_, owner)| owner.0 == player_id)
                     .count()
             };
-            if worker_count >= crate::handler::worker::WORKER_CAP {
-                return MessageOutcome::Reply(ServerMessage::Error(
-                    "Worker limit reached (max 7)".into(),
-                ));
+            if worker_count >= WORKER_CAP {
+                return MessageOutcome::Reply(GameError::WorkerCap { max: WORKER_CAP }.into());
             }
-            if lobby.players[idx].try_spend_gold(50) {
+            let cost = worker_hire_cost(lobby.players[idx].workers_hired);
+            let tick = lobby.game_state.tick;
+            if lobby.players[idx].try_spend_gold(cost, GoldReason::HireWorker, tick) {
+                let veins = match mineral {
+                    Mineral::Gold => crate::handler::worker::VEIN_POSITIONS,
+                    Mineral::Essence => crate::handler::worker::ESSENCE_VEIN_POSITIONS,
+                };
                 let targets = TargetPositions {
-                    vein: crate::handler::worker::VEIN_POSITIONS[idx],
+                    vein: veins[idx],
                     cart: crate::handler::worker::CART_POSITIONS[idx],
                 };
-                crate::handler::spawn::spawn_worker(
+                let worker = crate::handler::spawn::spawn_worker(
                     &mut lobby.game_state.world,
                     player_id,
                     targets,
                 );
+                lobby.game_state.world.entity_mut(worker).insert(mineral);
+                let player = &mut lobby.players[idx];
+                player.workers_hired += 1;
+                player.next_worker_cost = worker_hire_cost(player.workers_hired);
                 lobby.broadcast_changes();
                 MessageOutcome::Handled
             } else {
-                MessageOutcome::Reply(ServerMessage::Error(
-                    "Insufficient gold for Worker (cost: 50)".into(),
-                ))
+                MessageOutcome::Reply(
+                    GameError::InsufficientGold {
+                        needed: cost,
+                        have: lobby.players[idx].gold(),
+                    }
+                    .into(),
+                )
             }
         }
         ClientMessage::SendUnit { shape } => {
@@ -231,26 +291,34 @@ pub fn handle_client_message(
                 wave,
                 lobby.players[idx].sends_this_wave[i],
             );
-            if lobby.players[idx].try_spend_gold(cost) {
+            let tick = lobby.game_state.tick;
+            if lobby.players[idx].try_spend_gold(cost, GoldReason::Send, tick) {
                 lobby.players[idx].spawning_queue.push(shape);
                 lobby.players[idx].income += sent_profile.income;
                 lobby.players[idx].sends_this_wave[i] += 1;
                 lobby.players[idx].refresh_send_costs(wave);
+                lobby.record_stats(player_id, |s| s.sends_made += 1);
                 lobby.broadcast_changes();
                 MessageOutcome::Handled
             } else {
-                MessageOutcome::Reply(ServerMessage::Error(format!(
-                    "Insufficient gold for {} (cost: {})",
-                    sent_profile.name, cost
-                )))
+                MessageOutcome::Reply(
+                    GameError::InsufficientGold {
+                        needed: cost,
+                        have: lobby.players[idx].gold(),
+                    }
+                    .into(),
+                )
             }
         }
         ClientMessage::LeaveLobby => MessageOutcome::LeaveLobby,
         ClientMessage::SellById { entity_id } => {
             if lobby.game_state.phase != GamePhase::Build {
-                return MessageOutcome::Reply(ServerMessage::Error(
-                    "Tower selling is only allowed during the build phase.".into(),
-                ));
+                return MessageOutcome::Reply(
+                    GameError::WrongPhase {
+                        required: GamePhase::Build,
+                    }
+                    .into(),
+                );
             }
             if try_sell_entity(lobby, player_id, entity_id).is_some() {
                 lobby.broadcast_changes();
@@ -312,8 +380,8 @@ pub fn handle_client_message(
             };
             let sell_value = match (owner_id, shape, is_worker) {
                 (Some(oid), Some(sh), false) if oid == player_id => {
-                    let profile = crate::model::unit_config::get_unit_profile(sh);
-                    Some((profile.gold_cost as f32 * 0.75) as u32)
+                    let cost = crate::model::unit_config::tower_cost(sh, lobby.settings.mode);
+                    Some(crate::model::unit_config::sell_refund(cost).gold)
                 }
                 _ => None,
             };
@@ -331,9 +399,12 @@ pub fn handle_client_message(
         }
         ClientMessage::UpgradeKing {} => {
             if lobby.game_state.phase != GamePhase::Build {
-                return MessageOutcome::Reply(ServerMessage::Error(
-                    "King upgrades are only available during the build phase.".into(),
-                ));
+                return MessageOutcome::Reply(
+                    GameError::WrongPhase {
+                        required: GamePhase::Build,
+                    }
+                    .into(),
+                );
             }
             let player_idx = lobby.players.iter().position(|p| p.id == player_id);
             let Some(idx) = player_idx else {
@@ -341,18 +412,20 @@ pub fn handle_client_message(
             };
             let current_tier = lobby.players[idx].king_tier;
             if current_tier >= 4 {
-                return MessageOutcome::Reply(ServerMessage::Error(
-                    "King is already at maximum tier.".into(),
-                ));
+                return MessageOutcome::Reply(GameError::MaxTier.into());
             }
             let tier = &KING_UPGRADE_TIERS[current_tier as usize];
-            if !lobby.players[idx].can_afford(tier.cost) {
-                return MessageOutcome::Reply(ServerMessage::Error(
-                    "Insufficient gold for king upgrade.".into(),
-                ));
+            let tick = lobby.game_state.tick;
+            if !lobby.players[idx].try_spend_gold(tier.cost, GoldReason::KingUpgrade, tick) {
+                return MessageOutcome::Reply(
+                    GameError::InsufficientGold {
+                        needed: tier.cost,
+                        have: lobby.players[idx].gold(),
+                    }
+                    .into(),
+                );
             }
-            // Deduct gold, increment tier, add income.
-            lobby.players[idx].gold -= tier.cost;
+            // Gold is spent; increment tier, add income.
             lobby.players[idx].king_tier += 1;
             lobby.players[idx].income += tier.income_delta;
             let hp_delta = tier.hp_delta;
@@ -378,10 +451,131 @@ pub fn handle_client_message(
                 lobby.broadcast_changes();
                 MessageOutcome::Handled
             } else {
-                MessageOutcome::Reply(ServerMessage::Error("King not found.".into()))
+                MessageOutcome::Reply(GameError::KingNotFound.into())
             }
         }
+        ClientMessage::UpgradeWorkers { upgrade } => {
+            let player_idx = lobby.players.iter().position(|p| p.id == player_id);
+            let Some(idx) = player_idx else {
+                return MessageOutcome::Ignored;
+            };
+            let Some(cost) = lobby.players[idx].worker_tiers.next_cost(upgrade) else {
+                return MessageOutcome::Reply(GameError::MaxTier.into());
+            };
+            let tick = lobby.game_state.tick;
+            if !lobby.players[idx].try_spend_gold(cost, GoldReason::WorkerUpgrade, tick) {
+                return MessageOutcome::Reply(
+                    GameError::InsufficientGold {
+                        needed: cost,
+                        have: lobby.players[idx].gold(),
+                    }
+                    .into(),
+                );
+            }
+            *lobby.players[idx].worker_tiers.tier_mut(upgrade) += 1;
+            lobby.broadcast_changes();
+            MessageOutcome::Handled
+        }
         ClientMessage::RequestFullState => MessageOutcome::Reply(lobby.full_state_message()),
+        ClientMessage::RequestGoldLedger => {
+            let Some(player) = lobby.players.iter().find(|p| p.id == player_id) else {
+                return MessageOutcome::Ignored;
+            };
+            MessageOutcome::Reply(ServerMessage::GoldLedger {
+                gold: player.gold(),
+                transactions: player.ledger.recent(),
+            })
+        }
+        ClientMessage::Chat { text } => {
+            let Some(player) = lobby.players.iter().find(|p| p.id == player_id) else {
+                return MessageOutcome::Ignored;
+            };
+            let text = match chat::validate_chat(&text) {
+                Ok(text) if lobby.filter_chat => chat::filter_profanity(&text),
+                Ok(text) => text,
+                Err(e) => return MessageOutcome::Reply(e.into()),
+            };
+            let msg = ServerMessage::Chat {
+                from_id: player_id,
+                username: player.username.clone(),
+                text,
+            };
+            lobby.relay_from(player_id, &msg);
+            MessageOutcome::Handled
+        }
+        ClientMessage::Ping { x, y, kind } => {
+            if !lobby.players.iter().any(|p| p.id == player_id) {
+                return MessageOutcome::Ignored;
+            }
+            if !chat::ping_in_bounds(x, y) {
+                return MessageOutcome::Reply(GameError::InvalidPing.into());
+            }
+            lobby.relay_from(
+                player_id,
+                &ServerMessage::Ping {
+                    from_id: player_id,
+                    x,
+                    y,
+                    kind,
+                },
+            );
+            MessageOutcome::Handled
+        }
+        ClientMessage::MuteOpponent { muted } => {
+            lobby.set_opponent_muted(player_id, muted);
+            MessageOutcome::Reply(ServerMessage::OpponentMuted { muted })
+        }
+        ClientMessage::Surrender => {
+            if !lobby.players.iter().any(|p| p.id == player_id) {
+                return MessageOutcome::Ignored;
+            }
+            if !lobby.surrender(player_id) {
+                return MessageOutcome::Reply(GameError::MatchOver.into());
+            }
+            MessageOutcome::Handled
+        }
+        ClientMessage::RequestRematch { swap_sides } => {
+            if !lobby.players.iter().any(|p| p.id == player_id) {
+                return MessageOutcome::Ignored;
+            }
+            if !lobby.is_finished() {
+                return MessageOutcome::Reply(GameError::MatchNotOver.into());
+            }
+            if !lobby.is_full() {
+                return MessageOutcome::Reply(GameError::RematchUnavailable.into());
+            }
+            lobby.relay(&ServerMessage::RematchRequested {
+                player_id,
+                swap_sides,
+            });
+            match lobby.vote_rematch(player_id, swap_sides) {
+                Some(seating) => MessageOutcome::StartRematch {
+                    seating,
+                    settings: lobby.settings,
+                },
+                None => MessageOutcome::Handled,
+            }
+        }
+        ClientMessage::RequestPause => {
+            if lobby.is_finished() || !lobby.players.iter().any(|p| p.id == player_id) {
+                return MessageOutcome::Ignored;
+            }
+            if let Err(e) = lobby.pause.pause(player_id) {
+                return MessageOutcome::Reply(e.into());
+            }
+            lobby.broadcast_changes();
+            MessageOutcome::Handled
+        }
+        ClientMessage::Resume => {
+            if !lobby.players.iter().any(|p| p.id == player_id) {
+                return MessageOutcome::Ignored;
+            }
+            if !lobby.pause.resume() {
+                return MessageOutcome::Reply(GameError::NotPaused.into());
+            }
+            lobby.broadcast_changes();
+            MessageOutcome::Handled
+        }
         _ => MessageOutcome::Ignored,
     }
 }
@@ -392,36 +586,104 @@ pub async fn in_game_loop(
     server_state: &ServerState,
     match_id: u64,
     player_id: i64,
-    shutdown_rx: &mut mpsc::Receiver<()>,
+    shutdown_rx: &mut mpsc::Receiver<GameError>,
+    limiter: &mut ConnectionLimiter,
 ) -> InGameLoopResult {
-    let Some(lobby_arc) = server_state.matches.read().await.get(&match_id).cloned() else {
+    let Some(handle) = server_state.matches.read().await.get(&match_id).cloned() else {
         // Match already torn down (e.g. opponent left and cleanup raced us).
         return InGameLoopResult::PlayerLeft;
     };
-    let mut game_rx = lobby_arc.lock().await.tx.subscribe();
-    lobby_arc.lock().await.broadcast_gamestate();
+    let Some((mut game_rx, mut relay_rx, mut rematch_rx, mut opponent_muted)) = handle
+        .with_lobby(move |lobby| {
+            let rx = lobby.tx.subscribe();
+            lobby.mark_loaded(player_id);
+            lobby.broadcast_gamestate();
+            (
+                rx,
+                lobby.subscribe_relays(),
+                lobby.subscribe_rematch(),
+                lobby.has_muted_opponent(player_id),
+            )
+        })
+        .await
+    else {
+        return InGameLoopResult::PlayerLeft;
+    };
+    let mut shutdown_deadline = server_state.shutdown_deadline.subscribe();
+    let pending_shutdown = *shutdown_deadline.borrow_and_update();
+    if let Some(deadline) = pending_shutdown {
+        let _ = crate::routes::ws::send_message(ws_sender, shutdown_notice(deadline)).await;
+    }
 
     loop {
         tokio::select! {
-            _ = shutdown_rx.recv() => {
-                break InGameLoopResult::ForceDisconnect;
+            reason = shutdown_rx.recv() => {
+                break InGameLoopResult::ForceDisconnect(reason.unwrap_or(GameError::SessionReplaced));
+            },
+            Ok(()) = shutdown_deadline.changed() => {
+                // The match keeps running; the drain aborts it at the deadline.
+                let deadline = *shutdown_deadline.borrow_and_update();
+                if let Some(deadline) = deadline {
+                    let _ = crate::routes::ws::send_message(ws_sender, shutdown_notice(deadline)).await;
+                }
+            },
+            Ok(relay) = relay_rx.recv() => {
+                if opponent_muted && relay.from_id != player_id {
+                    continue;
+                }
+                if ws_sender.send(Message::Text(relay.msg.into())).await.is_err() {
+                    break InGameLoopResult::ClientDisconnected;
+                }
+            },
+            Ok(()) = rematch_rx.changed() => {
+                let Some(match_id) = *rematch_rx.borrow_and_update() else {
+                    continue;
+                };
+                let _ = crate::routes::ws::send_message(ws_sender, ServerMessage::RematchStarted { match_id }).await;
+                break InGameLoopResult::Rematch(match_id);
             },
             maybe_msg = ws_receiver.next() => {
                 match maybe_msg {
                     Some(Ok(msg)) => {
                         if let Message::Text(text) = msg {
-                            if let Ok(client_msg) = serde_json::from_str::<ClientMessage>(&text) {
-                                let outcome = {
-                                    let mut lobby = lobby_arc.lock().await;
-                                    handle_client_message(&mut lobby, player_id, client_msg)
-                                }; // lobby guard dropped here, before any network await
-                                match outcome {
-                                    MessageOutcome::Reply(reply) => {
-                                        let _ = crate::routes::ws::send_message(ws_sender, reply).await;
+                            let client_msg = match limiter.screen(ws_sender, &text).await {
+                                Screened::Message(client_msg) => client_msg,
+                                Screened::Dropped => continue,
+                                Screened::Disconnect => break InGameLoopResult::ClientDisconnected,
+                            };
+                            let outcome = handle
+                                .with_lobby(move |lobby| handle_client_message(lobby, player_id, client_msg))
+                                .await;
+                            let Some(outcome) = outcome else {
+                                // The match was torn down under us.
+                                break InGameLoopResult::PlayerLeft;
+                            };
+                            match outcome {
+                                MessageOutcome::Reply(reply) => {
+                                    if let ServerMessage::OpponentMuted { muted } = reply {
+                                        opponent_muted = muted;
                                     }
-                                    MessageOutcome::LeaveLobby => break InGameLoopResult::PlayerLeft,
-                                    MessageOutcome::Handled | MessageOutcome::Ignored => {}
+                                    let _ = crate::routes::ws::send_message(ws_sender, reply).await;
                                 }
+                                MessageOutcome::LeaveLobby => break InGameLoopResult::PlayerLeft,
+                                MessageOutcome::StartRematch { seating, settings } => {
+                                    let shutting_down = shutdown_deadline.borrow().is_some();
+                                    if shutting_down {
+                                        let _ = crate::routes::ws::send_message(ws_sender, GameError::ServerShuttingDown.into()).await;
+                                        continue;
+                                    }
+                                    let [first, second]: [(i64, String); 2] = match seating.try_into() {
+                                        Ok(seats) => seats,
+                                        Err(_) => continue,
+                                    };
+                                    let match_id = create_match(server_state, first, second, settings).await;
+                                    // Both loops, this one included, move over on seeing this.
+                                    if handle.with_lobby(move |lobby| lobby.announce_rematch(match_id)).await.is_none() {
+                                        let _ = crate::routes::ws::send_message(ws_sender, ServerMessage::RematchStarted { match_id }).await;
+                                        break InGameLoopResult::Rematch(match_id);
+                                    }
+                                }
+                                MessageOutcome::Handled | MessageOutcome::Ignored => {}
                             }
                         }
                     },
@@ -437,16 +699,20 @@ pub async fn in_game_loop(
                     }
                     Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {
                         // Missed deltas: rebaseline this client with a direct snapshot.
-                        let snapshot = {
-                            let mut lobby = lobby_arc.lock().await;
-                            lobby.full_state_message() // -> ServerMessage::GameState
+                        server_state
+                            .metrics
+                            .lagged_resyncs
+                            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                        let Some(snapshot) = handle.with_lobby(|lobby| lobby.full_state_message()).await else {
+                            break InGameLoopResult::PlayerLeft;
                         };
                         if crate::routes::ws::send_message(ws_sender, snapshot).await.is_err() {
                             break InGameLoopResult::ClientDisconnected;
                         }
                     }
                     Err(tokio::sync::broadcast::error::RecvError::Closed) => {
-                        break InGameLoopResult::ClientDisconnected;
+                        // The lobby was dropped, e.g. after its tick panicked.
+                        break InGameLoopResult::PlayerLeft;
                     }
                 }
             }
@@ -507,7 +773,7 @@ mod tests {
         } else {
             RIGHT_BOARD_START + (p2_valid.col as f32 * SQUARE_SIZE) + (SQUARE_SIZE / 2.0)
         };
-        assert!(x2 >= RIGHT_BOARD_START && x2 < RIGHT_BOARD_END);
+        assert!((RIGHT_BOARD_START..RIGHT_BOARD_END).contains(&x2));
     }
 
     #[test]
@@ -530,7 +796,7 @@ mod tests {
             .find(|pl| pl.id == player_id)
             .unwrap();
 
-        if player.try_spend_gold(profile.gold_cost) {
+        if player.try_spend_gold(profile.gold_cost, GoldReason::Tower, 0) {
             let x = (p.col as f32 * SQUARE_SIZE) + (SQUARE_SIZE / 2.0);
             let y = (p.row as f32 * SQUARE_SIZE) + (SQUARE_SIZE / 2.0);
             crate::handler::spawn::spawn_unit(
@@ -542,7 +808,11 @@ mod tests {
         }
         // --- END SIMULATED logic ---
 
-        assert_eq!(lobby.players[0].gold, 75, "Square costs 25, 100 - 25 = 75");
+        assert_eq!(
+            lobby.players[0].gold(),
+            75,
+            "Square costs 25, 100 - 25 = 75"
+        );
         let mut query = lobby.game_state.world.query::<&ShapeComponent>();
         assert_eq!(query.iter(&lobby.game_state.world).count(), 1);
     }
@@ -562,21 +832,21 @@ mod tests {
         // --- SIMULATED logic ---
         let profile = crate::model::unit_config::get_unit_profile(p.shape);
         let player_opt = lobby.players.iter_mut().find(|pl| pl.id == player_id);
-        if let Some(player) = player_opt {
-            if player.try_spend_gold(profile.gold_cost) {
-                let x = (p.col as f32 * SQUARE_SIZE) + (SQUARE_SIZE / 2.0);
-                let y = (p.row as f32 * SQUARE_SIZE) + (SQUARE_SIZE / 2.0);
-                crate::handler::spawn::spawn_unit(
-                    &mut lobby.game_state.world,
-                    Position { x, y },
-                    p.shape,
-                    player_id,
-                );
-            }
+        if let Some(player) = player_opt
+            && player.try_spend_gold(profile.gold_cost, GoldReason::Tower, 0)
+        {
+            let x = (p.col as f32 * SQUARE_SIZE) + (SQUARE_SIZE / 2.0);
+            let y = (p.row as f32 * SQUARE_SIZE) + (SQUARE_SIZE / 2.0);
+            crate::handler::spawn::spawn_unit(
+                &mut lobby.game_state.world,
+                Position { x, y },
+                p.shape,
+                player_id,
+            );
         }
         // --- END SIMULATED logic ---
 
-        assert_eq!(lobby.players[0].gold, 10, "Gold should NOT be deducted");
+        assert_eq!(lobby.players[0].gold(), 10, "Gold should NOT be deducted");
         let mut query = lobby.game_state.world.query::<&ShapeComponent>();
         assert_eq!(
             query.iter(&lobby.game_state.world).count(),
@@ -602,22 +872,23 @@ mod tests {
         if lobby.game_state.phase == GamePhase::Build {
             let profile = crate::model::unit_config::get_unit_profile(p.shape);
             let player_idx = lobby.players.iter().position(|pl| pl.id == player_id);
-            if let Some(idx) = player_idx {
-                if lobby.players[idx].try_spend_gold(profile.gold_cost) {
-                    let x = (p.col as f32 * SQUARE_SIZE) + (SQUARE_SIZE / 2.0);
-                    let y = (p.row as f32 * SQUARE_SIZE) + (SQUARE_SIZE / 2.0);
-                    crate::handler::spawn::spawn_unit(
-                        &mut lobby.game_state.world,
-                        Position { x, y },
-                        p.shape,
-                        player_id,
-                    );
-                }
+            if let Some(idx) = player_idx
+                && lobby.players[idx].try_spend_gold(profile.gold_cost, GoldReason::Tower, 0)
+            {
+                let x = (p.col as f32 * SQUARE_SIZE) + (SQUARE_SIZE / 2.0);
+                let y = (p.row as f32 * SQUARE_SIZE) + (SQUARE_SIZE / 2.0);
+                crate::handler::spawn::spawn_unit(
+                    &mut lobby.game_state.world,
+                    Position { x, y },
+                    p.shape,
+                    player_id,
+                );
             }
         }
 
         assert_eq!(
-            lobby.players[0].gold, 100,
+            lobby.players[0].gold(),
+            100,
             "Gold should NOT be deducted when placement is rejected during combat"
         );
         let mut query = lobby.game_state.world.query::<&ShapeComponent>();
@@ -645,22 +916,23 @@ mod tests {
         if lobby.game_state.phase == GamePhase::Build {
             let profile = crate::model::unit_config::get_unit_profile(p.shape);
             let player_idx = lobby.players.iter().position(|pl| pl.id == player_id);
-            if let Some(idx) = player_idx {
-                if lobby.players[idx].try_spend_gold(profile.gold_cost) {
-                    let x = (p.col as f32 * SQUARE_SIZE) + (SQUARE_SIZE / 2.0);
-                    let y = (p.row as f32 * SQUARE_SIZE) + (SQUARE_SIZE / 2.0);
-                    crate::handler::spawn::spawn_unit(
-                        &mut lobby.game_state.world,
-                        Position { x, y },
-                        p.shape,
-                        player_id,
-                    );
-                }
+            if let Some(idx) = player_idx
+                && lobby.players[idx].try_spend_gold(profile.gold_cost, GoldReason::Tower, 0)
+            {
+                let x = (p.col as f32 * SQUARE_SIZE) + (SQUARE_SIZE / 2.0);
+                let y = (p.row as f32 * SQUARE_SIZE) + (SQUARE_SIZE / 2.0);
+                crate::handler::spawn::spawn_unit(
+                    &mut lobby.game_state.world,
+                    Position { x, y },
+                    p.shape,
+                    player_id,
+                );
             }
         }
 
         assert_eq!(
-            lobby.players[0].gold, 75,
+            lobby.players[0].gold(),
+            75,
             "Gold should be deducted (100 - 25 = 75) when placement succeeds during build"
         );
         let mut query = lobby.game_state.world.query::<&ShapeComponent>();
@@ -695,11 +967,49 @@ mod tests {
         let outcome = handle_client_message(&mut lobby, 1, msg);
 
         assert!(matches!(outcome, MessageOutcome::Handled));
-        assert_eq!(lobby.players[0].gold, 75);
+        assert_eq!(lobby.players[0].gold(), 75);
         let mut query = lobby.game_state.world.query::<&ShapeComponent>();
         assert_eq!(query.iter(&lobby.game_state.world).count(), 1);
     }
 
+    #[test]
+    fn gold_ledger_lists_the_callers_own_transactions() {
+        use crate::model::gold_ledger::GoldTransaction;
+
+        let mut lobby = Lobby::new();
+        lobby.players.push(Player::new(1, "p1".into(), 100));
+        lobby.players.push(Player::new(2, "p2".into(), 100));
+        lobby.game_state.tick = 42;
+        handle_client_message(
+            &mut lobby,
+            1,
+            ClientMessage::HireWorker {
+                mineral: Mineral::Gold,
+            },
+        );
+
+        let MessageOutcome::Reply(ServerMessage::GoldLedger { gold, transactions }) =
+            handle_client_message(&mut lobby, 1, ClientMessage::RequestGoldLedger)
+        else {
+            panic!("expected a GoldLedger reply");
+        };
+        assert_eq!(gold, 50);
+        assert_eq!(
+            transactions,
+            vec![GoldTransaction {
+                tick: 42,
+                amount: -50,
+                reason: GoldReason::HireWorker
+            }]
+        );
+        let MessageOutcome::Reply(ServerMessage::GoldLedger { transactions, .. }) =
+            handle_client_message(&mut lobby, 2, ClientMessage::RequestGoldLedger)
+        else {
+            panic!("expected a GoldLedger reply");
+        };
+        assert!(transactions.is_empty(), "only the caller's own ledger");
+    }
+
     #[test]
     fn handle_place_rejects_occupied_cell_with_reply() {
         use crate::model::messages::{ClientMessage, ServerMessage};
@@ -732,14 +1042,56 @@ mod tests {
 
         assert!(matches!(
             outcome,
-            MessageOutcome::Reply(ServerMessage::Error(_))
+            MessageOutcome::Reply(ServerMessage::Error(ref e)) if e.error == GameError::CellOccupied
         ));
         assert_eq!(
-            lobby.players[0].gold, 175,
+            lobby.players[0].gold(),
+            175,
             "second placement must not charge gold"
         );
     }
 
+    #[test]
+    fn handle_place_rejects_tower_that_seals_the_lane() {
+        use crate::model::messages::{ClientMessage, ServerMessage};
+
+        let mut lobby = Lobby::new();
+        lobby.players.push(Player::new(1, "p1".into(), 1000));
+        handle_client_message(
+            &mut lobby,
+            1,
+            ClientMessage::PickFamily {
+                family: crate::model::family::Family::Basic,
+            },
+        );
+        let place = |col| {
+            ClientMessage::Place(PlaceMessage {
+                shape: UnitKind::Square,
+                row: 2,
+                col,
+            })
+        };
+        for col in 0..9 {
+            assert!(matches!(
+                handle_client_message(&mut lobby, 1, place(col)),
+                MessageOutcome::Handled
+            ));
+        }
+        let gold = lobby.players[0].gold();
+
+        let outcome = handle_client_message(&mut lobby, 1, place(9));
+
+        assert!(matches!(
+            outcome,
+            MessageOutcome::Reply(ServerMessage::Error(ref e)) if e.error == GameError::PathBlocked
+        ));
+        assert_eq!(
+            lobby.players[0].gold(),
+            gold,
+            "rejected tower must not charge"
+        );
+    }
+
     #[test]
     fn place_rejected_without_family_picked() {
         use crate::model::messages::{ClientMessage, PlaceMessage, ServerMessage};
@@ -757,7 +1109,7 @@ mod tests {
         let outcome = handle_client_message(&mut lobby, player_id, msg);
         match outcome {
             MessageOutcome::Reply(ServerMessage::Error(e)) => {
-                assert!(e.contains("family"), "expected family error, got: {e}");
+                assert_eq!(e.error, GameError::FamilyNotPicked);
             }
             other => panic!("expected family-required error, got {other:?}"),
         }
@@ -791,7 +1143,7 @@ mod tests {
         );
         match second {
             MessageOutcome::Reply(ServerMessage::Error(e)) => {
-                assert!(e.contains("locked") || e.contains("already"));
+                assert_eq!(e.error, GameError::FamilyAlreadyLocked);
             }
             other => panic!("expected already-locked error, got {other:?}"),
         }
@@ -959,7 +1311,7 @@ mod tests {
             lobby.game_state.world.entities().contains(tower_b),
             "The new tower must not be despawned by a stale ID"
         );
-        assert_eq!(lobby.players[0].gold, 100, "No refund for a stale ID");
+        assert_eq!(lobby.players[0].gold(), 100, "No refund for a stale ID");
     }
 
     #[test]
@@ -1001,7 +1353,11 @@ mod tests {
         let sold = try_sell_entity(&mut lobby, player_id, entity.to_bits());
 
         assert_eq!(sold, Some(18), "Square costs 25, refund is 75% = 18");
-        assert_eq!(lobby.players[0].gold, 118, "Refund should be added to gold");
+        assert_eq!(
+            lobby.players[0].gold(),
+            118,
+            "Refund should be added to gold"
+        );
         assert!(
             !lobby.game_state.world.entities().contains(entity),
             "Sold tower should be despawned"
@@ -1024,7 +1380,7 @@ mod tests {
         let sold = try_sell_entity(&mut lobby, player_id, worker.to_bits());
 
         assert_eq!(sold, None, "Workers must not be sellable");
-        assert_eq!(lobby.players[0].gold, 100, "Gold must not change");
+        assert_eq!(lobby.players[0].gold(), 100, "Gold must not change");
         assert!(
             lobby.game_state.world.entities().contains(worker),
             "Worker must not be despawned"
@@ -1042,7 +1398,7 @@ mod tests {
         let sold = try_sell_entity(&mut lobby, player_id, king.to_bits());
 
         assert_eq!(sold, None, "The king must not be sellable");
-        assert_eq!(lobby.players[0].gold, 100, "Gold must not change");
+        assert_eq!(lobby.players[0].gold(), 100, "Gold must not change");
         assert!(
             lobby.game_state.world.entities().contains(king),
             "King must not be despawned"
@@ -1104,7 +1460,7 @@ mod tests {
                 let profile = get_unit_profile(shape);
                 let refund = (profile.gold_cost as f32 * 0.75) as u32;
                 if let Some(player) = lobby.players.iter_mut().find(|p| p.id == player_id) {
-                    player.gold += refund;
+                    player.add_gold(refund, GoldReason::Refund, 0);
                 }
                 lobby.game_state.world.despawn(entity);
             }
@@ -1112,7 +1468,8 @@ mod tests {
 
         // Square costs 25; 25 * 0.75 = 18 (truncated)
         assert_eq!(
-            lobby.players[0].gold, 118,
+            lobby.players[0].gold(),
+            118,
             "Gold should be refunded 18 (75% of 25)"
         );
         let mut check = lobby.game_state.world.query::<&ShapeComponent>();
@@ -1151,12 +1508,13 @@ mod tests {
 
             if let Some((entity, _shape)) = found {
                 lobby.game_state.world.despawn(entity);
-                lobby.players[0].gold += 18;
+                lobby.players[0].add_gold(18, GoldReason::Refund, 0);
             }
         }
 
         assert_eq!(
-            lobby.players[0].gold, 100,
+            lobby.players[0].gold(),
+            100,
             "Gold should not change in combat phase"
         );
         let mut check = lobby.game_state.world.query::<&ShapeComponent>();
@@ -1422,15 +1780,15 @@ mod tests {
 
         // Simulate SendUnit handler
         let player_idx = lobby.players.iter().position(|p| p.id == player_id);
-        if let Some(idx) = player_idx {
-            if lobby.players[idx].try_spend_gold(profile.send_cost) {
-                lobby.players[idx].spawning_queue.push(shape);
-                lobby.players[idx].income += profile.income;
-            }
+        if let Some(idx) = player_idx
+            && lobby.players[idx].try_spend_gold(profile.send_cost, GoldReason::Send, 0)
+        {
+            lobby.players[idx].spawning_queue.push(shape);
+            lobby.players[idx].income += profile.income;
         }
 
         assert_eq!(
-            lobby.players[0].gold,
+            lobby.players[0].gold(),
             100 - profile.send_cost,
             "Gold should be deducted by send cost"
         );
@@ -1455,16 +1813,16 @@ mod tests {
 
         let player_idx = lobby.players.iter().position(|p| p.id == player_id);
         let mut sent = false;
-        if let Some(idx) = player_idx {
-            if lobby.players[idx].try_spend_gold(profile.send_cost) {
-                lobby.players[idx].spawning_queue.push(shape);
-                lobby.players[idx].income += profile.income;
-                sent = true;
-            }
+        if let Some(idx) = player_idx
+            && lobby.players[idx].try_spend_gold(profile.send_cost, GoldReason::Send, 0)
+        {
+            lobby.players[idx].spawning_queue.push(shape);
+            lobby.players[idx].income += profile.income;
+            sent = true;
         }
 
         assert!(!sent, "Purchase should be rejected");
-        assert_eq!(lobby.players[0].gold, 3, "Gold should not be deducted");
+        assert_eq!(lobby.players[0].gold(), 3, "Gold should not be deducted");
         assert_eq!(lobby.players[0].income, 0, "Income should not change");
         assert!(
             lobby.players[0].spawning_queue.is_empty(),
@@ -1485,17 +1843,17 @@ mod tests {
 
         // Buy a Square (costs 8, income 1)
         let idx = 0;
-        if lobby.players[idx].try_spend_gold(square_profile.send_cost) {
+        if lobby.players[idx].try_spend_gold(square_profile.send_cost, GoldReason::Send, 0) {
             lobby.players[idx].spawning_queue.push(UnitKind::Square);
             lobby.players[idx].income += square_profile.income;
         }
         // Buy a Triangle (costs 20, income 2)
-        if lobby.players[idx].try_spend_gold(triangle_profile.send_cost) {
+        if lobby.players[idx].try_spend_gold(triangle_profile.send_cost, GoldReason::Send, 0) {
             lobby.players[idx].spawning_queue.push(UnitKind::Triangle);
             lobby.players[idx].income += triangle_profile.income;
         }
 
-        assert_eq!(lobby.players[0].gold, 200 - 8 - 20);
+        assert_eq!(lobby.players[0].gold(), 200 - 8 - 20);
         assert_eq!(lobby.players[0].income, 1 + 2);
         assert_eq!(lobby.players[0].spawning_queue.len(), 2);
     }
@@ -1509,22 +1867,19 @@ mod tests {
 
         // Simulate HireWorker handler (no phase guard)
         let player_idx = lobby.players.iter().position(|p| p.id == player_id);
-        if let Some(idx) = player_idx {
-            if lobby.players[idx].try_spend_gold(50) {
-                let targets = TargetPositions {
-                    vein: crate::handler::worker::VEIN_POSITIONS[idx],
-                    cart: crate::handler::worker::CART_POSITIONS[idx],
-                };
-                crate::handler::spawn::spawn_worker(
-                    &mut lobby.game_state.world,
-                    player_id,
-                    targets,
-                );
-            }
+        if let Some(idx) = player_idx
+            && lobby.players[idx].try_spend_gold(50, GoldReason::HireWorker, 0)
+        {
+            let targets = TargetPositions {
+                vein: crate::handler::worker::VEIN_POSITIONS[idx],
+                cart: crate::handler::worker::CART_POSITIONS[idx],
+            };
+            crate::handler::spawn::spawn_worker(&mut lobby.game_state.world, player_id, targets);
         }
 
         assert_eq!(
-            lobby.players[0].gold, 50,
+            lobby.players[0].gold(),
+            50,
             "Gold should be deducted for worker hire during combat"
         );
         let mut query = lobby
@@ -1561,16 +1916,16 @@ mod tests {
         use crate::model::king_config::KING_UPGRADE_TIERS;
         let mut lobby = make_lobby_with_king(1);
         let tier = &KING_UPGRADE_TIERS[0];
-        let initial_gold = lobby.players[0].gold;
+        let initial_gold = lobby.players[0].gold();
         let initial_income = lobby.players[0].income;
 
         // Manually apply upgrade (same logic as handler).
-        lobby.players[0].gold -= tier.cost;
+        assert!(lobby.players[0].try_spend_gold(tier.cost, GoldReason::KingUpgrade, 0));
         lobby.players[0].king_tier += 1;
         lobby.players[0].income += tier.income_delta;
 
         assert_eq!(lobby.players[0].king_tier, 1);
-        assert_eq!(lobby.players[0].gold, initial_gold - tier.cost);
+        assert_eq!(lobby.players[0].gold(), initial_gold - tier.cost);
         assert_eq!(lobby.players[0].income, initial_income + tier.income_delta);
     }
 
@@ -1590,8 +1945,9 @@ mod tests {
     fn king_upgrade_rejected_if_insufficient_gold() {
         use crate::model::king_config::KING_UPGRADE_TIERS;
         let mut lobby = make_lobby_with_king(1);
-        // Set gold below tier 1 cost.
-        lobby.players[0].gold = KING_UPGRADE_TIERS[0].cost - 1;
+        // Spend gold down to just below the tier 1 cost.
+        let spare = lobby.players[0].gold() - (KING_UPGRADE_TIERS[0].cost - 1);
+        assert!(lobby.players[0].try_spend_gold(spare, GoldReason::Tower, 0));
         let can_afford = lobby.players[0].can_afford(KING_UPGRADE_TIERS[0].cost);
         assert!(
             !can_afford,
@@ -1651,7 +2007,7 @@ mod tests {
 
         // Wave 1: scouts cost 8 then 12.
         for expected_cost in [8u32, 12u32] {
-            let before = lobby.players[0].gold;
+            let before = lobby.players[0].gold();
             let outcome = handle_client_message(
                 &mut lobby,
                 1,
@@ -1660,7 +2016,7 @@ mod tests {
                 },
             );
             assert!(matches!(outcome, MessageOutcome::Handled));
-            assert_eq!(before - lobby.players[0].gold, expected_cost);
+            assert_eq!(before - lobby.players[0].gold(), expected_cost);
         }
         assert_eq!(lobby.players[0].sends_this_wave[0], 2);
         assert_eq!(lobby.players[0].next_send_costs[0], 16); // third scout
@@ -1687,7 +2043,18 @@ mod tests {
                 shape: UnitKind::Square,
             },
         );
-        assert!(matches!(rejected, MessageOutcome::Reply(_)));
+        match rejected {
+            MessageOutcome::Reply(crate::model::messages::ServerMessage::Error(e)) => {
+                assert_eq!(
+                    e.error,
+                    GameError::InsufficientGold {
+                        needed: 12,
+                        have: 7
+                    }
+                );
+            }
+            other => panic!("expected InsufficientGold, got {other:?}"),
+        }
         assert_eq!(
             lobby.players[0].sends_this_wave[0], 1,
             "failed send must not bump counter"
@@ -1703,21 +2070,342 @@ mod tests {
             vein: crate::handler::worker::VEIN_POSITIONS[0],
             cart: crate::handler::worker::CART_POSITIONS[0],
         };
-        for _ in 0..crate::handler::worker::WORKER_CAP {
+        for _ in 0..WORKER_CAP {
             crate::handler::spawn::spawn_worker(&mut lobby.game_state.world, 1, targets);
         }
-        let outcome = handle_client_message(&mut lobby, 1, ClientMessage::HireWorker {});
+        let outcome = handle_client_message(
+            &mut lobby,
+            1,
+            ClientMessage::HireWorker {
+                mineral: Mineral::Gold,
+            },
+        );
         assert!(
-            matches!(outcome, MessageOutcome::Reply(_)),
+            matches!(
+                outcome,
+                MessageOutcome::Reply(crate::model::messages::ServerMessage::Error(ref e))
+                    if e.error == GameError::WorkerCap { max: WORKER_CAP }
+            ),
             "8th worker must be rejected"
         );
-        assert_eq!(lobby.players[0].gold, 1000, "no gold charged on rejection");
+        assert_eq!(
+            lobby.players[0].gold(),
+            1000,
+            "no gold charged on rejection"
+        );
         let count = lobby
             .game_state
             .world
             .query::<&Worker>()
             .iter(&lobby.game_state.world)
             .count();
-        assert_eq!(count, crate::handler::worker::WORKER_CAP);
+        assert_eq!(count, WORKER_CAP);
+    }
+
+    #[test]
+    fn each_worker_costs_more_than_the_last() {
+        use crate::model::messages::{ClientMessage, ServerMessage};
+        use crate::model::unit_config::STARTER_WORKERS;
+
+        let mut lobby = Lobby::new();
+        lobby.players.push(Player::new(1, "p1".into(), 115));
+        let targets = TargetPositions {
+            vein: crate::handler::worker::VEIN_POSITIONS[0],
+            cart: crate::handler::worker::CART_POSITIONS[0],
+        };
+        for _ in 0..STARTER_WORKERS {
+            crate::handler::spawn::spawn_worker(&mut lobby.game_state.world, 1, targets);
+        }
+        let hire = || ClientMessage::HireWorker {
+            mineral: Mineral::Gold,
+        };
+        assert!(matches!(
+            handle_client_message(&mut lobby, 1, hire()),
+            MessageOutcome::Handled
+        ));
+        assert_eq!(lobby.players[0].gold(), 65);
+        assert_eq!(lobby.players[0].next_worker_cost, 60);
+        assert!(matches!(
+            handle_client_message(&mut lobby, 1, hire()),
+            MessageOutcome::Handled
+        ));
+        assert_eq!(lobby.players[0].gold(), 5);
+        assert!(matches!(
+            handle_client_message(&mut lobby, 1, hire()),
+            MessageOutcome::Reply(ServerMessage::Error(ref e))
+                if e.error == GameError::InsufficientGold { needed: 70, have: 5 }
+        ));
+    }
+
+    #[test]
+    fn worker_upgrades_climb_tiers_until_the_top() {
+        use crate::model::messages::{ClientMessage, ServerMessage};
+        use crate::model::unit_config::{WORKER_UPGRADE_COSTS, WorkerUpgrade};
+
+        let mut lobby = Lobby::new();
+        lobby.players.push(Player::new(1, "p1".into(), 1000));
+        let upgrade = || ClientMessage::UpgradeWorkers {
+            upgrade: WorkerUpgrade::Speed,
+        };
+        for _ in WORKER_UPGRADE_COSTS {
+            let outcome = handle_client_message(&mut lobby, 1, upgrade());
+            assert!(matches!(outcome, MessageOutcome::Handled));
+        }
+        let spent: u32 = WORKER_UPGRADE_COSTS.iter().sum();
+        assert_eq!(lobby.players[0].gold(), 1000 - spent);
+        assert_eq!(lobby.players[0].worker_tiers.speed, 3);
+        assert_eq!(lobby.players[0].worker_tiers.carry, 0);
+        assert_eq!(lobby.players[0].ledger.spent().worker_upgrades, spent);
+        assert!(matches!(
+            handle_client_message(&mut lobby, 1, upgrade()),
+            MessageOutcome::Reply(ServerMessage::Error(ref e)) if e.error == GameError::MaxTier
+        ));
+    }
+
+    #[test]
+    fn essence_workers_need_the_essence_mode() {
+        use crate::model::components::Mineral;
+        use crate::model::match_settings::GameMode;
+        use crate::model::messages::{ClientMessage, ServerMessage};
+
+        let mut lobby = Lobby::new();
+        lobby.players.push(Player::new(1, "p1".into(), 100));
+        let hire = || ClientMessage::HireWorker {
+            mineral: Mineral::Essence,
+        };
+        let outcome = handle_client_message(&mut lobby, 1, hire());
+        assert!(matches!(
+            outcome,
+            MessageOutcome::Reply(ServerMessage::Error(ref e)) if e.error == GameError::EssenceDisabled
+        ));
+        assert_eq!(lobby.players[0].gold(), 100);
+
+        lobby.settings.mode = GameMode::Essence;
+        let outcome = handle_client_message(&mut lobby, 1, hire());
+        assert!(matches!(outcome, MessageOutcome::Handled));
+        let mut query = lobby
+            .game_state
+            .world
+            .query::<(&Mineral, &TargetPositions)>();
+        let (mineral, targets) = query.single(&lobby.game_state.world).unwrap();
+        assert_eq!(*mineral, Mineral::Essence);
+        assert_eq!(
+            targets.vein,
+            crate::handler::worker::ESSENCE_VEIN_POSITIONS[0]
+        );
+    }
+
+    #[test]
+    fn essence_mode_towers_cost_and_refund_essence() {
+        use crate::model::family::Family;
+        use crate::model::match_settings::GameMode;
+        use crate::model::messages::{ClientMessage, PlaceMessage, ServerMessage};
+
+        let mut lobby = Lobby::new();
+        lobby.settings.mode = GameMode::Essence;
+        lobby.players.push(Player::new(1, "p1".into(), 200));
+        let MessageOutcome::Reply(ServerMessage::BuildCatalog(catalog)) = handle_client_message(
+            &mut lobby,
+            1,
+            ClientMessage::PickFamily {
+                family: Family::Basic,
+            },
+        ) else {
+            panic!("expected the build catalog");
+        };
+        let circle = catalog.iter().find(|e| e.unit_kind == UnitKind::Circle);
+        assert_eq!(circle.map(|e| e.essence_cost), Some(25));
+        let place = || {
+            ClientMessage::Place(PlaceMessage {
+                shape: UnitKind::Circle,
+                row: 1,
+                col: 1,
+            })
+        };
+
+        let outcome = handle_client_message(&mut lobby, 1, place());
+        assert!(matches!(
+            outcome,
+            MessageOutcome::Reply(ServerMessage::Error(ref e))
+                if e.error == GameError::InsufficientEssence { needed: 25, have: 0 }
+        ));
+        assert_eq!(
+            lobby.players[0].gold(),
+            200,
+            "no gold taken without essence"
+        );
+
+        lobby.players[0].add_essence(30);
+        let outcome = handle_client_message(&mut lobby, 1, place());
+        assert!(matches!(outcome, MessageOutcome::Handled));
+        assert_eq!(
+            (lobby.players[0].gold(), lobby.players[0].essence()),
+            (125, 5)
+        );
+
+        let tower = lobby
+            .game_state
+            .world
+            .query::<(Entity, &ShapeComponent)>()
+            .iter(&lobby.game_state.world)
+            .next()
+            .map(|(e, _)| e.to_bits())
+            .unwrap();
+        assert_eq!(try_sell_entity(&mut lobby, 1, tower), Some(56));
+        assert_eq!(
+            (lobby.players[0].gold(), lobby.players[0].essence()),
+            (181, 23)
+        );
+    }
+
+    #[test]
+    fn chat_is_filtered_and_relayed_on_the_lobby_channel() {
+        let mut lobby = Lobby::new();
+        lobby.players.push(Player::new(1, "p1".into(), 100));
+        lobby.players.push(Player::new(2, "p2".into(), 100));
+        let mut rx = lobby.subscribe_relays();
+
+        let chat = |text: &str| ClientMessage::Chat {
+            text: text.to_string(),
+        };
+        let outcome = handle_client_message(&mut lobby, 1, chat("  well shit, gg "));
+        assert!(matches!(outcome, MessageOutcome::Handled));
+        let relay = rx.try_recv().unwrap();
+        assert_eq!(relay.from_id, 1);
+        let relayed: serde_json::Value = serde_json::from_str(&relay.msg).unwrap();
+        assert_eq!(relayed["type"], "Chat");
+        assert_eq!(relayed["data"]["from_id"], 1);
+        assert_eq!(relayed["data"]["username"], "p1");
+        assert_eq!(relayed["data"]["text"], "well ****, gg");
+
+        lobby.filter_chat = false;
+        handle_client_message(&mut lobby, 1, chat("shit"));
+        assert!(rx.try_recv().unwrap().msg.contains("\"text\":\"shit\""));
+
+        let outcome = handle_client_message(&mut lobby, 2, chat(" "));
+        assert!(matches!(
+            outcome,
+            MessageOutcome::Reply(ServerMessage::Error(ref e))
+                if matches!(e.error, GameError::InvalidChat { .. })
+        ));
+        let outcome = handle_client_message(&mut lobby, 3, chat("not in this match"));
+        assert!(matches!(outcome, MessageOutcome::Ignored));
+        assert!(rx.try_recv().is_err(), "rejected chat must not be relayed");
+    }
+
+    #[test]
+    fn pings_off_the_map_are_rejected() {
+        use crate::model::chat::PingKind;
+        let mut lobby = Lobby::new();
+        lobby.players.push(Player::new(1, "p1".into(), 100));
+        let mut rx = lobby.subscribe_relays();
+
+        let ping = |x: f32, y: f32| ClientMessage::Ping {
+            x,
+            y,
+            kind: PingKind::Attack,
+        };
+        let outcome = handle_client_message(&mut lobby, 1, ping(-5.0, 10.0));
+        assert!(matches!(
+            outcome,
+            MessageOutcome::Reply(ServerMessage::Error(ref e)) if e.error == GameError::InvalidPing
+        ));
+        let outcome = handle_client_message(&mut lobby, 1, ping(f32::NAN, 10.0));
+        assert!(!matches!(outcome, MessageOutcome::Handled));
+        assert!(rx.try_recv().is_err());
+
+        let outcome = handle_client_message(&mut lobby, 1, ping(700.0, 300.0));
+        assert!(matches!(outcome, MessageOutcome::Handled));
+        let relay = rx.try_recv().unwrap();
+        assert_eq!(relay.from_id, 1);
+        assert!(relay.msg.starts_with(r#"{"type":"Ping""#));
+    }
+
+    #[test]
+    fn mute_opponent_is_remembered_by_the_lobby() {
+        let mut lobby = Lobby::new();
+        lobby.players.push(Player::new(1, "p1".into(), 100));
+        lobby.players.push(Player::new(2, "p2".into(), 100));
+
+        let outcome =
+            handle_client_message(&mut lobby, 1, ClientMessage::MuteOpponent { muted: true });
+        assert!(matches!(
+            outcome,
+            MessageOutcome::Reply(ServerMessage::OpponentMuted { muted: true })
+        ));
+        assert!(lobby.has_muted_opponent(1));
+        assert!(!lobby.has_muted_opponent(2));
+        handle_client_message(&mut lobby, 1, ClientMessage::MuteOpponent { muted: false });
+        assert!(!lobby.has_muted_opponent(1));
+    }
+
+    #[test]
+    fn surrender_ends_the_match_but_keeps_both_players_seated() {
+        let mut lobby = Lobby::new();
+        lobby.players.push(Player::new(1, "p1".into(), 100));
+        lobby.players.push(Player::new(2, "p2".into(), 100));
+        let mut rx = lobby.tx.subscribe();
+
+        handle_client_message(&mut lobby, 1, ClientMessage::Surrender);
+        assert_eq!(lobby.game_state.phase, GamePhase::GameOver);
+        assert_eq!(lobby.winner_id, Some(2));
+        assert_eq!(lobby.players.len(), 2);
+        assert!(
+            rx.try_recv()
+                .unwrap()
+                .starts_with(r#"{"type":"Surrendered""#)
+        );
+        assert!(rx.try_recv().unwrap().contains(r#""winner_id":2"#));
+
+        assert!(matches!(
+            handle_client_message(&mut lobby, 2, ClientMessage::Surrender),
+            MessageOutcome::Reply(ServerMessage::Error(ref e)) if e.error == GameError::MatchOver
+        ));
+        assert_eq!(lobby.winner_id, Some(2), "a finished match stays decided");
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn rematch_starts_once_both_players_ask() {
+        let mut lobby = Lobby::new();
+        lobby.players.push(Player::new(1, "p1".into(), 100));
+        lobby.players.push(Player::new(2, "p2".into(), 100));
+        let rematch = |swap_sides| ClientMessage::RequestRematch { swap_sides };
+
+        let outcome = handle_client_message(&mut lobby, 1, rematch(false));
+        assert!(matches!(
+            outcome,
+            MessageOutcome::Reply(ServerMessage::Error(ref e)) if e.error == GameError::MatchNotOver
+        ));
+        lobby.surrender(2);
+        assert!(matches!(
+            handle_client_message(&mut lobby, 1, rematch(false)),
+            MessageOutcome::Handled
+        ));
+        match handle_client_message(&mut lobby, 2, rematch(true)) {
+            MessageOutcome::StartRematch { seating, .. } => {
+                assert_eq!(seating, vec![(2, "p2".into()), (1, "p1".into())]);
+            }
+            other => panic!("expected StartRematch, got {other:?}"),
+        }
+        assert!(
+            matches!(
+                handle_client_message(&mut lobby, 1, rematch(false)),
+                MessageOutcome::Handled
+            ),
+            "the rematch is only created once"
+        );
+
+        lobby.players.retain(|p| p.id != 2);
+        assert!(matches!(
+            handle_client_message(&mut lobby, 1, rematch(false)),
+            MessageOutcome::Reply(ServerMessage::Error(ref e)) if e.error == GameError::RematchUnavailable
+        ));
+
+        let mut rematch_rx = lobby.subscribe_rematch();
+        assert_eq!(*rematch_rx.borrow_and_update(), None);
+        lobby.announce_rematch(9);
+        assert!(rematch_rx.has_changed().unwrap());
+        assert_eq!(*rematch_rx.borrow_and_update(), Some(9));
     }
 }
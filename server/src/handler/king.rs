@@ -9,7 +9,7 @@ fn get_board(x: f32) -> Option<u8> {
     use crate::model::constants::{LEFT_BOARD_END, RIGHT_BOARD_END, RIGHT_BOARD_START};
     if x < LEFT_BOARD_END {
         Some(0)
-    } else if x >= RIGHT_BOARD_START && x < RIGHT_BOARD_END {
+    } else if (RIGHT_BOARD_START..RIGHT_BOARD_END).contains(&x) {
         Some(1)
     } else {
         None
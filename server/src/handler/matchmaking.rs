@@ -1,21 +1,25 @@
-use crate::model::{lobby::Lobby, player::Player};
+use crate::handler::ready_check::{self, ReadyCheckTicket};
+use crate::model::{lobby::Lobby, match_settings::MatchSettings, player::Player};
 use crate::state::{QueuedPlayer, ServerState};
-use std::sync::Arc;
 use std::sync::atomic::Ordering;
-use tokio::sync::{Mutex, oneshot};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
 
 pub enum JoinQueueOutcome {
-    /// Stored as the waiter; the receiver fires with the match_id when paired.
-    Waiting(oneshot::Receiver<u64>),
+    /// Stored as the waiter; the receiver fires with a ready-check ticket
+    /// when paired.
+    Waiting(oneshot::Receiver<ReadyCheckTicket>),
     /// Paired immediately with the player who was waiting.
-    Matched(u64),
+    Paired(ReadyCheckTicket),
 }
 
 /// Pairs the caller with the waiting player, or stores the caller as the waiter.
-/// All pairing happens under the queue mutex, so double-pairing is impossible.
-/// A waiter whose oneshot receiver has been dropped (connection died) is
-/// discarded rather than matched. Re-queueing by the same account replaces the
-/// old entry (its receiver gets Err, which the old task treats as a no-op).
+/// A pairing opens a ready check (see `handler::ready_check`) rather than a
+/// match. All pairing happens under the queue mutex, so double-pairing is
+/// impossible. A waiter whose oneshot receiver has been dropped (connection
+/// died) is discarded rather than paired. Re-queueing by the same account
+/// replaces the old entry (its receiver gets Err, which the old task treats as
+/// a no-op).
 pub async fn join_queue(
     state: &ServerState,
     account_id: i64,
@@ -23,26 +27,30 @@ pub async fn join_queue(
 ) -> JoinQueueOutcome {
     let mut queue = state.queue.lock().await;
     match queue.take() {
-        Some(waiter) if waiter.account_id != account_id && !waiter.match_tx.is_closed() => {
-            let match_id = create_match(
+        Some(waiter) if waiter.account_id != account_id && !waiter.paired_tx.is_closed() => {
+            let (waiter_ticket, ticket) = ready_check::open(
                 state,
                 (waiter.account_id, waiter.username.clone()),
                 (account_id, username.clone()),
             )
             .await;
-            if waiter.match_tx.send(match_id).is_err() {
+            if let Err(waiter_ticket) = waiter.paired_tx.send(waiter_ticket) {
                 // Receiver dropped between the is_closed check and the send:
-                // destroy the ghost match and make the joiner the new waiter.
-                state.matches.write().await.remove(&match_id);
+                // drop the ghost check and make the joiner the new waiter.
+                state
+                    .ready_checks
+                    .lock()
+                    .await
+                    .remove(&waiter_ticket.check_id);
                 let (tx, rx) = oneshot::channel();
                 *queue = Some(QueuedPlayer {
                     account_id,
                     username,
-                    match_tx: tx,
+                    paired_tx: tx,
                 });
                 return JoinQueueOutcome::Waiting(rx);
             }
-            JoinQueueOutcome::Matched(match_id)
+            JoinQueueOutcome::Paired(ticket)
         }
         _ => {
             // Queue empty, same-account re-queue, or dead waiter: become the waiter.
@@ -50,13 +58,30 @@ pub async fn join_queue(
             *queue = Some(QueuedPlayer {
                 account_id,
                 username,
-                match_tx: tx,
+                paired_tx: tx,
             });
             JoinQueueOutcome::Waiting(rx)
         }
     }
 }
 
+/// Keeps the account out of the queue for `Config::decline_penalty`.
+pub async fn penalise(state: &ServerState, account_id: i64) {
+    let until = Instant::now() + state.config.decline_penalty();
+    let mut penalties = state.queue_penalties.lock().await;
+    penalties.retain(|_, until| *until > Instant::now());
+    penalties.insert(account_id, until);
+}
+
+/// How much longer the account must wait before queueing again, if at all.
+pub async fn penalty_remaining(state: &ServerState, account_id: i64) -> Option<Duration> {
+    let penalties = state.queue_penalties.lock().await;
+    let remaining = penalties
+        .get(&account_id)?
+        .saturating_duration_since(Instant::now());
+    (!remaining.is_zero()).then_some(remaining)
+}
+
 /// Removes this account's queue entry. Returns false when the account is not
 /// the current waiter — including when a pairing in flight already took the
 /// entry (the caller's oneshot will fire; the match wins).
@@ -70,28 +95,30 @@ pub async fn leave_queue(state: &ServerState, account_id: i64) -> bool {
     }
 }
 
-/// Creates a lobby containing both players, registers it in `matches`, and
-/// returns the new match_id. The game loop is spawned here from Task 3 onward.
-pub async fn create_match(state: &ServerState, p1: (i64, String), p2: (i64, String)) -> u64 {
+/// Creates a lobby containing both players, hands it to a simulation shard
+/// (which starts ticking it, holding the first Build timer until both are
+/// set up), registers the handle in `matches`, and returns the new match_id.
+pub async fn create_match(
+    state: &ServerState,
+    p1: (i64, String),
+    p2: (i64, String),
+    settings: MatchSettings,
+) -> u64 {
     let match_id = state.next_match_id.fetch_add(1, Ordering::Relaxed);
-    let mut lobby = Lobby::new();
-    lobby.players.push(Player::new(p1.0, p1.1, 100));
-    lobby.players.push(Player::new(p2.0, p2.1, 100));
-    state
-        .matches
-        .write()
-        .await
-        .insert(match_id, Arc::new(Mutex::new(lobby)));
-    tokio::spawn(crate::handler::game_loop::run_game_loop(
-        state.clone(),
-        match_id,
-    ));
+    let mut lobby = Lobby::with_settings(settings);
+    lobby.wait_for_players(state.config.setup_timeout_secs as f32);
+    lobby.filter_chat = state.config.chat_filter;
+    lobby.add_player(Player::new(p1.0, p1.1, settings.starting_gold));
+    lobby.add_player(Player::new(p2.0, p2.1, settings.starting_gold));
+    let handle = state.sim.spawn_match(match_id, lobby);
+    state.matches.write().await.insert(match_id, handle);
     match_id
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::Config;
     use crate::state::ServerStateData;
     use sqlx::sqlite::SqlitePoolOptions;
 
@@ -100,7 +127,7 @@ mod tests {
             .connect("sqlite::memory:")
             .await
             .unwrap();
-        ServerStateData::new(db_pool)
+        ServerStateData::new(db_pool, Config::default())
     }
 
     #[tokio::test]
@@ -109,7 +136,7 @@ mod tests {
         let outcome = join_queue(&state, 1, "a".into()).await;
         assert!(matches!(outcome, JoinQueueOutcome::Waiting(_)));
         assert!(state.queue.lock().await.is_some());
-        assert!(state.matches.read().await.is_empty());
+        assert!(state.ready_checks.lock().await.is_empty());
     }
 
     #[tokio::test]
@@ -118,26 +145,48 @@ mod tests {
         let JoinQueueOutcome::Waiting(rx) = join_queue(&state, 1, "a".into()).await else {
             panic!("first joiner must wait");
         };
-        let JoinQueueOutcome::Matched(match_id) = join_queue(&state, 2, "b".into()).await else {
-            panic!("second joiner must match");
+        let JoinQueueOutcome::Paired(ticket) = join_queue(&state, 2, "b".into()).await else {
+            panic!("second joiner must pair");
         };
         assert_eq!(
-            rx.await.unwrap(),
-            match_id,
-            "waiter must be notified with the same match_id"
+            rx.await.unwrap().check_id,
+            ticket.check_id,
+            "waiter must be handed the same ready check"
         );
         assert!(
             state.queue.lock().await.is_none(),
             "queue must be empty after pairing"
         );
+        assert!(
+            state.matches.read().await.is_empty(),
+            "no match before both players accept"
+        );
 
-        let matches = state.matches.read().await;
-        let lobby = matches.get(&match_id).unwrap().lock().await;
-        assert_eq!(lobby.players.len(), 2);
-        let ids: Vec<i64> = lobby.players.iter().map(|p| p.id).collect();
+        ready_check::accept(&state, ticket.check_id, 1).await;
+        ready_check::accept(&state, ticket.check_id, 2).await;
+        let ready_check::ReadyCheckStatus::Started(match_id) = *ticket.status_rx.borrow() else {
+            panic!("both accepting must start the match");
+        };
+        let handle = state.matches.read().await.get(&match_id).unwrap().clone();
+        let ids = handle
+            .with_lobby(|lobby| lobby.players.iter().map(|p| p.id).collect::<Vec<i64>>())
+            .await
+            .unwrap();
+        assert_eq!(ids.len(), 2);
         assert!(ids.contains(&1) && ids.contains(&2));
     }
 
+    #[tokio::test]
+    async fn penalty_expires() {
+        let state = test_state().await;
+        assert_eq!(penalty_remaining(&state, 1).await, None);
+        penalise(&state, 1).await;
+        let remaining = penalty_remaining(&state, 1).await.unwrap();
+        assert!(remaining <= state.config.decline_penalty());
+        state.queue_penalties.lock().await.insert(1, Instant::now());
+        assert_eq!(penalty_remaining(&state, 1).await, None);
+    }
+
     #[tokio::test]
     async fn rejoin_by_same_account_replaces_entry_not_self_match() {
         let state = test_state().await;
@@ -147,7 +196,7 @@ mod tests {
             matches!(outcome, JoinQueueOutcome::Waiting(_)),
             "same account re-queue must wait, never self-match"
         );
-        assert!(state.matches.read().await.is_empty());
+        assert!(state.ready_checks.lock().await.is_empty());
     }
 
     #[tokio::test]
@@ -160,11 +209,11 @@ mod tests {
         let outcome = join_queue(&state, 2, "b".into()).await;
         assert!(
             matches!(outcome, JoinQueueOutcome::Waiting(_)),
-            "joiner must become the new waiter, not match a dead one"
+            "joiner must become the new waiter, not pair with a dead one"
         );
         assert!(
-            state.matches.read().await.is_empty(),
-            "no ghost match may be created"
+            state.ready_checks.lock().await.is_empty(),
+            "no ghost ready check may be created"
         );
     }
 
@@ -192,8 +241,9 @@ mod tests {
     #[tokio::test]
     async fn create_match_assigns_unique_ids() {
         let state = test_state().await;
-        let a = create_match(&state, (1, "a".into()), (2, "b".into())).await;
-        let b = create_match(&state, (3, "c".into()), (4, "d".into())).await;
+        let settings = MatchSettings::default();
+        let a = create_match(&state, (1, "a".into()), (2, "b".into()), settings).await;
+        let b = create_match(&state, (3, "c".into()), (4, "d".into()), settings).await;
         assert_ne!(a, b);
         assert_eq!(state.matches.read().await.len(), 2);
     }
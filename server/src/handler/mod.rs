@@ -1,10 +1,17 @@
+pub mod challenge;
 pub mod cleanup;
 pub mod combat;
 pub mod game_loop;
+pub mod handshake;
 pub mod in_game;
 pub mod king;
 pub mod matchmaking;
 pub mod pre_game;
+pub mod private_lobby;
+pub mod rate_limit;
+pub mod ready_check;
+pub mod shard;
+pub mod shutdown;
 #[cfg(test)]
 mod sim_tests;
 pub mod spawn;
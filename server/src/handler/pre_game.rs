@@ -1,123 +1,525 @@
 use crate::{
+    handler::challenge::{self, ChallengeStatus, ChallengeTicket},
     handler::matchmaking::{self, JoinQueueOutcome},
+    handler::private_lobby::{self, PrivateLobbyStatus},
+    handler::rate_limit::{ConnectionLimiter, Screened},
+    handler::ready_check::{self, ReadyCheckStatus, ReadyCheckTicket},
+    handler::shutdown::shutdown_notice,
     model::{
+        game_error::GameError,
         messages::{ClientMessage, ServerMessage},
         unit_config,
     },
     routes::ws::send_message,
-    state::{ServerState, UpgradedWebSocket},
+    state::{Activity, Inbox, ServerState, UpgradedWebSocket},
 };
 use futures_util::{
     StreamExt,
     stream::{SplitSink, SplitStream},
 };
-use tokio::sync::mpsc;
+use std::future::Future;
+use std::time::Instant;
+use tokio::sync::{mpsc, oneshot, watch};
 use tokio_tungstenite::tungstenite::Message;
 
+/// MatchFound plus the per-match catalogs the client needs before its first
+/// game state. Also sent when a rematch starts.
+pub async fn announce_match(ws_sender: &mut SplitSink<UpgradedWebSocket, Message>) {
+    let _ = send_message(ws_sender, ServerMessage::MatchFound).await;
+    let catalog = ServerMessage::SendUnitCatalog(unit_config::send_unit_catalog());
+    let _ = send_message(ws_sender, catalog).await;
+    let families = ServerMessage::FamilyOptions(unit_config::family_catalog_options());
+    let _ = send_message(ws_sender, families).await;
+}
+
 pub enum PreGameLoopResult {
     Joined(u64), // match_id
     ClientDisconnected,
-    ForceDisconnect,
+    /// Closed from the server side; the error tells the client why.
+    ForceDisconnect(GameError),
 }
 
-/// Pre-game phase: the client is idle until it sends JoinQueue. Once queued it
-/// waits for a pairing (or cancels with LeaveQueue). Pairing outcomes:
-/// - Matched immediately: reply MatchFound, return Joined(match_id).
-/// - Waiting: reply Queued, then select over the oneshot / LeaveQueue / disconnect.
+/// Pre-game phase: the client is idle until it sends JoinQueue, creates or
+/// joins a private lobby, or challenges a friend. While idle, messages pushed
+/// by other players (`Inbox::notify_rx`) are relayed.
+/// - Queue: reply Queued and wait for a pairing (or LeaveQueue). A pairing
+///   runs a ready check; if the opponent misses it, queue again.
+/// - Private lobby: relay the lobby until both members are ready.
+/// - Challenge: wait for the friend's answer (or CancelChallenge). The
+///   friend accepts from their own idle state.
+///
+/// Either way a started match replies MatchFound and returns Joined(match_id).
+/// Leaving the queue or lobby goes back to idle.
 pub async fn pre_game_loop(
     ws_sender: &mut SplitSink<UpgradedWebSocket, Message>,
     ws_receiver: &mut SplitStream<UpgradedWebSocket>,
     server_state: &ServerState,
     player_id: i64,
     username: String,
-    shutdown_rx: &mut mpsc::Receiver<()>,
+    inbox: &mut Inbox,
+    limiter: &mut ConnectionLimiter,
 ) -> PreGameLoopResult {
+    let mut shutdown_deadline = server_state.shutdown_deadline.subscribe();
+    let pending_shutdown = *shutdown_deadline.borrow_and_update();
+    if let Some(deadline) = pending_shutdown {
+        let _ = send_message(ws_sender, shutdown_notice(deadline)).await;
+    }
+    let mut pre_game = PreGame {
+        ws_sender,
+        ws_receiver,
+        server_state,
+        player_id,
+        username,
+        shutdown_rx: &mut inbox.kill_rx,
+        shutdown_deadline,
+        limiter,
+    };
     loop {
-        tokio::select! {
-            _ = shutdown_rx.recv() => {
-                return PreGameLoopResult::ForceDisconnect;
-            },
-            maybe_msg = ws_receiver.next() => {
-                match maybe_msg {
-                    Some(Ok(Message::Text(text))) => {
-                        if let Ok(ClientMessage::JoinQueue) = serde_json::from_str(&text) {
-                            match matchmaking::join_queue(server_state, player_id, username.clone()).await {
-                                JoinQueueOutcome::Matched(match_id) => {
-                                    let _ = send_message(ws_sender, ServerMessage::MatchFound).await;
-                                    let _ = send_message(
-                                        ws_sender,
-                                        ServerMessage::SendUnitCatalog(unit_config::send_unit_catalog()),
-                                    )
-                                    .await;
-                                    let _ = send_message(
-                                        ws_sender,
-                                        ServerMessage::FamilyOptions(unit_config::family_catalog_options()),
-                                    )
-                                    .await;
-                                    return PreGameLoopResult::Joined(match_id);
-                                }
-                                JoinQueueOutcome::Waiting(mut match_rx) => {
-                                    if send_message(ws_sender, ServerMessage::Queued).await.is_err() {
-                                        matchmaking::leave_queue(server_state, player_id).await;
-                                        return PreGameLoopResult::ClientDisconnected;
-                                    }
-                                    // Waiting-in-queue inner loop.
-                                    loop {
-                                        tokio::select! {
-                                            _ = shutdown_rx.recv() => {
-                                                matchmaking::leave_queue(server_state, player_id).await;
-                                                return PreGameLoopResult::ForceDisconnect;
-                                            },
-                                            result = &mut match_rx => {
-                                                match result {
-                                                    Ok(match_id) => {
-                                                        let _ = send_message(ws_sender, ServerMessage::MatchFound).await;
-                                                        let _ = send_message(
-                                                            ws_sender,
-                                                            ServerMessage::SendUnitCatalog(unit_config::send_unit_catalog()),
-                                                        )
-                                                        .await;
-                                                        let _ = send_message(
-                                                            ws_sender,
-                                                            ServerMessage::FamilyOptions(unit_config::family_catalog_options()),
-                                                        )
-                                                        .await;
-                                                        return PreGameLoopResult::Joined(match_id);
-                                                    }
-                                                    // Sender dropped: our entry was replaced
-                                                    // (defensive; shouldn't happen for a live
-                                                    // connection). Back to idle.
-                                                    Err(_) => break,
-                                                }
-                                            },
-                                            maybe_msg = ws_receiver.next() => {
-                                                match maybe_msg {
-                                                    Some(Ok(Message::Text(text))) => {
-                                                        if let Ok(ClientMessage::LeaveQueue) = serde_json::from_str(&text) {
-                                                            if matchmaking::leave_queue(server_state, player_id).await {
-                                                                break; // back to idle pre-game
-                                                            }
-                                                            // false: a pairing already took our
-                                                            // entry — the match wins; keep
-                                                            // waiting for match_rx to fire.
-                                                        }
-                                                    },
-                                                    Some(Ok(_)) => {},
-                                                    Some(Err(_)) | None => {
-                                                        matchmaking::leave_queue(server_state, player_id).await;
-                                                        return PreGameLoopResult::ClientDisconnected;
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
+        inbox.activity.set(Activity::Idle);
+        let msg = match pre_game.next_event(inbox.notify_rx.recv()).await {
+            Event::Message(msg) => msg,
+            Event::Disconnected => return PreGameLoopResult::ClientDisconnected,
+            Event::Kill(reason) => return PreGameLoopResult::ForceDisconnect(reason),
+            Event::Other(notice) => {
+                if let Some(notice) = notice {
+                    let _ = pre_game.send(notice).await;
+                }
+                continue;
+            }
+        };
+        inbox.activity.set(Activity::Busy);
+        let outcome = match msg {
+            ClientMessage::JoinQueue => pre_game.queue().await,
+            ClientMessage::CreatePrivateLobby => pre_game.private_lobby(None).await,
+            ClientMessage::JoinPrivateLobby { code } => pre_game.private_lobby(Some(code)).await,
+            ClientMessage::ChallengeFriend { account_id } => pre_game.challenge(account_id).await,
+            ClientMessage::RespondToChallenge {
+                challenge_id,
+                accept,
+            } => pre_game.answer_challenge(challenge_id, accept).await,
+            _ => None,
+        };
+        if let Some(result) = outcome {
+            return result;
+        }
+    }
+}
+
+/// What `PreGame::next_event` woke up for.
+enum Event<T> {
+    Message(ClientMessage),
+    /// The socket closed or the limiter gave up on it.
+    Disconnected,
+    /// Closed from the server side, e.g. the account logged in elsewhere.
+    Kill(GameError),
+    /// The caller's own future finished first.
+    Other(T),
+}
+
+/// How a ready check ended for this player.
+enum ReadyCheckEnd {
+    /// The opponent missed it; search again.
+    Requeue,
+    /// `None`: back to idle.
+    Done(Option<PreGameLoopResult>),
+}
+
+/// One connection's pre-game state, shared by the idle, queue, ready-check
+/// and private-lobby stages. Stages return `None` to go back to idle and
+/// `Some` to end the pre-game phase.
+struct PreGame<'a> {
+    ws_sender: &'a mut SplitSink<UpgradedWebSocket, Message>,
+    ws_receiver: &'a mut SplitStream<UpgradedWebSocket>,
+    server_state: &'a ServerState,
+    player_id: i64,
+    username: String,
+    shutdown_rx: &'a mut mpsc::Receiver<GameError>,
+    shutdown_deadline: watch::Receiver<Option<Instant>>,
+    limiter: &'a mut ConnectionLimiter,
+}
+
+impl PreGame<'_> {
+    /// Waits for the next client message, a disconnect, a kill, or `other`.
+    /// Shutdown notices and throttled frames are handled here.
+    async fn next_event<T>(&mut self, other: impl Future<Output = T>) -> Event<T> {
+        let mut other = std::pin::pin!(other);
+        loop {
+            tokio::select! {
+                value = &mut other => return Event::Other(value),
+                reason = self.shutdown_rx.recv() => {
+                    return Event::Kill(reason.unwrap_or(GameError::SessionReplaced));
+                },
+                Ok(()) = self.shutdown_deadline.changed() => {
+                    let deadline = *self.shutdown_deadline.borrow_and_update();
+                    if let Some(deadline) = deadline {
+                        let _ = send_message(self.ws_sender, shutdown_notice(deadline)).await;
+                    }
+                },
+                maybe_msg = self.ws_receiver.next() => {
+                    match maybe_msg {
+                        Some(Ok(Message::Text(text))) => {
+                            match self.limiter.screen(self.ws_sender, &text).await {
+                                Screened::Message(msg) => return Event::Message(msg),
+                                Screened::Dropped => {}
+                                Screened::Disconnect => return Event::Disconnected,
+                            }
+                        },
+                        Some(Ok(_)) => {},
+                        Some(Err(_)) | None => return Event::Disconnected,
+                    }
+                }
+            }
+        }
+    }
+
+    async fn send(&mut self, msg: ServerMessage) -> Result<(), ()> {
+        send_message(self.ws_sender, msg).await.map_err(|_| ())
+    }
+
+    /// Tells the client matchmaking is closed if a shutdown has begun.
+    async fn refuse_if_shutting_down(&mut self) -> bool {
+        if self.server_state.shutdown_deadline.borrow().is_none() {
+            return false;
+        }
+        let _ = self.send(GameError::ServerShuttingDown.into()).await;
+        true
+    }
+
+    async fn announce_match(&mut self) {
+        announce_match(self.ws_sender).await;
+    }
+
+    /// JoinQueue through to a started match, searching again whenever the
+    /// opponent misses the ready check.
+    async fn queue(&mut self) -> Option<PreGameLoopResult> {
+        let (state, player_id) = (self.server_state, self.player_id);
+        if let Some(wait) = matchmaking::penalty_remaining(state, player_id).await {
+            let seconds_remaining = wait.as_secs().max(1);
+            let _ = self
+                .send(GameError::QueuePenalty { seconds_remaining }.into())
+                .await;
+            return None;
+        }
+        loop {
+            if self.refuse_if_shutting_down().await {
+                return None;
+            }
+            let ticket =
+                match matchmaking::join_queue(state, player_id, self.username.clone()).await {
+                    JoinQueueOutcome::Paired(ticket) => ticket,
+                    JoinQueueOutcome::Waiting(paired_rx) => {
+                        if self.send(ServerMessage::Queued).await.is_err() {
+                            self.abandon_queue(paired_rx).await;
+                            return Some(PreGameLoopResult::ClientDisconnected);
+                        }
+                        match self.wait_for_pairing(paired_rx).await {
+                            Ok(ticket) => ticket,
+                            Err(outcome) => return outcome,
+                        }
+                    }
+                };
+            match self.ready_check(ticket).await {
+                ReadyCheckEnd::Requeue => continue,
+                ReadyCheckEnd::Done(outcome) => return outcome,
+            }
+        }
+    }
+
+    /// Waits in the queue until paired. `Err` ends the queue stage.
+    async fn wait_for_pairing(
+        &mut self,
+        mut paired_rx: oneshot::Receiver<ReadyCheckTicket>,
+    ) -> Result<ReadyCheckTicket, Option<PreGameLoopResult>> {
+        loop {
+            match self.next_event(&mut paired_rx).await {
+                Event::Other(Ok(ticket)) => return Ok(ticket),
+                // Sender dropped: the shutdown drain cleared the queue, or
+                // (defensive) our entry was replaced. Back to idle.
+                Event::Other(Err(_)) => {
+                    self.refuse_if_shutting_down().await;
+                    return Err(None);
+                }
+                Event::Message(ClientMessage::LeaveQueue) => {
+                    if matchmaking::leave_queue(self.server_state, self.player_id).await {
+                        return Err(None);
+                    }
+                    // false: a pairing already took our entry — the pairing
+                    // wins; keep waiting for paired_rx to fire.
+                }
+                Event::Message(_) => {}
+                Event::Disconnected => {
+                    self.abandon_queue(paired_rx).await;
+                    return Err(Some(PreGameLoopResult::ClientDisconnected));
+                }
+                Event::Kill(reason) => {
+                    self.abandon_queue(paired_rx).await;
+                    return Err(Some(PreGameLoopResult::ForceDisconnect(reason)));
+                }
+            }
+        }
+    }
+
+    /// Leaves the queue for good, declining a pairing that raced the exit so
+    /// the opponent is not left waiting for the timeout.
+    async fn abandon_queue(&mut self, mut paired_rx: oneshot::Receiver<ReadyCheckTicket>) {
+        if !matchmaking::leave_queue(self.server_state, self.player_id).await
+            && let Ok(ticket) = paired_rx.try_recv()
+        {
+            ready_check::decline(self.server_state, ticket.check_id, self.player_id).await;
+        }
+    }
+
+    /// Asks the player to accept the pairing and waits for both answers or
+    /// the deadline.
+    async fn ready_check(&mut self, ticket: ReadyCheckTicket) -> ReadyCheckEnd {
+        let (state, player_id) = (self.server_state, self.player_id);
+        let ReadyCheckTicket {
+            check_id,
+            deadline,
+            mut status_rx,
+        } = ticket;
+        let timeout_secs = state.config.ready_check_secs;
+        if self
+            .send(ServerMessage::ReadyCheck { timeout_secs })
+            .await
+            .is_err()
+        {
+            ready_check::decline(state, check_id, player_id).await;
+            return ReadyCheckEnd::Done(Some(PreGameLoopResult::ClientDisconnected));
+        }
+        loop {
+            let status_changed = tokio::time::timeout_at(deadline.into(), status_rx.changed());
+            match self.next_event(status_changed).await {
+                // Deadline passed; expiry updates the status, seen next turn.
+                Event::Other(Err(_elapsed)) => {
+                    ready_check::expire(state, check_id, Instant::now()).await
+                }
+                Event::Other(Ok(changed)) => {
+                    let status = match changed {
+                        Ok(()) => status_rx.borrow_and_update().clone(),
+                        Err(_) => ReadyCheckStatus::Failed { declined: vec![] },
+                    };
+                    match status {
+                        ReadyCheckStatus::Pending => {}
+                        ReadyCheckStatus::Started(match_id) => {
+                            self.announce_match().await;
+                            return ReadyCheckEnd::Done(Some(PreGameLoopResult::Joined(match_id)));
+                        }
+                        ReadyCheckStatus::Failed { declined } => {
+                            let requeued = !declined.contains(&player_id);
+                            let _ = self
+                                .send(ServerMessage::ReadyCheckFailed { requeued })
+                                .await;
+                            return if requeued {
+                                ReadyCheckEnd::Requeue
+                            } else {
+                                ReadyCheckEnd::Done(None)
+                            };
+                        }
+                    }
+                }
+                Event::Message(ClientMessage::AcceptMatch) => {
+                    ready_check::accept(state, check_id, player_id).await
+                }
+                Event::Message(ClientMessage::DeclineMatch) => {
+                    ready_check::decline(state, check_id, player_id).await
+                }
+                Event::Message(_) => {}
+                Event::Disconnected => {
+                    ready_check::decline(state, check_id, player_id).await;
+                    return ReadyCheckEnd::Done(Some(PreGameLoopResult::ClientDisconnected));
+                }
+                Event::Kill(reason) => {
+                    ready_check::decline(state, check_id, player_id).await;
+                    return ReadyCheckEnd::Done(Some(PreGameLoopResult::ForceDisconnect(reason)));
+                }
+            }
+        }
+    }
+
+    /// Challenges a friend and waits for their answer.
+    async fn challenge(&mut self, friend_id: i64) -> Option<PreGameLoopResult> {
+        if self.refuse_if_shutting_down().await {
+            return None;
+        }
+        let state = self.server_state;
+        let ticket =
+            match challenge::send(state, self.player_id, self.username.clone(), friend_id).await {
+                Ok(ticket) => ticket,
+                Err(e) => {
+                    let _ = self.send(e.into()).await;
+                    return None;
+                }
+            };
+        let ChallengeTicket {
+            info,
+            deadline,
+            mut status_rx,
+        } = ticket;
+        let challenge_id = info.challenge_id;
+        if self.send(ServerMessage::ChallengeSent(info)).await.is_err() {
+            challenge::withdraw(state, challenge_id).await;
+            return Some(PreGameLoopResult::ClientDisconnected);
+        }
+        loop {
+            let status_changed = tokio::time::timeout_at(deadline.into(), status_rx.changed());
+            match self.next_event(status_changed).await {
+                Event::Other(Err(_elapsed)) => {
+                    // False: the answer raced the deadline; read it next turn.
+                    if challenge::withdraw(state, challenge_id).await {
+                        let _ = self.send(GameError::ChallengeExpired.into()).await;
+                        return None;
+                    }
+                }
+                Event::Other(Ok(changed)) => {
+                    let status = match changed {
+                        Ok(()) => status_rx.borrow_and_update().clone(),
+                        Err(_) => ChallengeStatus::Withdrawn,
+                    };
+                    match status {
+                        ChallengeStatus::Pending => {}
+                        ChallengeStatus::Started(match_id) => {
+                            self.announce_match().await;
+                            return Some(PreGameLoopResult::Joined(match_id));
+                        }
+                        ChallengeStatus::Declined => {
+                            let _ = self.send(GameError::ChallengeDeclined.into()).await;
+                            return None;
+                        }
+                        // Only the shutdown drain withdraws someone else's.
+                        ChallengeStatus::Withdrawn => {
+                            self.refuse_if_shutting_down().await;
+                            return None;
+                        }
+                    }
+                }
+                Event::Message(ClientMessage::CancelChallenge) => {
+                    if challenge::withdraw(state, challenge_id).await {
+                        return None;
+                    }
+                }
+                Event::Message(_) => {}
+                Event::Disconnected => {
+                    challenge::withdraw(state, challenge_id).await;
+                    return Some(PreGameLoopResult::ClientDisconnected);
+                }
+                Event::Kill(reason) => {
+                    challenge::withdraw(state, challenge_id).await;
+                    return Some(PreGameLoopResult::ForceDisconnect(reason));
+                }
+            }
+        }
+    }
+
+    /// A challenged player's answer; accepting starts the match.
+    async fn answer_challenge(
+        &mut self,
+        challenge_id: u64,
+        accept: bool,
+    ) -> Option<PreGameLoopResult> {
+        if accept && self.refuse_if_shutting_down().await {
+            return None;
+        }
+        match challenge::respond(self.server_state, challenge_id, self.player_id, accept).await {
+            Ok(Some(match_id)) => {
+                self.announce_match().await;
+                Some(PreGameLoopResult::Joined(match_id))
+            }
+            Ok(None) => None,
+            Err(e) => {
+                let _ = self.send(e.into()).await;
+                None
+            }
+        }
+    }
+
+    /// Creates (`code` is `None`) or joins a private lobby, then relays every
+    /// change of it to the client and applies its ConfigurePrivateLobby /
+    /// SetReady / LeavePrivateLobby until the match starts or the lobby is
+    /// left or closed.
+    async fn private_lobby(&mut self, code: Option<String>) -> Option<PreGameLoopResult> {
+        if self.refuse_if_shutting_down().await {
+            return None;
+        }
+        let (state, player_id) = (self.server_state, self.player_id);
+        let entered = match code {
+            Some(code) => {
+                let code = private_lobby::normalize_code(&code);
+                private_lobby::join(state, &code, player_id, self.username.clone())
+                    .await
+                    .map(|status_rx| (code, status_rx))
+            }
+            None => Ok(private_lobby::create(state, player_id, self.username.clone()).await),
+        };
+        let (code, mut status_rx) = match entered {
+            Ok(entered) => entered,
+            Err(e) => {
+                let _ = self.send(e.into()).await;
+                return None;
+            }
+        };
+        // Send the state we joined to as well as later changes.
+        status_rx.mark_changed();
+        loop {
+            let result = match self.next_event(status_rx.changed()).await {
+                Event::Other(changed) => {
+                    let status = match changed {
+                        Ok(()) => status_rx.borrow_and_update().clone(),
+                        Err(_) => PrivateLobbyStatus::Closed,
+                    };
+                    match status {
+                        PrivateLobbyStatus::Open(info) => {
+                            if self.send(ServerMessage::PrivateLobby(info)).await.is_err() {
+                                private_lobby::leave(state, &code, player_id).await;
+                                return Some(PreGameLoopResult::ClientDisconnected);
+                            }
+                        }
+                        PrivateLobbyStatus::Started(match_id) => {
+                            self.announce_match().await;
+                            return Some(PreGameLoopResult::Joined(match_id));
+                        }
+                        PrivateLobbyStatus::Closed => {
+                            // The drain closes every private lobby too.
+                            if !self.refuse_if_shutting_down().await {
+                                let _ = self.send(GameError::PrivateLobbyClosed.into()).await;
                             }
+                            return None;
                         }
-                    },
-                    Some(Ok(_)) => {},
-                    Some(Err(_)) | None => return PreGameLoopResult::ClientDisconnected,
+                    }
+                    Ok(())
+                }
+                Event::Message(ClientMessage::ConfigurePrivateLobby { settings }) => {
+                    private_lobby::configure(state, &code, player_id, settings).await
+                }
+                Event::Message(ClientMessage::SetReady { ready }) => {
+                    private_lobby::set_ready(state, &code, player_id, ready).await
+                }
+                Event::Message(ClientMessage::LeavePrivateLobby) => {
+                    private_lobby::leave(state, &code, player_id).await;
+                    // The other member's ready may already have started the
+                    // match; the match wins.
+                    let started = match *status_rx.borrow() {
+                        PrivateLobbyStatus::Started(match_id) => Some(match_id),
+                        _ => None,
+                    };
+                    if let Some(match_id) = started {
+                        self.announce_match().await;
+                        return Some(PreGameLoopResult::Joined(match_id));
+                    }
+                    return None;
+                }
+                Event::Message(_) => Ok(()),
+                Event::Disconnected => {
+                    private_lobby::leave(state, &code, player_id).await;
+                    return Some(PreGameLoopResult::ClientDisconnected);
+                }
+                Event::Kill(reason) => {
+                    private_lobby::leave(state, &code, player_id).await;
+                    return Some(PreGameLoopResult::ForceDisconnect(reason));
                 }
+            };
+            if let Err(e) = result {
+                let _ = self.send(e.into()).await;
             }
         }
     }
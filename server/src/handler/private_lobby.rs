@@ -0,0 +1,347 @@
+use crate::handler::matchmaking::create_match;
+use crate::model::{
+    game_error::GameError, match_settings::MatchSettings, messages::PrivateLobbyInfo,
+};
+use crate::state::ServerState;
+use tokio::sync::watch;
+
+/// Join codes avoid 0/O and 1/I so they survive being read out loud.
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+pub const CODE_LEN: usize = 6;
+
+/// What a member's connection loop reacts to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PrivateLobbyStatus {
+    Open(PrivateLobbyInfo),
+    /// Both members were ready; the match with this id has been created.
+    Started(u64),
+    /// The host left (or the server is shutting down).
+    Closed,
+}
+
+pub struct LobbyMember {
+    pub account_id: i64,
+    pub username: String,
+    pub ready: bool,
+}
+
+/// A lobby waiting for its second player and for both to ready up. Lives in
+/// `ServerStateData::private_lobbies` until it starts or its host leaves.
+pub struct PrivateLobby {
+    pub host: LobbyMember,
+    pub guest: Option<LobbyMember>,
+    pub settings: MatchSettings,
+    /// Both members' loops watch this.
+    status_tx: watch::Sender<PrivateLobbyStatus>,
+}
+
+impl PrivateLobby {
+    fn info(&self, code: &str) -> PrivateLobbyInfo {
+        PrivateLobbyInfo {
+            code: code.to_string(),
+            host: self.host.username.clone(),
+            guest: self.guest.as_ref().map(|g| g.username.clone()),
+            host_ready: self.host.ready,
+            guest_ready: self.guest.as_ref().is_some_and(|g| g.ready),
+            settings: self.settings,
+        }
+    }
+
+    fn publish(&self, code: &str) {
+        self.status_tx
+            .send_replace(PrivateLobbyStatus::Open(self.info(code)));
+    }
+
+    fn member_mut(&mut self, account_id: i64) -> Option<&mut LobbyMember> {
+        if self.host.account_id == account_id {
+            return Some(&mut self.host);
+        }
+        self.guest.as_mut().filter(|g| g.account_id == account_id)
+    }
+}
+
+/// Upper-cases and trims a code as typed by a player.
+pub fn normalize_code(code: &str) -> String {
+    code.trim().to_ascii_uppercase()
+}
+
+fn generate_code() -> String {
+    uuid::Uuid::new_v4().as_bytes()[..CODE_LEN]
+        .iter()
+        .map(|b| CODE_ALPHABET[*b as usize % CODE_ALPHABET.len()] as char)
+        .collect()
+}
+
+/// Opens a lobby hosted by the caller with default settings. Returns its
+/// join code and the status receiver the caller's loop should watch.
+pub async fn create(
+    state: &ServerState,
+    account_id: i64,
+    username: String,
+) -> (String, watch::Receiver<PrivateLobbyStatus>) {
+    let mut lobbies = state.private_lobbies.lock().await;
+    let code = loop {
+        let code = generate_code();
+        if !lobbies.contains_key(&code) {
+            break code;
+        }
+    };
+    let lobby = PrivateLobby {
+        host: LobbyMember {
+            account_id,
+            username,
+            ready: false,
+        },
+        guest: None,
+        settings: MatchSettings::default(),
+        // Replaced by the first `publish` below.
+        status_tx: watch::Sender::new(PrivateLobbyStatus::Closed),
+    };
+    lobby.publish(&code);
+    let status_rx = lobby.status_tx.subscribe();
+    lobbies.insert(code.clone(), lobby);
+    (code, status_rx)
+}
+
+/// Takes the free guest seat of the lobby with this code.
+pub async fn join(
+    state: &ServerState,
+    code: &str,
+    account_id: i64,
+    username: String,
+) -> Result<watch::Receiver<PrivateLobbyStatus>, GameError> {
+    let mut lobbies = state.private_lobbies.lock().await;
+    let lobby = lobbies
+        .get_mut(code)
+        .ok_or(GameError::PrivateLobbyNotFound)?;
+    if lobby.guest.is_some() || lobby.host.account_id == account_id {
+        return Err(GameError::PrivateLobbyFull);
+    }
+    lobby.guest = Some(LobbyMember {
+        account_id,
+        username,
+        ready: false,
+    });
+    lobby.publish(code);
+    Ok(lobby.status_tx.subscribe())
+}
+
+/// Host only. Replaces the lobby's settings and clears both ready flags, so
+/// nobody starts on settings they did not see.
+pub async fn configure(
+    state: &ServerState,
+    code: &str,
+    account_id: i64,
+    settings: MatchSettings,
+) -> Result<(), GameError> {
+    settings
+        .validate()
+        .map_err(|reason| GameError::InvalidMatchSettings { reason })?;
+    let mut lobbies = state.private_lobbies.lock().await;
+    let lobby = lobbies.get_mut(code).ok_or(GameError::NotInPrivateLobby)?;
+    if lobby.host.account_id != account_id {
+        return Err(GameError::NotLobbyHost);
+    }
+    lobby.settings = settings;
+    lobby.host.ready = false;
+    if let Some(guest) = &mut lobby.guest {
+        guest.ready = false;
+    }
+    lobby.publish(code);
+    Ok(())
+}
+
+/// Sets the caller's ready flag. Once both seats are filled and ready the
+/// lobby is removed and handed to `create_match` with its settings; both
+/// members then see `PrivateLobbyStatus::Started`.
+pub async fn set_ready(
+    state: &ServerState,
+    code: &str,
+    account_id: i64,
+    ready: bool,
+) -> Result<(), GameError> {
+    let mut lobbies = state.private_lobbies.lock().await;
+    let lobby = lobbies.get_mut(code).ok_or(GameError::NotInPrivateLobby)?;
+    let member = lobby
+        .member_mut(account_id)
+        .ok_or(GameError::NotInPrivateLobby)?;
+    member.ready = ready;
+    let all_ready = lobby.host.ready && lobby.guest.as_ref().is_some_and(|g| g.ready);
+    if !all_ready {
+        lobby.publish(code);
+        return Ok(());
+    }
+    let lobby = lobbies.remove(code).expect("looked up above");
+    let guest = lobby.guest.expect("all_ready implies a guest");
+    let match_id = create_match(
+        state,
+        (lobby.host.account_id, lobby.host.username),
+        (guest.account_id, guest.username),
+        lobby.settings,
+    )
+    .await;
+    lobby
+        .status_tx
+        .send_replace(PrivateLobbyStatus::Started(match_id));
+    Ok(())
+}
+
+/// Removes the caller from the lobby. A leaving host closes it; a leaving
+/// guest frees the seat and un-readies the host. No-op if the lobby is gone.
+pub async fn leave(state: &ServerState, code: &str, account_id: i64) {
+    let mut lobbies = state.private_lobbies.lock().await;
+    let Some(lobby) = lobbies.get_mut(code) else {
+        return;
+    };
+    if lobby.host.account_id == account_id {
+        if let Some(lobby) = lobbies.remove(code) {
+            lobby.status_tx.send_replace(PrivateLobbyStatus::Closed);
+        }
+    } else if lobby
+        .guest
+        .as_ref()
+        .is_some_and(|g| g.account_id == account_id)
+    {
+        lobby.guest = None;
+        lobby.host.ready = false;
+        lobby.publish(code);
+    }
+}
+
+/// Closes every open lobby; used by the shutdown drain.
+pub async fn close_all(state: &ServerState) {
+    for (_, lobby) in state.private_lobbies.lock().await.drain() {
+        lobby.status_tx.send_replace(PrivateLobbyStatus::Closed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::test_support::test_state;
+
+    fn info(rx: &watch::Receiver<PrivateLobbyStatus>) -> PrivateLobbyInfo {
+        match &*rx.borrow() {
+            PrivateLobbyStatus::Open(info) => info.clone(),
+            other => panic!("expected an open lobby, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn codes_use_the_unambiguous_alphabet() {
+        let code = generate_code();
+        assert_eq!(code.len(), CODE_LEN);
+        assert!(code.bytes().all(|b| CODE_ALPHABET.contains(&b)));
+        assert_eq!(normalize_code(" abc234 "), "ABC234");
+    }
+
+    #[tokio::test]
+    async fn both_ready_starts_a_match_with_the_host_settings() {
+        let state = test_state().await;
+        let (code, host_rx) = create(&state, 1, "host".into()).await;
+        let guest_rx = join(&state, &code, 2, "guest".into()).await.unwrap();
+        assert_eq!(info(&host_rx).guest.as_deref(), Some("guest"));
+
+        let settings = MatchSettings {
+            starting_gold: 500,
+            build_time_secs: 10,
+            starting_wave: 5,
+            ..MatchSettings::default()
+        };
+        assert_eq!(
+            configure(&state, &code, 2, settings).await,
+            Err(GameError::NotLobbyHost)
+        );
+        configure(&state, &code, 1, settings).await.unwrap();
+
+        set_ready(&state, &code, 1, true).await.unwrap();
+        assert!(info(&guest_rx).host_ready);
+        assert!(state.matches.read().await.is_empty());
+        set_ready(&state, &code, 2, true).await.unwrap();
+
+        let PrivateLobbyStatus::Started(match_id) = *guest_rx.borrow() else {
+            panic!("both ready must start the match");
+        };
+        assert_eq!(*host_rx.borrow(), PrivateLobbyStatus::Started(match_id));
+        assert!(state.private_lobbies.lock().await.is_empty());
+
+        let handle = state.matches.read().await.get(&match_id).unwrap().clone();
+        let (wave, gold, timer) = handle
+            .with_lobby(|lobby| {
+                (
+                    lobby.game_state.wave_number,
+                    lobby.players[0].gold(),
+                    lobby.game_state.phase_timer,
+                )
+            })
+            .await
+            .unwrap();
+        assert_eq!(wave, 5);
+        assert_eq!(gold, 500);
+        assert!(timer <= 10.0);
+    }
+
+    #[tokio::test]
+    async fn changing_settings_clears_ready_flags() {
+        let state = test_state().await;
+        let (code, host_rx) = create(&state, 1, "host".into()).await;
+        let _guest_rx = join(&state, &code, 2, "guest".into()).await.unwrap();
+        set_ready(&state, &code, 2, true).await.unwrap();
+        assert!(info(&host_rx).guest_ready);
+
+        let invalid = MatchSettings {
+            build_time_secs: 0,
+            ..MatchSettings::default()
+        };
+        assert!(matches!(
+            configure(&state, &code, 1, invalid).await,
+            Err(GameError::InvalidMatchSettings { .. })
+        ));
+        assert!(info(&host_rx).guest_ready);
+
+        let faster = MatchSettings {
+            build_time_secs: 15,
+            ..MatchSettings::default()
+        };
+        configure(&state, &code, 1, faster).await.unwrap();
+        assert!(!info(&host_rx).guest_ready);
+        assert_eq!(info(&host_rx).settings, faster);
+    }
+
+    #[tokio::test]
+    async fn join_rejects_unknown_full_and_own_lobbies() {
+        let state = test_state().await;
+        let (code, _host_rx) = create(&state, 1, "host".into()).await;
+        assert_eq!(
+            join(&state, "ZZZZZZ", 2, "guest".into()).await.err(),
+            Some(GameError::PrivateLobbyNotFound)
+        );
+        assert_eq!(
+            join(&state, &code, 1, "host".into()).await.err(),
+            Some(GameError::PrivateLobbyFull)
+        );
+        let _guest_rx = join(&state, &code, 2, "guest".into()).await.unwrap();
+        assert_eq!(
+            join(&state, &code, 3, "late".into()).await.err(),
+            Some(GameError::PrivateLobbyFull)
+        );
+    }
+
+    #[tokio::test]
+    async fn guest_leaving_frees_the_seat_and_host_leaving_closes() {
+        let state = test_state().await;
+        let (code, host_rx) = create(&state, 1, "host".into()).await;
+        let _first_guest = join(&state, &code, 2, "guest".into()).await.unwrap();
+        set_ready(&state, &code, 1, true).await.unwrap();
+
+        leave(&state, &code, 2).await;
+        let after = info(&host_rx);
+        assert_eq!(after.guest, None);
+        assert!(!after.host_ready);
+
+        let second_guest = join(&state, &code, 3, "other".into()).await.unwrap();
+        leave(&state, &code, 1).await;
+        assert_eq!(*second_guest.borrow(), PrivateLobbyStatus::Closed);
+        assert!(state.private_lobbies.lock().await.is_empty());
+    }
+}
@@ -0,0 +1,399 @@
+use crate::{
+    model::{game_error::GameError, messages::ClientMessage},
+    routes::ws::send_message,
+    state::UpgradedWebSocket,
+};
+use futures_util::stream::SplitSink;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::time::Instant;
+use tokio_tungstenite::tungstenite::Message;
+
+/// Bounds `max_message_bytes` may be configured within.
+pub const MAX_MESSAGE_BYTES_RANGE: std::ops::RangeInclusive<usize> = 256..=1024 * 1024;
+
+/// Burst size and sustained refill rate of one token bucket.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BucketConfig {
+    pub burst: u32,
+    pub per_second: f32,
+}
+
+/// Per-connection abuse limits, shared by every socket via `ServerStateData`.
+/// Read from the `rate_limits` section of `Config`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// Bucket for any `ClientMessage` kind without an entry in `per_kind`.
+    pub default: BucketConfig,
+    /// Overrides keyed by `ClientMessage::kind()` (the wire `action` name).
+    /// Entries from a config file replace the built-in one for that kind
+    /// and leave the rest alone.
+    #[serde(deserialize_with = "over_default_kinds")]
+    pub per_kind: HashMap<String, BucketConfig>,
+    /// Largest text frame accepted; bigger ones count as invalid. The
+    /// transport refuses frames past `transport_limit` outright.
+    pub max_message_bytes: usize,
+    /// Unparseable or oversized frames drain this bucket; once empty the
+    /// connection is dropped.
+    pub invalid_frames: BucketConfig,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        let per_kind = [
+            // Takes the lobby lock and snapshots every unit.
+            (
+                "requestFullState",
+                BucketConfig {
+                    burst: 2,
+                    per_second: 0.5,
+                },
+            ),
+            (
+                "requestUnitInfo",
+                BucketConfig {
+                    burst: 10,
+                    per_second: 5.0,
+                },
+            ),
+            // Slows down guessing other players' join codes.
+            (
+                "joinPrivateLobby",
+                BucketConfig {
+                    burst: 5,
+                    per_second: 0.5,
+                },
+            ),
+            // Each one queries the DB and pushes a notice to the friend.
+            (
+                "challengeFriend",
+                BucketConfig {
+                    burst: 3,
+                    per_second: 0.2,
+                },
+            ),
+            // Relayed to everyone in the match.
+            (
+                "chat",
+                BucketConfig {
+                    burst: 5,
+                    per_second: 0.5,
+                },
+            ),
+            (
+                "ping",
+                BucketConfig {
+                    burst: 3,
+                    per_second: 1.0,
+                },
+            ),
+            (
+                "sendUnit",
+                BucketConfig {
+                    burst: 10,
+                    per_second: 5.0,
+                },
+            ),
+        ]
+        .into_iter()
+        .map(|(kind, bucket)| (kind.to_string(), bucket))
+        .collect();
+
+        Self {
+            default: BucketConfig {
+                burst: 20,
+                per_second: 10.0,
+            },
+            per_kind,
+            max_message_bytes: 4 * 1024,
+            invalid_frames: BucketConfig {
+                burst: 5,
+                per_second: 0.2,
+            },
+        }
+    }
+}
+
+fn over_default_kinds<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, BucketConfig>, D::Error> {
+    let mut per_kind = RateLimitConfig::default().per_kind;
+    per_kind.extend(HashMap::<String, BucketConfig>::deserialize(deserializer)?);
+    Ok(per_kind)
+}
+
+impl RateLimitConfig {
+    /// The frame size tungstenite is told to refuse. Kept well above
+    /// `max_message_bytes` so moderately oversized frames reach the limiter
+    /// and count against `invalid_frames`, while huge ones are never buffered.
+    pub fn transport_limit(&self) -> usize {
+        self.max_message_bytes.saturating_mul(4)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let check = |name: &str, bucket: &BucketConfig| {
+            if bucket.burst == 0 {
+                return Err(format!("rate_limits.{name}.burst must be at least 1"));
+            }
+            if !(bucket.per_second.is_finite() && bucket.per_second > 0.0) {
+                return Err(format!("rate_limits.{name}.per_second must be positive"));
+            }
+            Ok(())
+        };
+        check("default", &self.default)?;
+        check("invalid_frames", &self.invalid_frames)?;
+        let kinds = crate::model::messages::client_message_kinds();
+        for (kind, bucket) in &self.per_kind {
+            if !kinds.contains(kind) {
+                return Err(format!("rate_limits.per_kind has unknown action {kind:?}"));
+            }
+            check(&format!("per_kind.{kind}"), bucket)?;
+        }
+        if !MAX_MESSAGE_BYTES_RANGE.contains(&self.max_message_bytes) {
+            return Err(format!(
+                "rate_limits.max_message_bytes must be {} to {}",
+                MAX_MESSAGE_BYTES_RANGE.start(),
+                MAX_MESSAGE_BYTES_RANGE.end()
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    config: BucketConfig,
+    tokens: f32,
+    last_refill: Instant,
+    /// Set once the client has been told this bucket is dry; cleared on the
+    /// next successful take.
+    throttle_notified: bool,
+}
+
+impl TokenBucket {
+    fn new(config: BucketConfig, now: Instant) -> Self {
+        Self {
+            config,
+            tokens: config.burst as f32,
+            last_refill: now,
+            throttle_notified: false,
+        }
+    }
+
+    fn try_take(&mut self, now: Instant) -> bool {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f32();
+        self.tokens =
+            (self.tokens + elapsed * self.config.per_second).min(self.config.burst as f32);
+        self.last_refill = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// What the connection loop should do with one inbound text frame.
+#[derive(Debug)]
+pub enum FrameVerdict {
+    Accept(ClientMessage),
+    /// Over the kind's rate: drop it. `Some` carries the error to send the
+    /// first time the bucket runs dry; repeats are dropped silently.
+    Throttled(Option<GameError>),
+    /// Unparseable or oversized, but still under the strike limit: drop it.
+    Invalid,
+    /// Too many invalid frames: send this error and close the connection.
+    Disconnect(GameError),
+}
+
+/// `FrameVerdict` after any error reply has already been sent.
+pub enum Screened {
+    Message(ClientMessage),
+    Dropped,
+    Disconnect,
+}
+
+/// Token buckets for one WebSocket connection. Lives on the connection task
+/// (no locking) and is threaded through both the pre-game and in-game loops
+/// so a client cannot reset its budget by joining a match.
+#[derive(Debug)]
+pub struct ConnectionLimiter {
+    config: RateLimitConfig,
+    buckets: HashMap<&'static str, TokenBucket>,
+    invalid_frames: TokenBucket,
+}
+
+impl ConnectionLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        let invalid_frames = TokenBucket::new(config.invalid_frames, Instant::now());
+        Self {
+            config,
+            buckets: HashMap::new(),
+            invalid_frames,
+        }
+    }
+
+    pub fn check_frame(&mut self, text: &str, now: Instant) -> FrameVerdict {
+        let parsed = if text.len() > self.config.max_message_bytes {
+            None
+        } else {
+            serde_json::from_str::<ClientMessage>(text).ok()
+        };
+        let Some(msg) = parsed else {
+            return if self.invalid_frames.try_take(now) {
+                FrameVerdict::Invalid
+            } else {
+                FrameVerdict::Disconnect(GameError::TooManyInvalidMessages)
+            };
+        };
+
+        let kind = msg.kind();
+        let config = self
+            .config
+            .per_kind
+            .get(kind)
+            .copied()
+            .unwrap_or(self.config.default);
+        let bucket = self
+            .buckets
+            .entry(kind)
+            .or_insert_with(|| TokenBucket::new(config, now));
+        if bucket.try_take(now) {
+            bucket.throttle_notified = false;
+            FrameVerdict::Accept(msg)
+        } else if bucket.throttle_notified {
+            FrameVerdict::Throttled(None)
+        } else {
+            bucket.throttle_notified = true;
+            FrameVerdict::Throttled(Some(GameError::RateLimited {
+                action: kind.to_string(),
+            }))
+        }
+    }
+
+    /// Runs `check_frame` and sends whatever error the verdict calls for, so
+    /// connection loops only have to act on the message or the disconnect.
+    pub async fn screen(
+        &mut self,
+        ws_sender: &mut SplitSink<UpgradedWebSocket, Message>,
+        text: &str,
+    ) -> Screened {
+        match self.check_frame(text, Instant::now()) {
+            FrameVerdict::Accept(msg) => Screened::Message(msg),
+            FrameVerdict::Throttled(Some(error)) => {
+                let _ = send_message(ws_sender, error.into()).await;
+                Screened::Dropped
+            }
+            FrameVerdict::Throttled(None) | FrameVerdict::Invalid => Screened::Dropped,
+            FrameVerdict::Disconnect(error) => {
+                log::warn!("Dropping connection: {}", error);
+                let _ = send_message(ws_sender, error.into()).await;
+                Screened::Disconnect
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const FULL_STATE: &str = r#"{"action":"requestFullState"}"#;
+
+    #[test]
+    fn burst_is_allowed_then_throttled_with_a_single_notice() {
+        let mut limiter = ConnectionLimiter::new(RateLimitConfig::default());
+        let now = Instant::now();
+        assert!(matches!(
+            limiter.check_frame(FULL_STATE, now),
+            FrameVerdict::Accept(_)
+        ));
+        assert!(matches!(
+            limiter.check_frame(FULL_STATE, now),
+            FrameVerdict::Accept(_)
+        ));
+        match limiter.check_frame(FULL_STATE, now) {
+            FrameVerdict::Throttled(Some(GameError::RateLimited { action })) => {
+                assert_eq!(action, "requestFullState")
+            }
+            other => panic!("expected first throttle notice, got {other:?}"),
+        }
+        assert!(matches!(
+            limiter.check_frame(FULL_STATE, now),
+            FrameVerdict::Throttled(None)
+        ));
+    }
+
+    #[test]
+    fn bucket_refills_over_time() {
+        let mut limiter = ConnectionLimiter::new(RateLimitConfig::default());
+        let now = Instant::now();
+        for _ in 0..2 {
+            limiter.check_frame(FULL_STATE, now);
+        }
+        assert!(matches!(
+            limiter.check_frame(FULL_STATE, now),
+            FrameVerdict::Throttled(_)
+        ));
+        // 0.5 tokens/s: two seconds buys one more request.
+        assert!(matches!(
+            limiter.check_frame(FULL_STATE, now + Duration::from_secs(2)),
+            FrameVerdict::Accept(_)
+        ));
+    }
+
+    #[test]
+    fn kinds_have_independent_buckets() {
+        let mut limiter = ConnectionLimiter::new(RateLimitConfig::default());
+        let now = Instant::now();
+        for _ in 0..3 {
+            limiter.check_frame(FULL_STATE, now);
+        }
+        assert!(matches!(
+            limiter.check_frame(r#"{"action":"skipToCombat"}"#, now),
+            FrameVerdict::Accept(_)
+        ));
+    }
+
+    #[test]
+    fn repeated_garbage_disconnects() {
+        let mut limiter = ConnectionLimiter::new(RateLimitConfig::default());
+        let now = Instant::now();
+        for _ in 0..5 {
+            assert!(matches!(
+                limiter.check_frame("not json", now),
+                FrameVerdict::Invalid
+            ));
+        }
+        assert!(matches!(
+            limiter.check_frame("not json", now),
+            FrameVerdict::Disconnect(GameError::TooManyInvalidMessages)
+        ));
+    }
+
+    #[test]
+    fn frames_just_over_the_limit_reach_the_limiter() {
+        let config = RateLimitConfig::default();
+        assert!(config.transport_limit() > config.max_message_bytes);
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn oversized_frame_counts_as_invalid() {
+        let config = RateLimitConfig {
+            max_message_bytes: 8,
+            ..RateLimitConfig::default()
+        };
+        let mut limiter = ConnectionLimiter::new(config);
+        assert!(matches!(
+            limiter.check_frame(FULL_STATE, Instant::now()),
+            FrameVerdict::Invalid
+        ));
+    }
+}
@@ -0,0 +1,217 @@
+use crate::handler::matchmaking::{self, create_match};
+use crate::model::match_settings::MatchSettings;
+use crate::state::ServerState;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+use tokio::sync::watch;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReadyCheckStatus {
+    Pending,
+    /// Both accepted; the match with this id has been created.
+    Started(u64),
+    /// These players declined or let the check time out and were penalised;
+    /// anyone else goes back to the queue.
+    Failed {
+        declined: Vec<i64>,
+    },
+}
+
+struct Candidate {
+    account_id: i64,
+    username: String,
+    accepted: bool,
+}
+
+/// A queue pairing waiting for both players to accept. Lives in
+/// `ServerStateData::ready_checks` until it starts or fails.
+pub struct ReadyCheck {
+    players: [Candidate; 2],
+    deadline: Instant,
+    status_tx: watch::Sender<ReadyCheckStatus>,
+}
+
+/// One player's handle on a ready check, handed out by the pairing.
+#[derive(Debug)]
+pub struct ReadyCheckTicket {
+    pub check_id: u64,
+    pub deadline: Instant,
+    pub status_rx: watch::Receiver<ReadyCheckStatus>,
+}
+
+/// Registers a ready check for a fresh pairing and returns one ticket per
+/// player, in argument order.
+pub async fn open(
+    state: &ServerState,
+    p1: (i64, String),
+    p2: (i64, String),
+) -> (ReadyCheckTicket, ReadyCheckTicket) {
+    let check_id = state.next_ready_check_id.fetch_add(1, Ordering::Relaxed);
+    let deadline = Instant::now() + state.config.ready_check_timeout();
+    let status_tx = watch::Sender::new(ReadyCheckStatus::Pending);
+    let ticket = || ReadyCheckTicket {
+        check_id,
+        deadline,
+        status_rx: status_tx.subscribe(),
+    };
+    let tickets = (ticket(), ticket());
+    let candidate = |(account_id, username)| Candidate {
+        account_id,
+        username,
+        accepted: false,
+    };
+    let check = ReadyCheck {
+        players: [candidate(p1), candidate(p2)],
+        deadline,
+        status_tx,
+    };
+    state.ready_checks.lock().await.insert(check_id, check);
+    tickets
+}
+
+/// Records the caller's acceptance. The second acceptance removes the check
+/// and creates the match. No-op if the check is already over.
+pub async fn accept(state: &ServerState, check_id: u64, account_id: i64) {
+    let mut checks = state.ready_checks.lock().await;
+    let Some(check) = checks.get_mut(&check_id) else {
+        return;
+    };
+    let Some(candidate) = check
+        .players
+        .iter_mut()
+        .find(|c| c.account_id == account_id)
+    else {
+        return;
+    };
+    candidate.accepted = true;
+    if !check.players.iter().all(|c| c.accepted) {
+        return;
+    }
+    let check = checks.remove(&check_id).expect("looked up above");
+    let [p1, p2] = check.players;
+    let match_id = create_match(
+        state,
+        (p1.account_id, p1.username),
+        (p2.account_id, p2.username),
+        MatchSettings::default(),
+    )
+    .await;
+    check
+        .status_tx
+        .send_replace(ReadyCheckStatus::Started(match_id));
+}
+
+/// Fails the check because the caller declined (or disconnected). No-op if
+/// the check is already over.
+pub async fn decline(state: &ServerState, check_id: u64, account_id: i64) {
+    fail(state, check_id, |c| c.account_id == account_id, None).await;
+}
+
+/// Fails the check if its deadline has passed, blaming whoever has not
+/// accepted. Both players' loops call this when their timer fires.
+pub async fn expire(state: &ServerState, check_id: u64, now: Instant) {
+    fail(state, check_id, |c| !c.accepted, Some(now)).await;
+}
+
+async fn fail(
+    state: &ServerState,
+    check_id: u64,
+    at_fault: impl Fn(&Candidate) -> bool,
+    expired_at: Option<Instant>,
+) {
+    let check = {
+        let mut checks = state.ready_checks.lock().await;
+        match checks.get(&check_id) {
+            Some(check) if expired_at.is_none_or(|now| now >= check.deadline) => {
+                checks.remove(&check_id)
+            }
+            _ => None,
+        }
+    };
+    let Some(check) = check else {
+        return;
+    };
+    let declined: Vec<i64> = check
+        .players
+        .iter()
+        .filter(|c| at_fault(c))
+        .map(|c| c.account_id)
+        .collect();
+    for account_id in &declined {
+        matchmaking::penalise(state, *account_id).await;
+    }
+    check
+        .status_tx
+        .send_replace(ReadyCheckStatus::Failed { declined });
+}
+
+/// Fails every pending check without penalising anyone; used by the
+/// shutdown drain. The players' loops then find matchmaking closed.
+pub async fn cancel_all(state: &ServerState) {
+    for (_, check) in state.ready_checks.lock().await.drain() {
+        check
+            .status_tx
+            .send_replace(ReadyCheckStatus::Failed { declined: vec![] });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::test_support::test_state;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn both_accepting_creates_the_match() {
+        let state = test_state().await;
+        let (t1, t2) = open(&state, (1, "a".into()), (2, "b".into())).await;
+        assert_eq!(t1.check_id, t2.check_id);
+
+        accept(&state, t1.check_id, 1).await;
+        assert_eq!(*t2.status_rx.borrow(), ReadyCheckStatus::Pending);
+        assert!(state.matches.read().await.is_empty());
+
+        accept(&state, t1.check_id, 2).await;
+        let ReadyCheckStatus::Started(match_id) = *t1.status_rx.borrow() else {
+            panic!("second acceptance must start the match");
+        };
+        assert!(state.matches.read().await.contains_key(&match_id));
+        assert!(state.ready_checks.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn declining_penalises_only_the_decliner() {
+        let state = test_state().await;
+        let (t1, _t2) = open(&state, (1, "a".into()), (2, "b".into())).await;
+        accept(&state, t1.check_id, 1).await;
+        decline(&state, t1.check_id, 2).await;
+
+        assert_eq!(
+            *t1.status_rx.borrow(),
+            ReadyCheckStatus::Failed { declined: vec![2] }
+        );
+        assert!(matchmaking::penalty_remaining(&state, 2).await.is_some());
+        assert!(matchmaking::penalty_remaining(&state, 1).await.is_none());
+        assert!(state.matches.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn expiry_blames_everyone_who_had_not_accepted() {
+        let state = test_state().await;
+        let (t1, _t2) = open(&state, (1, "a".into()), (2, "b".into())).await;
+        accept(&state, t1.check_id, 1).await;
+
+        expire(&state, t1.check_id, Instant::now()).await;
+        assert_eq!(
+            *t1.status_rx.borrow(),
+            ReadyCheckStatus::Pending,
+            "expiry before the deadline must do nothing"
+        );
+
+        expire(&state, t1.check_id, t1.deadline + Duration::from_millis(1)).await;
+        assert_eq!(
+            *t1.status_rx.borrow(),
+            ReadyCheckStatus::Failed { declined: vec![2] }
+        );
+    }
+}
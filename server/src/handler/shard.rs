@@ -0,0 +1,482 @@
+use crate::{
+    config::{Config, MissedTickPolicy},
+    database,
+    handler::game_loop::{build_main_schedule, run_tick},
+    metrics::Metrics,
+    model::{lobby::Lobby, match_result::MatchResult},
+};
+use bevy_ecs::prelude::Schedule;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+use tokio::sync::oneshot;
+
+/// Work sent to a shard; runs between ticks with exclusive access to the lobby
+/// (`None` when the shard no longer has the match).
+type LobbyJob = Box<dyn FnOnce(Option<&mut Lobby>) + Send>;
+
+enum ShardCommand {
+    AddMatch { match_id: u64, lobby: Box<Lobby> },
+    RemoveMatch { match_id: u64 },
+    Run { match_id: u64, job: LobbyJob },
+}
+
+/// Connection-side reference to a match living on a simulation shard. Cheap
+/// to clone; holds no lock, so it may be kept across network awaits.
+#[derive(Clone)]
+pub struct MatchHandle {
+    match_id: u64,
+    shard: Sender<ShardCommand>,
+}
+
+impl MatchHandle {
+    /// Runs `f` against the lobby on its shard, between ticks, and returns the
+    /// result. `None` once the match has been removed. `f` should stay short:
+    /// every other match on the shard waits for it.
+    pub async fn with_lobby<R, F>(&self, f: F) -> Option<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut Lobby) -> R + Send + 'static,
+    {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        let job: LobbyJob = Box::new(move |lobby| {
+            if let Some(lobby) = lobby {
+                let _ = reply_tx.send(f(lobby));
+            }
+        });
+        self.shard
+            .send(ShardCommand::Run {
+                match_id: self.match_id,
+                job,
+            })
+            .ok()?;
+        reply_rx.await.ok()
+    }
+
+    /// Stops ticking the match and drops its lobby. Callers remove the handle
+    /// from `ServerStateData::matches` first.
+    pub fn remove(&self) {
+        let _ = self.shard.send(ShardCommand::RemoveMatch {
+            match_id: self.match_id,
+        });
+    }
+}
+
+/// The tick clock settings every shard shares, taken from `Config`.
+#[derive(Clone, Copy, Debug)]
+struct TickSettings {
+    tick_delta: f32,
+    period: Duration,
+    policy: MissedTickPolicy,
+    max_catch_up_ticks: u32,
+}
+
+/// What a shard thread needs besides its own matches.
+struct ShardContext {
+    settings: TickSettings,
+    metrics: Arc<Metrics>,
+    db_pool: SqlitePool,
+    runtime: Handle,
+}
+
+/// Dedicated simulation threads. Each shard owns the lobbies assigned to it
+/// and ticks them off the async runtime, so CPU-heavy ECS work never delays
+/// socket I/O. Connection tasks talk to a lobby only through its
+/// `MatchHandle`.
+pub struct SimPool {
+    shards: Vec<Sender<ShardCommand>>,
+    next_shard: AtomicUsize,
+}
+
+impl SimPool {
+    /// Spawns `Config::sim_shards` threads (one per core when 0). Must be
+    /// called from inside the tokio runtime, which shards use for DB writes.
+    pub fn new(config: &Config, metrics: Arc<Metrics>, db_pool: SqlitePool) -> Self {
+        let count = match config.sim_shards {
+            0 => thread::available_parallelism().map_or(1, usize::from),
+            n => n,
+        };
+        let tick_delta = 1.0 / config.tick_rate;
+        let settings = TickSettings {
+            tick_delta,
+            period: Duration::from_secs_f32(tick_delta),
+            policy: config.missed_tick_policy,
+            max_catch_up_ticks: config.max_catch_up_ticks,
+        };
+        let runtime = Handle::current();
+        let shards = (0..count)
+            .map(|idx| {
+                let (tx, rx) = mpsc::channel();
+                let ctx = ShardContext {
+                    settings,
+                    metrics: metrics.clone(),
+                    db_pool: db_pool.clone(),
+                    runtime: runtime.clone(),
+                };
+                thread::Builder::new()
+                    .name(format!("sim-shard-{idx}"))
+                    .spawn(move || run_shard(rx, ctx))
+                    .expect("failed to spawn simulation shard thread");
+                tx
+            })
+            .collect();
+        Self {
+            shards,
+            next_shard: AtomicUsize::new(0),
+        }
+    }
+
+    /// Hands `lobby` to a shard (round-robin) and starts ticking it.
+    pub fn spawn_match(&self, match_id: u64, lobby: Lobby) -> MatchHandle {
+        let idx = self.next_shard.fetch_add(1, Ordering::Relaxed) % self.shards.len();
+        let shard = self.shards[idx].clone();
+        let _ = shard.send(ShardCommand::AddMatch {
+            match_id,
+            lobby: Box::new(lobby),
+        });
+        MatchHandle { match_id, shard }
+    }
+}
+
+struct SimMatch {
+    lobby: Lobby,
+    schedule: Schedule,
+    next_tick: Instant,
+    /// Share of `lobby.bytes_broadcast()` already added to the metrics.
+    bytes_reported: u64,
+}
+
+impl SimMatch {
+    fn new(lobby: Lobby, next_tick: Instant) -> Self {
+        Self {
+            lobby,
+            schedule: build_main_schedule(),
+            next_tick,
+            bytes_reported: 0,
+        }
+    }
+
+    /// Adds what the match broadcast since the last report to the
+    /// process-wide total.
+    fn report_broadcast_bytes(&mut self, metrics: &Metrics) {
+        let bytes = self.lobby.bytes_broadcast();
+        metrics
+            .broadcast_bytes
+            .fetch_add(bytes.saturating_sub(self.bytes_reported), Ordering::Relaxed);
+        self.bytes_reported = bytes;
+    }
+}
+
+/// Shard thread body: sleep until the earliest due tick or the next command,
+/// then run every due tick. Exits once all senders (the pool and every
+/// handle) are gone. A panic inside one match aborts only that match.
+fn run_shard(rx: Receiver<ShardCommand>, ctx: ShardContext) {
+    let mut matches: HashMap<u64, SimMatch> = HashMap::new();
+    loop {
+        let next_due = matches.values().map(|m| m.next_tick).min();
+        let received = match next_due {
+            Some(due) => rx.recv_timeout(due.saturating_duration_since(Instant::now())),
+            None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok(command) => apply_command(&mut matches, command, &ctx),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        tick_due_matches(&mut matches, Instant::now(), &ctx);
+    }
+}
+
+/// Ticks every match due by `now`, dropping any whose tick panicked.
+fn tick_due_matches(matches: &mut HashMap<u64, SimMatch>, now: Instant, ctx: &ShardContext) {
+    let mut crashed = Vec::new();
+    for (match_id, sim) in matches.iter_mut() {
+        if sim.next_tick <= now {
+            let ticked = panic::catch_unwind(AssertUnwindSafe(|| {
+                tick_match(*match_id, sim, now, ctx);
+            }));
+            if ticked.is_err() {
+                crashed.push(*match_id);
+            }
+        }
+    }
+    for match_id in crashed {
+        abort_crashed_match(matches, match_id, ctx);
+    }
+}
+
+/// Removes a match that panicked mid-tick or mid-job. Its players are told
+/// and the result is recorded as aborted, unless the lobby is too broken to
+/// manage even that. Dropping the lobby sends its players back to the menu.
+fn abort_crashed_match(matches: &mut HashMap<u64, SimMatch>, match_id: u64, ctx: &ShardContext) {
+    let Some(mut sim) = matches.remove(&match_id) else {
+        return;
+    };
+    log::error!("Match {} panicked; aborting it", match_id);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        sim.lobby.force_end("The match hit a server error.".into());
+        sim.lobby.take_result()
+    }));
+    sim.report_broadcast_bytes(&ctx.metrics);
+    match result {
+        Ok(Some(result)) => record_result(ctx, result),
+        Ok(None) => {}
+        Err(_) => log::error!("Match {} could not be recorded after its panic", match_id),
+    }
+}
+
+fn record_result(ctx: &ShardContext, result: MatchResult) {
+    let db_pool = ctx.db_pool.clone();
+    ctx.runtime.spawn(async move {
+        if let Err(e) = database::record_match_result(&db_pool, &result).await {
+            log::error!("Failed to record match result {:?}: {}", result, e);
+        }
+    });
+}
+
+fn apply_command(matches: &mut HashMap<u64, SimMatch>, command: ShardCommand, ctx: &ShardContext) {
+    match command {
+        ShardCommand::AddMatch { match_id, lobby } => {
+            let next_tick = Instant::now() + ctx.settings.period;
+            matches.insert(match_id, SimMatch::new(*lobby, next_tick));
+        }
+        ShardCommand::RemoveMatch { match_id } => {
+            if let Some(mut sim) = matches.remove(&match_id) {
+                sim.report_broadcast_bytes(&ctx.metrics);
+            }
+        }
+        ShardCommand::Run { match_id, job } => {
+            let lobby = matches.get_mut(&match_id).map(|sim| &mut sim.lobby);
+            if panic::catch_unwind(AssertUnwindSafe(|| job(lobby))).is_err() {
+                abort_crashed_match(matches, match_id, ctx);
+            }
+        }
+    }
+}
+
+fn tick_match(match_id: u64, sim: &mut SimMatch, now: Instant, ctx: &ShardContext) {
+    let settings = ctx.settings;
+    let (missed, drop_backlog) = missed_ticks(
+        settings.policy,
+        now.saturating_duration_since(sim.next_tick),
+        settings.period,
+        settings.max_catch_up_ticks,
+    );
+
+    let lobby = &mut sim.lobby;
+    let started = Instant::now();
+    run_tick(lobby, &mut sim.schedule, settings.tick_delta);
+    let elapsed = started.elapsed();
+    ctx.metrics.tick_duration.observe(elapsed);
+    if elapsed > settings.period {
+        ctx.metrics.tick_overruns.fetch_add(1, Ordering::Relaxed);
+    }
+    ctx.metrics
+        .missed_ticks
+        .fetch_add(missed, Ordering::Relaxed);
+    lobby.tick_stats.record_missed(missed);
+    if let Some(report) = lobby.tick_stats.record(elapsed, settings.period) {
+        log::warn!(
+            "Match {}: {}/{} recent ticks over the {:?} budget (mean {:?}, max {:?}, {} missed so far)",
+            match_id,
+            report.overruns_in_window,
+            report.window,
+            settings.period,
+            report.mean,
+            report.max,
+            lobby.tick_stats.missed_ticks,
+        );
+    }
+    if let Some(result) = lobby.take_result() {
+        record_result(ctx, result);
+    }
+    sim.report_broadcast_bytes(&ctx.metrics);
+
+    sim.next_tick = match settings.policy {
+        // Realign to the next slot after the ones that were skipped.
+        MissedTickPolicy::Skip => sim.next_tick + settings.period * (missed as u32 + 1),
+        MissedTickPolicy::CatchUp if drop_backlog => now + settings.period,
+        MissedTickPolicy::CatchUp => sim.next_tick + settings.period,
+    };
+}
+
+/// Ticks lost by a match that is `lag` behind its schedule, and whether it
+/// should drop the backlog (restart its clock) instead of bursting through
+/// it. `dt` stays fixed either way; only how many ticks run changes.
+fn missed_ticks(
+    policy: MissedTickPolicy,
+    lag: Duration,
+    period: Duration,
+    max_catch_up_ticks: u32,
+) -> (u64, bool) {
+    let behind = (lag.as_nanos() / period.as_nanos().max(1)) as u64;
+    match policy {
+        MissedTickPolicy::Skip => (behind, false),
+        MissedTickPolicy::CatchUp if behind > u64::from(max_catch_up_ticks) => (behind, true),
+        MissedTickPolicy::CatchUp => (0, false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::player::Player;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn test_pool(shards: usize) -> SimPool {
+        let db_pool = SqlitePoolOptions::new()
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let config = Config {
+            sim_shards: shards,
+            ..Config::default()
+        };
+        SimPool::new(&config, Arc::new(Metrics::default()), db_pool)
+    }
+
+    async fn test_context() -> ShardContext {
+        ShardContext {
+            settings: TickSettings {
+                tick_delta: 0.1,
+                period: Duration::from_millis(100),
+                policy: MissedTickPolicy::Skip,
+                max_catch_up_ticks: 0,
+            },
+            metrics: Arc::new(Metrics::default()),
+            db_pool: SqlitePoolOptions::new()
+                .connect("sqlite::memory:")
+                .await
+                .unwrap(),
+            runtime: Handle::current(),
+        }
+    }
+
+    #[test]
+    fn skip_policy_counts_ticks_that_were_skipped() {
+        let period = Duration::from_millis(30);
+        assert_eq!(
+            missed_ticks(MissedTickPolicy::Skip, Duration::from_millis(95), period, 5),
+            (3, false)
+        );
+        assert_eq!(
+            missed_ticks(MissedTickPolicy::Skip, Duration::from_millis(10), period, 5),
+            (0, false)
+        );
+    }
+
+    #[test]
+    fn catch_up_policy_bursts_until_the_cap_then_drops_backlog() {
+        let period = Duration::from_millis(30);
+        assert_eq!(
+            missed_ticks(
+                MissedTickPolicy::CatchUp,
+                Duration::from_millis(95),
+                period,
+                5
+            ),
+            (0, false)
+        );
+        assert_eq!(
+            missed_ticks(
+                MissedTickPolicy::CatchUp,
+                Duration::from_millis(200),
+                period,
+                5
+            ),
+            (6, true)
+        );
+    }
+
+    #[tokio::test]
+    async fn with_lobby_runs_on_the_owning_shard() {
+        let pool = test_pool(2).await;
+        let mut lobby = Lobby::new();
+        lobby.add_player(Player::new(7, "p".into(), 100));
+        let handle = pool.spawn_match(1, lobby);
+
+        let (ids, thread_name) = handle
+            .with_lobby(|lobby| {
+                let ids: Vec<i64> = lobby.players.iter().map(|p| p.id).collect();
+                (ids, thread::current().name().map(str::to_string))
+            })
+            .await
+            .unwrap();
+        assert_eq!(ids, vec![7]);
+        assert!(thread_name.unwrap().starts_with("sim-shard-"));
+    }
+
+    #[tokio::test]
+    async fn removed_match_no_longer_answers() {
+        let pool = test_pool(1).await;
+        let handle = pool.spawn_match(1, Lobby::new());
+        handle.remove();
+        assert_eq!(handle.with_lobby(|_| ()).await, None);
+    }
+
+    #[tokio::test]
+    async fn panicking_match_is_aborted_without_stopping_the_others() {
+        fn explode() {
+            panic!("injected tick panic");
+        }
+        let ctx = test_context().await;
+        let now = Instant::now();
+        let sim = || SimMatch::new(Lobby::new(), now);
+        let mut matches = HashMap::from([(1, sim()), (2, sim())]);
+        matches.get_mut(&1).unwrap().schedule.add_systems(explode);
+        let mut crashed_rx = matches[&1].lobby.tx.subscribe();
+
+        tick_due_matches(&mut matches, now, &ctx);
+        assert!(!matches.contains_key(&1));
+        assert_eq!(matches[&2].lobby.tick_stats.ticks, 1);
+        let mut notices = Vec::new();
+        while let Ok(msg) = crashed_rx.try_recv() {
+            notices.push(msg);
+        }
+        assert!(notices.iter().any(|m| m.contains("MatchAborted")));
+
+        // A panicking job is isolated the same way.
+        let job: LobbyJob = Box::new(|_| panic!("injected job panic"));
+        apply_command(&mut matches, ShardCommand::Run { match_id: 2, job }, &ctx);
+        assert!(matches.is_empty());
+    }
+
+    #[tokio::test]
+    async fn broadcast_bytes_stay_counted_after_the_match_ends() {
+        let ctx = test_context().await;
+        let mut lobby = Lobby::new();
+        let _rx = lobby.tx.subscribe();
+        lobby.broadcast_gamestate();
+        let bytes = lobby.bytes_broadcast();
+        assert!(bytes > 0);
+        let mut matches = HashMap::new();
+        let lobby = Box::new(lobby);
+        apply_command(
+            &mut matches,
+            ShardCommand::AddMatch { match_id: 1, lobby },
+            &ctx,
+        );
+        apply_command(
+            &mut matches,
+            ShardCommand::RemoveMatch { match_id: 1 },
+            &ctx,
+        );
+        assert_eq!(ctx.metrics.broadcast_bytes.load(Ordering::Relaxed), bytes);
+    }
+
+    #[tokio::test]
+    async fn shard_ticks_its_matches() {
+        let pool = test_pool(1).await;
+        let handle = pool.spawn_match(1, Lobby::new());
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let ticks = handle.with_lobby(|lobby| lobby.tick_stats.ticks).await;
+        assert!(ticks.unwrap() > 0);
+    }
+}
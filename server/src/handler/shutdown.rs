@@ -0,0 +1,191 @@
+use crate::{
+    database,
+    handler::{challenge, private_lobby, ready_check},
+    model::{match_result::MatchResult, messages::ServerMessage},
+    state::ServerState,
+};
+use log::{error, info, warn};
+use std::time::{Duration, Instant};
+
+/// How often the drain re-checks whether every match has finished.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Resolves on SIGINT (Ctrl-C) or, on Unix, SIGTERM.
+pub async fn wait_for_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// The notice sent to a client while a shutdown is pending.
+pub fn shutdown_notice(deadline: Instant) -> ServerMessage {
+    ServerMessage::ServerShutdown {
+        seconds_remaining: deadline.saturating_duration_since(Instant::now()).as_secs(),
+    }
+}
+
+/// Drains the server once the listener has stopped accepting: flags the
+/// shutdown (connection loops warn players and matchmaking closes), waits up
+/// to `Config::shutdown_grace` for running matches to finish, aborts the rest,
+/// records every unrecorded result and closes the DB pool. A second signal
+/// skips the wait.
+pub async fn drain(server_state: &ServerState) {
+    let grace = server_state.config.shutdown_grace();
+    let deadline = Instant::now() + grace;
+    server_state.shutdown_deadline.send_replace(Some(deadline));
+    // Nobody else can be paired now; queued players are told by their loops.
+    *server_state.queue.lock().await = None;
+    private_lobby::close_all(server_state).await;
+    ready_check::cancel_all(server_state).await;
+    challenge::withdraw_all(server_state).await;
+
+    info!(
+        "Shutting down: waiting up to {}s for running matches",
+        grace.as_secs()
+    );
+    tokio::select! {
+        _ = wait_for_matches(server_state) => info!("All matches finished"),
+        _ = tokio::time::sleep_until(deadline.into()) => {
+            warn!("Shutdown grace period elapsed; aborting remaining matches");
+        }
+        _ = wait_for_signal() => warn!("Second signal received; aborting remaining matches"),
+    }
+
+    for result in finish_all_matches(server_state).await {
+        if let Err(e) = database::record_match_result(&server_state.db_pool, &result).await {
+            error!("Failed to record match result {:?}: {}", result, e);
+        }
+    }
+    // Waits for in-flight queries (e.g. a game loop recording its result).
+    server_state.db_pool.close().await;
+    info!("Shutdown complete");
+}
+
+async fn wait_for_matches(server_state: &ServerState) {
+    loop {
+        let lobbies: Vec<_> = server_state
+            .matches
+            .read()
+            .await
+            .values()
+            .cloned()
+            .collect();
+        let mut all_finished = true;
+        for handle in lobbies {
+            // A match its shard already dropped counts as finished.
+            if handle.with_lobby(|lobby| lobby.is_finished()).await == Some(false) {
+                all_finished = false;
+                break;
+            }
+        }
+        if all_finished {
+            return;
+        }
+        tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+    }
+}
+
+/// Aborts every unfinished match and collects the results their game loops
+/// have not recorded yet.
+pub async fn finish_all_matches(server_state: &ServerState) -> Vec<MatchResult> {
+    let lobbies: Vec<_> = server_state
+        .matches
+        .read()
+        .await
+        .values()
+        .cloned()
+        .collect();
+    let mut results = Vec::new();
+    for handle in lobbies {
+        let result = handle
+            .with_lobby(|lobby| {
+                lobby.abort();
+                lobby.take_result()
+            })
+            .await;
+        results.extend(result.flatten());
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{
+        game_state::GamePhase, lobby::Lobby, match_result::MatchOutcome, player::Player,
+    };
+    use crate::state::test_support::test_state;
+
+    /// Registers a lobby on a shard the way `create_match` does.
+    async fn insert_match(state: &ServerState, match_id: u64, players: [i64; 2]) {
+        let mut lobby = Lobby::new();
+        for id in players {
+            lobby.add_player(Player::new(id, format!("p{id}"), 100));
+        }
+        let handle = state.sim.spawn_match(match_id, lobby);
+        state.matches.write().await.insert(match_id, handle);
+    }
+
+    #[tokio::test]
+    async fn finish_all_matches_aborts_running_and_keeps_finished() {
+        let state = test_state().await;
+        let (running, finished) = (0, 1);
+        insert_match(&state, running, [1, 2]).await;
+        insert_match(&state, finished, [3, 4]).await;
+        let handle = state.matches.read().await[&finished].clone();
+        handle
+            .with_lobby(|lobby| {
+                lobby.game_state.phase = GamePhase::GameOver;
+                lobby.winner_id = Some(3);
+            })
+            .await;
+
+        let mut results = finish_all_matches(&state).await;
+        results.sort_by_key(|r| r.participant_ids[0]);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].participant_ids, vec![1, 2]);
+        assert_eq!(results[0].outcome, MatchOutcome::Aborted);
+        assert_eq!(results[1].outcome, MatchOutcome::Finished);
+        assert_eq!(results[1].winner_id, Some(3));
+
+        let handle = state.matches.read().await[&running].clone();
+        assert_eq!(
+            handle.with_lobby(|lobby| lobby.is_finished()).await,
+            Some(true)
+        );
+        assert!(finish_all_matches(&state).await.is_empty());
+    }
+
+    #[test]
+    fn notice_counts_down_to_zero() {
+        let past = Instant::now() - Duration::from_secs(5);
+        assert!(matches!(
+            shutdown_notice(past),
+            ServerMessage::ServerShutdown {
+                seconds_remaining: 0
+            }
+        ));
+    }
+}
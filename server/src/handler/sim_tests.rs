@@ -61,7 +61,7 @@ fn wave_one_all_in_rush_is_survivable_and_net_negative() {
     loop {
         let cost = lobby.players[0].next_send_costs[0];
         // Keep 25g for one tower.
-        if lobby.players[0].gold < cost + 25 {
+        if lobby.players[0].gold() < cost + 25 {
             break;
         }
         let out = handle_client_message(
@@ -101,9 +101,9 @@ fn wave_one_all_in_rush_is_survivable_and_net_negative() {
         lobby.game_state.phase,
         lobby.game_state.wave_number,
         lobby.winner_id,
-        lobby.players[0].gold,
+        lobby.players[0].gold(),
         lobby.players[0].income,
-        lobby.players[1].gold,
+        lobby.players[1].gold(),
         lobby.players[1].income,
         diag_king_hps
     );
@@ -128,12 +128,12 @@ fn wave_one_all_in_rush_is_survivable_and_net_negative() {
     let remaining = 9u32;
     let r = &lobby.players[0];
     let d = &lobby.players[1];
-    let r_worth = r.gold + r.income * remaining;
-    let d_worth = d.gold + d.income * remaining;
+    let r_worth = r.gold() + r.income * remaining;
+    let d_worth = d.gold() + d.income * remaining;
     assert!(
         d_worth > r_worth,
         "defender must out-economy the rusher: D={} (g{} i{}) vs R={} (g{} i{})",
-        d_worth, d.gold, d.income, r_worth, r.gold, r.income
+        d_worth, d.gold(), d.income, r_worth, r.gold(), r.income
     );
 }
 
@@ -171,7 +171,7 @@ fn full_clean_game_reaches_victory_at_wave_12() {
             break;
         }
         for (pi, pid) in [1i64, 2].iter().enumerate() {
-            while lobby.players[pi].gold >= 25 && next_slot[pi] < 60 {
+            while lobby.players[pi].gold() >= 25 && next_slot[pi] < 60 {
                 let (row, col) = slot_to_cell(next_slot[pi]);
                 next_slot[pi] += 1;
                 handle_client_message(
@@ -192,9 +192,9 @@ fn full_clean_game_reaches_victory_at_wave_12() {
             lobby.game_state.phase,
             lobby.game_state.wave_number,
             lobby.winner_id,
-            lobby.players[0].gold,
+            lobby.players[0].gold(),
             lobby.players[0].income,
-            lobby.players[1].gold,
+            lobby.players[1].gold(),
             lobby.players[1].income,
             diag_king_hps
         );
@@ -205,6 +205,15 @@ fn full_clean_game_reaches_victory_at_wave_12() {
         "clean defended game must reach Victory (wave 12 cleared); ended wave {}",
         lobby.game_state.wave_number
     );
+    let result = lobby.take_result().expect("a finished game has a result");
+    assert_eq!(result.stats.len(), 2);
+    for stats in &result.stats {
+        assert!(stats.gold_spent.towers >= 4 * 25, "the opening towers count");
+        assert_eq!(stats.leaks_per_wave.len(), 12, "one leak counter per wave fought");
+        assert!(stats.creeps_killed > 0 && stats.gold_earned.workers > 0);
+        assert_eq!(stats.damage_by_tower.len(), 1, "only Squares were built");
+        assert_eq!(stats.damage_by_tower[0].kind, UnitKind::Square);
+    }
 }
 
 /// Fairness regression test: two players with byte-identical builds must see
@@ -266,8 +275,8 @@ fn symmetric_builds_produce_symmetric_outcomes() {
             tick_past_phase(&mut lobby, &mut schedule, GamePhase::Combat);
         }
 
-        let p1_gold = lobby.players[0].gold;
-        let p2_gold = lobby.players[1].gold;
+        let p1_gold = lobby.players[0].gold();
+        let p2_gold = lobby.players[1].gold();
         let mut hps = king_hps(&mut lobby);
         hps.sort_by_key(|(pid, _)| *pid);
         let (p1_king_hp, p2_king_hp) = match hps.as_slice() {
@@ -314,7 +323,7 @@ fn sustained_scout_spam_does_not_beat_static_defense() {
 
     for wave in 1..=4u32 {
         // Rusher: all gold into scouts, every build phase.
-        while lobby.players[0].gold >= lobby.players[0].next_send_costs[0] {
+        while lobby.players[0].gold() >= lobby.players[0].next_send_costs[0] {
             handle_client_message(&mut lobby, R, ClientMessage::SendUnit { shape: UnitKind::Square });
         }
         handle_client_message(&mut lobby, R, ClientMessage::SkipToCombat);
@@ -326,8 +335,8 @@ fn sustained_scout_spam_does_not_beat_static_defense() {
         eprintln!(
             "[spam] end wave {}: phase={:?} winner={:?} R(gold={} income={}) D(gold={} income={}) kings={:?}",
             wave, lobby.game_state.phase, lobby.winner_id,
-            lobby.players[0].gold, lobby.players[0].income,
-            lobby.players[1].gold, lobby.players[1].income,
+            lobby.players[0].gold(), lobby.players[0].income,
+            lobby.players[1].gold(), lobby.players[1].income,
             kings
         );
         if lobby.game_state.phase == GamePhase::GameOver {
@@ -353,3 +362,281 @@ fn sustained_scout_spam_does_not_beat_static_defense() {
         d_king
     );
 }
+
+#[test]
+fn first_build_timer_waits_until_both_players_are_set_up() {
+    let mut lobby = Lobby::new();
+    lobby.players.push(Player::new(R, "rusher".into(), 100));
+    lobby.players.push(Player::new(D, "defender".into(), 100));
+    lobby.wait_for_players(60.0);
+    let mut schedule = build_main_schedule();
+    let start = lobby.game_state.phase_timer;
+
+    lobby.mark_loaded(R);
+    lobby.mark_loaded(D);
+    handle_client_message(&mut lobby, R, ClientMessage::PickFamily { family: Family::Basic });
+    ticks(&mut lobby, &mut schedule, 5);
+    assert_eq!(lobby.game_state.phase_timer, start, "defender has not picked yet");
+    assert!(lobby.is_waiting_for_players());
+
+    handle_client_message(&mut lobby, D, ClientMessage::PickFamily { family: Family::Basic });
+    ticks(&mut lobby, &mut schedule, 1);
+    assert!(!lobby.is_waiting_for_players());
+    assert!(lobby.game_state.phase_timer < start);
+}
+
+#[test]
+fn setup_wait_gives_up_after_its_timeout() {
+    let mut lobby = Lobby::new();
+    lobby.players.push(Player::new(R, "rusher".into(), 100));
+    lobby.players.push(Player::new(D, "defender".into(), 100));
+    lobby.wait_for_players(1.0);
+    let mut schedule = build_main_schedule();
+    let start = lobby.game_state.phase_timer;
+
+    ticks(&mut lobby, &mut schedule, TICK_RATE as u32 + 1);
+    assert!(!lobby.is_waiting_for_players());
+    assert!(lobby.game_state.phase_timer < start);
+}
+
+#[test]
+fn hiring_during_the_setup_wait_is_refused_and_starters_still_spawn() {
+    use crate::handler::in_game::MessageOutcome;
+    use crate::model::components::{King, PlayerIdComponent, Worker};
+    use crate::model::game_error::GameError;
+    use crate::model::messages::ServerMessage;
+    use crate::model::unit_config::STARTER_WORKERS;
+    let mut lobby = Lobby::new();
+    lobby.players.push(Player::new(R, "rusher".into(), 100));
+    lobby.players.push(Player::new(D, "defender".into(), 100));
+    lobby.wait_for_players(60.0);
+    let mut schedule = build_main_schedule();
+    ticks(&mut lobby, &mut schedule, 5);
+
+    let out = handle_client_message(
+        &mut lobby,
+        R,
+        ClientMessage::HireWorker {
+            mineral: Default::default(),
+        },
+    );
+    assert!(matches!(
+        out,
+        MessageOutcome::Reply(ServerMessage::Error(ref e)) if e.error == GameError::WaitingForPlayers
+    ));
+    assert_eq!(lobby.players[0].gold(), 100);
+
+    for id in [R, D] {
+        lobby.mark_loaded(id);
+        handle_client_message(
+            &mut lobby,
+            id,
+            ClientMessage::PickFamily {
+                family: Family::Basic,
+            },
+        );
+    }
+    ticks(&mut lobby, &mut schedule, 1);
+    for id in [R, D] {
+        let workers = lobby
+            .game_state
+            .world
+            .query_filtered::<&PlayerIdComponent, bevy_ecs::prelude::With<Worker>>()
+            .iter(&lobby.game_state.world)
+            .filter(|owner| owner.0 == id)
+            .count();
+        assert_eq!(workers, STARTER_WORKERS, "player {id} workers");
+        let kings = lobby
+            .game_state
+            .world
+            .query_filtered::<&PlayerIdComponent, bevy_ecs::prelude::With<King>>()
+            .iter(&lobby.game_state.world)
+            .filter(|owner| owner.0 == id)
+            .count();
+        assert_eq!(kings, 1, "player {id} king");
+    }
+}
+
+#[test]
+fn pause_freezes_the_match_until_resumed_or_out_of_time() {
+    use crate::model::components::{Position, Worker};
+    use crate::model::pause::PAUSE_SECONDS_PER_PLAYER;
+    fn worker_positions(lobby: &mut Lobby) -> Vec<(f32, f32)> {
+        let mut query = lobby
+            .game_state
+            .world
+            .query_filtered::<&Position, bevy_ecs::prelude::With<Worker>>();
+        query
+            .iter(&lobby.game_state.world)
+            .map(|p| (p.x, p.y))
+            .collect()
+    }
+    let mut lobby = Lobby::new();
+    lobby.players.push(Player::new(R, "rusher".into(), 100));
+    lobby.players.push(Player::new(D, "defender".into(), 100));
+    let mut schedule = build_main_schedule();
+    ticks(&mut lobby, &mut schedule, 5);
+    handle_client_message(&mut lobby, R, ClientMessage::PickFamily { family: Family::Basic });
+    let mut rx = lobby.tx.subscribe();
+
+    handle_client_message(&mut lobby, R, ClientMessage::RequestPause);
+    let timer = lobby.game_state.phase_timer;
+    let workers = worker_positions(&mut lobby);
+    ticks(&mut lobby, &mut schedule, TICK_RATE as u32 * 2);
+    assert_eq!(lobby.game_state.phase_timer, timer);
+    assert_eq!(worker_positions(&mut lobby), workers);
+    let place = ClientMessage::Place(PlaceMessage {
+        shape: UnitKind::Square,
+        row: 1,
+        col: 1,
+    });
+    let outcome = handle_client_message(&mut lobby, R, place);
+    assert!(
+        matches!(outcome, crate::handler::in_game::MessageOutcome::Reply(_)),
+        "no building while paused"
+    );
+    let mut countdown = Vec::new();
+    while let Ok(msg) = rx.try_recv() {
+        let msg: serde_json::Value = serde_json::from_str(&msg).unwrap();
+        if let Some(left) = msg["data"]["phase_info"]["pause"]["seconds_left"].as_f64() {
+            countdown.push(left);
+        }
+    }
+    assert_eq!(countdown.first(), Some(&(PAUSE_SECONDS_PER_PLAYER as f64)));
+    assert!(
+        countdown.len() >= 2,
+        "the countdown is still broadcast: {countdown:?}"
+    );
+
+    handle_client_message(&mut lobby, D, ClientMessage::Resume);
+    ticks(&mut lobby, &mut schedule, 1);
+    assert!(lobby.game_state.phase_timer < timer);
+
+    handle_client_message(&mut lobby, R, ClientMessage::RequestPause);
+    ticks(
+        &mut lobby,
+        &mut schedule,
+        TICK_RATE as u32 * PAUSE_SECONDS_PER_PLAYER as u32,
+    );
+    assert!(!lobby.pause.is_paused(), "the rusher's pause time ran out");
+    assert_eq!(lobby.pause.budget(R).seconds_left, 0.0);
+}
+
+#[test]
+fn hire_price_shown_matches_the_price_charged_after_starter_workers() {
+    use crate::model::components::{Mineral, PlayerIdComponent, Worker};
+    use crate::model::unit_config::STARTER_WORKERS;
+    fn owned(lobby: &mut Lobby) -> usize {
+        let mut query = lobby
+            .game_state
+            .world
+            .query::<(&Worker, &PlayerIdComponent)>();
+        query
+            .iter(&lobby.game_state.world)
+            .filter(|(_, pid)| pid.0 == R)
+            .count()
+    }
+    let mut lobby = Lobby::new();
+    lobby.players.push(Player::new(R, "rusher".into(), 500));
+    lobby.players.push(Player::new(D, "defender".into(), 500));
+    let mut schedule = build_main_schedule();
+    ticks(&mut lobby, &mut schedule, 5);
+    assert_eq!(
+        owned(&mut lobby),
+        STARTER_WORKERS,
+        "starter workers spawned"
+    );
+
+    for hired in 1..=2 {
+        let shown = lobby.players[0].next_worker_cost;
+        let before = lobby.players[0].gold();
+        handle_client_message(
+            &mut lobby,
+            R,
+            ClientMessage::HireWorker {
+                mineral: Mineral::Gold,
+            },
+        );
+        assert_eq!(
+            before - lobby.players[0].gold(),
+            shown,
+            "hire {hired} charged the shown price"
+        );
+        assert_eq!(owned(&mut lobby), STARTER_WORKERS + hired);
+    }
+    assert_eq!(lobby.players[0].next_worker_cost, 70);
+}
+
+#[test]
+fn creeps_path_through_the_gap_in_a_wall() {
+    use crate::model::components::{Enemy, Position};
+    use crate::model::constants::{LEFT_BOARD_END, SQUARE_SIZE};
+    use bevy_ecs::prelude::{Entity, With};
+    const WALL_ROW: u32 = 5;
+    const GAP_COL: u32 = 0;
+    let mut lobby = Lobby::new();
+    lobby.players.push(Player::new(R, "builder".into(), 1000));
+    lobby.players.push(Player::new(D, "sender".into(), 3000));
+    let mut schedule = build_main_schedule();
+    ticks(&mut lobby, &mut schedule, 5);
+    handle_client_message(
+        &mut lobby,
+        R,
+        ClientMessage::PickFamily {
+            family: Family::Basic,
+        },
+    );
+
+    // A wall across the builder's board with one open cell at the edge,
+    // far from the spawn in the middle of the top row.
+    for col in (0..10).filter(|&c| c != GAP_COL) {
+        let out = handle_client_message(
+            &mut lobby,
+            R,
+            ClientMessage::Place(PlaceMessage {
+                shape: UnitKind::Square,
+                row: WALL_ROW,
+                col,
+            }),
+        );
+        assert!(
+            matches!(out, crate::handler::in_game::MessageOutcome::Handled),
+            "col {col}"
+        );
+    }
+    // More creeps than the wall can engage at once.
+    while lobby.players[1].gold() >= lobby.players[1].next_send_costs[0] {
+        handle_client_message(
+            &mut lobby,
+            D,
+            ClientMessage::SendUnit {
+                shape: UnitKind::Square,
+            },
+        );
+    }
+    tick_past_phase(&mut lobby, &mut schedule, GamePhase::Build);
+
+    // Where each creep on the builder's board first got past the wall.
+    let wall_bottom = (WALL_ROW + 1) as f32 * SQUARE_SIZE;
+    let mut crossings = std::collections::HashMap::new();
+    while lobby.game_state.phase == GamePhase::Combat {
+        run_tick(&mut lobby, &mut schedule, 1.0 / TICK_RATE);
+        let mut creeps = lobby
+            .game_state
+            .world
+            .query_filtered::<(Entity, &Position), With<Enemy>>();
+        for (entity, pos) in creeps.iter(&lobby.game_state.world) {
+            if pos.x < LEFT_BOARD_END && pos.y > wall_bottom {
+                crossings.entry(entity).or_insert(pos.x);
+            }
+        }
+        assert!(lobby.game_state.tick < 20_000, "wave never ended");
+    }
+    let gap_end = (GAP_COL + 1) as f32 * SQUARE_SIZE;
+    let through_gap = crossings.values().filter(|&&x| x < gap_end).count();
+    assert!(
+        through_gap > 0,
+        "no creep went round the wall through the gap; crossings at {:?}",
+        crossings.values().collect::<Vec<_>>()
+    );
+}
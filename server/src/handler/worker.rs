@@ -1,57 +1,89 @@
 use crate::model::{
-    components::{MiningTimer, PlayerIdComponent, Position, TargetPositions, Worker, WorkerState},
-    game_state::DeltaTime,
+    components::{
+        Mineral, MiningTimer, PlayerIdComponent, Position, TargetPositions, Worker, WorkerState,
+    },
+    game_state::{DeltaTime, GameTick},
+    gold_ledger::GoldReason,
     player::Players,
+    unit_config::WorkerStats,
 };
 use bevy_ecs::prelude::{Commands, Entity, Query, Res, ResMut, With};
 
-pub const WORKER_SPEED: f32 = 50.0;
-pub const WORKER_CAP: usize = 7;
-
 pub const VEIN_POSITIONS: [Position; 2] = [
     Position { x: 700.0, y: 50.0 },
     Position { x: 700.0, y: 350.0 },
 ];
+/// Where essence workers mine, one vein per side; they carry to the same
+/// carts as gold workers.
+pub const ESSENCE_VEIN_POSITIONS: [Position; 2] = [
+    Position { x: 650.0, y: 150.0 },
+    Position { x: 650.0, y: 450.0 },
+];
 pub const CART_POSITIONS: [Position; 2] = [
     Position { x: 700.0, y: 250.0 },
     Position { x: 700.0, y: 550.0 },
 ];
 
-/// Bevy system: moves workers along their vein→cart route and awards gold on deposit.
+type WorkerQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Position,
+        &'static mut WorkerState,
+        Option<&'static mut MiningTimer>,
+        &'static PlayerIdComponent,
+        &'static TargetPositions,
+        Option<&'static Mineral>,
+    ),
+    With<Worker>,
+>;
+
+/// Bevy system: moves workers along their vein→cart route and awards gold, or
+/// essence for workers carrying `Mineral::Essence`, on deposit. Speed, mining
+/// time and carry come from the owner's `worker_tiers`.
 /// Requires `DeltaTime` and `Players` resources to be present in the world.
 pub fn update_workers(
     mut commands: Commands,
-    mut worker_query: Query<
-        (
-            Entity,
-            &mut Position,
-            &mut WorkerState,
-            Option<&mut MiningTimer>,
-            &PlayerIdComponent,
-            &TargetPositions,
-        ),
-        With<Worker>,
-    >,
+    mut worker_query: WorkerQuery,
     time: Res<DeltaTime>,
     mut players: ResMut<Players>,
+    tick: Option<Res<GameTick>>,
 ) {
     let tick_delta = time.0;
-    let mut deposit_pids: Vec<i64> = Vec::new();
-
-    for (entity, mut pos, mut state, timer_opt, player_id, targets) in worker_query.iter_mut() {
+    let tick = tick.map_or(0, |t| t.0);
+    let stats_of = |pid: i64| {
+        players
+            .0
+            .iter()
+            .find(|p| p.id == pid)
+            .map(|p| p.worker_tiers)
+            .unwrap_or_default()
+            .stats()
+    };
+    let mut deposits: Vec<(i64, Mineral, u32)> = Vec::new();
+
+    for (entity, mut pos, mut state, timer_opt, player_id, targets, mineral) in
+        worker_query.iter_mut()
+    {
+        let WorkerStats {
+            speed,
+            mining_secs,
+            carry,
+        } = stats_of(player_id.0);
         match *state {
             WorkerState::MovingToVein => {
                 let target = targets.vein;
                 let dx = target.x - pos.x;
                 let dy = target.y - pos.y;
                 let dist = (dx * dx + dy * dy).sqrt();
-                let move_dist = WORKER_SPEED * tick_delta;
+                let move_dist = speed * tick_delta;
 
                 if dist <= move_dist {
                     pos.x = target.x;
                     pos.y = target.y;
                     *state = WorkerState::Mining;
-                    commands.entity(entity).insert(MiningTimer(10.0));
+                    commands.entity(entity).insert(MiningTimer(mining_secs));
                 } else {
                     pos.x += (dx / dist) * move_dist;
                     pos.y += (dy / dist) * move_dist;
@@ -71,13 +103,13 @@ pub fn update_workers(
                 let dx = target.x - pos.x;
                 let dy = target.y - pos.y;
                 let dist = (dx * dx + dy * dy).sqrt();
-                let move_dist = WORKER_SPEED * tick_delta;
+                let move_dist = speed * tick_delta;
 
                 if dist <= move_dist {
                     pos.x = target.x;
                     pos.y = target.y;
                     *state = WorkerState::MovingToVein;
-                    deposit_pids.push(player_id.0);
+                    deposits.push((player_id.0, mineral.copied().unwrap_or_default(), carry));
                 } else {
                     pos.x += (dx / dist) * move_dist;
                     pos.y += (dy / dist) * move_dist;
@@ -86,9 +118,12 @@ pub fn update_workers(
         }
     }
 
-    for pid in deposit_pids {
+    for (pid, mineral, carry) in deposits {
         if let Some(player) = players.0.iter_mut().find(|p| p.id == pid) {
-            player.gold += 1;
+            match mineral {
+                Mineral::Gold => player.add_gold(carry, GoldReason::Mining, tick),
+                Mineral::Essence => player.add_essence(carry),
+            }
         }
     }
 }
@@ -98,6 +133,9 @@ mod tests {
     use super::*;
     use crate::model::game_state::GamePhase;
     use crate::model::player::Player;
+    use crate::model::unit_config::{
+        WORKER_CARRY_BY_TIER, WORKER_MINING_SECS_BY_TIER, WORKER_SPEED_BY_TIER, WorkerTiers,
+    };
     use bevy_ecs::prelude::World;
     use bevy_ecs::system::RunSystemOnce;
 
@@ -158,13 +196,87 @@ mod tests {
 
         world.run_system_once(update_workers).unwrap();
 
-        let gold = world.resource::<Players>().0[0].gold;
+        let gold = world.resource::<Players>().0[0].gold();
         assert_eq!(
             gold, 1,
             "Depositing at cart should award 1 gold via Players resource"
         );
     }
 
+    #[test]
+    fn upgraded_workers_move_faster_mine_quicker_and_carry_more() {
+        let mut world = setup_world_with_player(1);
+        world.resource_mut::<Players>().0[0].worker_tiers = WorkerTiers {
+            speed: 1,
+            mining_time: 2,
+            carry: 3,
+        };
+        let targets = TargetPositions {
+            vein: VEIN_POSITIONS[0],
+            cart: CART_POSITIONS[0],
+        };
+        let walker = world
+            .spawn((
+                CART_POSITIONS[0],
+                Worker,
+                WorkerState::MovingToVein,
+                PlayerIdComponent(1),
+                targets,
+            ))
+            .id();
+        let miner = world
+            .spawn((
+                VEIN_POSITIONS[0],
+                Worker,
+                WorkerState::MovingToVein,
+                PlayerIdComponent(1),
+                targets,
+            ))
+            .id();
+        world.spawn((
+            CART_POSITIONS[0],
+            Worker,
+            WorkerState::MovingToCart,
+            PlayerIdComponent(1),
+            targets,
+        ));
+
+        world.run_system_once(update_workers).unwrap();
+
+        let moved = CART_POSITIONS[0].y - world.entity(walker).get::<Position>().unwrap().y;
+        assert!((moved - WORKER_SPEED_BY_TIER[1] / 30.0).abs() < 1e-3);
+        let timer = world.entity(miner).get::<MiningTimer>().unwrap();
+        assert_eq!(timer.0, WORKER_MINING_SECS_BY_TIER[2]);
+        assert_eq!(
+            world.resource::<Players>().0[0].gold(),
+            WORKER_CARRY_BY_TIER[3]
+        );
+    }
+
+    #[test]
+    fn essence_workers_deposit_essence_not_gold() {
+        let mut world = setup_world_with_player(1);
+
+        let targets = TargetPositions {
+            vein: ESSENCE_VEIN_POSITIONS[0],
+            cart: CART_POSITIONS[0],
+        };
+        world.spawn((
+            CART_POSITIONS[0],
+            Worker,
+            WorkerState::MovingToCart,
+            PlayerIdComponent(1),
+            targets,
+            Mineral::Essence,
+        ));
+
+        world.run_system_once(update_workers).unwrap();
+
+        let player = &world.resource::<Players>().0[0];
+        assert_eq!(player.essence(), 1);
+        assert_eq!(player.gold(), 0);
+    }
+
     // --- Existing behavior preserved ---
 
     #[test]
@@ -217,7 +329,7 @@ mod tests {
         let state = world.entity(worker).get::<WorkerState>().unwrap();
         assert_eq!(*state, WorkerState::MovingToVein);
 
-        let gold = world.resource::<Players>().0[0].gold;
+        let gold = world.resource::<Players>().0[0].gold();
         assert_eq!(gold, 1);
     }
 
@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How many failed logins one key (a username or a client IP) gets before it
+/// has to wait, and how long.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BackoffPolicy {
+    /// Failures allowed before any delay kicks in.
+    pub free_attempts: u32,
+    /// Delay after the first failure past `free_attempts`; doubles with each
+    /// further failure up to `max_delay`.
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Failures after which the key is locked out for `lockout`.
+    pub lockout_after: u32,
+    pub lockout: Duration,
+    /// A key with no failures for this long starts over.
+    pub reset_after: Duration,
+}
+
+impl BackoffPolicy {
+    /// How long `failures` consecutive failures block the key for.
+    fn block_for(&self, failures: u32) -> Option<Duration> {
+        if failures >= self.lockout_after {
+            return Some(self.lockout);
+        }
+        let over = failures.checked_sub(self.free_attempts)?.checked_sub(1)?;
+        let delay = self.base_delay.saturating_mul(1 << over.min(16));
+        Some(delay.min(self.max_delay))
+    }
+}
+
+/// Limits for `/api/auth/login`, shared by every request via `ServerStateData`.
+#[derive(Clone, Debug)]
+pub struct LoginGuardConfig {
+    /// Guessing one account's password.
+    pub per_username: BackoffPolicy,
+    /// One client spraying many accounts. Looser, since players behind one
+    /// NAT share an address.
+    pub per_ip: BackoffPolicy,
+}
+
+impl Default for LoginGuardConfig {
+    fn default() -> Self {
+        Self {
+            per_username: BackoffPolicy {
+                free_attempts: 3,
+                base_delay: Duration::from_secs(1),
+                max_delay: Duration::from_secs(60),
+                lockout_after: 10,
+                lockout: Duration::from_secs(15 * 60),
+                reset_after: Duration::from_secs(60 * 60),
+            },
+            per_ip: BackoffPolicy {
+                free_attempts: 10,
+                base_delay: Duration::from_secs(1),
+                max_delay: Duration::from_secs(60),
+                lockout_after: 50,
+                lockout: Duration::from_secs(15 * 60),
+                reset_after: Duration::from_secs(60 * 60),
+            },
+        }
+    }
+}
+
+/// Why a login failed, as stored in `login_failures.reason`. Never sent to
+/// the client, which always sees the same generic error.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LoginFailure {
+    UnknownUser,
+    BadPassword,
+    /// Refused without checking the password because the username or IP is
+    /// backing off or locked out.
+    Throttled,
+}
+
+impl LoginFailure {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LoginFailure::UnknownUser => "unknown_user",
+            LoginFailure::BadPassword => "bad_password",
+            LoginFailure::Throttled => "throttled",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Failures {
+    count: u32,
+    last_failure: Instant,
+    blocked_until: Option<Instant>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Key {
+    Username(String),
+    Ip(IpAddr),
+}
+
+/// Entries are pruned once the map grows past this many keys.
+const PRUNE_THRESHOLD: usize = 10_000;
+/// Longest username prefix tracked or audited, so junk names cannot bloat
+/// the map or the log.
+pub const MAX_TRACKED_USERNAME: usize = 64;
+
+/// Failed-login counters per username and per client IP, with exponential
+/// backoff and temporary lockout. Kept in memory: a restart forgives
+/// everyone, and the `login_failures` table keeps the audit trail.
+#[derive(Debug)]
+pub struct LoginGuard {
+    config: LoginGuardConfig,
+    failures: Mutex<HashMap<Key, Failures>>,
+}
+
+impl LoginGuard {
+    pub fn new(config: LoginGuardConfig) -> Self {
+        Self {
+            config,
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn keys(username: &str, ip: Option<IpAddr>) -> impl Iterator<Item = Key> {
+        let username = username.chars().take(MAX_TRACKED_USERNAME).collect();
+        std::iter::once(Key::Username(username)).chain(ip.map(Key::Ip))
+    }
+
+    fn policy(&self, key: &Key) -> &BackoffPolicy {
+        match key {
+            Key::Username(_) => &self.config.per_username,
+            Key::Ip(_) => &self.config.per_ip,
+        }
+    }
+
+    /// `Err(wait)` if either the username or the IP must wait before another
+    /// attempt.
+    pub fn check(&self, username: &str, ip: Option<IpAddr>, now: Instant) -> Result<(), Duration> {
+        let failures = self.failures.lock().unwrap();
+        let wait = Self::keys(username, ip)
+            .filter_map(|key| failures.get(&key)?.blocked_until)
+            .filter_map(|until| until.checked_duration_since(now))
+            .filter(|wait| !wait.is_zero())
+            .max();
+        wait.map_or(Ok(()), Err)
+    }
+
+    pub fn record_failure(&self, username: &str, ip: Option<IpAddr>, now: Instant) {
+        let mut failures = self.failures.lock().unwrap();
+        if failures.len() > PRUNE_THRESHOLD {
+            failures.retain(|key, entry| self.is_live(key, entry, now));
+        }
+        for key in Self::keys(username, ip) {
+            let policy = *self.policy(&key);
+            let entry = failures.entry(key).or_insert(Failures {
+                count: 0,
+                last_failure: now,
+                blocked_until: None,
+            });
+            if now.duration_since(entry.last_failure) >= policy.reset_after {
+                entry.count = 0;
+            }
+            entry.count += 1;
+            entry.last_failure = now;
+            entry.blocked_until = policy.block_for(entry.count).map(|delay| now + delay);
+        }
+    }
+
+    /// Clears the username's counter. The IP's is left to decay, so one good
+    /// login does not reset a client that is spraying other accounts.
+    pub fn record_success(&self, username: &str) {
+        let key = Self::keys(username, None).next().unwrap();
+        self.failures.lock().unwrap().remove(&key);
+    }
+
+    fn is_live(&self, key: &Key, entry: &Failures, now: Instant) -> bool {
+        entry.blocked_until.is_some_and(|until| until > now)
+            || now.duration_since(entry.last_failure) < self.policy(key).reset_after
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IP: Option<IpAddr> = Some(IpAddr::V4(std::net::Ipv4Addr::LOCALHOST));
+
+    fn fail(guard: &LoginGuard, username: &str, ip: Option<IpAddr>, times: u32, now: Instant) {
+        for _ in 0..times {
+            guard.record_failure(username, ip, now);
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_then_locks_out() {
+        let policy = LoginGuardConfig::default().per_username;
+        assert_eq!(policy.block_for(3), None);
+        assert_eq!(policy.block_for(4), Some(Duration::from_secs(1)));
+        assert_eq!(policy.block_for(5), Some(Duration::from_secs(2)));
+        assert_eq!(policy.block_for(6), Some(Duration::from_secs(4)));
+        assert_eq!(policy.block_for(10), Some(policy.lockout));
+    }
+
+    #[test]
+    fn username_is_blocked_after_free_attempts_and_waits_out_the_delay() {
+        let guard = LoginGuard::new(LoginGuardConfig::default());
+        let now = Instant::now();
+        fail(&guard, "alice", None, 3, now);
+        assert_eq!(guard.check("alice", None, now), Ok(()));
+
+        guard.record_failure("alice", None, now);
+        assert_eq!(guard.check("alice", None, now), Err(Duration::from_secs(1)));
+        assert_eq!(guard.check("bob", None, now), Ok(()));
+        assert_eq!(
+            guard.check("alice", None, now + Duration::from_secs(1)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn lockout_and_success_reset() {
+        let guard = LoginGuard::new(LoginGuardConfig::default());
+        let now = Instant::now();
+        fail(&guard, "alice", None, 10, now);
+        let lockout = LoginGuardConfig::default().per_username.lockout;
+        assert_eq!(guard.check("alice", None, now), Err(lockout));
+
+        guard.record_success("alice");
+        assert_eq!(guard.check("alice", None, now), Ok(()));
+    }
+
+    #[test]
+    fn one_ip_spraying_many_usernames_is_blocked() {
+        let guard = LoginGuard::new(LoginGuardConfig::default());
+        let now = Instant::now();
+        for n in 0..11 {
+            guard.record_failure(&format!("user{n}"), IP, now);
+        }
+        assert!(guard.check("someone-else", IP, now).is_err());
+        assert_eq!(guard.check("someone-else", None, now), Ok(()));
+    }
+
+    #[test]
+    fn failures_are_forgotten_after_a_quiet_period() {
+        let guard = LoginGuard::new(LoginGuardConfig::default());
+        let now = Instant::now();
+        fail(&guard, "alice", None, 3, now);
+        let later = now + LoginGuardConfig::default().per_username.reset_after;
+        guard.record_failure("alice", None, later);
+        assert_eq!(guard.check("alice", None, later), Ok(()));
+    }
+}
@@ -1,5 +1,8 @@
+mod config;
 mod database;
 mod handler;
+mod login_guard;
+mod metrics;
 mod model;
 mod router;
 mod routes;
@@ -9,5 +12,15 @@ mod state;
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     env_logger::init();
+    if std::env::args().any(|arg| arg == "--emit-schema") {
+        let schema = model::messages::protocol_schema();
+        println!("{}", serde_json::to_string_pretty(&schema)?);
+        return Ok(());
+    }
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(pos) = args.iter().position(|arg| arg == "--grant-admin") {
+        let username = args.get(pos + 1).ok_or("--grant-admin needs a username")?;
+        return server::grant_admin(username).await;
+    }
     server::run().await
 }
@@ -0,0 +1,355 @@
+use crate::login_guard::LoginFailure;
+use crate::state::ServerState;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds (seconds) of the tick-duration buckets. Dense around the
+/// 33ms budget of a 30Hz tick, where overruns start.
+const TICK_BUCKETS: [f64; 10] = [
+    0.001, 0.0025, 0.005, 0.01, 0.02, 0.0333, 0.05, 0.1, 0.25, 1.0,
+];
+
+/// Fixed-bucket histogram that can be updated through `&self` from any task.
+#[derive(Debug)]
+pub struct Histogram {
+    bounds: &'static [f64],
+    /// Per-bucket (non-cumulative) counts; the last slot is `+Inf`.
+    buckets: Vec<AtomicU64>,
+    sum_micros: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            buckets: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum_micros: AtomicU64::new(0),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, duration: Duration) {
+        let secs = duration.as_secs_f64();
+        let idx = self
+            .bounds
+            .iter()
+            .position(|&bound| secs <= bound)
+            .unwrap_or(self.bounds.len());
+        self.buckets[idx].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} histogram");
+        let mut cumulative = 0;
+        for (i, bucket) in self.buckets.iter().enumerate() {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let le = self
+                .bounds
+                .get(i)
+                .map_or_else(|| "+Inf".to_string(), |b| b.to_string());
+            let _ = writeln!(out, "{name}_bucket{{le=\"{le}\"}} {cumulative}");
+        }
+        let sum = self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(out, "{name}_sum {sum}");
+        let _ = writeln!(out, "{name}_count {}", self.count.load(Ordering::Relaxed));
+    }
+}
+
+/// Which authentication path a success/failure count belongs to.
+#[derive(Clone, Copy, Debug)]
+pub enum AuthEndpoint {
+    Login,
+    WebSocket,
+}
+
+impl AuthEndpoint {
+    fn label(self) -> &'static str {
+        match self {
+            AuthEndpoint::Login => "login",
+            AuthEndpoint::WebSocket => "ws",
+        }
+    }
+}
+
+/// Why an authentication attempt failed; each belongs to one endpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuthFailure {
+    UnknownUser,
+    BadPassword,
+    /// Refused by `LoginGuard` (429) before the password was checked.
+    Throttled,
+    /// A WebSocket upgrade without a valid session token.
+    InvalidToken,
+}
+
+impl AuthFailure {
+    const ALL: [AuthFailure; 4] = [
+        AuthFailure::UnknownUser,
+        AuthFailure::BadPassword,
+        AuthFailure::Throttled,
+        AuthFailure::InvalidToken,
+    ];
+
+    fn endpoint(self) -> AuthEndpoint {
+        match self {
+            AuthFailure::InvalidToken => AuthEndpoint::WebSocket,
+            _ => AuthEndpoint::Login,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            AuthFailure::UnknownUser => LoginFailure::UnknownUser.as_str(),
+            AuthFailure::BadPassword => LoginFailure::BadPassword.as_str(),
+            AuthFailure::Throttled => LoginFailure::Throttled.as_str(),
+            AuthFailure::InvalidToken => "invalid_token",
+        }
+    }
+}
+
+impl From<LoginFailure> for AuthFailure {
+    fn from(failure: LoginFailure) -> Self {
+        match failure {
+            LoginFailure::UnknownUser => AuthFailure::UnknownUser,
+            LoginFailure::BadPassword => AuthFailure::BadPassword,
+            LoginFailure::Throttled => AuthFailure::Throttled,
+        }
+    }
+}
+
+/// Process-wide counters, owned by `ServerStateData`. Gauges that already
+/// live elsewhere in the state (connections, matches, queue) are read at
+/// scrape time instead of being mirrored here. Match numbers are summed over
+/// every match, so the series count does not grow with matches played.
+#[derive(Debug)]
+pub struct Metrics {
+    pub tick_duration: Histogram,
+    /// Clients that fell behind the broadcast channel and got a full resync.
+    pub lagged_resyncs: AtomicU64,
+    /// Bytes broadcast to match clients; shards add each match's share.
+    pub broadcast_bytes: AtomicU64,
+    /// Ticks whose `run_tick` exceeded the tick period.
+    pub tick_overruns: AtomicU64,
+    /// Ticks skipped or dropped because a shard fell behind.
+    pub missed_ticks: AtomicU64,
+    /// `[endpoint][0 = success, 1 = failure]`
+    auth_attempts: [[AtomicU64; 2]; 2],
+    /// Failures by `AuthFailure`.
+    auth_failures: [AtomicU64; AuthFailure::ALL.len()],
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            tick_duration: Histogram::new(&TICK_BUCKETS),
+            lagged_resyncs: AtomicU64::new(0),
+            broadcast_bytes: AtomicU64::new(0),
+            tick_overruns: AtomicU64::new(0),
+            missed_ticks: AtomicU64::new(0),
+            auth_attempts: Default::default(),
+            auth_failures: Default::default(),
+        }
+    }
+}
+
+impl Metrics {
+    pub fn record_auth(&self, endpoint: AuthEndpoint, result: Result<(), AuthFailure>) {
+        self.auth_attempts[endpoint as usize][usize::from(result.is_err())]
+            .fetch_add(1, Ordering::Relaxed);
+        if let Err(reason) = result {
+            self.auth_failures[reason as usize].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn render_counters(&self, out: &mut String) {
+        for (name, help, value) in [
+            (
+                "nicktd_lagged_resyncs_total",
+                "Full-state resyncs sent to clients that lagged the broadcast channel.",
+                &self.lagged_resyncs,
+            ),
+            (
+                "nicktd_broadcast_bytes_total",
+                "Bytes broadcast to match clients, over all matches.",
+                &self.broadcast_bytes,
+            ),
+            (
+                "nicktd_tick_overruns_total",
+                "Ticks whose run_tick exceeded the tick period.",
+                &self.tick_overruns,
+            ),
+            (
+                "nicktd_missed_ticks_total",
+                "Ticks skipped or dropped because the loop fell behind.",
+                &self.missed_ticks,
+            ),
+        ] {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} counter");
+            let _ = writeln!(out, "{name} {}", value.load(Ordering::Relaxed));
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP nicktd_auth_attempts_total Authentication attempts by endpoint and result."
+        );
+        let _ = writeln!(out, "# TYPE nicktd_auth_attempts_total counter");
+        for endpoint in [AuthEndpoint::Login, AuthEndpoint::WebSocket] {
+            let name = endpoint.label();
+            for (slot, result) in ["success", "failure"].iter().enumerate() {
+                let value = self.auth_attempts[endpoint as usize][slot].load(Ordering::Relaxed);
+                let _ = writeln!(
+                    out,
+                    "nicktd_auth_attempts_total{{endpoint=\"{name}\",result=\"{result}\"}} {value}"
+                );
+            }
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP nicktd_auth_failures_total Failed authentication attempts by endpoint and reason."
+        );
+        let _ = writeln!(out, "# TYPE nicktd_auth_failures_total counter");
+        for reason in AuthFailure::ALL {
+            let value = self.auth_failures[reason as usize].load(Ordering::Relaxed);
+            let _ = writeln!(
+                out,
+                "nicktd_auth_failures_total{{endpoint=\"{}\",reason=\"{}\"}} {value}",
+                reason.endpoint().label(),
+                reason.label()
+            );
+        }
+
+        self.tick_duration.render(
+            out,
+            "nicktd_tick_duration_seconds",
+            "Wall time of one run_tick call.",
+        );
+    }
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: impl std::fmt::Display) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} gauge");
+    let _ = writeln!(out, "{name} {value}");
+}
+
+/// Renders every metric in the Prometheus text exposition format.
+pub async fn render(server_state: &ServerState) -> String {
+    let mut out = String::new();
+
+    let connections = server_state.active_connections.lock().await.len();
+    gauge(
+        &mut out,
+        "nicktd_active_connections",
+        "Authenticated WebSocket connections.",
+        connections,
+    );
+    let queued = usize::from(server_state.queue.lock().await.is_some());
+    gauge(
+        &mut out,
+        "nicktd_queue_length",
+        "Players waiting in the matchmaking queue.",
+        queued,
+    );
+
+    // Clone the handles and release the map lock before asking any shard.
+    let lobbies: Vec<_> = server_state
+        .matches
+        .read()
+        .await
+        .values()
+        .cloned()
+        .collect();
+    gauge(
+        &mut out,
+        "nicktd_live_matches",
+        "Matches currently registered.",
+        lobbies.len(),
+    );
+    // Only the worst match is reported, keeping one series however many run.
+    let mut slowest: f64 = 0.0;
+    for handle in lobbies {
+        let mean = handle
+            .with_lobby(|lobby| lobby.tick_stats.mean().as_secs_f64())
+            .await;
+        slowest = slowest.max(mean.unwrap_or(0.0));
+    }
+    gauge(
+        &mut out,
+        "nicktd_slowest_match_tick_mean_seconds",
+        "Highest mean run_tick time over recent ticks among live matches.",
+        slowest,
+    );
+
+    server_state.metrics.render_counters(&mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let histogram = Histogram::new(&TICK_BUCKETS);
+        histogram.observe(Duration::from_micros(500));
+        histogram.observe(Duration::from_millis(30));
+        histogram.observe(Duration::from_secs(5));
+        let mut out = String::new();
+        histogram.render(&mut out, "t", "help");
+        assert!(out.contains("t_bucket{le=\"0.001\"} 1\n"));
+        assert!(out.contains("t_bucket{le=\"0.0333\"} 2\n"));
+        assert!(out.contains("t_bucket{le=\"1\"} 2\n"));
+        assert!(out.contains("t_bucket{le=\"+Inf\"} 3\n"));
+        assert!(out.contains("t_count 3\n"));
+    }
+
+    #[test]
+    fn auth_attempts_are_labelled_by_endpoint_and_result() {
+        let metrics = Metrics::default();
+        metrics.record_auth(AuthEndpoint::Login, Ok(()));
+        metrics.record_auth(AuthEndpoint::Login, Err(AuthFailure::BadPassword));
+        metrics.record_auth(AuthEndpoint::Login, Err(LoginFailure::Throttled.into()));
+        metrics.record_auth(AuthEndpoint::WebSocket, Ok(()));
+        let mut out = String::new();
+        metrics.render_counters(&mut out);
+        assert!(
+            out.contains("nicktd_auth_attempts_total{endpoint=\"login\",result=\"failure\"} 2")
+        );
+        assert!(out.contains("nicktd_auth_attempts_total{endpoint=\"ws\",result=\"success\"} 1"));
+        assert!(out.contains("nicktd_auth_attempts_total{endpoint=\"ws\",result=\"failure\"} 0"));
+        assert!(
+            out.contains("nicktd_auth_failures_total{endpoint=\"login\",reason=\"throttled\"} 1")
+        );
+        assert!(
+            out.contains("nicktd_auth_failures_total{endpoint=\"ws\",reason=\"invalid_token\"} 0")
+        );
+    }
+
+    #[tokio::test]
+    async fn render_reports_live_matches_without_per_match_series() {
+        use crate::{model::lobby::Lobby, state::test_support::test_state};
+
+        let state = test_state().await;
+        let handle = state.sim.spawn_match(7, Lobby::new());
+        state.matches.write().await.insert(7, handle);
+        state
+            .metrics
+            .broadcast_bytes
+            .fetch_add(42, Ordering::Relaxed);
+
+        let out = render(&state).await;
+        assert!(out.contains("nicktd_live_matches 1\n"));
+        assert!(out.contains("nicktd_queue_length 0\n"));
+        assert!(out.contains("nicktd_slowest_match_tick_mean_seconds "));
+        assert!(out.contains("nicktd_broadcast_bytes_total 42\n"));
+        assert!(!out.contains("match_id"));
+    }
+}
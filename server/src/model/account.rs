@@ -1,13 +1,28 @@
+use super::family::Family;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use std::ops::RangeInclusive;
+
+/// Allowed username length, in characters.
+pub const USERNAME_LEN: RangeInclusive<usize> = 3..=20;
+/// Allowed password length, in bytes. bcrypt ignores everything past 72.
+pub const PASSWORD_LEN: RangeInclusive<usize> = 8..=72;
+pub const DISPLAY_NAME_MAX_LEN: usize = 24;
+/// Prefix of generated guest usernames; registrations may not use it.
+pub const GUEST_PREFIX: &str = "guest-";
+/// Size of the client's built-in avatar set; `Profile::avatar` indexes it.
+pub const AVATAR_COUNT: u8 = 12;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, FromRow)]
 pub struct Account {
     pub id: i64,
     pub username: String,
     pub password_hash: String,
-    pub session_id: Option<String>,
-    pub session_expires_at: Option<chrono::NaiveDateTime>,
+    /// May use the `/api/admin` routes.
+    pub is_admin: bool,
+    /// Created by `POST /api/auth/guest`: a generated name and no password
+    /// until upgraded via `/api/account/upgrade`.
+    pub is_guest: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -15,3 +30,161 @@ pub struct NewAccount {
     pub username: String,
     pub password: String,
 }
+
+impl NewAccount {
+    /// Rules a registration must pass. Login skips them so accounts created
+    /// before they existed can still sign in.
+    pub fn validate(&self) -> Result<(), String> {
+        validate_username(&self.username)?;
+        if self.username.starts_with(GUEST_PREFIX) {
+            return Err(format!(
+                "Usernames starting with {GUEST_PREFIX:?} are reserved"
+            ));
+        }
+        validate_password(&self.password)
+    }
+}
+
+/// A fresh generated username for a guest account.
+pub fn guest_username() -> String {
+    let suffix = uuid::Uuid::new_v4().simple().to_string();
+    format!("{GUEST_PREFIX}{}", &suffix[..8])
+}
+
+/// Letters, digits, `_` and `-` only, so names are safe to show anywhere.
+pub fn validate_username(username: &str) -> Result<(), String> {
+    if !USERNAME_LEN.contains(&username.chars().count()) {
+        return Err(format!(
+            "Username must be {} to {} characters",
+            USERNAME_LEN.start(),
+            USERNAME_LEN.end()
+        ));
+    }
+    if !username
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err("Username may only contain letters, digits, '_' and '-'".to_string());
+    }
+    Ok(())
+}
+
+pub fn validate_password(password: &str) -> Result<(), String> {
+    if !PASSWORD_LEN.contains(&password.len()) {
+        return Err(format!(
+            "Password must be {} to {} bytes",
+            PASSWORD_LEN.start(),
+            PASSWORD_LEN.end()
+        ));
+    }
+    Ok(())
+}
+
+/// What other players can see about an account.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Profile {
+    pub username: String,
+    /// Shown instead of the username when set.
+    pub display_name: Option<String>,
+    pub avatar: u8,
+    pub favourite_family: Option<Family>,
+}
+
+/// Body of a profile update. Replaces every field; omitted ones are cleared.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ProfileUpdate {
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub avatar: u8,
+    #[serde(default)]
+    pub favourite_family: Option<Family>,
+}
+
+impl ProfileUpdate {
+    /// Checks the update and trims the display name; a blank one clears it.
+    pub fn normalize(mut self) -> Result<Self, String> {
+        self.display_name = self
+            .display_name
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty());
+        if let Some(name) = &self.display_name {
+            if name.chars().count() > DISPLAY_NAME_MAX_LEN {
+                return Err(format!(
+                    "Display name must be at most {DISPLAY_NAME_MAX_LEN} characters"
+                ));
+            }
+            if name.chars().any(char::is_control) {
+                return Err("Display name may not contain control characters".to_string());
+            }
+        }
+        if self.avatar >= AVATAR_COUNT {
+            return Err(format!("Avatar must be below {AVATAR_COUNT}"));
+        }
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_account(username: &str, password: &str) -> NewAccount {
+        NewAccount {
+            username: username.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    #[test]
+    fn registration_rules() {
+        assert!(new_account("nick_td-1", "hunter22").validate().is_ok());
+        assert!(new_account("ab", "hunter22").validate().is_err());
+        assert!(new_account(&"a".repeat(21), "hunter22").validate().is_err());
+        assert!(new_account("nick td", "hunter22").validate().is_err());
+        assert!(new_account("nické", "hunter22").validate().is_err());
+        assert!(new_account("nick", "short").validate().is_err());
+        assert!(new_account("nick", &"p".repeat(73)).validate().is_err());
+        assert!(new_account("guest-1234", "hunter22").validate().is_err());
+    }
+
+    #[test]
+    fn guest_usernames_pass_the_username_rules() {
+        let name = guest_username();
+        assert!(name.starts_with(GUEST_PREFIX));
+        assert!(validate_username(&name).is_ok());
+        assert_ne!(guest_username(), name);
+    }
+
+    #[test]
+    fn profile_update_trims_and_checks_fields() {
+        let update = ProfileUpdate {
+            display_name: Some("  Nick  ".to_string()),
+            avatar: 3,
+            favourite_family: Some(Family::Basic),
+        };
+        let normalized = update.normalize().unwrap();
+        assert_eq!(normalized.display_name.as_deref(), Some("Nick"));
+
+        let blank = ProfileUpdate {
+            display_name: Some("   ".to_string()),
+            avatar: 0,
+            favourite_family: None,
+        };
+        assert_eq!(blank.normalize().unwrap().display_name, None);
+
+        let bad_avatar = ProfileUpdate {
+            display_name: None,
+            avatar: AVATAR_COUNT,
+            favourite_family: None,
+        };
+        assert!(bad_avatar.normalize().is_err());
+
+        let long_name = ProfileUpdate {
+            display_name: Some("x".repeat(DISPLAY_NAME_MAX_LEN + 1)),
+            avatar: 0,
+            favourite_family: None,
+        };
+        assert!(long_name.normalize().is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use super::constants::{KING_Y, RIGHT_BOARD_END, SQUARE_SIZE};
+use super::game_error::GameError;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Longest chat line, in characters, after trimming.
+pub const MAX_CHAT_CHARS: usize = 200;
+
+/// Masked whenever a word starts with one of these, so "shitty" goes too.
+const PROFANE_ROOTS: &[&str] = &["fuck", "shit", "bitch", "cunt", "wank", "twat"];
+
+/// Masked only as whole words; as prefixes they would hit "dickens" or
+/// "passage".
+const PROFANE_WORDS: &[&str] = &[
+    "ass", "asshole", "bastard", "bollocks", "dick", "dickhead", "piss", "prick",
+];
+
+/// What a map ping asks of the other players; the client picks the marker.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+pub enum PingKind {
+    Look,
+    Attack,
+    Defend,
+    Danger,
+}
+
+/// Trims a chat line and rejects it if it is empty, too long or carries
+/// control characters. Returns the text to relay.
+pub fn validate_chat(text: &str) -> Result<String, GameError> {
+    let text = text.trim();
+    let invalid = |reason: &str| {
+        Err(GameError::InvalidChat {
+            reason: reason.to_string(),
+        })
+    };
+    if text.is_empty() {
+        return invalid("message is empty");
+    }
+    if text.chars().count() > MAX_CHAT_CHARS {
+        return invalid(&format!("longer than {MAX_CHAT_CHARS} characters"));
+    }
+    if text.chars().any(char::is_control) {
+        return invalid("contains control characters");
+    }
+    Ok(text.to_string())
+}
+
+/// Whether (x, y) lies on the boards, the gap between them or the king row.
+pub fn ping_in_bounds(x: f32, y: f32) -> bool {
+    (0.0..=RIGHT_BOARD_END).contains(&x) && (0.0..=KING_Y + SQUARE_SIZE).contains(&y)
+}
+
+/// Replaces the letters of every profane word with `*`, leaving spacing and
+/// punctuation alone.
+pub fn filter_profanity(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut word = String::new();
+    for c in text.chars() {
+        if c.is_alphabetic() {
+            word.push(c);
+        } else {
+            push_word(&mut out, &word);
+            word.clear();
+            out.push(c);
+        }
+    }
+    push_word(&mut out, &word);
+    out
+}
+
+fn push_word(out: &mut String, word: &str) {
+    let lower = word.to_lowercase();
+    let profane = PROFANE_ROOTS.iter().any(|root| lower.starts_with(root))
+        || PROFANE_WORDS.contains(&lower.as_str());
+    if profane {
+        out.extend(word.chars().map(|_| '*'));
+    } else {
+        out.push_str(word);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profanity_is_masked_but_innocent_words_survive() {
+        assert_eq!(filter_profanity("Oh SHIT, gg!"), "Oh ****, gg!");
+        assert_eq!(filter_profanity("shitty dick move"), "****** **** move");
+        assert_eq!(
+            filter_profanity("Dickens passed the class"),
+            "Dickens passed the class"
+        );
+    }
+
+    #[test]
+    fn chat_is_trimmed_and_bounded() {
+        assert_eq!(validate_chat("  gl hf \n").unwrap(), "gl hf");
+        assert!(validate_chat("   ").is_err());
+        assert!(validate_chat(&"a".repeat(MAX_CHAT_CHARS)).is_ok());
+        assert!(validate_chat(&"a".repeat(MAX_CHAT_CHARS + 1)).is_err());
+        assert!(validate_chat("hi\u{7}there").is_err());
+    }
+}
@@ -1,9 +1,10 @@
 use bevy_ecs::prelude::{Component, Entity};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use super::unit_kind::UnitKind;
 
-#[derive(Component, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Component, Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Position {
     pub x: f32,
     pub y: f32,
@@ -57,6 +58,17 @@ pub enum WorkerState {
 #[derive(Component)]
 pub struct MiningTimer(pub f32);
 
+/// What a worker mines. Workers without this component mine gold.
+#[derive(
+    Component, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, JsonSchema,
+)]
+pub enum Mineral {
+    #[default]
+    Gold,
+    /// Only mined in `GameMode::Essence`, from its own vein.
+    Essence,
+}
+
 #[derive(Component, Clone, Copy, Debug, PartialEq)]
 pub struct CollisionRadius(pub f32);
 
@@ -72,14 +84,14 @@ pub struct Health {
     pub max: f32,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum School {
     PhysicalBasic,
     PhysicalPierce,
     Magical,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum Element {
     None,
     Fire,
@@ -87,7 +99,7 @@ pub enum Element {
     Poison,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct DamageType {
     pub school: School,
     pub element: Element,
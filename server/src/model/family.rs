@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// A player's chosen tower family for the match. Only `Basic` (today's three
@@ -6,11 +7,27 @@ use serde::{Deserialize, Serialize};
 /// (`unit_config::family_catalog`) is the single source of truth for which
 /// `UnitKind`s a family unlocks, so adding a family later is one match arm,
 /// not a schema change.
-#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
 pub enum Family {
     Basic,
 }
 
+impl Family {
+    /// Name stored in `accounts.favourite_family`; the same as the wire name.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Family::Basic => "Basic",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Basic" => Some(Family::Basic),
+            _ => None,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -19,4 +36,15 @@ mod tests {
     fn family_serializes_as_plain_string() {
         assert_eq!(serde_json::to_string(&Family::Basic).unwrap(), "\"Basic\"");
     }
+
+    #[test]
+    fn stored_name_round_trips_and_matches_the_wire_name() {
+        let name = Family::Basic.as_str();
+        assert_eq!(Family::from_name(name), Some(Family::Basic));
+        assert_eq!(
+            serde_json::to_string(&Family::Basic).unwrap(),
+            format!("\"{name}\"")
+        );
+        assert_eq!(Family::from_name("Elves"), None);
+    }
 }
@@ -0,0 +1,132 @@
+use serde::Serialize;
+
+/// One friendship or pending request as stored, seen from one account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FriendLink {
+    pub account_id: i64,
+    pub username: String,
+    pub accepted: bool,
+    /// The request was sent by the account whose list this is.
+    pub outgoing: bool,
+}
+
+/// What `database::send_friend_request` did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FriendRequestOutcome {
+    Sent,
+    /// The other account had already asked; that request was accepted.
+    Accepted,
+    AlreadyPending,
+    AlreadyFriends,
+}
+
+/// Where a friend is, as far as their game socket tells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Presence {
+    Offline,
+    Online,
+    InMatch,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Friend {
+    pub id: i64,
+    pub username: String,
+    /// Has a game socket open.
+    pub online: bool,
+    /// Playing a match, so a challenge would be refused.
+    pub in_match: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PendingFriend {
+    pub id: i64,
+    pub username: String,
+}
+
+/// Body of `GET /api/friends`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FriendsList {
+    pub friends: Vec<Friend>,
+    /// Requests waiting for the caller to accept.
+    pub incoming: Vec<PendingFriend>,
+    /// The caller's requests nobody has accepted yet.
+    pub outgoing: Vec<PendingFriend>,
+}
+
+impl FriendsList {
+    /// Sorts stored links into the list; `presence` looks up each friend.
+    pub fn build(links: Vec<FriendLink>, presence: impl Fn(i64) -> Presence) -> Self {
+        let mut list = FriendsList {
+            friends: Vec::new(),
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+        };
+        for link in links {
+            if link.accepted {
+                let presence = presence(link.account_id);
+                list.friends.push(Friend {
+                    id: link.account_id,
+                    username: link.username,
+                    online: presence != Presence::Offline,
+                    in_match: presence == Presence::InMatch,
+                });
+                continue;
+            }
+            let pending = PendingFriend {
+                id: link.account_id,
+                username: link.username,
+            };
+            if link.outgoing {
+                list.outgoing.push(pending);
+            } else {
+                list.incoming.push(pending);
+            }
+        }
+        // Online friends first, then by name.
+        list.friends
+            .sort_by(|a, b| b.online.cmp(&a.online).then(a.username.cmp(&b.username)));
+        list
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(account_id: i64, username: &str, accepted: bool, outgoing: bool) -> FriendLink {
+        FriendLink {
+            account_id,
+            username: username.to_string(),
+            accepted,
+            outgoing,
+        }
+    }
+
+    #[test]
+    fn build_sorts_links_and_lists_online_friends_first() {
+        let links = vec![
+            link(1, "amy", true, true),
+            link(2, "bob", true, false),
+            link(3, "cat", false, false),
+            link(4, "dan", false, true),
+            link(5, "eve", true, false),
+        ];
+        let list = FriendsList::build(links, |id| match id {
+            2 => Presence::Online,
+            5 => Presence::InMatch,
+            _ => Presence::Offline,
+        });
+        let friends: Vec<_> = list
+            .friends
+            .iter()
+            .map(|f| (f.id, f.online, f.in_match))
+            .collect();
+        assert_eq!(
+            friends,
+            vec![(2, true, false), (5, true, true), (1, false, false)]
+        );
+        assert_eq!(list.incoming[0].id, 3);
+        assert_eq!(list.outgoing[0].id, 4);
+    }
+}
@@ -0,0 +1,251 @@
+use super::game_state::GamePhase;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::fmt;
+
+/// Every reason the server rejects a client action. Serialized internally
+/// tagged as `{"code": "<Variant>", ...fields}` so the variant name is the
+/// stable, machine-readable code clients switch on; the human text from
+/// `Display` rides alongside it in `ErrorData::message` for clients that
+/// just want to show something.
+///
+/// Variant names are part of the wire protocol: rename one and every client
+/// that localizes on it breaks. Add new variants instead.
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(tag = "code")]
+pub enum GameError {
+    InsufficientGold {
+        needed: u32,
+        have: u32,
+    },
+    InsufficientEssence {
+        needed: u32,
+        have: u32,
+    },
+    CellOccupied,
+    InvalidPlacement,
+    /// The tower would wall the creeps' spawn off from the end of the lane.
+    PathBlocked,
+    WrongPhase {
+        required: GamePhase,
+    },
+    FamilyNotPicked,
+    FamilyAlreadyLocked,
+    NotInFamily,
+    MaxTier,
+    WorkerCap {
+        max: usize,
+    },
+    KingNotFound,
+    /// The account logged in elsewhere and this connection is being closed.
+    SessionReplaced,
+    /// The first frame on the socket was not a `Hello`.
+    HandshakeRequired,
+    /// The client speaks a protocol version this server does not.
+    ProtocolMismatch {
+        server: u32,
+        client: u32,
+    },
+    /// Too many `action` messages in a short window; further ones are dropped.
+    RateLimited {
+        action: String,
+    },
+    /// Repeated unparseable or oversized frames; the connection is closed.
+    TooManyInvalidMessages,
+    /// The server is draining for a restart and not starting new matches.
+    ServerShuttingDown,
+    /// An operator closed this connection.
+    Kicked {
+        reason: String,
+    },
+    /// The account was deleted while this connection was open.
+    AccountDeleted,
+    /// This device's session was signed out from another device.
+    SessionRevoked,
+    /// Another device holds this account's socket and the server is set to
+    /// turn second devices away.
+    AlreadyConnected,
+    /// No open private lobby has that join code.
+    PrivateLobbyNotFound,
+    /// The private lobby already has two players.
+    PrivateLobbyFull,
+    /// Only the player who created the private lobby may do that.
+    NotLobbyHost,
+    /// Not in a private lobby, or the lobby already started.
+    NotInPrivateLobby,
+    InvalidMatchSettings {
+        reason: String,
+    },
+    /// The host left, so the private lobby was closed.
+    PrivateLobbyClosed,
+    /// Missed a ready check recently; may queue again after this long.
+    QueuePenalty {
+        seconds_remaining: u64,
+    },
+    /// Only accepted friends can be challenged.
+    NotFriends,
+    /// The friend has no game socket open.
+    FriendOffline,
+    /// The friend is not idle in the menu (queued, in a lobby or match), or
+    /// has too many unanswered notices to take a challenge.
+    FriendBusy,
+    /// No open challenge with that id is addressed to this player.
+    ChallengeNotFound,
+    ChallengeDeclined,
+    /// The friend did not answer in time.
+    ChallengeExpired,
+    InvalidChat {
+        reason: String,
+    },
+    /// The ping lies off the map.
+    InvalidPing,
+    AlreadyPaused,
+    /// The player has used all their pauses or pause time.
+    NoPausesLeft,
+    NotPaused,
+    /// Building, selling and sending wait until the match is resumed.
+    GamePaused,
+    /// Rematches can only be asked for once the match is over.
+    MatchNotOver,
+    /// The opponent already left the finished match.
+    RematchUnavailable,
+    /// Essence is only mined and spent in `GameMode::Essence`.
+    EssenceDisabled,
+    /// Building, hiring and sending wait until both players have loaded
+    /// and picked a family.
+    WaitingForPlayers,
+    /// The match already ended, so there is nothing to surrender.
+    MatchOver,
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameError::InsufficientGold { needed, have } => {
+                write!(f, "Insufficient gold (cost: {needed}, have: {have}).")
+            }
+            GameError::InsufficientEssence { needed, have } => {
+                write!(f, "Insufficient essence (cost: {needed}, have: {have}).")
+            }
+            GameError::CellOccupied => write!(f, "That square is already occupied."),
+            GameError::InvalidPlacement => write!(f, "Invalid placement coordinates."),
+            GameError::PathBlocked => write!(f, "That would block the creeps' path."),
+            GameError::WrongPhase { required } => {
+                write!(f, "Only allowed during the {required:?} phase.")
+            }
+            GameError::FamilyNotPicked => write!(f, "Pick a family before building."),
+            GameError::FamilyAlreadyLocked => write!(f, "Family already locked for this match."),
+            GameError::NotInFamily => write!(f, "That unit isn't in your family."),
+            GameError::MaxTier => write!(f, "Already at maximum tier."),
+            GameError::WorkerCap { max } => write!(f, "Worker limit reached (max {max})."),
+            GameError::KingNotFound => write!(f, "King not found."),
+            GameError::SessionReplaced => write!(f, "Logged in from another location."),
+            GameError::HandshakeRequired => {
+                write!(f, "Protocol handshake required; please update your client.")
+            }
+            GameError::ProtocolMismatch { server, client } => write!(
+                f,
+                "Client protocol v{client} is not supported (server speaks v{server}); please update your client."
+            ),
+            GameError::RateLimited { action } => {
+                write!(f, "Too many {action} requests; slow down.")
+            }
+            GameError::TooManyInvalidMessages => {
+                write!(f, "Too many malformed messages; disconnecting.")
+            }
+            GameError::ServerShuttingDown => {
+                write!(f, "Server is restarting; matchmaking is closed.")
+            }
+            GameError::Kicked { reason } => {
+                write!(f, "Disconnected by an administrator: {reason}")
+            }
+            GameError::AccountDeleted => write!(f, "This account has been deleted."),
+            GameError::SessionRevoked => write!(f, "This device was signed out."),
+            GameError::AlreadyConnected => {
+                write!(f, "This account is already playing on another device.")
+            }
+            GameError::PrivateLobbyNotFound => write!(f, "No lobby with that code."),
+            GameError::PrivateLobbyFull => write!(f, "That lobby is full."),
+            GameError::NotLobbyHost => write!(f, "Only the lobby host can do that."),
+            GameError::NotInPrivateLobby => write!(f, "You are not in a private lobby."),
+            GameError::InvalidMatchSettings { reason } => {
+                write!(f, "Invalid match settings: {reason}")
+            }
+            GameError::PrivateLobbyClosed => write!(f, "The host closed the lobby."),
+            GameError::QueuePenalty { seconds_remaining } => write!(
+                f,
+                "You missed a match; you can queue again in {seconds_remaining}s."
+            ),
+            GameError::NotFriends => write!(f, "You can only challenge your friends."),
+            GameError::FriendOffline => write!(f, "That friend is offline."),
+            GameError::FriendBusy => write!(f, "That friend is busy right now."),
+            GameError::ChallengeNotFound => write!(f, "That challenge is no longer open."),
+            GameError::ChallengeDeclined => write!(f, "Your friend declined the challenge."),
+            GameError::ChallengeExpired => write!(f, "Your friend did not answer the challenge."),
+            GameError::InvalidChat { reason } => write!(f, "Message not sent: {reason}."),
+            GameError::InvalidPing => write!(f, "You can only ping a spot on the map."),
+            GameError::AlreadyPaused => write!(f, "The match is already paused."),
+            GameError::NoPausesLeft => write!(f, "You have no pauses left."),
+            GameError::NotPaused => write!(f, "The match is not paused."),
+            GameError::GamePaused => write!(f, "The match is paused."),
+            GameError::MatchNotOver => write!(f, "The match is still being played."),
+            GameError::RematchUnavailable => write!(f, "Your opponent has left."),
+            GameError::EssenceDisabled => write!(f, "This game mode has no essence."),
+            GameError::WaitingForPlayers => write!(f, "Waiting for both players to get ready."),
+            GameError::MatchOver => write!(f, "The match is already over."),
+        }
+    }
+}
+
+/// Wire payload of `ServerMessage::Error`: the error's code and fields
+/// flattened in, plus the rendered English message.
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ErrorData {
+    #[serde(flatten)]
+    pub error: GameError,
+    pub message: String,
+}
+
+impl From<GameError> for ErrorData {
+    fn from(error: GameError) -> Self {
+        let message = error.to_string();
+        ErrorData { error, message }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_variant_serializes_code_and_message() {
+        let json = serde_json::to_string(&ErrorData::from(GameError::CellOccupied)).unwrap();
+        assert_eq!(
+            json,
+            r#"{"code":"CellOccupied","message":"That square is already occupied."}"#
+        );
+    }
+
+    #[test]
+    fn struct_variant_flattens_fields_next_to_code() {
+        let data = ErrorData::from(GameError::InsufficientGold {
+            needed: 25,
+            have: 10,
+        });
+        let v = serde_json::to_value(&data).unwrap();
+        assert_eq!(v["code"], "InsufficientGold");
+        assert_eq!(v["needed"], 25);
+        assert_eq!(v["have"], 10);
+        assert!(v["message"].as_str().unwrap().contains("25"));
+    }
+
+    #[test]
+    fn wrong_phase_names_the_required_phase() {
+        let v = serde_json::to_value(ErrorData::from(GameError::WrongPhase {
+            required: GamePhase::Build,
+        }))
+        .unwrap();
+        assert_eq!(v["code"], "WrongPhase");
+        assert_eq!(v["required"], "Build");
+    }
+}
@@ -1,8 +1,11 @@
 use bevy_ecs::prelude::{Resource, World};
+use schemars::JsonSchema;
 use serde::Serialize;
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
 use tokio::sync::broadcast;
 
-#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Hash, Resource)]
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Hash, Resource, JsonSchema)]
 pub enum GamePhase {
     Build,
     Combat,
@@ -14,16 +17,28 @@ pub enum GamePhase {
 #[derive(Debug, Resource)]
 pub struct DeltaTime(pub f32);
 
+/// Number of the tick being run, counted from the match's start; paused
+/// ticks are not counted. Stamped on `GoldLedger` entries.
+#[derive(Debug, Default, Clone, Copy, Resource)]
+pub struct GameTick(pub u64);
+
 /// ECS Resource holding the tokio broadcast sender so systems can push network messages.
 #[derive(Resource)]
 pub struct NetworkChannel(pub broadcast::Sender<String>);
 
+/// ECS Resource sharing the lobby's broadcast byte counter, so systems that
+/// send on `NetworkChannel` are counted in the match's metrics too.
+#[derive(Resource, Default)]
+pub struct BroadcastBytes(pub Arc<AtomicU64>);
+
 #[derive(Debug)]
 pub struct GameState {
     pub world: World,
     pub phase: GamePhase,
     pub phase_timer: f32,
     pub wave_number: u32,
+    /// Ticks run so far; mirrored into the world as `GameTick`.
+    pub tick: u64,
 }
 
 impl GameState {
@@ -35,6 +50,7 @@ impl GameState {
             phase: GamePhase::Build,
             phase_timer: 30.0,
             wave_number: 1,
+            tick: 0,
         }
     }
 }
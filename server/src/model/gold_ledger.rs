@@ -0,0 +1,151 @@
+use super::match_stats::{GoldEarned, GoldSpent};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::VecDeque;
+
+/// Transactions a ledger keeps for `recent`; older ones only count in the
+/// totals.
+pub const RECENT_TRANSACTIONS: usize = 50;
+
+/// Why a player's gold changed.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+pub enum GoldReason {
+    /// A worker delivered ore to the cart.
+    Mining,
+    Bounty,
+    WaveReward,
+    Income,
+    PerfectClear,
+    /// A share of banked gold paid at a wave end, if the match has interest.
+    Interest,
+    /// Selling a tower.
+    Refund,
+    LeakPenalty,
+    Tower,
+    HireWorker,
+    WorkerUpgrade,
+    Send,
+    KingUpgrade,
+}
+
+/// One change to a player's gold; `amount` is negative for spending.
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GoldTransaction {
+    pub tick: u64,
+    pub amount: i64,
+    pub reason: GoldReason,
+}
+
+/// Every change to one player's gold, kept on `Player` and fed only by its
+/// gold methods. Holds the last `RECENT_TRANSACTIONS` entries plus running
+/// totals per reason, so it stays small however long the match runs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GoldLedger {
+    opening_balance: u32,
+    /// Sum of every recorded amount.
+    net: i64,
+    totals: Vec<(GoldReason, i64)>,
+    recent: VecDeque<GoldTransaction>,
+}
+
+impl GoldLedger {
+    pub fn new(opening_balance: u32) -> Self {
+        Self {
+            opening_balance,
+            ..Self::default()
+        }
+    }
+
+    pub fn record(&mut self, tick: u64, amount: i64, reason: GoldReason) {
+        if amount == 0 {
+            return;
+        }
+        self.net += amount;
+        match self.totals.iter_mut().find(|(r, _)| *r == reason) {
+            Some((_, total)) => *total += amount,
+            None => self.totals.push((reason, amount)),
+        }
+        if self.recent.len() == RECENT_TRANSACTIONS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(GoldTransaction {
+            tick,
+            amount,
+            reason,
+        });
+    }
+
+    /// Newest last.
+    pub fn recent(&self) -> Vec<GoldTransaction> {
+        self.recent.iter().cloned().collect()
+    }
+
+    /// Gold moved for `reason` over the whole match, as a positive number.
+    pub fn total(&self, reason: GoldReason) -> u32 {
+        self.totals
+            .iter()
+            .find(|(r, _)| *r == reason)
+            .map_or(0, |(_, total)| total.unsigned_abs() as u32)
+    }
+
+    /// The balance the recorded transactions account for.
+    pub fn expected_balance(&self) -> i64 {
+        self.opening_balance as i64 + self.net
+    }
+
+    pub fn earned(&self) -> GoldEarned {
+        GoldEarned {
+            workers: self.total(GoldReason::Mining),
+            bounties: self.total(GoldReason::Bounty),
+            wave_reward: self.total(GoldReason::WaveReward),
+            income: self.total(GoldReason::Income),
+            perfect_clear: self.total(GoldReason::PerfectClear),
+            interest: self.total(GoldReason::Interest),
+            refunds: self.total(GoldReason::Refund),
+        }
+    }
+
+    pub fn spent(&self) -> GoldSpent {
+        GoldSpent {
+            towers: self.total(GoldReason::Tower),
+            workers: self.total(GoldReason::HireWorker),
+            worker_upgrades: self.total(GoldReason::WorkerUpgrade),
+            sends: self.total(GoldReason::Send),
+            king_upgrades: self.total(GoldReason::KingUpgrade),
+            leak_penalties: self.total(GoldReason::LeakPenalty),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn totals_outlive_the_recent_window() {
+        let mut ledger = GoldLedger::new(100);
+        for tick in 0..RECENT_TRANSACTIONS as u64 + 5 {
+            ledger.record(tick, 1, GoldReason::Mining);
+        }
+        ledger.record(60, -25, GoldReason::Tower);
+        ledger.record(61, 0, GoldReason::Bounty);
+
+        let recent = ledger.recent();
+        assert_eq!(recent.len(), RECENT_TRANSACTIONS);
+        assert_eq!(
+            recent.last(),
+            Some(&GoldTransaction {
+                tick: 60,
+                amount: -25,
+                reason: GoldReason::Tower
+            })
+        );
+        assert_eq!(ledger.earned().workers, RECENT_TRANSACTIONS as u32 + 5);
+        assert_eq!(ledger.spent().towers, 25);
+        assert_eq!(ledger.total(GoldReason::Bounty), 0);
+        assert_eq!(
+            ledger.expected_balance(),
+            100 + RECENT_TRANSACTIONS as i64 + 5 - 25
+        );
+    }
+}
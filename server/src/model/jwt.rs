@@ -2,8 +2,6 @@ use chrono::{DateTime, Utc};
 use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
 use serde::{Deserialize, Serialize};
 
-const SECRET_KEY: &[u8] = b"your_secret_key"; // TODO: Replace with secret.
-
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
@@ -25,16 +23,17 @@ pub fn create_jwt(
     username: String,
     session_id: String,
     exp: DateTime<Utc>,
+    secret: &[u8],
 ) -> Result<String, jsonwebtoken::errors::Error> {
     let claims = Claims::new(username, session_id, exp);
     let header = Header::default();
-    encode(&header, &claims, &EncodingKey::from_secret(SECRET_KEY))
+    encode(&header, &claims, &EncodingKey::from_secret(secret))
 }
 
-pub fn decode_jwt(token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
+pub fn decode_jwt(token: &str, secret: &[u8]) -> Result<Claims, jsonwebtoken::errors::Error> {
     decode::<Claims>(
         token,
-        &DecodingKey::from_secret(SECRET_KEY),
+        &DecodingKey::from_secret(secret),
         &Validation::default(),
     )
     .map(|data| data.claims)
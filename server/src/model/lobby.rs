@@ -2,19 +2,37 @@ use super::components::{
     Dead, Enemy, Health, King, Mana, PlayerIdComponent, Position, ShapeComponent, Worker,
     WorkerState,
 };
-use super::game_state::{GameState, NetworkChannel};
+use super::game_state::{BroadcastBytes, GamePhase, GameState, NetworkChannel};
+use super::match_result::{MatchOutcome, MatchResult};
+use super::match_settings::MatchSettings;
+use super::match_stats::{MatchStats, PlayerStats};
 use super::messages::{
     CombatEvent, GameStateDelta, PhaseInfo, SerializableGameState, ServerMessage, Unit,
 };
+use super::pause::{PauseInfo, PauseState};
 use super::player::{Player, Players};
+use super::tick_stats::TickStats;
+use crate::handler::combat::{FlowField, SpatialGrid};
 use bevy_ecs::message::Messages;
 use bevy_ecs::prelude::{Entity, With, Without};
-use std::collections::HashMap;
-use tokio::sync::broadcast;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::{broadcast, watch};
+
+/// A player's chat line or ping, tagged with its sender so each connection
+/// can drop the ones from an opponent its player muted.
+#[derive(Clone, Debug)]
+pub struct PlayerRelay {
+    pub from_id: i64,
+    pub msg: String,
+}
 
 pub struct Lobby {
     pub game_state: GameState,
     pub players: Vec<Player>,
+    /// Starting gold, Build length and opening wave; see `with_settings`.
+    pub settings: MatchSettings,
     pub tx: broadcast::Sender<String>,
     /// Set when a king dies; `Some(id)` = that player won, `None` = draw.
     pub winner_id: Option<i64>,
@@ -27,6 +45,41 @@ pub struct Lobby {
     /// Last-broadcast phase/timer/winner snapshot, used to detect when `phase_info`
     /// must be resent.
     last_phase_info: Option<PhaseInfo>,
+    /// Everyone added via `add_player`; unlike `players`, never shrinks when
+    /// someone leaves, so match history still names the forfeiting player.
+    participant_ids: Vec<i64>,
+    /// Set by `abort`; recorded as `MatchOutcome::Aborted`.
+    aborted: bool,
+    /// Whether `take_result` has already handed out this match's result.
+    result_taken: bool,
+    /// Bytes sent on `tx` so far; shared with the world as `BroadcastBytes`.
+    bytes_broadcast: Arc<AtomicU64>,
+    /// `run_tick` timing, updated by the game loop.
+    pub tick_stats: TickStats,
+    /// Players whose connection has attached to the match.
+    loaded_ids: HashSet<i64>,
+    /// Seconds the first Build timer may still be held for players to load
+    /// and pick a family; `None` once it runs. See `wait_for_players`.
+    setup_time_left: Option<f32>,
+    /// Set by `start` on the first tick the Build timer runs.
+    started: bool,
+    /// Whether relayed chat goes through `chat::filter_profanity`.
+    pub filter_chat: bool,
+    /// Players who muted their opponent; their connections drop the
+    /// opponent's chat and pings.
+    muted_opponent: HashSet<i64>,
+    /// Chat and pings, kept off `tx` so they can carry their sender.
+    relay_tx: broadcast::Sender<PlayerRelay>,
+    /// Pause flag and per-player budgets; see `run_tick`.
+    pub pause: PauseState,
+    /// Post-game rematch votes: player id to whether they asked to swap
+    /// sides.
+    rematch_votes: HashMap<i64, bool>,
+    /// Set once the votes are complete, so only one rematch is created.
+    rematch_started: bool,
+    /// The rematch's match id once it exists; every player's connection
+    /// watches this to move over to it.
+    rematch_tx: watch::Sender<Option<u64>>,
 }
 
 impl Lobby {
@@ -35,23 +88,317 @@ impl Lobby {
         let mut game_state = GameState::new();
         game_state.world.init_resource::<Messages<CombatEvent>>();
         game_state.world.insert_resource(NetworkChannel(tx.clone()));
+        let bytes_broadcast = Arc::new(AtomicU64::new(0));
+        game_state
+            .world
+            .insert_resource(BroadcastBytes(bytes_broadcast.clone()));
         game_state.world.insert_resource(Players::default());
+        game_state.world.insert_resource(MatchStats::default());
+        // Rebuilt every tick; present from the start so systems can rely on them.
+        game_state.world.init_resource::<SpatialGrid>();
+        game_state.world.init_resource::<FlowField>();
         Lobby {
             game_state,
             players: Vec::new(),
+            settings: MatchSettings::default(),
             tx,
             winner_id: None,
             seq: 0,
             broadcast_cache: HashMap::new(),
             last_players: Vec::new(),
             last_phase_info: None,
+            participant_ids: Vec::new(),
+            aborted: false,
+            result_taken: false,
+            bytes_broadcast,
+            tick_stats: TickStats::default(),
+            loaded_ids: HashSet::new(),
+            setup_time_left: None,
+            started: false,
+            filter_chat: true,
+            muted_opponent: HashSet::new(),
+            relay_tx: broadcast::channel(16).0,
+            pause: PauseState::default(),
+            rematch_votes: HashMap::new(),
+            rematch_started: false,
+            rematch_tx: watch::Sender::new(None),
         }
     }
 
+    /// A lobby whose first Build phase and wave follow `settings`. Players
+    /// still bring their own starting gold to `add_player`.
+    pub fn with_settings(settings: MatchSettings) -> Self {
+        let mut lobby = Lobby::new();
+        lobby.game_state.phase_timer = settings.build_time();
+        lobby.game_state.wave_number = settings.starting_wave;
+        lobby.settings = settings;
+        lobby
+    }
+
+    /// Holds the first Build timer (and worker/king spawns) until every
+    /// player has loaded and picked a family, or `timeout` seconds of ticks
+    /// have passed. Without this a lobby starts its timer on the first tick.
+    pub fn wait_for_players(&mut self, timeout: f32) {
+        self.setup_time_left = Some(timeout);
+    }
+
+    /// Called when the player's connection attaches to the match.
+    pub fn mark_loaded(&mut self, player_id: i64) {
+        self.loaded_ids.insert(player_id);
+    }
+
+    pub fn is_waiting_for_players(&self) -> bool {
+        self.setup_time_left.is_some()
+    }
+
+    /// Advances the wait set up by `wait_for_players`; true once the Build
+    /// timer may run.
+    pub fn tick_setup(&mut self, dt: f32) -> bool {
+        let Some(time_left) = self.setup_time_left.as_mut() else {
+            return true;
+        };
+        let everyone_set = self
+            .players
+            .iter()
+            .all(|p| p.family.is_some() && self.loaded_ids.contains(&p.id));
+        *time_left -= dt;
+        if everyone_set || *time_left <= 0.0 {
+            self.setup_time_left = None;
+            return true;
+        }
+        false
+    }
+
+    /// Marks the match as started; true only on the first call, when
+    /// `run_tick` spawns the starter workers and kings.
+    pub fn start(&mut self) -> bool {
+        !std::mem::replace(&mut self.started, true)
+    }
+
+    /// The running pause, if any; a finished match is never shown paused.
+    pub fn pause_info(&self) -> Option<PauseInfo> {
+        if self.is_finished() {
+            return None;
+        }
+        self.pause.info()
+    }
+
     pub fn is_full(&self) -> bool {
         self.players.len() >= 2
     }
 
+    /// Total bytes broadcast to this match's subscribers.
+    pub fn bytes_broadcast(&self) -> u64 {
+        self.bytes_broadcast.load(Ordering::Relaxed)
+    }
+
+    fn send(&self, msg_str: String) {
+        self.bytes_broadcast
+            .fetch_add(msg_str.len() as u64, Ordering::Relaxed);
+        let _ = self.tx.send(msg_str);
+    }
+
+    /// Sends a one-off event to every subscriber of `tx`.
+    pub fn relay(&self, msg: &ServerMessage) {
+        if let Ok(msg) = serde_json::to_string(msg) {
+            self.send(msg);
+        }
+    }
+
+    /// Relays `from_id`'s chat line or ping to every connection in the match.
+    pub fn relay_from(&self, from_id: i64, msg: &ServerMessage) {
+        if let Ok(msg) = serde_json::to_string(msg) {
+            self.bytes_broadcast
+                .fetch_add(msg.len() as u64, Ordering::Relaxed);
+            let _ = self.relay_tx.send(PlayerRelay { from_id, msg });
+        }
+    }
+
+    pub fn subscribe_relays(&self) -> broadcast::Receiver<PlayerRelay> {
+        self.relay_tx.subscribe()
+    }
+
+    pub fn set_opponent_muted(&mut self, player_id: i64, muted: bool) {
+        if muted {
+            self.muted_opponent.insert(player_id);
+        } else {
+            self.muted_opponent.remove(&player_id);
+        }
+    }
+
+    pub fn has_muted_opponent(&self, player_id: i64) -> bool {
+        self.muted_opponent.contains(&player_id)
+    }
+
+    /// Seats a player, prices their sends for the current wave and remembers
+    /// them as a participant for match history.
+    pub fn add_player(&mut self, mut player: Player) {
+        player.refresh_send_costs(self.game_state.wave_number);
+        self.participant_ids.push(player.id);
+        self.game_state
+            .world
+            .resource_mut::<MatchStats>()
+            .player(player.id)
+            .username = player.username.clone();
+        self.players.push(player);
+    }
+
+    /// Copies seated players' gold totals from their ledgers into
+    /// `MatchStats`. Run before a player is unseated and when the match
+    /// ends, so the summary covers everyone who played.
+    pub fn sync_gold_stats(&mut self) {
+        let mut stats = self.game_state.world.resource_mut::<MatchStats>();
+        for player in &self.players {
+            let entry = stats.player(player.id);
+            entry.gold_earned = player.ledger.earned();
+            entry.gold_spent = player.ledger.spent();
+        }
+    }
+
+    /// Updates `player_id`'s stats for the post-game `MatchSummary`.
+    pub fn record_stats(&mut self, player_id: i64, f: impl FnOnce(&mut PlayerStats)) {
+        f(self
+            .game_state
+            .world
+            .resource_mut::<MatchStats>()
+            .player(player_id));
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.game_state.phase,
+            GamePhase::GameOver | GamePhase::Victory
+        )
+    }
+
+    /// Ends an unfinished match with no winner, e.g. when the server shuts
+    /// down before it could be played out. No-op once the match is over.
+    pub fn abort(&mut self) {
+        if self.is_finished() {
+            return;
+        }
+        self.aborted = true;
+        self.game_state.phase = GamePhase::GameOver;
+        self.game_state.world.insert_resource(GamePhase::GameOver);
+        self.winner_id = None;
+        self.broadcast_gamestate();
+    }
+
+    /// Aborts the match on an operator's request and tells its players why.
+    /// Returns false if the match had already ended.
+    pub fn force_end(&mut self, reason: String) -> bool {
+        if self.is_finished() {
+            return false;
+        }
+        self.abort();
+        if let Ok(msg) = serde_json::to_string(&ServerMessage::MatchAborted { reason }) {
+            self.send(msg);
+        }
+        true
+    }
+
+    /// Ends the running match as a loss for `player_id`, who stays seated
+    /// so both players can still ask for a rematch. Returns false if the
+    /// match was already over or `player_id` is not playing in it.
+    pub fn surrender(&mut self, player_id: i64) -> bool {
+        if self.is_finished() || !self.is_full() {
+            return false;
+        }
+        let Some(player) = self.players.iter().find(|p| p.id == player_id) else {
+            return false;
+        };
+        let msg = ServerMessage::Surrendered {
+            player_id,
+            username: player.username.clone(),
+        };
+        self.game_state.phase = GamePhase::GameOver;
+        self.game_state.world.insert_resource(GamePhase::GameOver);
+        self.winner_id = self
+            .players
+            .iter()
+            .find(|p| p.id != player_id)
+            .map(|p| p.id);
+        // Ahead of the final state, so clients know why the game ended.
+        self.relay(&msg);
+        self.broadcast_gamestate();
+        true
+    }
+
+    /// Records a finished match's rematch vote. Once every seated player has
+    /// voted, returns the seating for the new match, swapped if anyone
+    /// asked; this happens only once per lobby.
+    pub fn vote_rematch(&mut self, player_id: i64, swap_sides: bool) -> Option<Vec<(i64, String)>> {
+        self.rematch_votes.insert(player_id, swap_sides);
+        let everyone_voted = self
+            .players
+            .iter()
+            .all(|p| self.rematch_votes.contains_key(&p.id));
+        if self.rematch_started || !self.is_full() || !everyone_voted {
+            return None;
+        }
+        self.rematch_started = true;
+        let mut seating: Vec<(i64, String)> = self
+            .players
+            .iter()
+            .map(|p| (p.id, p.username.clone()))
+            .collect();
+        if self.rematch_votes.values().any(|&swap| swap) {
+            seating.reverse();
+        }
+        Some(seating)
+    }
+
+    /// Sends every watching connection to the rematch created for this
+    /// lobby's vote.
+    pub fn announce_rematch(&self, match_id: u64) {
+        self.rematch_tx.send_replace(Some(match_id));
+    }
+
+    pub fn subscribe_rematch(&self) -> watch::Receiver<Option<u64>> {
+        self.rematch_tx.subscribe()
+    }
+
+    /// The match's result, the first time this is called after it ended;
+    /// `None` while it is still running or once the result was taken. Both
+    /// the game loop and the shutdown drain call this, so each match is
+    /// recorded exactly once whichever gets there first. Also broadcasts
+    /// the `MatchSummary`, so players get it exactly once too.
+    pub fn take_result(&mut self) -> Option<MatchResult> {
+        if !self.is_finished() || self.result_taken {
+            return None;
+        }
+        self.result_taken = true;
+        // Gold that moved outside the ledger means a code path skipped it,
+        // or something else wrote to `gold`.
+        for player in &self.players {
+            let drift = player.gold_discrepancy();
+            if drift != 0 {
+                log::warn!(
+                    "Player {} ends the match with {} gold the ledger does not account for",
+                    player.id,
+                    drift
+                );
+            }
+        }
+        self.sync_gold_stats();
+        let stats = self.game_state.world.resource::<MatchStats>().0.clone();
+        self.relay(&ServerMessage::MatchSummary {
+            winner_id: self.winner_id,
+            players: stats.clone(),
+        });
+        Some(MatchResult {
+            participant_ids: self.participant_ids.clone(),
+            winner_id: self.winner_id,
+            stats,
+            outcome: if self.aborted {
+                MatchOutcome::Aborted
+            } else {
+                MatchOutcome::Finished
+            },
+            ended_at: chrono::Utc::now(),
+        })
+    }
+
     /// Queries the world for every non-dead unit and builds the wire representation.
     /// Extracted so both the full snapshot and the delta path share one query.
     fn snapshot_units(&mut self) -> Vec<Unit> {
@@ -92,7 +439,7 @@ impl Lobby {
                         id: entity.to_bits(),
                         x: pos.x,
                         y: pos.y,
-                        shape: shape.0.clone(),
+                        shape: shape.0,
                         owner_id: maybe_owner.map_or(-1, |owner| owner.0),
                         is_enemy: maybe_enemy.is_some(),
                         current_hp: maybe_health.map_or(100.0, |h| h.current),
@@ -124,6 +471,8 @@ impl Lobby {
             phase_timer: self.game_state.phase_timer,
             winner_id: self.winner_id,
             seq: self.seq,
+            waiting_for_players: self.is_waiting_for_players(),
+            pause: self.pause_info(),
         };
 
         self.broadcast_cache = units
@@ -134,11 +483,7 @@ impl Lobby {
             })
             .collect();
         self.last_players = self.players.clone();
-        self.last_phase_info = Some(PhaseInfo {
-            phase: self.game_state.phase,
-            phase_timer: self.game_state.phase_timer.floor(),
-            winner_id: self.winner_id,
-        });
+        self.last_phase_info = Some(self.phase_info());
 
         serializable_state
     }
@@ -149,8 +494,7 @@ impl Lobby {
     pub fn broadcast_gamestate(&mut self) {
         let serializable_state = self.build_full_state();
         let msg = ServerMessage::GameState(serializable_state);
-        let msg_str = serde_json::to_string(&msg).unwrap();
-        let _ = self.tx.send(msg_str);
+        self.send(serde_json::to_string(&msg).unwrap());
     }
 
     /// Full-state message for ONE client, e.g. lag recovery or a direct
@@ -170,9 +514,26 @@ impl Lobby {
             phase_timer: self.game_state.phase_timer,
             winner_id: self.winner_id,
             seq: self.seq,
+            waiting_for_players: self.is_waiting_for_players(),
+            pause: self.pause_info(),
         })
     }
 
+    /// Phase, timer, winner and pause, with the timers floored to whole
+    /// seconds so sub-second ticks don't count as a change.
+    fn phase_info(&self) -> PhaseInfo {
+        PhaseInfo {
+            phase: self.game_state.phase,
+            phase_timer: self.game_state.phase_timer.floor(),
+            winner_id: self.winner_id,
+            waiting_for_players: self.is_waiting_for_players(),
+            pause: self.pause_info().map(|mut info| {
+                info.seconds_left = info.seconds_left.floor();
+                info
+            }),
+        }
+    }
+
     /// Sends only what changed since the last broadcast (snapshot or delta): added,
     /// updated, and removed units, plus `players`/`phase_info` when those changed.
     /// Sends nothing and does not bump `seq` when there is nothing to report.
@@ -203,11 +564,7 @@ impl Lobby {
 
         let players_changed = self.players != self.last_players;
 
-        let current_phase_info = PhaseInfo {
-            phase: self.game_state.phase,
-            phase_timer: self.game_state.phase_timer.floor(),
-            winner_id: self.winner_id,
-        };
+        let current_phase_info = self.phase_info();
         let phase_info = if self.last_phase_info.as_ref() != Some(&current_phase_info) {
             Some(current_phase_info)
         } else {
@@ -238,10 +595,9 @@ impl Lobby {
         };
 
         let msg = ServerMessage::GameStateDelta(delta);
-        let msg_str = serde_json::to_string(&msg).unwrap();
-        let _ = self.tx.send(msg_str);
+        self.send(serde_json::to_string(&msg).unwrap());
 
-        for unit in added.into_iter().chain(updated.into_iter()) {
+        for unit in added.into_iter().chain(updated) {
             self.broadcast_cache.insert(unit.id, unit);
         }
         for id in &removed {
@@ -283,6 +639,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn lobby_world_has_the_combat_indexes() {
+        let lobby = Lobby::new();
+        let world = &lobby.game_state.world;
+        assert!(world.get_resource::<SpatialGrid>().is_some());
+        assert!(world.get_resource::<FlowField>().is_some());
+    }
+
     #[test]
     fn lobby_world_has_network_channel_resource() {
         let lobby = Lobby::new();
@@ -640,4 +1004,76 @@ mod tests {
             "Revived tower should have full health in the broadcast"
         );
     }
+
+    #[test]
+    fn take_result_is_none_until_finished_then_only_once() {
+        let mut lobby = Lobby::new();
+        lobby.add_player(Player::new(1, "p1".into(), 100));
+        lobby.add_player(Player::new(2, "p2".into(), 100));
+        assert!(lobby.take_result().is_none());
+
+        lobby.game_state.phase = GamePhase::GameOver;
+        lobby.winner_id = Some(2);
+        // The loser already left: history still lists them.
+        lobby.players.retain(|p| p.id != 1);
+        let result = lobby.take_result().expect("finished match yields a result");
+        assert_eq!(result.participant_ids, vec![1, 2]);
+        assert_eq!(result.winner_id, Some(2));
+        assert_eq!(result.outcome, MatchOutcome::Finished);
+        assert!(lobby.take_result().is_none());
+    }
+
+    #[test]
+    fn take_result_broadcasts_the_match_summary_once() {
+        let mut lobby = Lobby::new();
+        let mut rx = lobby.tx.subscribe();
+        lobby.add_player(Player::new(1, "p1".into(), 100));
+        lobby.add_player(Player::new(2, "p2".into(), 100));
+        lobby.record_stats(1, |s| s.sends_made += 2);
+        lobby.game_state.phase = GamePhase::Victory;
+
+        let result = lobby.take_result().unwrap();
+        assert_eq!(result.stats[0].username, "p1");
+        assert_eq!(result.stats[0].sends_made, 2);
+        let msg: serde_json::Value = serde_json::from_str(&rx.try_recv().unwrap()).unwrap();
+        assert_eq!(msg["type"], "MatchSummary");
+        assert_eq!(msg["data"]["players"][1]["username"], "p2");
+        lobby.take_result();
+        assert!(rx.try_recv().is_err(), "the summary is sent only once");
+    }
+
+    #[test]
+    fn abort_ends_running_match_without_winner() {
+        let mut lobby = Lobby::new();
+        lobby.add_player(Player::new(1, "p1".into(), 100));
+        let mut rx = lobby.tx.subscribe();
+        lobby.abort();
+        assert_eq!(lobby.game_state.phase, GamePhase::GameOver);
+        assert!(rx.try_recv().is_ok(), "clients see the final state");
+        let result = lobby.take_result().unwrap();
+        assert_eq!(result.outcome, MatchOutcome::Aborted);
+        assert_eq!(result.winner_id, None);
+    }
+
+    #[test]
+    fn abort_leaves_finished_match_alone() {
+        let mut lobby = Lobby::new();
+        lobby.game_state.phase = GamePhase::Victory;
+        lobby.abort();
+        assert_eq!(lobby.game_state.phase, GamePhase::Victory);
+        assert_eq!(lobby.take_result().unwrap().outcome, MatchOutcome::Finished);
+    }
+
+    #[test]
+    fn force_end_aborts_and_tells_players_why() {
+        let mut lobby = Lobby::new();
+        lobby.add_player(Player::new(1, "p1".into(), 100));
+        let mut rx = lobby.tx.subscribe();
+        assert!(lobby.force_end("stuck match".into()));
+        let _final_state = rx.try_recv().unwrap();
+        let notice = rx.try_recv().unwrap();
+        assert!(notice.contains("MatchAborted") && notice.contains("stuck match"));
+        assert_eq!(lobby.take_result().unwrap().outcome, MatchOutcome::Aborted);
+        assert!(!lobby.force_end("again".into()), "already over");
+    }
 }
@@ -0,0 +1,34 @@
+use super::match_stats::PlayerStats;
+use chrono::{DateTime, Utc};
+
+/// How a match ended, as stored in `matches.outcome`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MatchOutcome {
+    /// Played to `GameOver`/`Victory`, including forfeits.
+    Finished,
+    /// Cut short by a server shutdown before it could finish.
+    Aborted,
+}
+
+impl MatchOutcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MatchOutcome::Finished => "finished",
+            MatchOutcome::Aborted => "aborted",
+        }
+    }
+}
+
+/// One row of match history, produced exactly once per lobby by
+/// `Lobby::take_result`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatchResult {
+    /// Everyone who started the match, including players who later left.
+    pub participant_ids: Vec<i64>,
+    pub winner_id: Option<i64>,
+    /// Everyone's `MatchSummary` line, stored per player in
+    /// `match_players.stats`.
+    pub stats: Vec<PlayerStats>,
+    pub outcome: MatchOutcome,
+    pub ended_at: DateTime<Utc>,
+}
@@ -0,0 +1,160 @@
+use crate::handler::wave::FINAL_WAVE;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+pub const STARTING_GOLD_RANGE: RangeInclusive<u32> = 0..=10_000;
+pub const BUILD_TIME_RANGE: RangeInclusive<u32> = 5..=300;
+pub const INTEREST_PERCENT_RANGE: RangeInclusive<u32> = 0..=25;
+pub const INTEREST_CAP_RANGE: RangeInclusive<u32> = 0..=500;
+
+/// Which economy a match plays with.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub enum GameMode {
+    /// Gold only.
+    #[default]
+    Classic,
+    /// Workers can also mine essence, and the stronger towers cost some.
+    /// See `unit_config::tower_cost`.
+    Essence,
+}
+
+/// Knobs a private lobby's host can turn before the match starts. Queue
+/// matches always use `MatchSettings::default()`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+pub struct MatchSettings {
+    /// Gold each player starts with.
+    pub starting_gold: u32,
+    /// Length of every Build phase, in seconds.
+    pub build_time_secs: u32,
+    /// Wave the match opens on, so later matchups can be played directly.
+    pub starting_wave: u32,
+    #[serde(default)]
+    pub mode: GameMode,
+    /// Percent of banked gold paid as interest at each wave end, before
+    /// that wave's rewards. Interest is off while this or the cap is zero.
+    #[serde(default)]
+    pub interest_percent: u32,
+    /// Most interest paid at one wave end.
+    #[serde(default)]
+    pub interest_cap: u32,
+}
+
+impl Default for MatchSettings {
+    fn default() -> Self {
+        Self {
+            starting_gold: 100,
+            build_time_secs: 30,
+            starting_wave: 1,
+            mode: GameMode::Classic,
+            interest_percent: 0,
+            interest_cap: 0,
+        }
+    }
+}
+
+impl MatchSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !STARTING_GOLD_RANGE.contains(&self.starting_gold) {
+            return Err(format!(
+                "Starting gold must be {} to {}",
+                STARTING_GOLD_RANGE.start(),
+                STARTING_GOLD_RANGE.end()
+            ));
+        }
+        if !BUILD_TIME_RANGE.contains(&self.build_time_secs) {
+            return Err(format!(
+                "Build time must be {} to {} seconds",
+                BUILD_TIME_RANGE.start(),
+                BUILD_TIME_RANGE.end()
+            ));
+        }
+        if !(1..=FINAL_WAVE).contains(&self.starting_wave) {
+            return Err(format!("Starting wave must be 1 to {FINAL_WAVE}"));
+        }
+        if !INTEREST_PERCENT_RANGE.contains(&self.interest_percent) {
+            return Err(format!(
+                "Interest must be {} to {} percent",
+                INTEREST_PERCENT_RANGE.start(),
+                INTEREST_PERCENT_RANGE.end()
+            ));
+        }
+        if !INTEREST_CAP_RANGE.contains(&self.interest_cap) {
+            return Err(format!(
+                "Interest cap must be {} to {}",
+                INTEREST_CAP_RANGE.start(),
+                INTEREST_CAP_RANGE.end()
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn build_time(&self) -> f32 {
+        self.build_time_secs as f32
+    }
+
+    /// Interest owed on `banked` gold at a wave end, rounded down.
+    pub fn interest(&self, banked: u32) -> u32 {
+        (banked.saturating_mul(self.interest_percent) / 100).min(self.interest_cap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_are_valid_and_bounds_are_enforced() {
+        let defaults = MatchSettings::default();
+        assert_eq!(defaults.validate(), Ok(()));
+
+        let rich = MatchSettings {
+            starting_gold: STARTING_GOLD_RANGE.end() + 1,
+            ..defaults
+        };
+        assert!(rich.validate().is_err());
+
+        let rushed = MatchSettings {
+            build_time_secs: 0,
+            ..defaults
+        };
+        assert!(rushed.validate().is_err());
+
+        let last_wave = MatchSettings {
+            starting_wave: FINAL_WAVE,
+            ..defaults
+        };
+        assert_eq!(last_wave.validate(), Ok(()));
+        let past_the_end = MatchSettings {
+            starting_wave: FINAL_WAVE + 1,
+            ..defaults
+        };
+        assert!(past_the_end.validate().is_err());
+
+        let usurious = MatchSettings {
+            interest_percent: INTEREST_PERCENT_RANGE.end() + 1,
+            ..defaults
+        };
+        assert!(usurious.validate().is_err());
+    }
+
+    #[test]
+    fn interest_is_a_capped_share_of_banked_gold() {
+        let settings = MatchSettings {
+            interest_percent: 10,
+            interest_cap: 25,
+            ..MatchSettings::default()
+        };
+        assert_eq!(settings.interest(99), 9);
+        assert_eq!(settings.interest(1000), 25);
+        assert_eq!(MatchSettings::default().interest(1000), 0, "off by default");
+    }
+
+    #[test]
+    fn settings_from_older_clients_default_to_classic_without_interest() {
+        let settings: MatchSettings =
+            serde_json::from_str(r#"{"starting_gold":100,"build_time_secs":30,"starting_wave":1}"#)
+                .unwrap();
+        assert_eq!(settings, MatchSettings::default());
+    }
+}
@@ -0,0 +1,162 @@
+use super::unit_kind::UnitKind;
+use bevy_ecs::prelude::Resource;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// Gold a player earned, by where it came from. Filled in from the
+/// player's `GoldLedger`.
+#[derive(Serialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct GoldEarned {
+    pub workers: u32,
+    pub bounties: u32,
+    pub wave_reward: u32,
+    pub income: u32,
+    pub perfect_clear: u32,
+    pub interest: u32,
+    /// Refunds from selling towers.
+    pub refunds: u32,
+}
+
+/// Gold a player spent, by what it bought.
+#[derive(Serialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct GoldSpent {
+    pub towers: u32,
+    pub workers: u32,
+    pub worker_upgrades: u32,
+    pub sends: u32,
+    pub king_upgrades: u32,
+    pub leak_penalties: u32,
+}
+
+/// Damage one kind of tower dealt, after armor and without overkill.
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TowerDamage {
+    pub kind: UnitKind,
+    pub damage: f32,
+}
+
+/// Creeps that reached a player's king during one wave's combat.
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct WaveLeaks {
+    pub wave: u32,
+    pub leaks: u32,
+}
+
+/// One player's line of the post-game `MatchSummary`.
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PlayerStats {
+    pub player_id: i64,
+    pub username: String,
+    pub damage_by_tower: Vec<TowerDamage>,
+    pub creeps_killed: u32,
+    /// One entry per wave whose combat started, in order.
+    pub leaks_per_wave: Vec<WaveLeaks>,
+    pub gold_earned: GoldEarned,
+    pub gold_spent: GoldSpent,
+    pub sends_made: u32,
+}
+
+impl PlayerStats {
+    pub fn new(player_id: i64, username: String) -> Self {
+        Self {
+            player_id,
+            username,
+            damage_by_tower: Vec::new(),
+            creeps_killed: 0,
+            leaks_per_wave: Vec::new(),
+            gold_earned: GoldEarned::default(),
+            gold_spent: GoldSpent::default(),
+            sends_made: 0,
+        }
+    }
+
+    pub fn add_damage(&mut self, kind: UnitKind, damage: f32) {
+        match self.damage_by_tower.iter_mut().find(|d| d.kind == kind) {
+            Some(entry) => entry.damage += damage,
+            None => self.damage_by_tower.push(TowerDamage { kind, damage }),
+        }
+    }
+
+    /// Counts a leak against the wave currently in combat.
+    pub fn add_leak(&mut self) {
+        if let Some(wave) = self.leaks_per_wave.last_mut() {
+            wave.leaks += 1;
+        }
+    }
+}
+
+/// ECS Resource accumulating every player's `PlayerStats` over the match.
+/// Unlike `Players` it lives only in the world, so entries outlast a
+/// player leaving; the lobby reads it out once in `take_result`.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct MatchStats(pub Vec<PlayerStats>);
+
+impl MatchStats {
+    /// The stats for `player_id`, created empty if they have none yet.
+    pub fn player(&mut self, player_id: i64) -> &mut PlayerStats {
+        let idx = match self.0.iter().position(|s| s.player_id == player_id) {
+            Some(idx) => idx,
+            None => {
+                self.0.push(PlayerStats::new(player_id, String::new()));
+                self.0.len() - 1
+            }
+        };
+        &mut self.0[idx]
+    }
+
+    /// Opens a leak counter for `wave` on each of `player_ids`; called as
+    /// its combat starts.
+    pub fn begin_wave(&mut self, wave: u32, player_ids: impl IntoIterator<Item = i64>) {
+        for player_id in player_ids {
+            self.player(player_id)
+                .leaks_per_wave
+                .push(WaveLeaks { wave, leaks: 0 });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn damage_is_summed_per_tower_kind() {
+        let mut stats = MatchStats::default();
+        stats.player(1).add_damage(UnitKind::Square, 10.0);
+        stats.player(1).add_damage(UnitKind::Circle, 4.0);
+        stats.player(1).add_damage(UnitKind::Square, 2.5);
+        assert_eq!(
+            stats.player(1).damage_by_tower,
+            vec![
+                TowerDamage {
+                    kind: UnitKind::Square,
+                    damage: 12.5
+                },
+                TowerDamage {
+                    kind: UnitKind::Circle,
+                    damage: 4.0
+                },
+            ]
+        );
+        assert!(stats.player(2).damage_by_tower.is_empty());
+    }
+
+    #[test]
+    fn leaks_land_on_the_wave_in_combat() {
+        let mut stats = MatchStats::default();
+        stats.player(1).add_leak();
+        assert!(stats.player(1).leaks_per_wave.is_empty());
+        stats.begin_wave(3, [1]);
+        stats.player(1).add_leak();
+        stats.begin_wave(4, [1]);
+        stats.player(1).add_leak();
+        stats.player(1).add_leak();
+        assert_eq!(
+            stats.player(1).leaks_per_wave,
+            vec![
+                WaveLeaks { wave: 3, leaks: 1 },
+                WaveLeaks { wave: 4, leaks: 2 },
+            ]
+        );
+    }
+}
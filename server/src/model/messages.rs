@@ -1,12 +1,25 @@
-use super::components::{DamageType, Position};
+use super::chat::PingKind;
+use super::components::{DamageType, Mineral, Position};
 use super::family::Family;
+use super::game_error::{ErrorData, GameError};
 use super::game_state::GamePhase;
+use super::gold_ledger::GoldTransaction;
+use super::match_settings::MatchSettings;
+use super::match_stats::PlayerStats;
+use super::pause::PauseInfo;
 use super::player::Player;
+use super::unit_config::WorkerUpgrade;
 use super::unit_kind::UnitKind;
 use bevy_ecs::message::Message;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Clone, Debug, Message)]
+/// Wire protocol version. Bump on any breaking change to `ClientMessage` or
+/// `ServerMessage`, then regenerate `view/protocol.schema.json` with
+/// `cargo run -- --emit-schema`.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Serialize, Clone, Debug, Message, JsonSchema)]
 pub struct CombatEvent {
     pub attacker_id: u64,
     pub target_id: u64,
@@ -15,23 +28,31 @@ pub struct CombatEvent {
     pub end_pos: Position,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct PlaceMessage {
     pub shape: UnitKind,
     pub row: u32,
     pub col: u32,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 #[serde(tag = "action", content = "payload", rename_all = "camelCase")]
 pub enum ClientMessage {
+    /// Must be the first frame on a new socket; see `handler::handshake`.
+    Hello {
+        protocol_version: u32,
+    },
     Place(PlaceMessage),
     SellById {
         entity_id: u64,
     },
     SkipToCombat,
     LeaveLobby,
-    HireWorker {},
+    /// `mineral` picks the vein; `Essence` needs a game mode that has it.
+    HireWorker {
+        #[serde(default)]
+        mineral: Mineral,
+    },
     RequestUnitInfo {
         entity_id: u64,
     },
@@ -39,6 +60,10 @@ pub enum ClientMessage {
         shape: UnitKind,
     },
     UpgradeKing {},
+    /// Buys the next tier of `upgrade` for all of the player's workers.
+    UpgradeWorkers {
+        upgrade: WorkerUpgrade,
+    },
     /// Client detected a seq gap (missed a delta) and asks for a direct resync.
     RequestFullState,
     JoinQueue,
@@ -46,9 +71,105 @@ pub enum ClientMessage {
     PickFamily {
         family: Family,
     },
+    /// Opens a private lobby; the reply carries the join code to share.
+    CreatePrivateLobby,
+    JoinPrivateLobby {
+        code: String,
+    },
+    /// Host only. Changing the settings clears both ready flags.
+    ConfigurePrivateLobby {
+        settings: MatchSettings,
+    },
+    /// The match starts once both members of a full private lobby are ready.
+    SetReady {
+        ready: bool,
+    },
+    LeavePrivateLobby,
+    /// Answers to a `ReadyCheck`.
+    AcceptMatch,
+    DeclineMatch,
+    /// Invites a friend who is online to a match with default settings.
+    ChallengeFriend {
+        account_id: i64,
+    },
+    /// Withdraws the caller's outstanding challenge.
+    CancelChallenge,
+    /// Answers a `ChallengeReceived`.
+    RespondToChallenge {
+        challenge_id: u64,
+        accept: bool,
+    },
+    /// In-match chat line, relayed to everyone watching the match.
+    Chat {
+        text: String,
+    },
+    /// Marks a spot on the map for everyone watching the match.
+    Ping {
+        x: f32,
+        y: f32,
+        kind: PingKind,
+    },
+    /// Hides (or shows again) the opponent's chat and pings for this
+    /// connection.
+    MuteOpponent {
+        muted: bool,
+    },
+    /// Pauses the match on the caller's pause budget.
+    RequestPause,
+    /// Either player may resume.
+    Resume,
+    /// Concedes the running match; the opponent wins.
+    Surrender,
+    /// After the match, asks for another one against the same opponent.
+    /// It starts once both players asked; sides swap if either wants.
+    RequestRematch {
+        swap_sides: bool,
+    },
+    /// The caller's recent gold transactions; answered with `GoldLedger`.
+    RequestGoldLedger,
 }
 
-#[derive(Serialize, Clone, Debug, PartialEq)]
+impl ClientMessage {
+    /// The wire `action` name, used as the rate-limit key.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ClientMessage::Hello { .. } => "hello",
+            ClientMessage::Place(_) => "place",
+            ClientMessage::SellById { .. } => "sellById",
+            ClientMessage::SkipToCombat => "skipToCombat",
+            ClientMessage::LeaveLobby => "leaveLobby",
+            ClientMessage::HireWorker { .. } => "hireWorker",
+            ClientMessage::RequestUnitInfo { .. } => "requestUnitInfo",
+            ClientMessage::SendUnit { .. } => "sendUnit",
+            ClientMessage::UpgradeKing {} => "upgradeKing",
+            ClientMessage::UpgradeWorkers { .. } => "upgradeWorkers",
+            ClientMessage::RequestFullState => "requestFullState",
+            ClientMessage::JoinQueue => "joinQueue",
+            ClientMessage::LeaveQueue => "leaveQueue",
+            ClientMessage::PickFamily { .. } => "pickFamily",
+            ClientMessage::CreatePrivateLobby => "createPrivateLobby",
+            ClientMessage::JoinPrivateLobby { .. } => "joinPrivateLobby",
+            ClientMessage::ConfigurePrivateLobby { .. } => "configurePrivateLobby",
+            ClientMessage::SetReady { .. } => "setReady",
+            ClientMessage::LeavePrivateLobby => "leavePrivateLobby",
+            ClientMessage::AcceptMatch => "acceptMatch",
+            ClientMessage::DeclineMatch => "declineMatch",
+            ClientMessage::ChallengeFriend { .. } => "challengeFriend",
+            ClientMessage::CancelChallenge => "cancelChallenge",
+            ClientMessage::RespondToChallenge { .. } => "respondToChallenge",
+            ClientMessage::Chat { .. } => "chat",
+            ClientMessage::Ping { .. } => "ping",
+            ClientMessage::MuteOpponent { .. } => "muteOpponent",
+            ClientMessage::RequestPause => "requestPause",
+            ClientMessage::Resume => "resume",
+            ClientMessage::Surrender => "surrender",
+            ClientMessage::RequestRematch { .. } => "requestRematch",
+            ClientMessage::RequestGoldLedger => "requestGoldLedger",
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Unit {
     /// Full entity bits (index + generation) so stale IDs never match recycled entities.
     pub id: u64,
@@ -66,7 +187,7 @@ pub struct Unit {
     pub is_king: bool,
 }
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, JsonSchema)]
 pub struct UnitInfoData {
     pub entity_id: u64,
     pub attack_damage: Option<f32>,
@@ -78,7 +199,7 @@ pub struct UnitInfoData {
     pub sell_value: Option<u32>,
 }
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, JsonSchema)]
 pub struct SerializableGameState {
     pub units: Vec<Unit>,
     pub players: Vec<Player>,
@@ -86,22 +207,30 @@ pub struct SerializableGameState {
     pub phase_timer: f32,
     pub winner_id: Option<i64>,
     pub seq: u64,
+    /// The first Build timer is held until both players have loaded and
+    /// picked a family; see `Lobby::wait_for_players`.
+    pub waiting_for_players: bool,
+    /// Set while a player has the match paused.
+    pub pause: Option<PauseInfo>,
 }
 
 /// Snapshot of the fields a client needs to detect a phase/timer/winner change.
 /// `phase_timer` is stored floored to the whole second so sub-second ticks don't
 /// spuriously mark this "changed" (see the diff rule in `Lobby::broadcast_changes`).
-#[derive(Serialize, Clone, Debug, PartialEq)]
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct PhaseInfo {
     pub phase: GamePhase,
     pub phase_timer: f32,
     pub winner_id: Option<i64>,
+    pub waiting_for_players: bool,
+    /// `seconds_left` is floored like `phase_timer`.
+    pub pause: Option<PauseInfo>,
 }
 
 /// Delta-compressed game state broadcast: only units that were added, changed, or
 /// removed since the last broadcast (snapshot or delta). `players`/`phase_info` are
 /// only populated when they actually changed.
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, JsonSchema)]
 pub struct GameStateDelta {
     pub seq: u64,
     pub added: Vec<Unit>,
@@ -116,7 +245,7 @@ pub struct GameStateDelta {
 /// built purely from server data — adding a new sendable unit requires no
 /// client change. See `send_unit_catalog` doc comment for the order
 /// contract with `Player::next_send_costs`.
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, JsonSchema)]
 pub struct SendUnitCatalogEntry {
     pub shape: UnitKind,
     pub name: &'static str,
@@ -129,24 +258,65 @@ pub struct SendUnitCatalogEntry {
 /// family. Sent as `ServerMessage::BuildCatalog` right after a successful
 /// `PickFamily`. The client builds its shop buttons purely from this list —
 /// adding a tower to a family requires no client change.
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, JsonSchema)]
 pub struct BuildCatalogEntry {
     pub unit_kind: UnitKind,
     pub name: &'static str,
     pub cost: u32,
+    /// Zero outside `GameMode::Essence`.
+    pub essence_cost: u32,
+}
+
+/// What both members of a private lobby see; resent on every change.
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PrivateLobbyInfo {
+    pub code: String,
+    pub host: String,
+    pub guest: Option<String>,
+    pub host_ready: bool,
+    pub guest_ready: bool,
+    pub settings: MatchSettings,
 }
 
-#[derive(Serialize, Clone, Debug)]
+/// A challenge between two friends, as both of them see it.
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ChallengeInfo {
+    pub challenge_id: u64,
+    pub from_id: i64,
+    pub from_username: String,
+    pub to_id: i64,
+    pub to_username: String,
+    /// Seconds the challenged friend has to answer.
+    pub timeout_secs: u64,
+}
+
+#[derive(Serialize, Clone, Debug, JsonSchema)]
 #[serde(tag = "type", content = "data")]
 pub enum ServerMessage {
+    /// Handshake accepted; echoes the protocol version both sides speak.
+    Welcome {
+        protocol_version: u32,
+    },
     GameState(SerializableGameState),
     GameStateDelta(GameStateDelta),
     CombatEvents(Vec<CombatEvent>),
     PlayerId(i64),
-    Error(String),
+    /// A rejected action: stable `code` plus a human `message`.
+    Error(ErrorData),
     UnitInfo(UnitInfoData),
     /// Ack: the player is in the matchmaking queue ("searching…").
     Queued,
+    /// An opponent was found; reply `AcceptMatch` or `DeclineMatch` within
+    /// `timeout_secs`.
+    ReadyCheck {
+        timeout_secs: u64,
+    },
+    /// The ready check failed. `requeued`: the opponent missed it and this
+    /// player is searching again (`Queued` follows); otherwise this player
+    /// missed it and is kept out of the queue for a while.
+    ReadyCheckFailed {
+        requeued: bool,
+    },
     /// A match was created; the client should proceed to the game screen.
     MatchFound,
     /// Server-driven mercenary send catalog, sent once right after
@@ -157,6 +327,106 @@ pub enum ServerMessage {
     /// Server-driven build catalog for the picking player's chosen family,
     /// sent once in reply to a successful `PickFamily`.
     BuildCatalog(Vec<BuildCatalogEntry>),
+    /// The server is restarting. Running matches may continue for up to
+    /// `seconds_remaining`; after that they are ended as aborted.
+    ServerShutdown {
+        seconds_remaining: u64,
+    },
+    /// An operator ended the match early, or it hit a server error; it is
+    /// recorded as aborted with no winner.
+    MatchAborted {
+        reason: String,
+    },
+    /// The private lobby the player is in, sent on creating or joining it
+    /// and whenever its members, settings or ready flags change.
+    PrivateLobby(PrivateLobbyInfo),
+    /// Ack of `ChallengeFriend`: waiting for the friend to answer.
+    ChallengeSent(ChallengeInfo),
+    /// A friend challenged this player; reply `RespondToChallenge`.
+    ChallengeReceived(ChallengeInfo),
+    /// The challenger cancelled or the challenge timed out.
+    ChallengeWithdrawn {
+        challenge_id: u64,
+    },
+    /// A chat line from a player in the match, already filtered. Also
+    /// echoed to its sender.
+    Chat {
+        from_id: i64,
+        username: String,
+        text: String,
+    },
+    /// A map ping from a player in the match.
+    Ping {
+        from_id: i64,
+        x: f32,
+        y: f32,
+        kind: PingKind,
+    },
+    /// Ack of `MuteOpponent`.
+    OpponentMuted {
+        muted: bool,
+    },
+    /// Sent just before the final game state of a match ended by
+    /// `Surrender`.
+    Surrendered {
+        player_id: i64,
+        username: String,
+    },
+    /// A player asked for a rematch.
+    RematchRequested {
+        player_id: i64,
+        swap_sides: bool,
+    },
+    /// Both players asked for a rematch; `MatchFound` for the new match
+    /// follows.
+    RematchStarted {
+        match_id: u64,
+    },
+    /// Per-player stats for the whole match, sent once when it ends.
+    MatchSummary {
+        winner_id: Option<i64>,
+        players: Vec<PlayerStats>,
+    },
+    /// Reply to `RequestGoldLedger`: the last gold transactions, oldest
+    /// first, and the balance they lead to.
+    GoldLedger {
+        gold: u32,
+        transactions: Vec<GoldTransaction>,
+    },
+}
+
+/// Machine-readable description of both message directions, committed as
+/// `view/protocol.schema.json` so the client can be checked against it.
+pub fn protocol_schema() -> serde_json::Value {
+    serde_json::json!({
+        "protocol_version": PROTOCOL_VERSION,
+        "client_message": schemars::schema_for!(ClientMessage),
+        "server_message": schemars::schema_for!(ServerMessage),
+    })
+}
+
+/// Every wire `action` name a client may send, read from the schema so it
+/// cannot drift from `ClientMessage`.
+pub fn client_message_kinds() -> Vec<String> {
+    let schema = serde_json::to_value(schemars::schema_for!(ClientMessage)).unwrap_or_default();
+    schema["oneOf"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|variant| {
+            let action = &variant["properties"]["action"];
+            action["const"]
+                .as_str()
+                .or_else(|| action["enum"][0].as_str())
+                .map(str::to_string)
+        })
+        .collect()
+}
+
+impl From<GameError> for ServerMessage {
+    fn from(error: GameError) -> Self {
+        ServerMessage::Error(error.into())
+    }
 }
 
 #[cfg(test)]
@@ -167,10 +437,19 @@ mod tests {
     fn deserialize_upgrade_king() {
         let json = r#"{"action": "upgradeKing", "payload": {}}"#;
         let msg: ClientMessage = serde_json::from_str(json).unwrap();
-        match msg {
-            ClientMessage::UpgradeKing {} => assert!(true),
-            _ => panic!("Wrong message type"),
-        }
+        assert!(matches!(msg, ClientMessage::UpgradeKing {}));
+    }
+
+    #[test]
+    fn deserialize_upgrade_workers() {
+        let json = r#"{"action": "upgradeWorkers", "payload": {"upgrade": "MiningTime"}}"#;
+        let msg: ClientMessage = serde_json::from_str(json).unwrap();
+        assert!(matches!(
+            msg,
+            ClientMessage::UpgradeWorkers {
+                upgrade: WorkerUpgrade::MiningTime
+            }
+        ));
     }
 
     #[test]
@@ -183,6 +462,8 @@ mod tests {
             phase_timer: 0.0,
             winner_id: Some(42),
             seq: 1,
+            waiting_for_players: false,
+            pause: None,
         };
         let json = serde_json::to_string(&state).unwrap();
         assert!(json.contains("\"winner_id\":42"));
@@ -202,20 +483,27 @@ mod tests {
     fn deserialize_hire_worker() {
         let json = r#"{"action": "hireWorker", "payload": {}}"#;
         let msg: ClientMessage = serde_json::from_str(json).unwrap();
-        match msg {
-            ClientMessage::HireWorker {} => assert!(true),
-            _ => panic!("Wrong message type"),
-        }
+        assert!(matches!(
+            msg,
+            ClientMessage::HireWorker {
+                mineral: Mineral::Gold
+            }
+        ));
+        let json = r#"{"action": "hireWorker", "payload": {"mineral": "Essence"}}"#;
+        let msg: ClientMessage = serde_json::from_str(json).unwrap();
+        assert!(matches!(
+            msg,
+            ClientMessage::HireWorker {
+                mineral: Mineral::Essence
+            }
+        ));
     }
 
     #[test]
     fn deserialize_request_full_state() {
         let json = r#"{"action":"requestFullState"}"#;
         let msg: ClientMessage = serde_json::from_str(json).unwrap();
-        match msg {
-            ClientMessage::RequestFullState => assert!(true),
-            _ => panic!("Wrong message type"),
-        }
+        assert!(matches!(msg, ClientMessage::RequestFullState));
     }
 
     #[test]
@@ -362,12 +650,14 @@ mod tests {
             unit_kind: UnitKind::Square,
             name: "Square",
             cost: 25,
+            essence_cost: 0,
         }]);
         let json = serde_json::to_string(&msg).unwrap();
         assert!(json.starts_with(r#"{"type":"BuildCatalog","data":["#));
         assert!(json.contains(r#""unit_kind":"Square""#));
         assert!(json.contains(r#""name":"Square""#));
         assert!(json.contains(r#""cost":25"#));
+        assert!(json.contains(r#""essence_cost":0"#));
     }
 
     #[test]
@@ -387,4 +677,101 @@ mod tests {
         assert!(json.contains(r#""income":1"#));
         assert!(json.contains(r#""bounty":6"#));
     }
+
+    #[test]
+    fn serialize_error_carries_code_and_message() {
+        let msg: ServerMessage = GameError::WorkerCap { max: 7 }.into();
+        let json = serde_json::to_string(&msg).unwrap();
+        assert_eq!(
+            json,
+            r#"{"type":"Error","data":{"code":"WorkerCap","max":7,"message":"Worker limit reached (max 7)."}}"#
+        );
+    }
+
+    #[test]
+    fn deserialize_hello() {
+        let json = r#"{"action":"hello","payload":{"protocol_version":1}}"#;
+        let msg: ClientMessage = serde_json::from_str(json).unwrap();
+        assert!(matches!(
+            msg,
+            ClientMessage::Hello {
+                protocol_version: 1
+            }
+        ));
+    }
+
+    #[test]
+    fn serialize_welcome() {
+        let msg = ServerMessage::Welcome {
+            protocol_version: PROTOCOL_VERSION,
+        };
+        assert_eq!(
+            serde_json::to_string(&msg).unwrap(),
+            format!(r#"{{"type":"Welcome","data":{{"protocol_version":{PROTOCOL_VERSION}}}}}"#)
+        );
+    }
+
+    #[test]
+    fn committed_protocol_schema_is_up_to_date() {
+        let committed: serde_json::Value =
+            serde_json::from_str(include_str!("../../../view/protocol.schema.json")).unwrap();
+        assert_eq!(
+            committed,
+            protocol_schema(),
+            "view/protocol.schema.json is stale: run `cargo run -- --emit-schema > ../view/protocol.schema.json`"
+        );
+    }
+
+    #[test]
+    fn deserialize_private_lobby_messages() {
+        let json = r#"{"action":"joinPrivateLobby","payload":{"code":"ABC234"}}"#;
+        let msg: ClientMessage = serde_json::from_str(json).unwrap();
+        assert!(matches!(msg, ClientMessage::JoinPrivateLobby { code } if code == "ABC234"));
+
+        let json = r#"{"action":"configurePrivateLobby","payload":{"settings":{"starting_gold":500,"build_time_secs":10,"starting_wave":6}}}"#;
+        let msg: ClientMessage = serde_json::from_str(json).unwrap();
+        let ClientMessage::ConfigurePrivateLobby { settings } = msg else {
+            panic!("Wrong message type");
+        };
+        assert_eq!(settings.starting_wave, 6);
+    }
+
+    #[test]
+    fn deserialize_respond_to_challenge() {
+        let json = r#"{"action":"respondToChallenge","payload":{"challenge_id":7,"accept":true}}"#;
+        let msg: ClientMessage = serde_json::from_str(json).unwrap();
+        assert!(matches!(
+            msg,
+            ClientMessage::RespondToChallenge {
+                challenge_id: 7,
+                accept: true
+            }
+        ));
+    }
+
+    #[test]
+    fn client_message_kinds_lists_every_action() {
+        let kinds = client_message_kinds();
+        assert!(kinds.contains(&"chat".to_string()));
+        assert!(kinds.contains(&"requestFullState".to_string()));
+        assert!(
+            kinds.contains(
+                &ClientMessage::HireWorker {
+                    mineral: Mineral::Gold
+                }
+                .kind()
+                .to_string()
+            )
+        );
+        assert!(!kinds.contains(&"Chat".to_string()));
+    }
+
+    #[test]
+    fn kind_matches_wire_action_name() {
+        let msg = ClientMessage::SendUnit {
+            shape: UnitKind::Square,
+        };
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(json["action"], msg.kind());
+    }
 }
@@ -1,12 +1,22 @@
 pub mod account;
+pub mod chat;
 pub mod components;
 pub mod constants;
 pub mod family;
+pub mod friend;
+pub mod game_error;
 pub mod game_state;
+pub mod gold_ledger;
 pub mod jwt;
 pub mod king_config;
 pub mod lobby;
+pub mod match_result;
+pub mod match_settings;
+pub mod match_stats;
 pub mod messages;
+pub mod pause;
 pub mod player;
+pub mod session;
+pub mod tick_stats;
 pub mod unit_kind;
 pub mod unit_config;
@@ -0,0 +1,122 @@
+use super::game_error::GameError;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Pauses each player may call per match.
+pub const PAUSES_PER_PLAYER: u32 = 3;
+/// Seconds all of a player's pauses may last together.
+pub const PAUSE_SECONDS_PER_PLAYER: f32 = 90.0;
+
+/// What a player has left to pause with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PauseBudget {
+    pub pauses_left: u32,
+    pub seconds_left: f32,
+}
+
+impl Default for PauseBudget {
+    fn default() -> Self {
+        Self {
+            pauses_left: PAUSES_PER_PLAYER,
+            seconds_left: PAUSE_SECONDS_PER_PLAYER,
+        }
+    }
+}
+
+/// Wire view of a running pause. `seconds_left` is the pauser's remaining
+/// budget; the match resumes by itself when it reaches zero.
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PauseInfo {
+    pub paused_by: i64,
+    pub seconds_left: f32,
+}
+
+/// Whether the match is paused and every player's budget. Lives on the
+/// `Lobby`; `run_tick` skips the simulation and phase timer while paused.
+#[derive(Debug, Default)]
+pub struct PauseState {
+    paused_by: Option<i64>,
+    budgets: HashMap<i64, PauseBudget>,
+}
+
+impl PauseState {
+    pub fn is_paused(&self) -> bool {
+        self.paused_by.is_some()
+    }
+
+    pub fn budget(&self, player_id: i64) -> PauseBudget {
+        self.budgets.get(&player_id).copied().unwrap_or_default()
+    }
+
+    /// Starts a pause on `player_id`'s budget.
+    pub fn pause(&mut self, player_id: i64) -> Result<(), GameError> {
+        if self.is_paused() {
+            return Err(GameError::AlreadyPaused);
+        }
+        let budget = self.budgets.entry(player_id).or_default();
+        if budget.pauses_left == 0 || budget.seconds_left <= 0.0 {
+            return Err(GameError::NoPausesLeft);
+        }
+        budget.pauses_left -= 1;
+        self.paused_by = Some(player_id);
+        Ok(())
+    }
+
+    /// Ends the pause. Returns false if the match was not paused.
+    pub fn resume(&mut self) -> bool {
+        self.paused_by.take().is_some()
+    }
+
+    /// Charges one tick to the pauser's budget, resuming once it is spent.
+    /// Returns whether the match is still paused for this tick.
+    pub fn tick(&mut self, dt: f32) -> bool {
+        let Some(player_id) = self.paused_by else {
+            return false;
+        };
+        let budget = self.budgets.entry(player_id).or_default();
+        budget.seconds_left = (budget.seconds_left - dt).max(0.0);
+        if budget.seconds_left <= 0.0 {
+            self.paused_by = None;
+            return false;
+        }
+        true
+    }
+
+    pub fn info(&self) -> Option<PauseInfo> {
+        self.paused_by.map(|paused_by| PauseInfo {
+            paused_by,
+            seconds_left: self.budget(paused_by).seconds_left,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pauses_are_limited_by_count() {
+        let mut state = PauseState::default();
+        for _ in 0..PAUSES_PER_PLAYER {
+            assert_eq!(state.pause(1), Ok(()));
+            assert_eq!(state.pause(2), Err(GameError::AlreadyPaused));
+            assert!(state.resume());
+        }
+        assert_eq!(state.pause(1), Err(GameError::NoPausesLeft));
+        assert!(!state.resume());
+        assert_eq!(state.pause(2), Ok(()), "budgets are per player");
+    }
+
+    #[test]
+    fn a_pause_ends_when_its_seconds_run_out() {
+        let mut state = PauseState::default();
+        state.pause(1).unwrap();
+        assert!(state.tick(PAUSE_SECONDS_PER_PLAYER - 1.0));
+        assert_eq!(state.info().unwrap().seconds_left, 1.0);
+        assert!(!state.tick(1.0));
+        assert!(!state.is_paused());
+        assert_eq!(state.budget(1).pauses_left, PAUSES_PER_PLAYER - 1);
+        assert_eq!(state.pause(1), Err(GameError::NoPausesLeft));
+    }
+}
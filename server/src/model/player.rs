@@ -1,24 +1,39 @@
 use bevy_ecs::prelude::Resource;
+use schemars::JsonSchema;
 use serde::Serialize;
 
 use crate::model::family::Family;
+use crate::model::game_error::GameError;
+use crate::model::gold_ledger::{GoldLedger, GoldReason};
+use crate::model::unit_config::{Cost, WorkerTiers, worker_hire_cost};
 use crate::model::unit_kind::UnitKind;
 
 /// ECS Resource wrapping the lobby's player list so systems can read and award gold.
 #[derive(Resource, Clone, Debug, Default)]
 pub struct Players(pub Vec<Player>);
 
-#[derive(Clone, Debug, Serialize, PartialEq)]
+#[derive(Clone, Debug, Serialize, PartialEq, JsonSchema)]
 pub struct Player {
     pub id: i64,
     pub username: String,
-    pub gold: u32,
+    // Private so every change goes through the gold methods and the ledger.
+    gold: u32,
+    /// Second resource, mined by essence workers; only used in
+    /// `GameMode::Essence`.
+    essence: u32,
     /// Permanent income awarded to this player at the end of each combat round.
     pub income: u32,
     /// Units queued to be sent to the opponent's board on the next combat phase.
     pub spawning_queue: Vec<UnitKind>,
     /// Current king upgrade tier (0 = base, max 4).
     pub king_tier: u32,
+    /// Worker upgrade tiers bought so far; they apply to every worker.
+    pub worker_tiers: WorkerTiers,
+    /// Workers bought with `HireWorker`; starter workers are not counted.
+    #[serde(skip)]
+    pub workers_hired: u32,
+    /// Price of the next `HireWorker`, which rises with each worker hired.
+    pub next_worker_cost: u32,
     /// Sends of each shape this wave (Square/Triangle/Circle); resets each wave.
     pub sends_this_wave: [u32; 3],
     /// Price of the NEXT send of each shape — server-computed so the client
@@ -30,6 +45,10 @@ pub struct Player {
     pub leaks_this_wave: u32,
     /// Family locked in for this match on first `PickFamily`; `None` until picked.
     pub family: Option<Family>,
+    /// Every change to `gold`; private to the owner, who asks for it with
+    /// `RequestGoldLedger`.
+    #[serde(skip)]
+    pub ledger: GoldLedger,
 }
 
 impl Player {
@@ -38,33 +57,88 @@ impl Player {
             id,
             username,
             gold,
+            essence: 0,
             income: 0,
             spawning_queue: Vec::new(),
             king_tier: 0,
+            worker_tiers: WorkerTiers::default(),
+            workers_hired: 0,
+            next_worker_cost: worker_hire_cost(0),
             sends_this_wave: [0; 3],
             next_send_costs: [0; 3],
             leaks_this_wave: 0,
             family: None,
+            ledger: GoldLedger::new(gold),
         };
         player.refresh_send_costs(1);
         player
     }
 
+    pub fn gold(&self) -> u32 {
+        self.gold
+    }
+
+    #[cfg(test)]
+    pub fn essence(&self) -> u32 {
+        self.essence
+    }
+
     pub fn can_afford(&self, amount: u32) -> bool {
         self.gold >= amount
     }
 
-    pub fn try_spend_gold(&mut self, amount: u32) -> bool {
+    // Gold only changes through these three, so `ledger` sees every change.
+
+    pub fn try_spend_gold(&mut self, amount: u32, reason: GoldReason, tick: u64) -> bool {
         if self.can_afford(amount) {
             self.gold -= amount;
+            self.ledger.record(tick, -(amount as i64), reason);
             true
         } else {
             false
         }
     }
 
-    pub fn add_gold(&mut self, amount: u32) {
+    pub fn add_gold(&mut self, amount: u32, reason: GoldReason, tick: u64) {
         self.gold += amount;
+        self.ledger.record(tick, amount as i64, reason);
+    }
+
+    /// Takes up to `amount`, stopping at zero gold. Returns what was taken.
+    pub fn charge_gold(&mut self, amount: u32, reason: GoldReason, tick: u64) -> u32 {
+        let taken = amount.min(self.gold);
+        self.gold -= taken;
+        self.ledger.record(tick, -(taken as i64), reason);
+        taken
+    }
+
+    /// Pays a gold-and-essence `cost`, or nothing if either falls short.
+    pub fn try_pay(&mut self, cost: Cost, reason: GoldReason, tick: u64) -> Result<(), GameError> {
+        if !self.can_afford(cost.gold) {
+            return Err(GameError::InsufficientGold {
+                needed: cost.gold,
+                have: self.gold,
+            });
+        }
+        if self.essence < cost.essence {
+            return Err(GameError::InsufficientEssence {
+                needed: cost.essence,
+                have: self.essence,
+            });
+        }
+        self.try_spend_gold(cost.gold, reason, tick);
+        self.essence -= cost.essence;
+        Ok(())
+    }
+
+    pub fn add_essence(&mut self, amount: u32) {
+        self.essence += amount;
+    }
+
+    /// How far `gold` has drifted from what the ledger accounts for; zero
+    /// unless something changed it without going through the ledger.
+    pub fn gold_discrepancy(&self) -> i64 {
+        self.gold as i64 - self.ledger.expected_balance()
     }
 
     /// Recomputes `next_send_costs` from the current wave and counters.
@@ -131,16 +205,51 @@ mod tests {
         let mut player = Player::new(1, "test".to_string(), 100);
 
         // Success case
-        assert!(player.try_spend_gold(40));
+        assert!(player.try_spend_gold(40, GoldReason::Tower, 1));
         assert_eq!(player.gold, 60);
 
         // Success case (boundary)
-        assert!(player.try_spend_gold(60));
+        assert!(player.try_spend_gold(60, GoldReason::Tower, 2));
         assert_eq!(player.gold, 0);
 
         // Failure case
-        assert!(!player.try_spend_gold(1));
+        assert!(!player.try_spend_gold(1, GoldReason::Tower, 3));
+        assert_eq!(player.gold, 0);
+    }
+
+    #[test]
+    fn gold_changes_are_recorded_in_the_ledger() {
+        let mut player = Player::new(1, "test".to_string(), 10);
+        player.add_gold(5, GoldReason::Bounty, 7);
+        assert_eq!(player.charge_gold(20, GoldReason::LeakPenalty, 8), 15);
         assert_eq!(player.gold, 0);
+        assert_eq!(player.ledger.recent().len(), 2);
+        assert_eq!(player.ledger.spent().leak_penalties, 15);
+        assert_eq!(player.gold_discrepancy(), 0);
+
+        player.gold = 40; // bypasses the ledger
+        assert_eq!(player.gold_discrepancy(), 40);
+    }
+
+    #[test]
+    fn try_pay_needs_both_resources() {
+        let mut player = Player::new(1, "test".to_string(), 50);
+        player.add_essence(5);
+        let cost = Cost {
+            gold: 40,
+            essence: 10,
+        };
+        assert_eq!(
+            player.try_pay(cost, GoldReason::Tower, 1),
+            Err(GameError::InsufficientEssence {
+                needed: 10,
+                have: 5
+            })
+        );
+        assert_eq!((player.gold, player.essence), (50, 5), "nothing is taken");
+        player.add_essence(7);
+        assert_eq!(player.try_pay(cost, GoldReason::Tower, 2), Ok(()));
+        assert_eq!((player.gold, player.essence), (10, 2));
     }
 
     // --- Task 1 TDD tests ---
@@ -0,0 +1,79 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// One signed-in device. Access tokens carry `id` as their `sid` claim and
+/// stop working as soon as the row is gone.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, FromRow)]
+pub struct Session {
+    pub id: String,
+    pub account_id: i64,
+    #[serde(skip)]
+    pub refresh_token_hash: String,
+    /// Client-supplied label (the login's `User-Agent`), for the sessions list.
+    pub device: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub last_used_at: chrono::NaiveDateTime,
+    /// When the refresh token lapses; pushed back on every refresh.
+    pub expires_at: chrono::NaiveDateTime,
+}
+
+/// A refresh token handed to a client: `<session id>.<secret>`. Only the
+/// secret's hash is stored, so a leaked database cannot mint tokens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefreshToken {
+    pub session_id: String,
+    pub secret: String,
+}
+
+impl RefreshToken {
+    /// A fresh secret for `session_id`.
+    pub fn generate(session_id: &str) -> Self {
+        Self {
+            session_id: session_id.to_string(),
+            secret: format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple()),
+        }
+    }
+
+    pub fn parse(token: &str) -> Option<Self> {
+        let (session_id, secret) = token.split_once('.')?;
+        if session_id.is_empty() || secret.is_empty() {
+            return None;
+        }
+        Some(Self {
+            session_id: session_id.to_string(),
+            secret: secret.to_string(),
+        })
+    }
+
+    pub fn hash(&self) -> String {
+        format!("{:x}", Sha256::digest(self.secret.as_bytes()))
+    }
+
+    pub fn encode(&self) -> String {
+        format!("{}.{}", self.session_id, self.secret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refresh_tokens_round_trip_and_hash_stably() {
+        let token = RefreshToken::generate("sid-1");
+        let parsed = RefreshToken::parse(&token.encode()).unwrap();
+        assert_eq!(parsed, token);
+        assert_eq!(parsed.hash(), token.hash());
+        assert_eq!(token.hash().len(), 64);
+        assert_ne!(RefreshToken::generate("sid-1").hash(), token.hash());
+    }
+
+    #[test]
+    fn malformed_refresh_tokens_are_rejected() {
+        for bad in ["", "no-dot", ".secret", "sid."] {
+            assert_eq!(RefreshToken::parse(bad), None, "{bad:?}");
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// How many recent ticks the overrun check looks at (3s at 30Hz).
+const WINDOW: usize = 90;
+/// A lobby is "consistently" over budget once this share of the window
+/// overran.
+const OVERRUN_WARN_RATIO: f32 = 0.25;
+
+/// Emitted by `TickStats::record` when a lobby keeps blowing its budget.
+#[derive(Debug, PartialEq)]
+pub struct OverrunReport {
+    pub overruns_in_window: usize,
+    pub window: usize,
+    pub mean: Duration,
+    pub max: Duration,
+}
+
+/// Per-match timing of `run_tick`, kept on the `Lobby` so `/metrics` can
+/// report it alongside the match.
+#[derive(Debug, Default)]
+pub struct TickStats {
+    recent: VecDeque<(Duration, bool)>,
+    pub ticks: u64,
+    /// Ticks whose `run_tick` took longer than the tick period.
+    pub overruns: u64,
+    /// Ticks the loop never ran: skipped under `MissedTickPolicy::Skip`, or
+    /// dropped past the catch-up cap.
+    pub missed_ticks: u64,
+    pub max: Duration,
+    /// `ticks` at the last report; reports are at most one per window.
+    last_report_at: Option<u64>,
+}
+
+impl TickStats {
+    /// Records one tick and returns a report when at least
+    /// `OVERRUN_WARN_RATIO` of the last `WINDOW` ticks were over `budget`.
+    pub fn record(&mut self, elapsed: Duration, budget: Duration) -> Option<OverrunReport> {
+        let over = elapsed > budget;
+        self.ticks += 1;
+        self.overruns += u64::from(over);
+        self.max = self.max.max(elapsed);
+        if self.recent.len() == WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((elapsed, over));
+
+        if self.recent.len() < WINDOW
+            || self
+                .last_report_at
+                .is_some_and(|at| self.ticks - at < WINDOW as u64)
+        {
+            return None;
+        }
+        let overruns_in_window = self.recent.iter().filter(|(_, over)| *over).count();
+        if (overruns_in_window as f32) < OVERRUN_WARN_RATIO * WINDOW as f32 {
+            return None;
+        }
+        self.last_report_at = Some(self.ticks);
+        Some(OverrunReport {
+            overruns_in_window,
+            window: WINDOW,
+            mean: self.mean(),
+            max: self
+                .recent
+                .iter()
+                .map(|(d, _)| *d)
+                .max()
+                .unwrap_or_default(),
+        })
+    }
+
+    pub fn record_missed(&mut self, ticks: u64) {
+        self.missed_ticks += ticks;
+    }
+
+    /// Mean `run_tick` time over the recent window.
+    pub fn mean(&self) -> Duration {
+        if self.recent.is_empty() {
+            return Duration::ZERO;
+        }
+        self.recent.iter().map(|(d, _)| *d).sum::<Duration>() / self.recent.len() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUDGET: Duration = Duration::from_millis(33);
+
+    #[test]
+    fn occasional_overruns_do_not_report() {
+        let mut stats = TickStats::default();
+        for i in 0..WINDOW * 2 {
+            let elapsed = if i % 10 == 0 {
+                Duration::from_millis(50)
+            } else {
+                Duration::from_millis(5)
+            };
+            assert_eq!(stats.record(elapsed, BUDGET), None);
+        }
+        assert_eq!(stats.overruns, (WINDOW * 2 / 10) as u64);
+        assert_eq!(stats.max, Duration::from_millis(50));
+    }
+
+    #[test]
+    fn sustained_overruns_report_once_per_window() {
+        let mut stats = TickStats::default();
+        let reports: Vec<_> = (0..WINDOW * 2)
+            .filter_map(|_| stats.record(Duration::from_millis(40), BUDGET))
+            .collect();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].overruns_in_window, WINDOW);
+        assert_eq!(reports[0].mean, Duration::from_millis(40));
+    }
+}
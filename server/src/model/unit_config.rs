@@ -1,6 +1,9 @@
 use super::components::{AttackProfile, CombatProfile, DamageType, Mana};
 use super::family::Family;
+use super::match_settings::GameMode;
 use super::unit_kind::UnitKind;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
 // --- Balance Constants ---
 pub const DEFAULT_COLLISION_RADIUS: f32 = 20.0;
@@ -95,6 +98,41 @@ pub fn get_unit_profile(shape: UnitKind) -> UnitProfile {
     }
 }
 
+/// Fraction of a tower's cost returned when it is sold.
+pub const SELL_REFUND_RATE: f32 = 0.75;
+
+/// What building something costs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Cost {
+    pub gold: u32,
+    pub essence: u32,
+}
+
+/// A tower's price in `mode`. Essence mode keeps the gold prices and adds
+/// essence to the two stronger towers.
+pub fn tower_cost(shape: UnitKind, mode: GameMode) -> Cost {
+    let essence = match mode {
+        GameMode::Classic => 0,
+        GameMode::Essence => match shape {
+            UnitKind::Square => 0,
+            UnitKind::Triangle => 10,
+            UnitKind::Circle => 25,
+        },
+    };
+    Cost {
+        gold: get_unit_profile(shape).gold_cost,
+        essence,
+    }
+}
+
+/// What selling a tower bought for `cost` gives back, rounded down.
+pub fn sell_refund(cost: Cost) -> Cost {
+    Cost {
+        gold: (cost.gold as f32 * SELL_REFUND_RATE) as u32,
+        essence: (cost.essence as f32 * SELL_REFUND_RATE) as u32,
+    }
+}
+
 // --- Sent Unit Balance Constants ---
 
 /// Gold cost to send each unit type.
@@ -235,6 +273,93 @@ pub fn family_catalog_options() -> Vec<Family> {
     vec![Family::Basic]
 }
 
+// --- Worker Balance Constants ---
+
+/// Most workers one player may own, starter workers included.
+pub const WORKER_CAP: usize = 7;
+
+/// Free workers each player gets when the match starts. They do not count
+/// towards `worker_hire_cost`.
+pub const STARTER_WORKERS: usize = 3;
+
+/// Gold for a player's first hired worker; each worker they have hired
+/// since adds `WORKER_HIRE_COST_STEP`.
+pub const WORKER_HIRE_BASE_COST: u32 = 50;
+pub const WORKER_HIRE_COST_STEP: u32 = 10;
+
+/// Price of the next worker for a player who has hired `hired` already.
+pub fn worker_hire_cost(hired: u32) -> u32 {
+    WORKER_HIRE_BASE_COST + WORKER_HIRE_COST_STEP * hired
+}
+
+/// Gold for each worker upgrade tier, the same for every `WorkerUpgrade`;
+/// index 0 buys tier 1.
+pub const WORKER_UPGRADE_COSTS: [u32; 3] = [75, 125, 200];
+
+/// Per tier (index 0 is unupgraded): travel speed in px/s, seconds spent
+/// mining at the vein, and ore delivered per trip.
+pub const WORKER_SPEED_BY_TIER: [f32; 4] = [50.0, 60.0, 70.0, 85.0];
+pub const WORKER_MINING_SECS_BY_TIER: [f32; 4] = [10.0, 8.5, 7.0, 5.5];
+pub const WORKER_CARRY_BY_TIER: [u32; 4] = [1, 2, 3, 4];
+
+/// Which worker stat an upgrade improves. Upgrades apply to all of a
+/// player's workers, hired before or after.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+pub enum WorkerUpgrade {
+    Speed,
+    MiningTime,
+    Carry,
+}
+
+/// A player's bought worker upgrade tiers, 0 to `WORKER_UPGRADE_COSTS.len()`.
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub struct WorkerTiers {
+    pub speed: u32,
+    pub mining_time: u32,
+    pub carry: u32,
+}
+
+/// What a player's workers do at their current tiers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WorkerStats {
+    pub speed: f32,
+    pub mining_secs: f32,
+    pub carry: u32,
+}
+
+impl WorkerTiers {
+    pub fn tier(&self, upgrade: WorkerUpgrade) -> u32 {
+        match upgrade {
+            WorkerUpgrade::Speed => self.speed,
+            WorkerUpgrade::MiningTime => self.mining_time,
+            WorkerUpgrade::Carry => self.carry,
+        }
+    }
+
+    pub fn tier_mut(&mut self, upgrade: WorkerUpgrade) -> &mut u32 {
+        match upgrade {
+            WorkerUpgrade::Speed => &mut self.speed,
+            WorkerUpgrade::MiningTime => &mut self.mining_time,
+            WorkerUpgrade::Carry => &mut self.carry,
+        }
+    }
+
+    /// Gold for the next tier of `upgrade`, or `None` at the top tier.
+    pub fn next_cost(&self, upgrade: WorkerUpgrade) -> Option<u32> {
+        WORKER_UPGRADE_COSTS
+            .get(self.tier(upgrade) as usize)
+            .copied()
+    }
+
+    pub fn stats(&self) -> WorkerStats {
+        WorkerStats {
+            speed: WORKER_SPEED_BY_TIER[self.speed as usize],
+            mining_secs: WORKER_MINING_SECS_BY_TIER[self.mining_time as usize],
+            carry: WORKER_CARRY_BY_TIER[self.carry as usize],
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -302,6 +427,47 @@ mod tests {
         assert_eq!(circle.gold_cost, 75);
     }
 
+    #[test]
+    fn essence_mode_adds_essence_to_the_stronger_towers() {
+        for shape in [UnitKind::Square, UnitKind::Triangle, UnitKind::Circle] {
+            let classic = tower_cost(shape, GameMode::Classic);
+            let essence = tower_cost(shape, GameMode::Essence);
+            assert_eq!(classic.essence, 0);
+            assert_eq!(classic.gold, essence.gold);
+        }
+        assert_eq!(tower_cost(UnitKind::Square, GameMode::Essence).essence, 0);
+        assert_eq!(
+            sell_refund(tower_cost(UnitKind::Circle, GameMode::Essence)),
+            Cost {
+                gold: 56,
+                essence: 18
+            }
+        );
+    }
+
+    #[test]
+    fn worker_hire_cost_rises_per_worker_hired() {
+        assert_eq!(worker_hire_cost(0), 50);
+        assert_eq!(worker_hire_cost(1), 60);
+        let last_hire = (WORKER_CAP - STARTER_WORKERS - 1) as u32;
+        assert_eq!(worker_hire_cost(last_hire), 80);
+    }
+
+    #[test]
+    fn worker_tiers_cover_every_upgrade_cost() {
+        let mut tiers = WorkerTiers::default();
+        assert_eq!(tiers.stats().speed, 50.0);
+        assert_eq!(tiers.stats().mining_secs, 10.0);
+        assert_eq!(tiers.stats().carry, 1);
+        for cost in WORKER_UPGRADE_COSTS {
+            assert_eq!(tiers.next_cost(WorkerUpgrade::Carry), Some(cost));
+            *tiers.tier_mut(WorkerUpgrade::Carry) += 1;
+        }
+        assert_eq!(tiers.next_cost(WorkerUpgrade::Carry), None);
+        assert_eq!(tiers.next_cost(WorkerUpgrade::Speed), Some(75));
+        assert_eq!(tiers.stats().carry, 4, "top tier stays in range");
+    }
+
     // --- Task 1 TDD tests ---
 
     #[test]
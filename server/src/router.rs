@@ -1,5 +1,5 @@
 use crate::{
-    routes::{auth, ws},
+    routes::{account, admin, auth, friends, metrics, ws},
     state::ServerState,
 };
 use http_body_util::Full;
@@ -14,6 +14,15 @@ pub async fn router(
     state: ServerState,
 ) -> Result<Response<Full<Bytes>>, hyper::Error> {
     let mut response = Response::new(Full::new(Bytes::new()));
+    // Resolved up front: the handlers below consume the request.
+    let request_origin = req
+        .headers()
+        .get(header::ORIGIN)
+        .and_then(|v| v.to_str().ok());
+    let allowed_origin = state
+        .config
+        .allowed_origin(request_origin)
+        .map(str::to_string);
 
     if req.method() == Method::OPTIONS {
         *response.status_mut() = StatusCode::OK;
@@ -28,6 +37,32 @@ pub async fn router(
             (&Method::POST, "/api/auth/logout") => {
                 response = auth::handle_logout(req, state).await;
             }
+            (&Method::POST, "/api/auth/guest") => {
+                response = auth::handle_guest(req, state).await;
+            }
+            (&Method::POST, "/api/auth/refresh") => {
+                response = auth::handle_refresh(req, state).await;
+            }
+            (_, path)
+                if path == "/api/auth/sessions" || path.starts_with("/api/auth/sessions/") =>
+            {
+                response = auth::handle_sessions(req, state).await;
+            }
+            (_, path) if path.starts_with("/api/account/") => {
+                response = account::handle_account(req, state).await;
+            }
+            (_, path) if path == "/api/friends" || path.starts_with("/api/friends/") => {
+                response = friends::handle_friends(req, state).await;
+            }
+            (&Method::GET, path) if path.starts_with("/api/profile/") => {
+                response = account::handle_get_profile(req, state).await;
+            }
+            (&Method::GET, "/metrics") => {
+                response = metrics::handle_metrics(state).await;
+            }
+            (_, path) if path.starts_with("/api/admin/") => {
+                response = admin::handle_admin(req, state).await;
+            }
             (&Method::GET, "/ws") => {
                 response = ws::handle_ws_upgrade(&mut req, state).await;
             }
@@ -39,7 +74,12 @@ pub async fn router(
 
     // Add CORS headers to all responses
     let headers = response.headers_mut();
-    headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*".parse().unwrap());
+    if let Some(origin) = allowed_origin
+        && let Ok(value) = origin.parse()
+    {
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, value);
+        headers.insert(header::VARY, "Origin".parse().unwrap());
+    }
     headers.insert(
         header::ACCESS_CONTROL_ALLOW_METHODS,
         "GET, POST, OPTIONS".parse().unwrap(),
@@ -0,0 +1,423 @@
+use super::{
+    auth::{authenticate_session, bearer_token},
+    error_response, json_response, read_json,
+};
+use crate::{
+    database,
+    model::{
+        account::{Account, NewAccount, ProfileUpdate, validate_password},
+        game_error::GameError,
+    },
+    state::ServerState,
+};
+use http_body_util::Full;
+use hyper::{
+    Method, Request, Response, StatusCode,
+    body::{Bytes, Incoming as Body},
+};
+use log::{error, info};
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Deserialize)]
+struct ChangePasswordRequest {
+    old_password: String,
+    new_password: String,
+}
+
+#[derive(Deserialize)]
+struct DeleteAccountRequest {
+    #[serde(default)]
+    password: String,
+}
+
+/// Entry point for everything under `/api/account/`: changes to the caller's
+/// own account. Requires a valid session token.
+pub async fn handle_account(req: Request<Body>, state: ServerState) -> Response<Full<Bytes>> {
+    let Some(token) = bearer_token(&req) else {
+        return error_response(StatusCode::UNAUTHORIZED, "Missing token");
+    };
+    let (account, session) = match authenticate_session(token, &state).await {
+        Ok(authenticated) => authenticated,
+        Err(e) => return error_response(StatusCode::UNAUTHORIZED, &e),
+    };
+
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    match (method, path.as_str()) {
+        (Method::POST, "/api/account/password") => match read_json(req).await {
+            Some(body) => change_password(&state, &account, &session.id, body).await,
+            None => error_response(StatusCode::BAD_REQUEST, "Invalid request payload"),
+        },
+        (Method::POST, "/api/account/delete") => match read_json(req).await {
+            Some(body) => delete_account(&state, &account, body).await,
+            None => error_response(StatusCode::BAD_REQUEST, "Invalid request payload"),
+        },
+        (Method::POST, "/api/account/upgrade") => match read_json(req).await {
+            Some(body) => upgrade_guest(&state, &account, body).await,
+            None => error_response(StatusCode::BAD_REQUEST, "Invalid request payload"),
+        },
+        (Method::POST, "/api/account/profile") => match read_json(req).await {
+            Some(body) => update_profile(&state, &account, body).await,
+            None => error_response(StatusCode::BAD_REQUEST, "Invalid request payload"),
+        },
+        _ => error_response(StatusCode::NOT_FOUND, "Not found"),
+    }
+}
+
+/// `GET /api/profile/{username}`: anyone's public profile, no login needed.
+pub async fn handle_get_profile(req: Request<Body>, state: ServerState) -> Response<Full<Bytes>> {
+    let username = req.uri().path().trim_start_matches("/api/profile/");
+    match database::get_profile(&state.db_pool, username).await {
+        Ok(Some(profile)) => json_response(StatusCode::OK, json!(profile)),
+        Ok(None) => error_response(StatusCode::NOT_FOUND, "No such account"),
+        Err(e) => {
+            error!("Failed to load profile: {}", e);
+            error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to load profile")
+        }
+    }
+}
+
+/// Changes the password and signs out every other device.
+async fn change_password(
+    state: &ServerState,
+    account: &Account,
+    session_id: &str,
+    request: ChangePasswordRequest,
+) -> Response<Full<Bytes>> {
+    if !database::verify_password(&request.old_password, &account.password_hash).await {
+        return error_response(StatusCode::UNAUTHORIZED, "Invalid credentials");
+    }
+    if let Err(e) = validate_password(&request.new_password) {
+        return error_response(StatusCode::BAD_REQUEST, &e);
+    }
+    if let Err(e) =
+        database::update_password(&state.db_pool, account.id, &request.new_password).await
+    {
+        error!("Failed to change password: {}", e);
+        return error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to change password",
+        );
+    }
+    if let Err(e) =
+        database::delete_other_sessions(&state.db_pool, account.id, Some(session_id)).await
+    {
+        error!("Failed to sign out other sessions: {}", e);
+    }
+    let other_socket = state
+        .active_connections
+        .lock()
+        .await
+        .get(&account.id)
+        .map(|c| c.session_id.clone())
+        .filter(|sid| sid != session_id);
+    if let Some(sid) = other_socket {
+        state
+            .disconnect(account.id, Some(&sid), GameError::SessionRevoked)
+            .await;
+    }
+    json_response(StatusCode::OK, json!({ "message": "Password changed" }))
+}
+
+/// Gives a guest account a chosen username and password. The account id is
+/// kept, so match history and signed-in devices carry over.
+async fn upgrade_guest(
+    state: &ServerState,
+    account: &Account,
+    request: NewAccount,
+) -> Response<Full<Bytes>> {
+    if !account.is_guest {
+        return error_response(StatusCode::CONFLICT, "Not a guest account");
+    }
+    if let Err(e) = request.validate() {
+        return error_response(StatusCode::BAD_REQUEST, &e);
+    }
+    let username = request.username.clone();
+    match database::upgrade_guest(&state.db_pool, account.id, request).await {
+        Ok(true) => {
+            info!("Guest {} upgraded to {}", account.username, username);
+            json_response(
+                StatusCode::OK,
+                json!({ "id": account.id, "username": username }),
+            )
+        }
+        Ok(false) => error_response(StatusCode::CONFLICT, "Not a guest account"),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            error_response(StatusCode::CONFLICT, "Username already taken")
+        }
+        Err(e) => {
+            error!("Failed to upgrade guest: {}", e);
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to upgrade account",
+            )
+        }
+    }
+}
+
+/// Deletes the account after re-checking its password and drops any live
+/// socket it still has open. Guests have no password to check.
+async fn delete_account(
+    state: &ServerState,
+    account: &Account,
+    request: DeleteAccountRequest,
+) -> Response<Full<Bytes>> {
+    if !account.is_guest
+        && !database::verify_password(&request.password, &account.password_hash).await
+    {
+        return error_response(StatusCode::UNAUTHORIZED, "Invalid credentials");
+    }
+    if let Err(e) = database::delete_account(&state.db_pool, account.id).await {
+        error!("Failed to delete account: {}", e);
+        return error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to delete account",
+        );
+    }
+    info!("Account {} deleted", account.username);
+    state
+        .disconnect(account.id, None, GameError::AccountDeleted)
+        .await;
+    json_response(StatusCode::OK, json!({ "message": "Account deleted" }))
+}
+
+async fn update_profile(
+    state: &ServerState,
+    account: &Account,
+    update: ProfileUpdate,
+) -> Response<Full<Bytes>> {
+    let update = match update.normalize() {
+        Ok(update) => update,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &e),
+    };
+    if let Err(e) = database::update_profile(&state.db_pool, account.id, &update).await {
+        error!("Failed to update profile: {}", e);
+        return error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to update profile",
+        );
+    }
+    match database::get_profile(&state.db_pool, &account.username).await {
+        Ok(Some(profile)) => json_response(StatusCode::OK, json!(profile)),
+        _ => error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to load profile"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::family::Family;
+    use crate::state::test_support::migrated_test_state;
+    use crate::state::{ActiveConnection, ActivityCell};
+    use http_body_util::BodyExt;
+    use serde_json::Value;
+    use tokio::sync::mpsc;
+
+    async fn register(state: &ServerState, username: &str, password: &str) -> Account {
+        let new_account = NewAccount {
+            username: username.to_string(),
+            password: password.to_string(),
+        };
+        database::create_account(&state.db_pool, new_account)
+            .await
+            .unwrap()
+    }
+
+    async fn body_json(response: Response<Full<Bytes>>) -> Value {
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    async fn sign_in(state: &ServerState, account: &Account, session_id: &str) {
+        let expires_at = chrono::Utc::now() + chrono::Duration::hours(1);
+        database::create_session(
+            &state.db_pool,
+            account.id,
+            session_id,
+            "hash",
+            None,
+            expires_at,
+        )
+        .await
+        .unwrap();
+    }
+
+    async fn connect(
+        state: &ServerState,
+        account: &Account,
+        session_id: &str,
+    ) -> mpsc::Receiver<GameError> {
+        let (kill_tx, kill_rx) = mpsc::channel(1);
+        let connection = ActiveConnection {
+            session_id: session_id.to_string(),
+            kill_tx,
+            notify_tx: mpsc::channel(1).0,
+            activity: ActivityCell::default(),
+        };
+        state
+            .active_connections
+            .lock()
+            .await
+            .insert(account.id, connection);
+        kill_rx
+    }
+
+    #[tokio::test]
+    async fn change_password_requires_the_old_one_and_signs_out_other_devices() {
+        let state = migrated_test_state().await;
+        let account = register(&state, "alice", "old-password").await;
+        sign_in(&state, &account, "phone").await;
+        sign_in(&state, &account, "laptop").await;
+        let mut laptop_socket = connect(&state, &account, "laptop").await;
+        let request = |old: &str, new: &str| ChangePasswordRequest {
+            old_password: old.to_string(),
+            new_password: new.to_string(),
+        };
+
+        let wrong =
+            change_password(&state, &account, "phone", request("nope", "new-password")).await;
+        assert_eq!(wrong.status(), StatusCode::UNAUTHORIZED);
+        let weak =
+            change_password(&state, &account, "phone", request("old-password", "short")).await;
+        assert_eq!(weak.status(), StatusCode::BAD_REQUEST);
+        let ok = change_password(
+            &state,
+            &account,
+            "phone",
+            request("old-password", "new-password"),
+        )
+        .await;
+        assert_eq!(ok.status(), StatusCode::OK);
+
+        let stored = database::get_account_by_username(&state.db_pool, "alice")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(database::verify_password("new-password", &stored.password_hash).await);
+        let sessions = database::list_sessions(&state.db_pool, account.id)
+            .await
+            .unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, "phone");
+        assert_eq!(laptop_socket.recv().await, Some(GameError::SessionRevoked));
+    }
+
+    #[tokio::test]
+    async fn delete_account_removes_it_and_drops_the_socket() {
+        let state = migrated_test_state().await;
+        let account = register(&state, "bob", "bob-password").await;
+        sign_in(&state, &account, "desktop").await;
+        let mut kill_rx = connect(&state, &account, "desktop").await;
+
+        let wrong = DeleteAccountRequest {
+            password: "nope".to_string(),
+        };
+        let response = delete_account(&state, &account, wrong).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let right = DeleteAccountRequest {
+            password: "bob-password".to_string(),
+        };
+        let response = delete_account(&state, &account, right).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(kill_rx.recv().await, Some(GameError::AccountDeleted));
+        assert!(
+            database::get_account_by_username(&state.db_pool, "bob")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            database::get_session(&state.db_pool, "desktop")
+                .await
+                .unwrap()
+                .is_none(),
+            "sessions cascade with the account"
+        );
+    }
+
+    #[tokio::test]
+    async fn guest_upgrade_keeps_the_account_and_its_history() {
+        let state = migrated_test_state().await;
+        let guest = database::create_guest_account(&state.db_pool, "guest-0000abcd")
+            .await
+            .unwrap();
+        assert!(guest.is_guest);
+        register(&state, "taken", "taken-password").await;
+        let result = crate::model::match_result::MatchResult {
+            participant_ids: vec![guest.id],
+            winner_id: Some(guest.id),
+            stats: Vec::new(),
+            outcome: crate::model::match_result::MatchOutcome::Finished,
+            ended_at: chrono::Utc::now(),
+        };
+        database::record_match_result(&state.db_pool, &result)
+            .await
+            .unwrap();
+        let upgrade = |username: &str| NewAccount {
+            username: username.to_string(),
+            password: "guest-password".to_string(),
+        };
+
+        let response = upgrade_guest(&state, &guest, upgrade("taken")).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let response = upgrade_guest(&state, &guest, upgrade("guest-mine")).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = upgrade_guest(&state, &guest, upgrade("dave")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let dave = database::get_account_by_username(&state.db_pool, "dave")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(dave.id, guest.id);
+        assert!(!dave.is_guest);
+        assert!(database::verify_password("guest-password", &dave.password_hash).await);
+        let winner = sqlx::query_scalar!("SELECT winner_id FROM matches")
+            .fetch_one(&state.db_pool)
+            .await
+            .unwrap();
+        assert_eq!(winner, Some(dave.id));
+
+        let again = upgrade_guest(&state, &dave, upgrade("dave2")).await;
+        assert_eq!(again.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn profile_updates_are_validated_and_public() {
+        let state = migrated_test_state().await;
+        let account = register(&state, "carol", "carol-password").await;
+
+        let fresh = database::get_profile(&state.db_pool, "carol")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((fresh.display_name, fresh.avatar), (None, 0));
+
+        let update = ProfileUpdate {
+            display_name: Some(" Carol ".to_string()),
+            avatar: 4,
+            favourite_family: Some(Family::Basic),
+        };
+        let response = update_profile(&state, &account, update).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let profile = body_json(response).await;
+        assert_eq!(profile["display_name"], "Carol");
+        assert_eq!(profile["avatar"], 4);
+        assert_eq!(profile["favourite_family"], "Basic");
+
+        let invalid = ProfileUpdate {
+            display_name: None,
+            avatar: 200,
+            favourite_family: None,
+        };
+        let response = update_profile(&state, &account, invalid).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let kept = database::get_profile(&state.db_pool, "carol")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(kept.avatar, 4);
+    }
+}
@@ -0,0 +1,256 @@
+use super::{
+    auth::{authenticate_token, bearer_token},
+    error_response, json_response, read_json,
+};
+use crate::{
+    database,
+    model::{game_error::GameError, messages::ServerMessage},
+    state::ServerState,
+};
+use http_body_util::Full;
+use hyper::{
+    Method, Request, Response, StatusCode,
+    body::{Bytes, Incoming as Body},
+};
+use log::{info, warn};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+/// Body of the force-end and kick routes.
+#[derive(Deserialize)]
+struct ReasonRequest {
+    reason: String,
+}
+
+/// Entry point for everything under `/api/admin/`. Requires a valid session
+/// token for an account with `is_admin` set.
+pub async fn handle_admin(req: Request<Body>, state: ServerState) -> Response<Full<Bytes>> {
+    let Some(token) = bearer_token(&req) else {
+        return error_response(StatusCode::UNAUTHORIZED, "Missing token");
+    };
+    let admin = match authenticate_token(token, &state).await {
+        Ok(account) if account.is_admin => account.username,
+        Ok(_) => return error_response(StatusCode::FORBIDDEN, "Admin role required"),
+        Err(e) => return error_response(StatusCode::UNAUTHORIZED, &e),
+    };
+
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let segments: Vec<&str> = path
+        .trim_start_matches("/api/admin")
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    match (method, segments.as_slice()) {
+        (Method::GET, ["matches"]) => json_response(StatusCode::OK, list_matches(&state).await),
+        (Method::GET, ["matches", id]) => {
+            let Ok(match_id) = id.parse() else {
+                return error_response(StatusCode::BAD_REQUEST, "Invalid match id");
+            };
+            match dump_match(&state, match_id).await {
+                Some(dump) => json_response(StatusCode::OK, dump),
+                None => error_response(StatusCode::NOT_FOUND, "No such match"),
+            }
+        }
+        (Method::POST, ["matches", id, "end"]) => {
+            let Ok(match_id) = id.parse() else {
+                return error_response(StatusCode::BAD_REQUEST, "Invalid match id");
+            };
+            let Some(reason) = read_reason(req).await else {
+                return error_response(StatusCode::BAD_REQUEST, "Invalid request payload");
+            };
+            info!("Admin {admin} force-ending match {match_id}: {reason}");
+            match end_match(&state, match_id, reason).await {
+                Some(true) => json_response(StatusCode::OK, json!({ "ended": match_id })),
+                Some(false) => error_response(StatusCode::CONFLICT, "Match already over"),
+                None => error_response(StatusCode::NOT_FOUND, "No such match"),
+            }
+        }
+        (Method::POST, ["connections", id, "kick"]) => {
+            let Ok(account_id) = id.parse() else {
+                return error_response(StatusCode::BAD_REQUEST, "Invalid account id");
+            };
+            let Some(reason) = read_reason(req).await else {
+                return error_response(StatusCode::BAD_REQUEST, "Invalid request payload");
+            };
+            info!("Admin {admin} kicking account {account_id}: {reason}");
+            if kick(&state, account_id, reason).await {
+                json_response(StatusCode::OK, json!({ "kicked": account_id }))
+            } else {
+                error_response(StatusCode::NOT_FOUND, "Account is not connected")
+            }
+        }
+        (Method::GET, ["queue"]) => json_response(StatusCode::OK, view_queue(&state).await),
+        _ => error_response(StatusCode::NOT_FOUND, "Not found"),
+    }
+}
+
+async fn read_reason(req: Request<Body>) -> Option<String> {
+    read_json::<ReasonRequest>(req).await.map(|r| r.reason)
+}
+
+/// Every registered match with its players, phase and wave.
+async fn list_matches(state: &ServerState) -> Value {
+    let handles: Vec<_> = state
+        .matches
+        .read()
+        .await
+        .iter()
+        .map(|(id, handle)| (*id, handle.clone()))
+        .collect();
+    let mut matches = Vec::with_capacity(handles.len());
+    for (match_id, handle) in handles {
+        let summary = handle
+            .with_lobby(move |lobby| {
+                let players: Vec<Value> = lobby
+                    .players
+                    .iter()
+                    .map(|p| json!({ "id": p.id, "username": p.username }))
+                    .collect();
+                json!({
+                    "match_id": match_id,
+                    "players": players,
+                    "phase": lobby.game_state.phase,
+                    "wave": lobby.game_state.wave_number,
+                })
+            })
+            .await;
+        matches.extend(summary);
+    }
+    json!({ "matches": matches })
+}
+
+/// The full game state a client would get on resync, plus tick timing.
+async fn dump_match(state: &ServerState, match_id: u64) -> Option<Value> {
+    let handle = state.matches.read().await.get(&match_id).cloned()?;
+    handle
+        .with_lobby(move |lobby| {
+            let ServerMessage::GameState(game_state) = lobby.full_state_message() else {
+                unreachable!("full_state_message always builds a GameState");
+            };
+            let stats = &lobby.tick_stats;
+            json!({
+                "match_id": match_id,
+                "wave": lobby.game_state.wave_number,
+                "state": game_state,
+                "tick_stats": {
+                    "ticks": stats.ticks,
+                    "overruns": stats.overruns,
+                    "missed_ticks": stats.missed_ticks,
+                    "mean_secs": stats.mean().as_secs_f64(),
+                    "max_secs": stats.max.as_secs_f64(),
+                },
+            })
+        })
+        .await
+}
+
+/// `None` if the match does not exist; otherwise whether it was still running.
+async fn end_match(state: &ServerState, match_id: u64, reason: String) -> Option<bool> {
+    let handle = state.matches.read().await.get(&match_id).cloned()?;
+    handle
+        .with_lobby(move |lobby| lobby.force_end(reason))
+        .await
+}
+
+/// Disconnects the account's socket and signs out all of its sessions so it
+/// has to log in again. Returns false if the account has no live connection.
+async fn kick(state: &ServerState, account_id: i64, reason: String) -> bool {
+    if !state
+        .disconnect(account_id, None, GameError::Kicked { reason })
+        .await
+    {
+        return false;
+    }
+    if let Err(e) = database::delete_other_sessions(&state.db_pool, account_id, None).await {
+        warn!("Failed to clear sessions of kicked account {account_id}: {e}");
+    }
+    true
+}
+
+async fn view_queue(state: &ServerState) -> Value {
+    let queue = state.queue.lock().await;
+    let waiting: Vec<Value> = queue
+        .iter()
+        .map(|p| json!({ "account_id": p.account_id, "username": p.username }))
+        .collect();
+    json!({ "waiting": waiting })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{lobby::Lobby, player::Player};
+    use crate::state::test_support::test_state;
+    use crate::state::{ActiveConnection, ActivityCell, QueuedPlayer};
+    use tokio::sync::{mpsc, oneshot};
+
+    async fn insert_match(state: &ServerState, match_id: u64) {
+        let mut lobby = Lobby::new();
+        lobby.add_player(Player::new(1, "alice".into(), 100));
+        lobby.add_player(Player::new(2, "bob".into(), 100));
+        let handle = state.sim.spawn_match(match_id, lobby);
+        state.matches.write().await.insert(match_id, handle);
+    }
+
+    #[tokio::test]
+    async fn list_and_dump_describe_live_matches() {
+        let state = test_state().await;
+        insert_match(&state, 3).await;
+
+        let list = list_matches(&state).await;
+        assert_eq!(list["matches"][0]["match_id"], 3);
+        assert_eq!(list["matches"][0]["players"][1]["username"], "bob");
+        assert_eq!(list["matches"][0]["wave"], 1);
+
+        let dump = dump_match(&state, 3).await.unwrap();
+        assert_eq!(dump["state"]["players"].as_array().unwrap().len(), 2);
+        assert!(dump_match(&state, 4).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn end_match_aborts_once() {
+        let state = test_state().await;
+        insert_match(&state, 0).await;
+        assert_eq!(end_match(&state, 0, "test".into()).await, Some(true));
+        assert_eq!(end_match(&state, 0, "test".into()).await, Some(false));
+        assert_eq!(end_match(&state, 9, "test".into()).await, None);
+    }
+
+    #[tokio::test]
+    async fn kick_relays_reason_and_unregisters_connection() {
+        let state = test_state().await;
+        let (kill_tx, mut kill_rx) = mpsc::channel(1);
+        let connection = ActiveConnection {
+            session_id: "sid".into(),
+            kill_tx,
+            notify_tx: mpsc::channel(1).0,
+            activity: ActivityCell::default(),
+        };
+        state.active_connections.lock().await.insert(5, connection);
+
+        assert!(kick(&state, 5, "spam".into()).await);
+        assert_eq!(
+            kill_rx.recv().await,
+            Some(GameError::Kicked {
+                reason: "spam".into()
+            })
+        );
+        assert!(!state.active_connections.lock().await.contains_key(&5));
+        assert!(!kick(&state, 5, "spam".into()).await);
+    }
+
+    #[tokio::test]
+    async fn queue_lists_the_waiting_player() {
+        let state = test_state().await;
+        assert_eq!(view_queue(&state).await, json!({ "waiting": [] }));
+        let (paired_tx, _paired_rx) = oneshot::channel();
+        *state.queue.lock().await = Some(QueuedPlayer {
+            account_id: 8,
+            username: "carol".into(),
+            paired_tx,
+        });
+        assert_eq!(view_queue(&state).await["waiting"][0]["username"], "carol");
+    }
+}
@@ -1,16 +1,29 @@
+use super::{error_response, json_response, read_json};
 use crate::{
     database,
-    model::{account::NewAccount, jwt},
+    login_guard::{LoginFailure, MAX_TRACKED_USERNAME},
+    metrics::AuthEndpoint,
+    model::{
+        account::{Account, NewAccount, guest_username},
+        game_error::GameError,
+        jwt,
+        session::{RefreshToken, Session},
+    },
     state::ServerState,
 };
-use chrono::{Duration, Utc};
+use chrono::Utc;
 use http_body_util::{BodyExt, Full};
 use hyper::{
-    Response, StatusCode,
+    Method, Response, StatusCode,
     body::{Bytes, Incoming as Body},
     header,
 };
-use log::error;
+use log::{error, info, warn};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Instant;
+use uuid::Uuid;
 
 pub async fn handle_register(
     req: hyper::Request<Body>,
@@ -31,6 +44,13 @@ pub async fn handle_register(
     };
 
     match serde_json::from_slice::<NewAccount>(&body_bytes) {
+        Ok(payload) if let Err(e) = payload.validate() => Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Full::new(Bytes::from(
+                serde_json::to_string(&serde_json::json!({ "error": e })).unwrap(),
+            )))
+            .unwrap(),
         Ok(payload) => match database::create_account(&state.db_pool, payload).await {
             Ok(account) => Response::builder()
                 .status(StatusCode::CREATED)
@@ -67,131 +87,369 @@ pub async fn handle_register(
     }
 }
 
-use uuid::Uuid;
-
 pub async fn handle_login(req: hyper::Request<Body>, state: ServerState) -> Response<Full<Bytes>> {
-    let body_bytes = match req.collect().await {
-        Ok(body) => body.to_bytes(),
-        Err(_) => {
-            return Response::builder()
-                .status(StatusCode::BAD_REQUEST)
-                .body(Full::new(Bytes::from("Invalid request payload")))
-                .unwrap();
+    let response = login(req, &state).await;
+    // Failures are counted with their reason by `audit_failure`.
+    if response.status() == StatusCode::OK {
+        state.metrics.record_auth(AuthEndpoint::Login, Ok(()));
+    }
+    response
+}
+
+/// Longest `User-Agent` kept as a session's device label.
+const MAX_DEVICE_LEN: usize = 120;
+
+/// The request's `User-Agent`, trimmed for use as a session's device label.
+fn device_label(req: &hyper::Request<Body>) -> Option<String> {
+    req.headers()
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(|ua| ua.chars().take(MAX_DEVICE_LEN).collect())
+}
+
+async fn login(req: hyper::Request<Body>, state: &ServerState) -> Response<Full<Bytes>> {
+    let ip = req.extensions().get::<SocketAddr>().map(SocketAddr::ip);
+    let device = device_label(&req);
+    let Some(payload) = read_json::<NewAccount>(req).await else {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid request payload");
+    };
+    let username = payload.username.as_str();
+
+    // Refused before touching the password, so a locked-out guesser learns
+    // nothing from further attempts.
+    if let Err(wait) = state.login_guard.check(username, ip, Instant::now()) {
+        audit_failure(state, username, ip, LoginFailure::Throttled).await;
+        let mut response = error_response(
+            StatusCode::TOO_MANY_REQUESTS,
+            "Too many login attempts; try again later",
+        );
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, wait.as_secs().max(1).into());
+        return response;
+    }
+
+    let account = match database::get_account_by_username(&state.db_pool, username).await {
+        Ok(Some(account)) => account,
+        Ok(None) => {
+            database::verify_password_against_nothing(&payload.password).await;
+            return reject_login(state, username, ip, LoginFailure::UnknownUser).await;
+        }
+        Err(e) => {
+            error!("Failed to load account: {}", e);
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to log in");
         }
     };
+    if !database::verify_password(&payload.password, &account.password_hash).await {
+        return reject_login(state, username, ip, LoginFailure::BadPassword).await;
+    }
+    state.login_guard.record_success(username);
 
-    match serde_json::from_slice::<NewAccount>(&body_bytes) {
-        Ok(payload) => {
-            match database::get_account_by_username(&state.db_pool, &payload.username).await {
-                Ok(Some(account)) => {
-                    if database::verify_password(&payload.password, &account.password_hash).await {
-                        let session_id = Uuid::new_v4().to_string();
-                        let expires_at = Utc::now() + Duration::hours(24);
-
-                        match database::update_session(
-                            &state.db_pool,
-                            account.id,
-                            &session_id,
-                            expires_at,
-                        )
-                        .await
-                        {
-                            Ok(_) => match jwt::create_jwt(account.username, session_id, expires_at)
-                            {
-                                Ok(token) => Response::builder()
-                                    .status(StatusCode::OK)
-                                    .header(header::CONTENT_TYPE, "application/json")
-                                    .body(Full::new(Bytes::from(
-                                        serde_json::to_string(
-                                            &serde_json::json!({ "token": token }),
-                                        )
-                                        .unwrap(),
-                                    )))
-                                    .unwrap(),
-                                Err(_) => Response::builder()
-                                    .status(StatusCode::INTERNAL_SERVER_ERROR)
-                                    .header(header::CONTENT_TYPE, "application/json")
-                                    .body(Full::new(Bytes::from(
-                                        serde_json::to_string(
-                                            &serde_json::json!({"error": "Failed to create token"}),
-                                        )
-                                        .unwrap(),
-                                    )))
-                                    .unwrap(),
-                            },
-                            Err(_) => Response::builder()
-                                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                                .header(header::CONTENT_TYPE, "application/json")
-                                .body(Full::new(Bytes::from(
-                                    serde_json::to_string(
-                                        &serde_json::json!({"error": "Failed to update session"}),
-                                    )
-                                    .unwrap(),
-                                )))
-                                .unwrap(),
-                        }
-                    } else {
-                        Response::builder()
-                            .status(StatusCode::UNAUTHORIZED)
-                            .header(header::CONTENT_TYPE, "application/json")
-                            .body(Full::new(Bytes::from(
-                                serde_json::to_string(
-                                    &serde_json::json!({"error": "Invalid credentials"}),
-                                )
-                                .unwrap(),
-                            )))
-                            .unwrap()
-                    }
-                }
-                _ => Response::builder()
-                    .status(StatusCode::UNAUTHORIZED)
-                    .header(header::CONTENT_TYPE, "application/json")
-                    .body(Full::new(Bytes::from(
-                        serde_json::to_string(&serde_json::json!({"error": "Invalid credentials"}))
-                            .unwrap(),
-                    )))
-                    .unwrap(),
+    start_session(state, account, device.as_deref()).await
+}
+
+/// Signs a new device in: stores its session and replies with its tokens.
+async fn start_session(
+    state: &ServerState,
+    account: Account,
+    device: Option<&str>,
+) -> Response<Full<Bytes>> {
+    let refresh_token = RefreshToken::generate(&Uuid::new_v4().to_string());
+    let expires_at = Utc::now() + state.config.session_lifetime();
+    if let Err(e) = database::create_session(
+        &state.db_pool,
+        account.id,
+        &refresh_token.session_id,
+        &refresh_token.hash(),
+        device,
+        expires_at,
+    )
+    .await
+    {
+        error!("Failed to create session: {}", e);
+        return error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to create session",
+        );
+    }
+    token_response(state, account.username, &refresh_token)
+}
+
+/// Generated names can collide; give up after this many tries.
+const GUEST_NAME_ATTEMPTS: usize = 5;
+
+/// `POST /api/auth/guest`: creates a guest account with a generated name and
+/// signs it in. The reply is the same as a login's plus the `username`.
+pub async fn handle_guest(req: hyper::Request<Body>, state: ServerState) -> Response<Full<Bytes>> {
+    let device = device_label(&req);
+    let mut last_error = None;
+    for _ in 0..GUEST_NAME_ATTEMPTS {
+        match database::create_guest_account(&state.db_pool, &guest_username()).await {
+            Ok(account) => {
+                info!("Created guest account {}", account.username);
+                return start_session(&state, account, device.as_deref()).await;
             }
+            Err(e) => last_error = Some(e),
         }
-        Err(_) => Response::builder()
-            .status(StatusCode::BAD_REQUEST)
-            .body(Full::new(Bytes::from("Invalid request payload")))
-            .unwrap(),
     }
+    error!("Failed to create guest account: {:?}", last_error);
+    error_response(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Failed to create guest account",
+    )
 }
 
-pub async fn handle_logout(req: hyper::Request<Body>, state: ServerState) -> Response<Full<Bytes>> {
-    let auth_header = req.headers().get(header::AUTHORIZATION);
-    if let Some(auth_header) = auth_header {
-        if let Ok(auth_str) = auth_header.to_str() {
-            if auth_str.starts_with("Bearer ") {
-                let token = &auth_str[7..];
-                if let Ok(claims) = jwt::decode_jwt(token) {
-                    if let Ok(Some(account)) =
-                        database::get_account_by_username(&state.db_pool, &claims.sub).await
-                    {
-                        // Check if the session ID in the token matches the one in the database
-                        if account.session_id.as_deref() == Some(&claims.sid) {
-                            if let Err(e) =
-                                database::clear_session(&state.db_pool, account.id).await
-                            {
-                                error!("Failed to clear session: {}", e);
-                                return Response::builder()
-                                    .status(StatusCode::INTERNAL_SERVER_ERROR)
-                                    .header(header::CONTENT_TYPE, "application/json")
-                                    .body(Full::new(Bytes::from(
-                                        serde_json::to_string(
-                                            &serde_json::json!({"error": "Failed to logout"}),
-                                        )
-                                        .unwrap(),
-                                    )))
-                                    .unwrap();
-                            }
-                        }
-                    }
-                }
+/// Counts a failed credential check towards backoff and answers with the
+/// same error whether or not the username exists.
+async fn reject_login(
+    state: &ServerState,
+    username: &str,
+    ip: Option<IpAddr>,
+    reason: LoginFailure,
+) -> Response<Full<Bytes>> {
+    state
+        .login_guard
+        .record_failure(username, ip, Instant::now());
+    audit_failure(state, username, ip, reason).await;
+    error_response(StatusCode::UNAUTHORIZED, "Invalid credentials")
+}
+
+async fn audit_failure(
+    state: &ServerState,
+    username: &str,
+    ip: Option<IpAddr>,
+    reason: LoginFailure,
+) {
+    state
+        .metrics
+        .record_auth(AuthEndpoint::Login, Err(reason.into()));
+    let username: String = username.chars().take(MAX_TRACKED_USERNAME).collect();
+    let ip = ip.map(|ip| ip.to_string());
+    warn!(
+        "Failed login for {:?} from {}: {}",
+        username,
+        ip.as_deref().unwrap_or("unknown address"),
+        reason.as_str()
+    );
+    if let Err(e) =
+        database::record_login_failure(&state.db_pool, &username, ip.as_deref(), reason).await
+    {
+        error!("Failed to record login failure: {}", e);
+    }
+}
+
+/// The login/refresh reply: a fresh access JWT plus the device's refresh token.
+fn token_response(
+    state: &ServerState,
+    username: String,
+    refresh_token: &RefreshToken,
+) -> Response<Full<Bytes>> {
+    let lifetime = state.config.access_token_lifetime();
+    match jwt::create_jwt(
+        username.clone(),
+        refresh_token.session_id.clone(),
+        Utc::now() + lifetime,
+        state.config.jwt_secret(),
+    ) {
+        Ok(token) => json_response(
+            StatusCode::OK,
+            json!({
+                "token": token,
+                "refresh_token": refresh_token.encode(),
+                "expires_in": lifetime.num_seconds(),
+                "username": username,
+            }),
+        ),
+        Err(_) => error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to create token"),
+    }
+}
+
+#[derive(Deserialize)]
+struct RefreshRequest {
+    refresh_token: String,
+}
+
+/// `POST /api/auth/refresh`: trades a refresh token for a new access token
+/// and a new refresh token. Each refresh token works once; presenting a used
+/// one signs that device out, since it means the token was copied.
+pub async fn handle_refresh(
+    req: hyper::Request<Body>,
+    state: ServerState,
+) -> Response<Full<Bytes>> {
+    let Some(request) = read_json::<RefreshRequest>(req).await else {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid request payload");
+    };
+    match refresh(&state, &request.refresh_token).await {
+        Ok(response) => response,
+        Err(e) => error_response(StatusCode::UNAUTHORIZED, &e),
+    }
+}
+
+async fn refresh(state: &ServerState, token: &str) -> Result<Response<Full<Bytes>>, String> {
+    const INVALID: &str = "Invalid refresh token";
+    let presented = RefreshToken::parse(token).ok_or(INVALID)?;
+    let session = database::get_session(&state.db_pool, &presented.session_id)
+        .await
+        .map_err(|e| format!("Database error: {}", e))?
+        .ok_or(INVALID)?;
+    if Utc::now() > session.expires_at.and_utc() {
+        return Err("Session expired".to_string());
+    }
+    let presented_hash = presented.hash();
+    if presented_hash != session.refresh_token_hash {
+        warn!(
+            "Reused refresh token for session {}; signing it out",
+            session.id
+        );
+        revoke_session(state, session.account_id, &session.id).await;
+        return Err(INVALID.to_string());
+    }
+    let account = database::get_account_by_id(&state.db_pool, session.account_id)
+        .await
+        .map_err(|e| format!("Database error: {}", e))?
+        .ok_or("Account not found")?;
+
+    let next = RefreshToken::generate(&session.id);
+    let expires_at = Utc::now() + state.config.session_lifetime();
+    let rotated = database::rotate_refresh_token(
+        &state.db_pool,
+        &session.id,
+        &presented_hash,
+        &next.hash(),
+        expires_at,
+    )
+    .await
+    .map_err(|e| format!("Database error: {}", e))?;
+    if !rotated {
+        // A concurrent refresh with the same token won the race.
+        return Err(INVALID.to_string());
+    }
+    Ok(token_response(state, account.username, &next))
+}
+
+/// Resolves an access JWT to its account and session, rejecting tokens whose
+/// session was signed out or has expired. The session decides the account:
+/// `sub` is informational, since an upgraded guest keeps its sessions under
+/// a new username.
+pub async fn authenticate_session(
+    token: &str,
+    state: &ServerState,
+) -> Result<(Account, Session), String> {
+    let claims = jwt::decode_jwt(token, state.config.jwt_secret())
+        .map_err(|e| format!("Invalid token: {}", e))?;
+
+    let session = database::get_session(&state.db_pool, &claims.sid)
+        .await
+        .map_err(|e| format!("Database error: {}", e))?
+        .ok_or_else(|| "Session signed out".to_string())?;
+    if Utc::now() > session.expires_at.and_utc() {
+        return Err("Session expired".to_string());
+    }
+
+    let account = database::get_account_by_id(&state.db_pool, session.account_id)
+        .await
+        .map_err(|e| format!("Database error: {}", e))?
+        .ok_or_else(|| "Account not found".to_string())?;
+
+    Ok((account, session))
+}
+
+/// [`authenticate_session`] for callers that only need the account.
+pub async fn authenticate_token(token: &str, state: &ServerState) -> Result<Account, String> {
+    authenticate_session(token, state)
+        .await
+        .map(|(account, _)| account)
+}
+
+/// The token from an `Authorization: Bearer <token>` header.
+pub fn bearer_token(req: &hyper::Request<Body>) -> Option<&str> {
+    req.headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
+
+/// Deletes one session and closes the socket it opened, if any.
+async fn revoke_session(state: &ServerState, account_id: i64, session_id: &str) -> bool {
+    let deleted = match database::delete_session(&state.db_pool, account_id, session_id).await {
+        Ok(deleted) => deleted,
+        Err(e) => {
+            error!("Failed to delete session: {}", e);
+            return false;
+        }
+    };
+    state
+        .disconnect(account_id, Some(session_id), GameError::SessionRevoked)
+        .await;
+    deleted
+}
+
+/// `/api/auth/sessions` and `/api/auth/sessions/{id}/revoke`: the caller's
+/// signed-in devices.
+pub async fn handle_sessions(
+    req: hyper::Request<Body>,
+    state: ServerState,
+) -> Response<Full<Bytes>> {
+    let Some(token) = bearer_token(&req) else {
+        return error_response(StatusCode::UNAUTHORIZED, "Missing token");
+    };
+    let (account, current) = match authenticate_session(token, &state).await {
+        Ok(authenticated) => authenticated,
+        Err(e) => return error_response(StatusCode::UNAUTHORIZED, &e),
+    };
+    let segments: Vec<&str> = req
+        .uri()
+        .path()
+        .trim_start_matches("/api/auth/sessions")
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    match (req.method(), segments.as_slice()) {
+        (&Method::GET, []) => match database::list_sessions(&state.db_pool, account.id).await {
+            Ok(sessions) => {
+                let sessions: Vec<Value> = sessions
+                    .into_iter()
+                    .map(|session| {
+                        let is_current = session.id == current.id;
+                        let mut value = json!(session);
+                        value["current"] = json!(is_current);
+                        value
+                    })
+                    .collect();
+                json_response(StatusCode::OK, json!({ "sessions": sessions }))
+            }
+            Err(e) => {
+                error!("Failed to list sessions: {}", e);
+                error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to list sessions")
+            }
+        },
+        (&Method::POST, [session_id, "revoke"]) => {
+            if revoke_session(&state, account.id, session_id).await {
+                json_response(StatusCode::OK, json!({ "revoked": session_id }))
+            } else {
+                error_response(StatusCode::NOT_FOUND, "No such session")
             }
         }
+        _ => error_response(StatusCode::NOT_FOUND, "Not found"),
+    }
+}
+
+/// Signs the calling device out. Other devices stay signed in.
+pub async fn handle_logout(req: hyper::Request<Body>, state: ServerState) -> Response<Full<Bytes>> {
+    if let Some(token) = bearer_token(&req)
+        && let Ok((account, session)) = authenticate_session(token, &state).await
+        && let Err(e) = database::delete_session(&state.db_pool, account.id, &session.id).await
+    {
+        error!("Failed to clear session: {}", e);
+        return Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Full::new(Bytes::from(
+                serde_json::to_string(&serde_json::json!({"error": "Failed to logout"})).unwrap(),
+            )))
+            .unwrap();
     }
     // Always return OK, even if the token is invalid or the session doesn't exist.
     // This prevents leaking information about session validity.
@@ -204,3 +462,171 @@ pub async fn handle_logout(req: hyper::Request<Body>, state: ServerState) -> Res
         )))
         .unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::test_support::migrated_test_state;
+    use http_body_util::BodyExt;
+
+    /// An account with one session; returns the session's refresh token.
+    async fn signed_in(state: &ServerState, username: &str) -> RefreshToken {
+        let new_account = NewAccount {
+            username: username.to_string(),
+            password: "password".to_string(),
+        };
+        let account = database::create_account(&state.db_pool, new_account)
+            .await
+            .unwrap();
+        let token = RefreshToken::generate(&Uuid::new_v4().to_string());
+        let expires_at = Utc::now() + state.config.session_lifetime();
+        database::create_session(
+            &state.db_pool,
+            account.id,
+            &token.session_id,
+            &token.hash(),
+            Some("test"),
+            expires_at,
+        )
+        .await
+        .unwrap();
+        token
+    }
+
+    async fn body_json(response: Response<Full<Bytes>>) -> Value {
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn refresh_rotates_the_token_and_issues_a_working_access_token() {
+        let state = migrated_test_state().await;
+        let first = signed_in(&state, "alice").await;
+
+        let body = body_json(refresh(&state, &first.encode()).await.unwrap()).await;
+        let second = RefreshToken::parse(body["refresh_token"].as_str().unwrap()).unwrap();
+        assert_eq!(second.session_id, first.session_id);
+        assert_ne!(second.secret, first.secret);
+
+        let (account, session) = authenticate_session(body["token"].as_str().unwrap(), &state)
+            .await
+            .unwrap();
+        assert_eq!(account.username, "alice");
+        assert_eq!(session.id, first.session_id);
+    }
+
+    #[tokio::test]
+    async fn reusing_a_refresh_token_signs_the_device_out() {
+        let state = migrated_test_state().await;
+        let first = signed_in(&state, "bob").await;
+        let body = body_json(refresh(&state, &first.encode()).await.unwrap()).await;
+        let access_token = body["token"].as_str().unwrap().to_string();
+
+        assert!(refresh(&state, &first.encode()).await.is_err());
+        let second = body["refresh_token"].as_str().unwrap();
+        assert!(
+            refresh(&state, second).await.is_err(),
+            "the whole session is gone, not just the old token"
+        );
+        assert!(authenticate_session(&access_token, &state).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rejected_logins_are_audited_and_throttled() {
+        let state = migrated_test_state().await;
+        let ip = Some(IpAddr::from([10, 0, 0, 1]));
+        for reason in [LoginFailure::UnknownUser, LoginFailure::BadPassword] {
+            let response = reject_login(&state, "ghost", ip, reason).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert_eq!(
+                body_json(response).await["error"],
+                "Invalid credentials",
+                "the same error whether or not the account exists"
+            );
+        }
+        for _ in 0..2 {
+            reject_login(&state, "ghost", ip, LoginFailure::UnknownUser).await;
+        }
+        assert!(
+            state
+                .login_guard
+                .check("ghost", None, Instant::now())
+                .is_err()
+        );
+
+        let rows = sqlx::query!("SELECT username, ip, reason FROM login_failures ORDER BY id")
+            .fetch_all(&state.db_pool)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[0].username, "ghost");
+        assert_eq!(rows[0].ip.as_deref(), Some("10.0.0.1"));
+        assert_eq!(rows[1].reason, "bad_password");
+        let metrics = crate::metrics::render(&state).await;
+        assert!(
+            metrics.contains(
+                "nicktd_auth_failures_total{endpoint=\"login\",reason=\"unknown_user\"} 3"
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn guests_without_a_session_are_purged() {
+        let state = migrated_test_state().await;
+        let kept = database::create_guest_account(&state.db_pool, &guest_username())
+            .await
+            .unwrap();
+        let abandoned = database::create_guest_account(&state.db_pool, &guest_username())
+            .await
+            .unwrap();
+        signed_in(&state, "regular").await;
+        let response = start_session(&state, kept.clone(), None).await;
+        assert_eq!(
+            body_json(response).await["username"],
+            kept.username.as_str()
+        );
+
+        assert_eq!(
+            database::purge_abandoned_guests(&state.db_pool)
+                .await
+                .unwrap(),
+            1
+        );
+        let exists = |id| database::get_account_by_id(&state.db_pool, id);
+        assert!(exists(kept.id).await.unwrap().is_some());
+        assert!(exists(abandoned.id).await.unwrap().is_none());
+        assert!(
+            database::get_account_by_username(&state.db_pool, "regular")
+                .await
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[tokio::test]
+    async fn sessions_are_independent_per_device() {
+        let state = migrated_test_state().await;
+        let phone = signed_in(&state, "carol").await;
+        let account = database::get_account_by_username(&state.db_pool, "carol")
+            .await
+            .unwrap()
+            .unwrap();
+        let laptop = RefreshToken::generate("laptop");
+        let expires_at = Utc::now() + state.config.session_lifetime();
+        database::create_session(
+            &state.db_pool,
+            account.id,
+            &laptop.session_id,
+            &laptop.hash(),
+            None,
+            expires_at,
+        )
+        .await
+        .unwrap();
+
+        assert!(revoke_session(&state, account.id, "laptop").await);
+        assert!(!revoke_session(&state, account.id, "laptop").await);
+        assert!(refresh(&state, &laptop.encode()).await.is_err());
+        assert!(refresh(&state, &phone.encode()).await.is_ok());
+    }
+}
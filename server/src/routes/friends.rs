@@ -0,0 +1,261 @@
+use super::{
+    auth::{authenticate_session, bearer_token},
+    error_response, json_response, read_json,
+};
+use crate::{
+    database,
+    model::{
+        account::Account,
+        friend::{FriendRequestOutcome, FriendsList, Presence},
+    },
+    state::{Activity, ServerState},
+};
+use http_body_util::Full;
+use hyper::{
+    Method, Request, Response, StatusCode,
+    body::{Bytes, Incoming as Body},
+};
+use log::error;
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Deserialize)]
+struct FriendRequest {
+    username: String,
+}
+
+#[derive(Deserialize)]
+struct FriendTarget {
+    account_id: i64,
+}
+
+/// Entry point for `/api/friends` and everything under it: the caller's
+/// friends list and requests. Requires a valid session token.
+pub async fn handle_friends(req: Request<Body>, state: ServerState) -> Response<Full<Bytes>> {
+    let Some(token) = bearer_token(&req) else {
+        return error_response(StatusCode::UNAUTHORIZED, "Missing token");
+    };
+    let account = match authenticate_session(token, &state).await {
+        Ok((account, _)) => account,
+        Err(e) => return error_response(StatusCode::UNAUTHORIZED, &e),
+    };
+
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    match (method, path.as_str()) {
+        (Method::GET, "/api/friends") => list_friends(&state, &account).await,
+        (Method::POST, "/api/friends/request") => match read_json(req).await {
+            Some(body) => send_request(&state, &account, body).await,
+            None => error_response(StatusCode::BAD_REQUEST, "Invalid request payload"),
+        },
+        (Method::POST, "/api/friends/accept") => match read_json(req).await {
+            Some(body) => accept_request(&state, &account, body).await,
+            None => error_response(StatusCode::BAD_REQUEST, "Invalid request payload"),
+        },
+        (Method::POST, "/api/friends/remove") => match read_json(req).await {
+            Some(body) => remove_friend(&state, &account, body).await,
+            None => error_response(StatusCode::BAD_REQUEST, "Invalid request payload"),
+        },
+        _ => error_response(StatusCode::NOT_FOUND, "Not found"),
+    }
+}
+
+/// Friends with their presence, plus pending requests both ways.
+async fn list_friends(state: &ServerState, account: &Account) -> Response<Full<Bytes>> {
+    let links = match database::list_friend_links(&state.db_pool, account.id).await {
+        Ok(links) => links,
+        Err(e) => {
+            error!("Failed to list friends: {}", e);
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to list friends");
+        }
+    };
+    let online = state.active_connections.lock().await;
+    let list = FriendsList::build(links, |id| match online.get(&id) {
+        None => Presence::Offline,
+        Some(c) if c.activity.get() == Activity::InMatch => Presence::InMatch,
+        Some(_) => Presence::Online,
+    });
+    json_response(StatusCode::OK, json!(list))
+}
+
+async fn send_request(
+    state: &ServerState,
+    account: &Account,
+    request: FriendRequest,
+) -> Response<Full<Bytes>> {
+    let other = match database::get_account_by_username(&state.db_pool, &request.username).await {
+        Ok(Some(other)) => other,
+        Ok(None) => return error_response(StatusCode::NOT_FOUND, "No such account"),
+        Err(e) => {
+            error!("Failed to look up friend: {}", e);
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to send friend request",
+            );
+        }
+    };
+    if other.id == account.id {
+        return error_response(StatusCode::BAD_REQUEST, "You cannot befriend yourself");
+    }
+    match database::send_friend_request(&state.db_pool, account.id, other.id).await {
+        Ok(FriendRequestOutcome::Sent) => json_response(
+            StatusCode::OK,
+            json!({ "id": other.id, "username": other.username, "status": "sent" }),
+        ),
+        Ok(FriendRequestOutcome::Accepted) => json_response(
+            StatusCode::OK,
+            json!({ "id": other.id, "username": other.username, "status": "accepted" }),
+        ),
+        Ok(FriendRequestOutcome::AlreadyPending) => {
+            error_response(StatusCode::CONFLICT, "Friend request already sent")
+        }
+        Ok(FriendRequestOutcome::AlreadyFriends) => {
+            error_response(StatusCode::CONFLICT, "Already friends")
+        }
+        Err(e) => {
+            error!("Failed to send friend request: {}", e);
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to send friend request",
+            )
+        }
+    }
+}
+
+async fn accept_request(
+    state: &ServerState,
+    account: &Account,
+    target: FriendTarget,
+) -> Response<Full<Bytes>> {
+    match database::accept_friend_request(&state.db_pool, account.id, target.account_id).await {
+        Ok(true) => json_response(StatusCode::OK, json!({ "message": "Friend added" })),
+        Ok(false) => error_response(StatusCode::NOT_FOUND, "No such friend request"),
+        Err(e) => {
+            error!("Failed to accept friend request: {}", e);
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to accept friend request",
+            )
+        }
+    }
+}
+
+/// Unfriends, declines an incoming request or withdraws an outgoing one.
+async fn remove_friend(
+    state: &ServerState,
+    account: &Account,
+    target: FriendTarget,
+) -> Response<Full<Bytes>> {
+    match database::remove_friend(&state.db_pool, account.id, target.account_id).await {
+        Ok(true) => json_response(StatusCode::OK, json!({ "message": "Friend removed" })),
+        Ok(false) => error_response(StatusCode::NOT_FOUND, "Not a friend"),
+        Err(e) => {
+            error!("Failed to remove friend: {}", e);
+            error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to remove friend")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::account::NewAccount;
+    use crate::state::test_support::migrated_test_state;
+    use crate::state::{ActiveConnection, ActivityCell};
+    use http_body_util::BodyExt;
+    use serde_json::Value;
+    use tokio::sync::mpsc;
+
+    async fn register(state: &ServerState, username: &str) -> Account {
+        let new_account = NewAccount {
+            username: username.to_string(),
+            password: "password".to_string(),
+        };
+        database::create_account(&state.db_pool, new_account)
+            .await
+            .unwrap()
+    }
+
+    async fn body_json(response: Response<Full<Bytes>>) -> Value {
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    fn request(username: &str) -> FriendRequest {
+        FriendRequest {
+            username: username.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn request_accept_and_list_with_presence() {
+        let state = migrated_test_state().await;
+        let alice = register(&state, "alice").await;
+        let bob = register(&state, "bob").await;
+
+        let response = send_request(&state, &alice, request("nobody")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = send_request(&state, &alice, request("alice")).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = send_request(&state, &alice, request("bob")).await;
+        assert_eq!(body_json(response).await["status"], "sent");
+        let response = send_request(&state, &alice, request("bob")).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let pending = body_json(list_friends(&state, &bob).await).await;
+        assert_eq!(pending["incoming"][0]["username"], "alice");
+        assert_eq!(pending["friends"], json!([]));
+
+        let target = FriendTarget {
+            account_id: alice.id,
+        };
+        let response = accept_request(&state, &bob, target).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        state.active_connections.lock().await.insert(
+            bob.id,
+            ActiveConnection {
+                session_id: "sid".into(),
+                kill_tx: mpsc::channel(1).0,
+                notify_tx: mpsc::channel(1).0,
+                activity: ActivityCell::default(),
+            },
+        );
+        let list = body_json(list_friends(&state, &alice).await).await;
+        assert_eq!(
+            list["friends"],
+            json!([{ "id": bob.id, "username": "bob", "online": true, "in_match": false }])
+        );
+        let activity = state.active_connections.lock().await[&bob.id]
+            .activity
+            .clone();
+        activity.set(Activity::InMatch);
+        let list = body_json(list_friends(&state, &alice).await).await;
+        assert_eq!(list["friends"][0]["in_match"], true);
+        assert_eq!(list["outgoing"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn crossed_requests_become_a_friendship_and_remove_ends_it() {
+        let state = migrated_test_state().await;
+        let alice = register(&state, "alice").await;
+        let bob = register(&state, "bob").await;
+
+        send_request(&state, &alice, request("bob")).await;
+        let response = send_request(&state, &bob, request("alice")).await;
+        assert_eq!(body_json(response).await["status"], "accepted");
+        assert!(
+            database::are_friends(&state.db_pool, alice.id, bob.id)
+                .await
+                .unwrap()
+        );
+
+        let target = FriendTarget { account_id: bob.id };
+        let response = remove_friend(&state, &alice, target).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let list = body_json(list_friends(&state, &bob).await).await;
+        assert_eq!(list["friends"], json!([]));
+        let again = remove_friend(&state, &alice, FriendTarget { account_id: bob.id }).await;
+        assert_eq!(again.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,11 @@
+use crate::{metrics, state::ServerState};
+use http_body_util::Full;
+use hyper::{Response, StatusCode, body::Bytes, header};
+
+pub async fn handle_metrics(state: ServerState) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/plain; version=0.0.4")
+        .body(Full::new(Bytes::from(metrics::render(&state).await)))
+        .unwrap()
+}
@@ -1,2 +1,33 @@
+pub mod account;
+pub mod admin;
 pub mod auth;
+pub mod friends;
+pub mod metrics;
 pub mod ws;
+
+use http_body_util::{BodyExt, Full};
+use hyper::{
+    Request, Response, StatusCode,
+    body::{Bytes, Incoming as Body},
+    header,
+};
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+
+fn json_response(status: StatusCode, body: Value) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(body.to_string())))
+        .unwrap()
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response<Full<Bytes>> {
+    json_response(status, json!({ "error": message }))
+}
+
+/// The request body parsed as JSON, or `None` if it is unreadable or malformed.
+async fn read_json<T: DeserializeOwned>(req: Request<Body>) -> Option<T> {
+    let body = req.collect().await.ok()?.to_bytes();
+    serde_json::from_slice(&body).ok()
+}
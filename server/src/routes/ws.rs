@@ -1,9 +1,10 @@
 use crate::{
-    database, handler,
-    model::{account::Account, jwt, messages::ServerMessage},
-    state::{ServerState, UpgradedWebSocket},
+    config::SecondDevicePolicy,
+    handler,
+    metrics::{AuthEndpoint, AuthFailure},
+    model::{account::Account, game_error::GameError, messages::ServerMessage, session::Session},
+    state::{ActiveConnection, Activity, ActivityCell, Inbox, ServerState, UpgradedWebSocket},
 };
-use chrono::Utc;
 use futures_util::stream::SplitSink;
 use futures_util::{SinkExt, StreamExt};
 use http_body_util::Full;
@@ -12,14 +13,26 @@ use hyper::{Request, Response, StatusCode};
 use log::error;
 use std::collections::HashMap;
 use tokio::sync::mpsc;
-use tokio_tungstenite::tungstenite::protocol::Message;
+use tokio_tungstenite::tungstenite::protocol::{Message, WebSocketConfig};
+
+/// Pushed messages a connection may have waiting; see
+/// `ActiveConnection::notify_tx`.
+const NOTIFY_CAPACITY: usize = 16;
 
 pub async fn handle_ws_upgrade(
     req: &mut Request<Body>,
     state: ServerState,
 ) -> Response<Full<Bytes>> {
-    let authenticated_account = match authenticate_websocket_request(&req, &state).await {
-        Ok(account) => account,
+    let authenticated = authenticate_websocket_request(req, &state).await;
+    state.metrics.record_auth(
+        AuthEndpoint::WebSocket,
+        authenticated
+            .as_ref()
+            .map(|_| ())
+            .map_err(|_| AuthFailure::InvalidToken),
+    );
+    let (authenticated_account, session) = match authenticated {
+        Ok(authenticated) => authenticated,
         Err(e) => {
             return Response::builder()
                 .status(StatusCode::UNAUTHORIZED)
@@ -28,6 +41,13 @@ pub async fn handle_ws_upgrade(
         }
     };
 
+    if state.shutdown_deadline.borrow().is_some() {
+        return Response::builder()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .body(Full::new(Bytes::from("Server is shutting down")))
+            .unwrap();
+    }
+
     if !hyper_tungstenite::is_upgrade_request(req) {
         return Response::builder()
             .status(StatusCode::BAD_REQUEST)
@@ -35,7 +55,13 @@ pub async fn handle_ws_upgrade(
             .unwrap();
     }
 
-    let (response, websocket) = match hyper_tungstenite::upgrade(req, None) {
+    // Let tungstenite refuse huge frames before buffering them; smaller
+    // oversized ones are left to the limiter so they count as invalid.
+    let max_bytes = state.rate_limits.transport_limit();
+    let ws_config = WebSocketConfig::default()
+        .max_message_size(Some(max_bytes))
+        .max_frame_size(Some(max_bytes));
+    let (response, websocket) = match hyper_tungstenite::upgrade(req, Some(ws_config)) {
         Ok((res, ws)) => (res, ws),
         Err(e) => {
             error!("WebSocket upgrade error: {}", e);
@@ -57,6 +83,7 @@ pub async fn handle_ws_upgrade(
                     state,
                     authenticated_account.id,
                     authenticated_account.username,
+                    session.id,
                 )
                 .await;
             }
@@ -72,7 +99,7 @@ pub async fn handle_ws_upgrade(
 async fn authenticate_websocket_request(
     req: &Request<Body>,
     state: &ServerState,
-) -> Result<Account, String> {
+) -> Result<(Account, Session), String> {
     let uri = req.uri();
     let query_params: HashMap<_, _> = uri
         .query()
@@ -87,29 +114,7 @@ async fn authenticate_websocket_request(
         .get("token")
         .ok_or_else(|| "Missing token".to_string())?;
 
-    let claims = jwt::decode_jwt(token).map_err(|e| format!("Invalid token: {}", e))?;
-
-    let account = database::get_account_by_username(&state.db_pool, &claims.sub)
-        .await
-        .map_err(|e| format!("Database error: {}", e))?
-        .ok_or_else(|| "Account not found".to_string())?;
-
-    // CRITICAL CHECK 1: Session ID Match
-    if account.session_id.as_deref() != Some(&claims.sid) {
-        return Err("Session ID mismatch or session invalidated".to_string());
-    }
-
-    // CRITICAL CHECK 2: Session Expiration
-    if let Some(expires_at) = account.session_expires_at {
-        if Utc::now() > expires_at.and_utc() {
-            return Err("Session expired".to_string());
-        }
-    } else {
-        // If session_id is present but session_expires_at is null, it's an invalid state
-        return Err("Invalid session state: expiration missing".to_string());
-    }
-
-    Ok(account)
+    crate::routes::auth::authenticate_session(token, state).await
 }
 
 async fn handle_connection(
@@ -117,19 +122,56 @@ async fn handle_connection(
     server_state: ServerState,
     account_id: i64,
     username: String,
+    session_id: String,
 ) {
-    // 1. Manage Active Connection
-    let (kill_tx, mut kill_rx) = mpsc::channel(1);
+    let (mut ws_sender, mut ws_receiver) = ws_stream.split();
+
+    // 1. Protocol handshake, before this socket can displace an existing session.
+    match handler::handshake::handshake(&mut ws_sender, &mut ws_receiver).await {
+        handler::handshake::HandshakeResult::Accepted => {}
+        handler::handshake::HandshakeResult::Rejected => {
+            let _ = ws_sender.close().await;
+            return;
+        }
+        handler::handshake::HandshakeResult::ClientDisconnected => return,
+    }
+
+    // 2. Manage Active Connection
+    let (kill_tx, kill_rx) = mpsc::channel(1);
+    // `notify_tx` stays alive with the socket, so `notify_rx` never closes.
+    let (notify_tx, notify_rx) = mpsc::channel(NOTIFY_CAPACITY);
+    let activity = ActivityCell::default();
+    let mut inbox = Inbox {
+        kill_rx,
+        notify_rx,
+        activity: activity.clone(),
+    };
     {
         let mut active_conns = server_state.active_connections.lock().await;
-        if let Some(old_tx) = active_conns.get(&account_id) {
-            let _ = old_tx.send(()).await; // Kill existing connection
+        if let Some(old) = active_conns.get(&account_id) {
+            match server_state.config.second_device {
+                SecondDevicePolicy::Replace => {
+                    let _ = old.kill_tx.send(GameError::SessionReplaced).await; // Kill existing connection
+                }
+                SecondDevicePolicy::Reject => {
+                    drop(active_conns);
+                    let _ = send_message(&mut ws_sender, GameError::AlreadyConnected.into()).await;
+                    let _ = ws_sender.close().await;
+                    return;
+                }
+            }
         }
-        active_conns.insert(account_id, kill_tx);
+        active_conns.insert(
+            account_id,
+            ActiveConnection {
+                session_id,
+                kill_tx,
+                notify_tx: notify_tx.clone(),
+                activity,
+            },
+        );
     }
 
-    let (mut ws_sender, mut ws_receiver) = ws_stream.split();
-
     if send_message(&mut ws_sender, ServerMessage::PlayerId(account_id))
         .await
         .is_err()
@@ -143,81 +185,82 @@ async fn handle_connection(
         return;
     }
 
+    // One limiter for the whole connection, so re-queueing never refills it.
+    let mut limiter = handler::rate_limit::ConnectionLimiter::new(server_state.rate_limits.clone());
+
     let mut final_match_id: Option<u64> = None;
     let mut forced_disconnect = false;
+    // Set when a finished match is followed straight by a rematch.
+    let mut rematch: Option<u64> = None;
 
     loop {
-        match handler::pre_game::pre_game_loop(
+        let match_id = match rematch.take() {
+            Some(match_id) => match_id,
+            None => match handler::pre_game::pre_game_loop(
+                &mut ws_sender,
+                &mut ws_receiver,
+                &server_state,
+                account_id,
+                username.clone(),
+                &mut inbox,
+                &mut limiter,
+            )
+            .await
+            {
+                handler::pre_game::PreGameLoopResult::Joined(match_id) => match_id,
+                handler::pre_game::PreGameLoopResult::ForceDisconnect(reason) => {
+                    forced_disconnect = true;
+                    let _ = send_message(&mut ws_sender, reason.into()).await;
+                    break;
+                }
+                handler::pre_game::PreGameLoopResult::ClientDisconnected => {
+                    break;
+                }
+            },
+        };
+        final_match_id = Some(match_id);
+        inbox.activity.set(Activity::InMatch);
+        let result = handler::in_game::in_game_loop(
             &mut ws_sender,
             &mut ws_receiver,
             &server_state,
+            match_id,
             account_id,
-            username.clone(),
-            &mut kill_rx,
+            &mut inbox.kill_rx,
+            &mut limiter,
         )
-        .await
-        {
-            handler::pre_game::PreGameLoopResult::Joined(match_id) => {
-                final_match_id = Some(match_id);
-                let result = handler::in_game::in_game_loop(
-                    &mut ws_sender,
-                    &mut ws_receiver,
-                    &server_state,
-                    match_id,
-                    account_id,
-                    &mut kill_rx,
-                )
-                .await;
+        .await;
 
-                match result {
-                    handler::in_game::InGameLoopResult::PlayerLeft => {
-                        handler::cleanup::remove_player_from_match(
-                            match_id,
-                            account_id,
-                            &server_state,
-                        )
-                        .await;
-                        final_match_id = None;
-                        continue;
-                    }
-                    handler::in_game::InGameLoopResult::ClientDisconnected => break,
-                    handler::in_game::InGameLoopResult::ForceDisconnect => {
-                        forced_disconnect = true;
-                        // Send error message to client before closing
-                        let _ = send_message(
-                            &mut ws_sender,
-                            ServerMessage::Error("Logged in from another location".into()),
-                        )
-                        .await;
-                        break;
-                    }
-                }
+        match result {
+            handler::in_game::InGameLoopResult::PlayerLeft => {
+                handler::cleanup::remove_player_from_match(match_id, account_id, &server_state)
+                    .await;
+                final_match_id = None;
+                continue;
             }
-            handler::pre_game::PreGameLoopResult::ForceDisconnect => {
-                forced_disconnect = true;
-                let _ = send_message(
-                    &mut ws_sender,
-                    ServerMessage::Error("Logged in from another location".into()),
-                )
-                .await;
-                break;
+            handler::in_game::InGameLoopResult::Rematch(next_match_id) => {
+                // The old match is over, so leaving it forfeits nothing.
+                handler::cleanup::remove_player_from_match(match_id, account_id, &server_state)
+                    .await;
+                final_match_id = None;
+                handler::pre_game::announce_match(&mut ws_sender).await;
+                rematch = Some(next_match_id);
+                continue;
             }
-            handler::pre_game::PreGameLoopResult::ClientDisconnected => {
+            handler::in_game::InGameLoopResult::ClientDisconnected => break,
+            handler::in_game::InGameLoopResult::ForceDisconnect(reason) => {
+                forced_disconnect = true;
+                // Send error message to client before closing
+                let _ = send_message(&mut ws_sender, reason.into()).await;
                 break;
             }
         }
     }
 
-    // Call cleanup here, after the loop breaks
+    // Call cleanup here, after the loop breaks. The session outlives the
+    // socket: the device stays signed in until it logs out or is revoked.
     if let Some(match_id) = final_match_id {
-        handler::cleanup::cleanup(match_id, account_id, &server_state).await;
-    } else {
-        // Only clear session if this wasn't a forced disconnect (i.e. replaced by new session)
-        if !forced_disconnect {
-            if let Err(e) = database::clear_session(&server_state.db_pool, account_id).await {
-                log::error!("Failed to clear session for player {}: {}", account_id, e);
-            }
-        }
+        handler::cleanup::remove_player_from_match(match_id, account_id, &server_state).await;
     }
 
     // Remove from active connections map IF it is still THIS connection
@@ -227,7 +270,8 @@ async fn handle_connection(
         // Optimization: We can't easily check if the value in the map is *our* sender
         // without comparing pointers or IDs, but Sender doesn't expose that easily.
         // However, if we were forced_disconnect, we know the map has already been updated
-        // with the NEW sender, so we should NOT remove it.
+        // with the NEW sender (or the entry was removed by `ServerStateData::disconnect`), so we should
+        // NOT remove it.
         if !forced_disconnect {
             active_conns.remove(&account_id);
         }
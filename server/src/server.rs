@@ -1,27 +1,43 @@
-use crate::{database, router::router, state::ServerStateData};
+use crate::{config::Config, database, handler::shutdown, router::router, state::ServerStateData};
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
 use hyper_util::rt::TokioIo;
 use log::{error, info};
-use std::net::SocketAddr;
 use tokio::net::TcpListener;
 
 pub async fn run() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let db_pool = database::init_db().await.unwrap();
-    let server_state = ServerStateData::new(db_pool);
+    let config = Config::load()?;
+    let db_pool = database::init_db(&config.database_path, config.db_pool_size).await?;
+    let purged = database::purge_abandoned_guests(&db_pool).await?;
+    if purged > 0 {
+        info!("Purged {} abandoned guest accounts", purged);
+    }
+    let addr = config.bind_addr;
+    let server_state = ServerStateData::new(db_pool, config);
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], 9001));
     let listener = TcpListener::bind(addr).await?;
     info!("Server listening on {}", addr);
 
+    let signal = shutdown::wait_for_signal();
+    tokio::pin!(signal);
     loop {
-        let (stream, _) = listener.accept().await?;
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = &mut signal => break,
+        };
         let io = TokioIo::new(stream);
         let state_clone = server_state.clone();
 
         tokio::task::spawn(async move {
             if let Err(err) = http1::Builder::new()
-                .serve_connection(io, service_fn(move |req| router(req, state_clone.clone())))
+                .serve_connection(
+                    io,
+                    service_fn(move |mut req| {
+                        // Read by handlers that need the client address (login throttling).
+                        req.extensions_mut().insert(peer);
+                        router(req, state_clone.clone())
+                    }),
+                )
                 .with_upgrades()
                 .await
             {
@@ -29,4 +45,21 @@ pub async fn run() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             }
         });
     }
+
+    // Stop accepting before draining; open sockets keep running until exit.
+    drop(listener);
+    shutdown::drain(&server_state).await;
+    Ok(())
+}
+
+/// Marks an existing account as an admin, for bootstrapping the first
+/// operator without a running server.
+pub async fn grant_admin(username: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let config = Config::load()?;
+    let db_pool = database::init_db(&config.database_path, config.db_pool_size).await?;
+    if !database::set_admin(&db_pool, username, true).await? {
+        return Err(format!("no account named {username:?}").into());
+    }
+    println!("Granted admin to {username}");
+    Ok(())
 }
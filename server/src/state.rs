@@ -1,48 +1,224 @@
-use crate::model::lobby::Lobby;
+use crate::config::Config;
+use crate::handler::challenge::Challenge;
+use crate::handler::private_lobby::PrivateLobby;
+use crate::handler::rate_limit::RateLimitConfig;
+use crate::handler::ready_check::{ReadyCheck, ReadyCheckTicket};
+use crate::handler::shard::{MatchHandle, SimPool};
+use crate::login_guard::{LoginGuard, LoginGuardConfig};
+use crate::metrics::Metrics;
+use crate::model::game_error::GameError;
+use crate::model::messages::ServerMessage;
 use hyper::upgrade::Upgraded;
 use hyper_util::rt::TokioIo;
 use sqlx::SqlitePool;
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::sync::atomic::AtomicU64;
-use tokio::sync::{Mutex, RwLock, mpsc, oneshot};
+use std::sync::atomic::{AtomicU8, AtomicU64, Ordering};
+use std::time::Instant;
+use tokio::sync::{Mutex, RwLock, mpsc, oneshot, watch};
 use tokio_tungstenite::WebSocketStream;
 
 /// A player waiting in the matchmaking queue.
 pub struct QueuedPlayer {
     pub account_id: i64,
     pub username: String,
-    /// Fires with the match_id when this player gets paired.
-    pub match_tx: oneshot::Sender<u64>,
+    /// Fires with this player's ready-check ticket when they get paired.
+    pub paired_tx: oneshot::Sender<ReadyCheckTicket>,
+}
+
+/// The live game socket of one account.
+pub struct ActiveConnection {
+    /// Session the socket authenticated with, so signing that device out can
+    /// close it.
+    pub session_id: String,
+    /// Kill switch; the error sent is relayed to the client before the
+    /// connection closes.
+    pub kill_tx: mpsc::Sender<GameError>,
+    /// Messages pushed by other players (e.g. a friend's challenge). The
+    /// connection relays them while the player is in the menu; a full
+    /// channel means the player is not reading them.
+    pub notify_tx: mpsc::Sender<ServerMessage>,
+    /// What the player is doing; the socket task keeps it current.
+    pub activity: ActivityCell,
+}
+
+/// The receiving ends of an `ActiveConnection`, owned by its socket task.
+pub struct Inbox {
+    pub kill_rx: mpsc::Receiver<GameError>,
+    pub notify_rx: mpsc::Receiver<ServerMessage>,
+    pub activity: ActivityCell,
+}
+
+/// What a connected player is doing, as seen by other players.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Activity {
+    /// In the menu, relaying pushed messages (e.g. challenges) right away.
+    Idle,
+    /// Queued, in a ready check or private lobby, or waiting on a challenge.
+    Busy,
+    InMatch,
+}
+
+/// An `Activity` shared between a socket task and `active_connections`.
+/// Starts out `Busy` until the connection reaches the menu.
+#[derive(Debug, Clone, Default)]
+pub struct ActivityCell(Arc<AtomicU8>);
+
+impl ActivityCell {
+    pub fn get(&self) -> Activity {
+        match self.0.load(Ordering::Relaxed) {
+            1 => Activity::Idle,
+            2 => Activity::InMatch,
+            _ => Activity::Busy,
+        }
+    }
+
+    pub fn set(&self, activity: Activity) {
+        let value = match activity {
+            Activity::Busy => 0,
+            Activity::Idle => 1,
+            Activity::InMatch => 2,
+        };
+        self.0.store(value, Ordering::Relaxed);
+    }
 }
 
 pub struct ServerStateData {
-    /// match_id -> lobby. LOCK RULE: held only to insert/remove/get+clone the
-    /// Arc — never while holding a lobby guard, never across a network await.
-    pub matches: RwLock<HashMap<u64, Arc<Mutex<Lobby>>>>,
+    /// match_id -> handle to the shard that owns the lobby. LOCK RULE: held
+    /// only to insert/remove/get+clone a handle — never across a network await
+    /// or a `MatchHandle::with_lobby` round trip.
+    pub matches: RwLock<HashMap<u64, MatchHandle>>,
+    /// Simulation threads that own and tick every lobby.
+    pub sim: SimPool,
     pub next_match_id: AtomicU64,
     /// At most one waiter (1v1: the second joiner always pairs immediately).
     /// LOCK RULE: never overlaps a lobby guard or a network await.
     pub queue: Mutex<Option<QueuedPlayer>>,
+    /// Join code -> private lobby still waiting to start. LOCK RULE: as for
+    /// `queue`; may be held while `create_match` registers the match.
+    pub private_lobbies: Mutex<HashMap<String, PrivateLobby>>,
+    /// Ready-check id -> queue pairing waiting for both players to accept.
+    /// LOCK RULE: as for `private_lobbies`; taken after `queue`, never before.
+    pub ready_checks: Mutex<HashMap<u64, ReadyCheck>>,
+    pub next_ready_check_id: AtomicU64,
+    /// account_id -> when that account may queue again after missing a
+    /// ready check.
+    pub queue_penalties: Mutex<HashMap<i64, Instant>>,
+    /// Challenge id -> friend challenge waiting for an answer. LOCK RULE: as
+    /// for `private_lobbies`; never held while taking `active_connections`.
+    pub challenges: Mutex<HashMap<u64, Challenge>>,
+    pub next_challenge_id: AtomicU64,
     pub db_pool: SqlitePool,
-    pub active_connections: Mutex<HashMap<i64, mpsc::Sender<()>>>,
+    /// account_id -> that account's socket. At most one per account; see
+    /// `Config::second_device`.
+    pub active_connections: Mutex<HashMap<i64, ActiveConnection>>,
+    /// Per-connection message limits; each socket builds its own
+    /// `ConnectionLimiter` from this.
+    pub rate_limits: RateLimitConfig,
+    /// Failed-login backoff per username and per client IP.
+    pub login_guard: LoginGuard,
+    pub config: Config,
+    /// `Some(deadline)` once a graceful shutdown has begun; connection loops
+    /// watch it to warn players and stop matchmaking. See `handler::shutdown`.
+    pub shutdown_deadline: watch::Sender<Option<Instant>>,
+    pub metrics: Arc<Metrics>,
 }
 impl ServerStateData {
-    pub fn new(db_pool: SqlitePool) -> Arc<Self> {
+    pub fn new(db_pool: SqlitePool, config: Config) -> Arc<Self> {
+        let metrics = Arc::new(Metrics::default());
+        let sim = SimPool::new(&config, metrics.clone(), db_pool.clone());
         Arc::new(Self {
             matches: RwLock::new(HashMap::new()),
+            sim,
             next_match_id: AtomicU64::new(0),
             queue: Mutex::new(None),
+            private_lobbies: Mutex::new(HashMap::new()),
+            ready_checks: Mutex::new(HashMap::new()),
+            next_ready_check_id: AtomicU64::new(0),
+            queue_penalties: Mutex::new(HashMap::new()),
+            challenges: Mutex::new(HashMap::new()),
+            next_challenge_id: AtomicU64::new(0),
             db_pool,
             active_connections: Mutex::new(HashMap::new()),
+            rate_limits: config.rate_limits.clone(),
+            login_guard: LoginGuard::new(LoginGuardConfig::default()),
+            config,
+            shutdown_deadline: watch::Sender::new(None),
+            metrics,
         })
     }
 }
 
+impl ServerStateData {
+    /// Closes the account's socket with `reason`, if it has one and (when
+    /// `session_id` is given) it belongs to that session. Returns whether a
+    /// socket was closed.
+    pub async fn disconnect(
+        &self,
+        account_id: i64,
+        session_id: Option<&str>,
+        reason: GameError,
+    ) -> bool {
+        let connection = {
+            let mut connections = self.active_connections.lock().await;
+            match connections.get(&account_id) {
+                Some(c) if session_id.is_none_or(|sid| c.session_id == sid) => {
+                    // Removed here: the socket skips its own removal on a
+                    // forced disconnect.
+                    connections.remove(&account_id)
+                }
+                _ => None,
+            }
+        };
+        let Some(connection) = connection else {
+            return false;
+        };
+        let _ = connection.kill_tx.send(reason).await;
+        true
+    }
+}
+
 pub type ServerState = Arc<ServerStateData>;
 
 pub type UpgradedWebSocket = WebSocketStream<TokioIo<Upgraded>>;
 
+/// Server state fixtures shared by the unit tests.
+#[cfg(test)]
+pub mod test_support {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    /// A 1s tick keeps the shards from ticking (and recording results)
+    /// while a test is still looking at a match.
+    fn test_config() -> Config {
+        Config {
+            tick_rate: 1.0,
+            ..Config::default()
+        }
+    }
+
+    /// State over an empty in-memory database, for tests that never query it.
+    pub async fn test_state() -> ServerState {
+        let db_pool = SqlitePoolOptions::new()
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        ServerStateData::new(db_pool, test_config())
+    }
+
+    /// State over an in-memory database with the schema applied.
+    pub async fn migrated_test_state() -> ServerState {
+        // One connection: every pooled `sqlite::memory:` connection is its own database.
+        let db_pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&db_pool).await.unwrap();
+        ServerStateData::new(db_pool, test_config())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -55,7 +231,7 @@ mod tests {
             .connect("sqlite::memory:")
             .await
             .unwrap();
-        let state = ServerStateData::new(db_pool);
+        let state = ServerStateData::new(db_pool, Config::default());
         assert!(state.matches.read().await.is_empty());
         assert!(state.queue.lock().await.is_none());
         assert_eq!(state.next_match_id.load(Ordering::Relaxed), 0);
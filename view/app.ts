@@ -1,5 +1,5 @@
 import { initRenderer, RendererHandle, ClickHit } from './renderer';
-import { Unit, Player, CombatEvent, SendUnitCatalogEntry, DamageType, BuildCatalogEntry, Family, UnitKind } from './types';
+import { Unit, Player, CombatEvent, SendUnitCatalogEntry, DamageType, BuildCatalogEntry, Family, UnitKind, ServerError, MatchSettings, GameMode, Mineral, WorkerUpgrade, PrivateLobbyInfo, ChallengeInfo, FriendsList, PingKind, PauseInfo, PlayerStats, GoldTransaction, PROTOCOL_VERSION } from './types';
 import { applyThemeToDom } from './theme';
 import { UnitInfoPanel } from './unit_info_panel';
 import { MercenaryPanel } from './mercenary_panel';
@@ -19,17 +19,36 @@ interface UnitStaticInfo {
 }
 
 type ClientMessagePayload =
+    | { action: 'hello'; payload: { protocol_version: number } }
     | { action: 'joinQueue' }
     | { action: 'leaveQueue' }
+    | { action: 'acceptMatch' }
+    | { action: 'declineMatch' }
+    | { action: 'challengeFriend'; payload: { account_id: number } }
+    | { action: 'cancelChallenge' }
+    | { action: 'respondToChallenge'; payload: { challenge_id: number; accept: boolean } }
+    | { action: 'createPrivateLobby' }
+    | { action: 'joinPrivateLobby'; payload: { code: string } }
+    | { action: 'configurePrivateLobby'; payload: { settings: MatchSettings } }
+    | { action: 'setReady'; payload: { ready: boolean } }
+    | { action: 'leavePrivateLobby' }
     | { action: 'place'; payload: { shape: string; row: number; col: number } }
     | { action: 'sellById'; payload: { entity_id: number } }
     | { action: 'skipToCombat' }
     | { action: 'leaveLobby' }
-    | { action: 'hireWorker'; payload: Record<string, never> }
+    | { action: 'hireWorker'; payload: { mineral?: Mineral } }
     | { action: 'requestUnitInfo'; payload: { entity_id: number } }
     | { action: 'sendUnit'; payload: { shape: string } }
     | { action: 'upgradeKing'; payload: Record<string, never> }
-    | { action: 'requestFullState' };
+    | { action: 'upgradeWorkers'; payload: { upgrade: WorkerUpgrade } }
+    | { action: 'requestFullState' }
+    | { action: 'chat'; payload: { text: string } }
+    | { action: 'ping'; payload: { x: number; y: number; kind: PingKind } }
+    | { action: 'muteOpponent'; payload: { muted: boolean } }
+    | { action: 'requestPause' }
+    | { action: 'resume' }
+    | { action: 'surrender' }
+    | { action: 'requestRematch'; payload: { swap_sides: boolean } };
 
 interface GameState {
     units: Unit[];
@@ -38,12 +57,16 @@ interface GameState {
     phase_timer: number;
     winner_id: number | null;
     seq: number;
+    waiting_for_players: boolean;
+    pause: PauseInfo | null;
 }
 
 interface PhaseInfo {
     phase: string;
     phase_timer: number;
     winner_id: number | null;
+    waiting_for_players: boolean;
+    pause: PauseInfo | null;
 }
 
 interface GameStateDelta {
@@ -56,7 +79,10 @@ interface GameStateDelta {
 }
 
 type ServerMessage =
+    | { type: 'Welcome'; data: { protocol_version: number } }
     | { type: 'Queued' }
+    | { type: 'ReadyCheck'; data: { timeout_secs: number } }
+    | { type: 'ReadyCheckFailed'; data: { requeued: boolean } }
     | { type: 'MatchFound' }
     | { type: 'SendUnitCatalog'; data: SendUnitCatalogEntry[] }
     | { type: 'FamilyOptions'; data: Family[] }
@@ -65,14 +91,31 @@ type ServerMessage =
     | { type: 'GameStateDelta'; data: GameStateDelta }
     | { type: 'CombatEvents'; data: CombatEvent[] }
     | { type: 'PlayerId'; data: number }
-    | { type: 'Error'; data: string }
-    | { type: 'UnitInfo'; data: UnitStaticInfo };
+    | { type: 'Error'; data: ServerError }
+    | { type: 'UnitInfo'; data: UnitStaticInfo }
+    | { type: 'ServerShutdown'; data: { seconds_remaining: number } }
+    | { type: 'MatchAborted'; data: { reason: string } }
+    | { type: 'PrivateLobby'; data: PrivateLobbyInfo }
+    | { type: 'ChallengeSent'; data: ChallengeInfo }
+    | { type: 'ChallengeReceived'; data: ChallengeInfo }
+    | { type: 'ChallengeWithdrawn'; data: { challenge_id: number } }
+    | { type: 'Chat'; data: { from_id: number; username: string; text: string } }
+    | { type: 'Ping'; data: { from_id: number; x: number; y: number; kind: PingKind } }
+    | { type: 'OpponentMuted'; data: { muted: boolean } }
+    | { type: 'Surrendered'; data: { player_id: number; username: string } }
+    | { type: 'RematchRequested'; data: { player_id: number; swap_sides: boolean } }
+    | { type: 'RematchStarted'; data: { match_id: number } }
+    | { type: 'MatchSummary'; data: { winner_id: number | null; players: PlayerStats[] } }
+    | { type: 'GoldLedger'; data: { gold: number; transactions: GoldTransaction[] } };
 
 // Game Over overlay
 const gameOverOverlay = document.getElementById('game-over-overlay') as HTMLDivElement;
 const gameResultTitle = document.getElementById('game-result-title') as HTMLHeadingElement;
 const gameResultSubtitle = document.getElementById('game-result-subtitle') as HTMLParagraphElement;
 const overlayLeaveLobbyButton = document.getElementById('overlay-leave-lobby') as HTMLButtonElement;
+const rematchBtn = document.getElementById('rematch-btn') as HTMLButtonElement;
+const swapSidesEl = document.getElementById('swap-sides') as HTMLInputElement;
+const matchSummaryEl = document.getElementById('match-summary') as HTMLTableElement;
 
 // Views
 const authView = document.getElementById('auth-view') as HTMLDivElement;
@@ -82,12 +125,43 @@ const gameView = document.getElementById('game-view') as HTMLDivElement;
 // Auth elements
 const registerForm = document.getElementById('register-form') as HTMLFormElement;
 const loginForm = document.getElementById('login-form') as HTMLFormElement;
+const guestBtn = document.getElementById('guest-btn') as HTMLButtonElement;
 const authStatus = document.getElementById('auth-status') as HTMLDivElement;
 
 // Lobby elements
 const queueBtn = document.getElementById('queue-btn') as HTMLButtonElement;
 const cancelQueueBtn = document.getElementById('cancel-queue-btn') as HTMLButtonElement;
 const queueStatus = document.getElementById('queue-status') as HTMLParagraphElement;
+const readyCheckEl = document.getElementById('ready-check') as HTMLDivElement;
+const readyCheckStatus = document.getElementById('ready-check-status') as HTMLParagraphElement;
+const acceptMatchBtn = document.getElementById('accept-match-btn') as HTMLButtonElement;
+const declineMatchBtn = document.getElementById('decline-match-btn') as HTMLButtonElement;
+const privateLobbyEntry = document.getElementById('private-lobby-entry') as HTMLDivElement;
+const createLobbyBtn = document.getElementById('create-lobby-btn') as HTMLButtonElement;
+const joinCodeInput = document.getElementById('join-code') as HTMLInputElement;
+const joinLobbyBtn = document.getElementById('join-lobby-btn') as HTMLButtonElement;
+const privateLobbyEl = document.getElementById('private-lobby') as HTMLDivElement;
+const lobbyCodeEl = document.getElementById('lobby-code') as HTMLSpanElement;
+const lobbyMembersEl = document.getElementById('lobby-members') as HTMLParagraphElement;
+const settingGoldInput = document.getElementById('setting-gold') as HTMLInputElement;
+const settingBuildTimeInput = document.getElementById('setting-build-time') as HTMLInputElement;
+const settingWaveInput = document.getElementById('setting-wave') as HTMLInputElement;
+const settingModeSelect = document.getElementById('setting-mode') as HTMLSelectElement;
+const settingInterestInput = document.getElementById('setting-interest') as HTMLInputElement;
+const settingInterestCapInput = document.getElementById('setting-interest-cap') as HTMLInputElement;
+const applySettingsBtn = document.getElementById('apply-settings-btn') as HTMLButtonElement;
+const readyBtn = document.getElementById('ready-btn') as HTMLButtonElement;
+const leavePrivateLobbyBtn = document.getElementById('leave-private-lobby-btn') as HTMLButtonElement;
+const friendUsernameInput = document.getElementById('friend-username') as HTMLInputElement;
+const addFriendBtn = document.getElementById('add-friend-btn') as HTMLButtonElement;
+const friendListEl = document.getElementById('friend-list') as HTMLUListElement;
+const challengePromptEl = document.getElementById('challenge-prompt') as HTMLDivElement;
+const challengeTextEl = document.getElementById('challenge-text') as HTMLParagraphElement;
+const acceptChallengeBtn = document.getElementById('accept-challenge-btn') as HTMLButtonElement;
+const declineChallengeBtn = document.getElementById('decline-challenge-btn') as HTMLButtonElement;
+const challengePendingEl = document.getElementById('challenge-pending') as HTMLDivElement;
+const challengePendingTextEl = document.getElementById('challenge-pending-text') as HTMLParagraphElement;
+const cancelChallengeBtn = document.getElementById('cancel-challenge-btn') as HTMLButtonElement;
 
 // Game elements
 const leaveLobbyButton = document.getElementById('leave-lobby') as HTMLButtonElement;
@@ -96,10 +170,27 @@ const gameTimerEl = document.getElementById('game-timer') as HTMLSpanElement;
 const goldDisplay = document.getElementById('gold-display') as HTMLSpanElement;
 const livesDisplay = document.getElementById('lives-display') as HTMLSpanElement;
 const hireWorkerBtn = document.getElementById('hire-worker-btn') as HTMLButtonElement;
+const hireEssenceWorkerBtn = document.getElementById('hire-essence-worker-btn') as HTMLButtonElement;
+const workerUpgradeBtns = document.querySelectorAll<HTMLButtonElement>('#worker-upgrades button');
+const essenceStatusEl = document.getElementById('essence-status') as HTMLSpanElement;
+const essenceDisplay = document.getElementById('essence-display') as HTMLSpanElement;
 const familyPickEl = document.getElementById('family-pick') as HTMLDivElement;
 const buildShopEl = document.getElementById('build-shop') as HTMLDivElement;
+const chatLogEl = document.getElementById('chat-log') as HTMLDivElement;
+const chatForm = document.getElementById('chat-form') as HTMLFormElement;
+const chatInput = document.getElementById('chat-input') as HTMLInputElement;
+const pingKindEl = document.getElementById('ping-kind') as HTMLSelectElement;
+const muteOpponentEl = document.getElementById('mute-opponent') as HTMLInputElement;
+const pauseBtn = document.getElementById('pause-btn') as HTMLButtonElement;
+const surrenderBtn = document.getElementById('surrender-btn') as HTMLButtonElement;
+const goldLedgerEl = document.getElementById('gold-ledger') as HTMLDetailsElement;
+const goldLedgerList = document.getElementById('gold-ledger-list') as HTMLOListElement;
 
 const WORKER_CAP = 7;
+// Mirrors `WORKER_UPGRADE_COSTS` (server/src/model/unit_config.rs).
+const WORKER_UPGRADE_COSTS = [75, 125, 200];
+const WORKER_UPGRADE_LABELS: Record<WorkerUpgrade, string> = { Speed: 'Worker Speed', MiningTime: 'Mining Time', Carry: 'Carry' };
+const WORKER_TIER_FIELDS = { Speed: 'speed', MiningTime: 'mining_time', Carry: 'carry' } as const;
 
 let selectedUnitKind: UnitKind | null = null;
 let unitMap = new Map<number, Unit>();
@@ -110,6 +201,11 @@ let socket: WebSocket | null = null;
 let isInGame = false;
 let gamePhase = '';
 let gameTimer = 0;
+// The first Build timer is held until both players have loaded and picked a family.
+let waitingForPlayers = false;
+let pause: PauseInfo | null = null;
+// Who conceded the current match, for the game-over subtitle.
+let surrenderedBy: string | null = null;
 
 const panel = new UnitInfoPanel(
     document.getElementById('ui-panel') as HTMLElement,
@@ -159,6 +255,7 @@ function showLobbyView() {
     authView.style.display = 'none';
     lobbySelectionView.style.display = 'block';
     gameView.style.display = 'none';
+    loadFriends();
 }
 
 function showGameView() {
@@ -208,6 +305,7 @@ loginForm.addEventListener('submit', async (e) => {
         if (response.ok) {
             const data = await response.json();
             localStorage.setItem('jwt', data.token);
+            localStorage.setItem('refreshToken', data.refresh_token);
             authStatus.innerHTML = '';
             connectAndShowLobby();
         } else {
@@ -219,9 +317,53 @@ loginForm.addEventListener('submit', async (e) => {
     }
 });
 
+guestBtn.addEventListener('click', async () => {
+    try {
+        const response = await fetch(`${API_BASE_URL}/api/auth/guest`, { method: 'POST' });
+        if (response.ok) {
+            const data = await response.json();
+            localStorage.setItem('jwt', data.token);
+            localStorage.setItem('refreshToken', data.refresh_token);
+            // @ts-ignore
+            M.toast({ html: `Playing as ${data.username}` });
+            authStatus.innerHTML = '';
+            connectAndShowLobby();
+        } else {
+            const error = await response.json();
+            authStatus.innerHTML = `<p class="red-text">Guest login failed: ${error.error}</p>`;
+        }
+    } catch (error) {
+        authStatus.innerHTML = `<p class="red-text">Error: Could not connect to server.</p>`;
+    }
+});
+
+// Access tokens are short-lived: trade the refresh token for a fresh one
+// before opening the socket. Each refresh token works once.
+async function refreshAccessToken(): Promise<void> {
+    const refreshToken = localStorage.getItem('refreshToken');
+    if (!refreshToken) return;
+    try {
+        const response = await fetch(`${API_BASE_URL}/api/auth/refresh`, {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ refresh_token: refreshToken }),
+        });
+        if (response.ok) {
+            const data = await response.json();
+            localStorage.setItem('jwt', data.token);
+            localStorage.setItem('refreshToken', data.refresh_token);
+        } else {
+            localStorage.removeItem('jwt');
+            localStorage.removeItem('refreshToken');
+        }
+    } catch (error) {
+        // Server unreachable: try the stored access token as-is.
+    }
+}
 
 // --- WEBSOCKET AND GAME LOGIC ---
-function connectAndShowLobby() {
+async function connectAndShowLobby() {
+    await refreshAccessToken();
     const token = localStorage.getItem('jwt');
     if (!token) {
         showAuthView();
@@ -232,20 +374,42 @@ function connectAndShowLobby() {
     socket = new WebSocket(`ws://127.0.0.1:9001/ws?token=${token}`);
 
     socket.onopen = function () {
+        const hello: ClientMessagePayload = { action: 'hello', payload: { protocol_version: PROTOCOL_VERSION } };
+        socket?.send(JSON.stringify(hello));
         showLobbyView();
     };
 
     socket.onmessage = function (event) {
         const serverMsg: ServerMessage = JSON.parse(event.data);
         switch (serverMsg.type) {
+            case 'Welcome':
+                break;
             case 'Queued':
                 queueBtn.style.display = 'none';
                 cancelQueueBtn.style.display = 'inline-block';
                 queueStatus.textContent = 'Searching for opponent…';
                 break;
+            case 'ReadyCheck':
+                showReadyCheck(serverMsg.data.timeout_secs);
+                break;
+            case 'ReadyCheckFailed':
+                hideReadyCheck();
+                if (serverMsg.data.requeued) {
+                    // @ts-ignore
+                    M.toast({ html: 'Your opponent did not accept. Searching again…' });
+                } else {
+                    resetQueueUi();
+                }
+                break;
             case 'MatchFound':
                 isInGame = true;
+                hideReadyCheck();
+                hideChallengePrompt();
+                challengePendingEl.style.display = 'none';
                 resetQueueUi();
+                resetPrivateLobbyUi();
+                chatLogEl.replaceChildren();
+                muteOpponentEl.checked = false;
                 showGameView();
                 break;
             case 'SendUnitCatalog':
@@ -260,6 +424,8 @@ function connectAndShowLobby() {
                 break;
             case 'BuildCatalog':
                 familyPickEl.innerHTML = '';
+                // Only essence matches price anything in essence.
+                setEssenceUi(serverMsg.data.some(entry => entry.essence_cost > 0));
                 renderBuildShop(buildShopEl, serverMsg.data, (unitKind) => {
                     selectedUnitKind = unitKind;
                 });
@@ -281,13 +447,86 @@ function connectAndShowLobby() {
                 myPlayerId = serverMsg.data;
                 break;
             case 'Error':
+                if (serverMsg.data.code === 'ServerShuttingDown') {
+                    hideReadyCheck();
+                    resetQueueUi();
+                }
+                if (serverMsg.data.code === 'ServerShuttingDown' || serverMsg.data.code === 'PrivateLobbyClosed') {
+                    resetPrivateLobbyUi();
+                }
+                if (serverMsg.data.code === 'ChallengeDeclined' || serverMsg.data.code === 'ChallengeExpired') {
+                    challengePendingEl.style.display = 'none';
+                }
                 // @ts-ignore
-                M.toast({ html: serverMsg.data });
-                renderer.flashError(serverMsg.data);
+                M.toast({ html: serverMsg.data.message });
+                renderer.flashError(serverMsg.data.message);
                 break;
             case 'UnitInfo':
                 panel.applyStaticInfo(serverMsg.data);
                 break;
+            case 'ServerShutdown': {
+                const minutes = Math.ceil(serverMsg.data.seconds_remaining / 60);
+                const html = isInGame
+                    ? `Server restarting: this match ends in ${minutes} min if unfinished.`
+                    : 'Server restarting shortly; matchmaking is paused.';
+                // @ts-ignore
+                M.toast({ html, displayLength: 10000 });
+                break;
+            }
+            case 'MatchAborted':
+                // @ts-ignore
+                M.toast({ html: `Match ended early: ${serverMsg.data.reason}`, displayLength: 10000 });
+                break;
+            case 'PrivateLobby':
+                showPrivateLobby(serverMsg.data);
+                break;
+            case 'ChallengeSent':
+                challengePendingTextEl.textContent = `Waiting for ${serverMsg.data.to_username} to answer…`;
+                challengePendingEl.style.display = 'block';
+                break;
+            case 'ChallengeReceived':
+                incomingChallengeId = serverMsg.data.challenge_id;
+                challengeTextEl.textContent = `${serverMsg.data.from_username} challenges you to a match!`;
+                challengePromptEl.style.display = 'block';
+                break;
+            case 'ChallengeWithdrawn':
+                if (serverMsg.data.challenge_id === incomingChallengeId) hideChallengePrompt();
+                break;
+            case 'Chat':
+                appendChatLine(serverMsg.data.username, serverMsg.data.text, serverMsg.data.from_id === myPlayerId);
+                break;
+            case 'Ping':
+                renderer.showPing(serverMsg.data.x, serverMsg.data.y, serverMsg.data.kind);
+                break;
+            case 'OpponentMuted':
+                muteOpponentEl.checked = serverMsg.data.muted;
+                break;
+            case 'Surrendered':
+                surrenderedBy = serverMsg.data.username;
+                break;
+            case 'RematchRequested':
+                if (serverMsg.data.player_id === myPlayerId) {
+                    rematchBtn.disabled = true;
+                    rematchBtn.textContent = 'Waiting for opponent…';
+                } else {
+                    // @ts-ignore
+                    M.toast({ html: 'Your opponent wants a rematch.' });
+                }
+                break;
+            case 'RematchStarted':
+                // MatchFound and a fresh game state follow.
+                resetMatchView();
+                break;
+            case 'MatchSummary':
+                renderMatchSummary(serverMsg.data.players);
+                break;
+            case 'GoldLedger':
+                goldLedgerList.replaceChildren(...serverMsg.data.transactions.slice().reverse().map(t => {
+                    const li = document.createElement('li');
+                    li.textContent = `${t.amount > 0 ? '+' : ''}${t.amount} ${t.reason} (tick ${t.tick})`;
+                    return li;
+                }));
+                break;
         }
     };
 
@@ -324,6 +563,110 @@ cancelQueueBtn.onclick = () => {
     resetQueueUi();
 };
 
+let readyCheckTimer: number | undefined;
+
+function showReadyCheck(timeoutSecs: number) {
+    cancelQueueBtn.style.display = 'none';
+    queueStatus.textContent = '';
+    readyCheckEl.style.display = 'block';
+    acceptMatchBtn.disabled = false;
+    declineMatchBtn.disabled = false;
+    let left = timeoutSecs;
+    readyCheckStatus.textContent = `Accept within ${left}s`;
+    clearInterval(readyCheckTimer);
+    readyCheckTimer = window.setInterval(() => {
+        left = Math.max(0, left - 1);
+        if (!acceptMatchBtn.disabled) readyCheckStatus.textContent = `Accept within ${left}s`;
+    }, 1000);
+}
+
+function hideReadyCheck() {
+    clearInterval(readyCheckTimer);
+    readyCheckEl.style.display = 'none';
+}
+
+acceptMatchBtn.onclick = () => {
+    socket?.send(JSON.stringify({ action: 'acceptMatch' }));
+    acceptMatchBtn.disabled = true;
+    declineMatchBtn.disabled = true;
+    readyCheckStatus.textContent = 'Waiting for your opponent…';
+};
+
+declineMatchBtn.onclick = () => {
+    socket?.send(JSON.stringify({ action: 'declineMatch' }));
+    acceptMatchBtn.disabled = true;
+    declineMatchBtn.disabled = true;
+};
+
+// Whether this client created the private lobby it is in; only the host may
+// change its settings.
+let isLobbyHost = false;
+
+function showPrivateLobby(info: PrivateLobbyInfo) {
+    privateLobbyEntry.style.display = 'none';
+    queueBtn.style.display = 'none';
+    privateLobbyEl.style.display = 'block';
+    lobbyCodeEl.textContent = info.code;
+    const mark = (ready: boolean) => (ready ? ' ✔' : '');
+    lobbyMembersEl.textContent = info.guest
+        ? `${info.host}${mark(info.host_ready)} vs ${info.guest}${mark(info.guest_ready)}`
+        : `${info.host}${mark(info.host_ready)}, waiting for an opponent… share code ${info.code}`;
+    settingGoldInput.value = String(info.settings.starting_gold);
+    settingBuildTimeInput.value = String(info.settings.build_time_secs);
+    settingWaveInput.value = String(info.settings.starting_wave);
+    settingModeSelect.value = info.settings.mode;
+    settingInterestInput.value = String(info.settings.interest_percent);
+    settingInterestCapInput.value = String(info.settings.interest_cap);
+    for (const input of [
+        settingGoldInput, settingBuildTimeInput, settingWaveInput,
+        settingModeSelect, settingInterestInput, settingInterestCapInput, applySettingsBtn,
+    ]) {
+        input.disabled = !isLobbyHost;
+    }
+    const myReady = isLobbyHost ? info.host_ready : info.guest_ready;
+    readyBtn.textContent = myReady ? 'Not ready' : 'Ready';
+    readyBtn.disabled = !info.guest;
+}
+
+function resetPrivateLobbyUi() {
+    privateLobbyEl.style.display = 'none';
+    privateLobbyEntry.style.display = 'block';
+    queueBtn.style.display = 'inline-block';
+}
+
+createLobbyBtn.onclick = () => {
+    isLobbyHost = true;
+    socket?.send(JSON.stringify({ action: 'createPrivateLobby' }));
+};
+
+joinLobbyBtn.onclick = () => {
+    const code = joinCodeInput.value.trim();
+    if (!code) return;
+    isLobbyHost = false;
+    socket?.send(JSON.stringify({ action: 'joinPrivateLobby', payload: { code } }));
+};
+
+applySettingsBtn.onclick = () => {
+    const settings: MatchSettings = {
+        starting_gold: Number(settingGoldInput.value),
+        build_time_secs: Number(settingBuildTimeInput.value),
+        starting_wave: Number(settingWaveInput.value),
+        mode: settingModeSelect.value as GameMode,
+        interest_percent: Number(settingInterestInput.value),
+        interest_cap: Number(settingInterestCapInput.value),
+    };
+    socket?.send(JSON.stringify({ action: 'configurePrivateLobby', payload: { settings } }));
+};
+
+readyBtn.onclick = () => {
+    socket?.send(JSON.stringify({ action: 'setReady', payload: { ready: readyBtn.textContent === 'Ready' } }));
+};
+
+leavePrivateLobbyBtn.onclick = () => {
+    socket?.send(JSON.stringify({ action: 'leavePrivateLobby' }));
+    resetPrivateLobbyUi();
+};
+
 function applyPanelBoardSide(): void {
     if (myPlayerId === null) return;
     const idx = currentPlayers.findIndex(p => p.id === myPlayerId);
@@ -345,6 +688,18 @@ function refreshDerivedDisplays() {
         goldDisplay.textContent = me.income > 0
             ? `${me.gold} (+${me.income}/round)`
             : me.gold.toString();
+        essenceDisplay.textContent = me.essence.toString();
+        hireWorkerBtn.textContent = `Hire Worker (${me.next_worker_cost}g)`;
+        hireEssenceWorkerBtn.textContent = `Hire Essence Worker (${me.next_worker_cost}g)`;
+        for (const btn of workerUpgradeBtns) {
+            const upgrade = btn.dataset.upgrade as WorkerUpgrade;
+            const tier = me.worker_tiers[WORKER_TIER_FIELDS[upgrade]];
+            const cost = WORKER_UPGRADE_COSTS[tier];
+            btn.textContent = cost === undefined
+                ? `${WORKER_UPGRADE_LABELS[upgrade]} (max)`
+                : `${WORKER_UPGRADE_LABELS[upgrade]} ${tier + 1} (${cost}g)`;
+            btn.disabled = cost === undefined || me.gold < cost;
+        }
         mercPanel.updatePlayer(me.gold, me.next_send_costs);
     }
 
@@ -360,6 +715,7 @@ function refreshDerivedDisplays() {
     if (myPlayerId !== null) {
         const myWorkers = currentUnits().filter(u => u.is_worker && u.owner_id === myPlayerId).length;
         hireWorkerBtn.disabled = myWorkers >= WORKER_CAP;
+        hireEssenceWorkerBtn.disabled = myWorkers >= WORKER_CAP;
     }
 
     // Update king upgrade panel
@@ -378,7 +734,13 @@ function setPhaseText(phase: string, timer: number) {
     gamePhase = phase;
     gameTimer = Math.max(0, timer);
 
-    gamePhaseEl.textContent = gamePhase;
+    if (pause) {
+        const by = currentPlayers.find(p => p.id === pause!.paused_by)?.username ?? 'a player';
+        gamePhaseEl.textContent = `${gamePhase} (paused by ${by}, ${pause.seconds_left}s left)`;
+    } else {
+        gamePhaseEl.textContent = waitingForPlayers ? `${gamePhase} (waiting for players)` : gamePhase;
+    }
+    pauseBtn.textContent = pause ? 'Resume' : 'Pause';
     gameTimerEl.textContent = gameTimer.toFixed(1);
 }
 
@@ -394,11 +756,11 @@ function updateGameOverOverlay(winnerId: number | null) {
         } else if (isLoser) {
             gameResultTitle.textContent = 'Defeat';
             gameResultTitle.className = 'defeat';
-            gameResultSubtitle.textContent = 'Your base was overrun.';
+            gameResultSubtitle.textContent = surrenderedBy ? 'You surrendered.' : 'Your base was overrun.';
         } else {
             gameResultTitle.textContent = 'Victory!';
             gameResultTitle.className = 'victory';
-            gameResultSubtitle.textContent = 'Your opponent\'s base fell!';
+            gameResultSubtitle.textContent = surrenderedBy ? `${surrenderedBy} surrendered.` : 'Your opponent\'s base fell!';
         }
         gameOverOverlay.style.display = 'flex';
         mercPanel.hide();
@@ -424,6 +786,8 @@ function applyFullState(newState: GameState) {
 
     // Phase must be set before refreshDerivedDisplays: the king upgrade panel
     // gates on the module-level gamePhase.
+    waitingForPlayers = newState.waiting_for_players;
+    pause = newState.pause;
     setPhaseText(newState.phase, newState.phase_timer);
     refreshDerivedDisplays();
     panel.syncDynamicState(currentUnits(), gamePhase);
@@ -455,6 +819,8 @@ function applyDelta(d: GameStateDelta) {
     // -> kingUpgradePanel.update() unconditionally shows the panel, which would
     // re-show it right after the overlay hid it if called in the other order.
     if (d.phase_info) {
+        waitingForPlayers = d.phase_info.waiting_for_players;
+        pause = d.phase_info.pause;
         setPhaseText(d.phase_info.phase, d.phase_info.phase_timer);
     }
     refreshDerivedDisplays();
@@ -470,11 +836,21 @@ function handleCombatEvents(events: CombatEvent[]) {
 }
 
 // --- ONE-TIME EVENT REGISTRATION ---
-hireWorkerBtn.onclick = () => {
+function hireWorker(mineral: Mineral) {
     if (socket && socket.readyState === WebSocket.OPEN) {
-        socket.send(JSON.stringify({ action: 'hireWorker', payload: {} }));
+        socket.send(JSON.stringify({ action: 'hireWorker', payload: { mineral } }));
     }
-};
+}
+hireWorkerBtn.onclick = () => hireWorker('Gold');
+hireEssenceWorkerBtn.onclick = () => hireWorker('Essence');
+for (const btn of workerUpgradeBtns) {
+    btn.onclick = () => {
+        if (socket && socket.readyState === WebSocket.OPEN) {
+            const upgrade = btn.dataset.upgrade as WorkerUpgrade;
+            socket.send(JSON.stringify({ action: 'upgradeWorkers', payload: { upgrade } }));
+        }
+    };
+}
 document.getElementById('skip-to-combat')!.onclick = () => {
     if (socket && socket.readyState === WebSocket.OPEN) {
         socket.send(JSON.stringify({ action: 'skipToCombat' }));
@@ -512,14 +888,106 @@ renderer.onClick((hit: ClickHit) => {
     }
 });
 
-function handleLeaveLobby() {
-    isInGame = false;
+renderer.onPing((x: number, y: number) => {
+    if (!isInGame) return;
+    socket?.send(JSON.stringify({ action: 'ping', payload: { x, y, kind: pingKindEl.value } }));
+});
+
+function appendChatLine(username: string, text: string, mine: boolean) {
+    const line = document.createElement('div');
+    const from = document.createElement('span');
+    from.className = mine ? 'chat-from mine' : 'chat-from';
+    from.textContent = `${username}: `;
+    line.append(from, text);
+    chatLogEl.appendChild(line);
+    chatLogEl.scrollTop = chatLogEl.scrollHeight;
+}
+
+chatForm.onsubmit = (e) => {
+    e.preventDefault();
+    const text = chatInput.value.trim();
+    if (!text) return;
+    socket?.send(JSON.stringify({ action: 'chat', payload: { text } }));
+    chatInput.value = '';
+};
+
+pauseBtn.onclick = () => {
+    socket?.send(JSON.stringify({ action: pause ? 'resume' : 'requestPause' }));
+};
+
+muteOpponentEl.onchange = () => {
+    socket?.send(JSON.stringify({ action: 'muteOpponent', payload: { muted: muteOpponentEl.checked } }));
+};
+
+// Refreshed each time the log is opened; the server only answers on request.
+goldLedgerEl.ontoggle = () => {
+    if (goldLedgerEl.open) socket?.send(JSON.stringify({ action: 'requestGoldLedger' }));
+};
+
+surrenderBtn.onclick = () => {
+    if (!confirm('Surrender this match?')) return;
+    socket?.send(JSON.stringify({ action: 'surrender' }));
+};
+
+rematchBtn.onclick = () => {
+    socket?.send(JSON.stringify({ action: 'requestRematch', payload: { swap_sides: swapSidesEl.checked } }));
+};
+
+// Fills the game-over table: one column per player, one row per stat.
+function renderMatchSummary(players: PlayerStats[]) {
+    const sum = (values: number[]) => values.reduce((a, b) => a + b, 0);
+    const rows: [string, (p: PlayerStats) => string][] = [
+        ['Damage', p => p.damage_by_tower.map(d => `${d.kind} ${Math.round(d.damage)}`).join(', ') || '0'],
+        ['Creeps killed', p => String(p.creeps_killed)],
+        ['Leaks', p => String(sum(p.leaks_per_wave.map(w => w.leaks)))],
+        ['Gold earned', p => {
+            const g = p.gold_earned;
+            return String(g.workers + g.bounties + g.wave_reward + g.income + g.perfect_clear + g.interest + g.refunds);
+        }],
+        ['Gold spent', p => {
+            const g = p.gold_spent;
+            return `${g.towers + g.workers + g.worker_upgrades + g.sends + g.king_upgrades} (towers ${g.towers}, sends ${g.sends})`;
+        }],
+        ['Sends', p => String(p.sends_made)],
+    ];
+    matchSummaryEl.replaceChildren();
+    const header = matchSummaryEl.insertRow();
+    header.insertCell().textContent = '';
+    for (const p of players) header.insertCell().textContent = p.username;
+    for (const [label, value] of rows) {
+        const row = matchSummaryEl.insertRow();
+        row.insertCell().textContent = label;
+        for (const p of players) row.insertCell().textContent = value(p);
+    }
+    matchSummaryEl.style.display = 'table';
+}
+
+// Clears the finished match so the next one starts from a blank board.
+function setEssenceUi(visible: boolean) {
+    const display = visible ? 'inline' : 'none';
+    essenceStatusEl.style.display = display;
+    hireEssenceWorkerBtn.style.display = visible ? 'inline-block' : 'none';
+}
+
+function resetMatchView() {
+    setEssenceUi(false);
     gameOverOverlay.style.display = 'none';
-    socket?.send(JSON.stringify({ action: 'leaveLobby' }));
+    matchSummaryEl.style.display = 'none';
     panel.clearSelection();
     mercPanel.hide();
     kingUpgradePanel.hide();
     renderer.reset();
+    unitMap = new Map();
+    lastSeq = -1;
+    surrenderedBy = null;
+    rematchBtn.disabled = false;
+    rematchBtn.textContent = 'Rematch';
+}
+
+function handleLeaveLobby() {
+    isInGame = false;
+    socket?.send(JSON.stringify({ action: 'leaveLobby' }));
+    resetMatchView();
     resetQueueUi();
     showLobbyView();
 }
@@ -528,3 +996,102 @@ leaveLobbyButton.onclick = handleLeaveLobby;
 overlayLeaveLobbyButton.onclick = handleLeaveLobby;
 
 showAuthView();
+
+// --- FRIENDS ---
+
+// The challenge shown in the prompt, if any.
+let incomingChallengeId: number | null = null;
+
+async function friendsRequest(path: string, body?: object): Promise<Response> {
+    const token = localStorage.getItem('jwt');
+    return fetch(`${API_BASE_URL}/api/friends${path}`, {
+        method: body ? 'POST' : 'GET',
+        headers: { 'Content-Type': 'application/json', Authorization: `Bearer ${token}` },
+        body: body ? JSON.stringify(body) : undefined,
+    });
+}
+
+async function loadFriends() {
+    try {
+        const response = await friendsRequest('');
+        if (!response.ok) return;
+        renderFriends(await response.json());
+    } catch (error) {
+        // Server unreachable: keep the old list.
+    }
+}
+
+function friendRow(label: string, buttons: [string, () => void][]): HTMLLIElement {
+    const li = document.createElement('li');
+    li.className = 'collection-item';
+    li.textContent = label;
+    for (const [text, onClick] of buttons) {
+        const button = document.createElement('button');
+        button.className = 'btn-flat btn-small waves-effect';
+        button.textContent = text;
+        button.onclick = onClick;
+        li.appendChild(button);
+    }
+    return li;
+}
+
+function renderFriends(list: FriendsList) {
+    friendListEl.innerHTML = '';
+    const remove = (id: number) => async () => {
+        await friendsRequest('/remove', { account_id: id });
+        loadFriends();
+    };
+    for (const f of list.incoming) {
+        const accept = async () => {
+            await friendsRequest('/accept', { account_id: f.id });
+            loadFriends();
+        };
+        friendListEl.appendChild(friendRow(`${f.username} wants to be friends`, [['Accept', accept], ['Decline', remove(f.id)]]));
+    }
+    for (const f of list.friends) {
+        const challenge = () => socket?.send(JSON.stringify({ action: 'challengeFriend', payload: { account_id: f.id } }));
+        const buttons: [string, () => void][] = f.online && !f.in_match ? [['Challenge', challenge]] : [];
+        buttons.push(['Remove', remove(f.id)]);
+        const status = f.in_match ? ' (in match)' : '';
+        friendListEl.appendChild(friendRow(`${f.online ? '●' : '○'} ${f.username}${status}`, buttons));
+    }
+    for (const f of list.outgoing) {
+        friendListEl.appendChild(friendRow(`${f.username} (request sent)`, [['Cancel', remove(f.id)]]));
+    }
+}
+
+function hideChallengePrompt() {
+    incomingChallengeId = null;
+    challengePromptEl.style.display = 'none';
+}
+
+addFriendBtn.onclick = async () => {
+    const username = friendUsernameInput.value.trim();
+    if (!username) return;
+    const response = await friendsRequest('/request', { username });
+    if (!response.ok) {
+        const data = await response.json();
+        // @ts-ignore
+        M.toast({ html: data.error });
+        return;
+    }
+    friendUsernameInput.value = '';
+    loadFriends();
+};
+
+acceptChallengeBtn.onclick = () => {
+    if (incomingChallengeId === null) return;
+    socket?.send(JSON.stringify({ action: 'respondToChallenge', payload: { challenge_id: incomingChallengeId, accept: true } }));
+    hideChallengePrompt();
+};
+
+declineChallengeBtn.onclick = () => {
+    if (incomingChallengeId === null) return;
+    socket?.send(JSON.stringify({ action: 'respondToChallenge', payload: { challenge_id: incomingChallengeId, accept: false } }));
+    hideChallengePrompt();
+};
+
+cancelChallengeBtn.onclick = () => {
+    socket?.send(JSON.stringify({ action: 'cancelChallenge' }));
+    challengePendingEl.style.display = 'none';
+};
@@ -22,7 +22,8 @@ export function renderBuildShop(
     container.innerHTML = '';
     for (const entry of catalog) {
         const btn = document.createElement('button');
-        btn.textContent = `${entry.name} (${entry.cost}g)`;
+        const essence = entry.essence_cost > 0 ? ` + ${entry.essence_cost}e` : '';
+        btn.textContent = `${entry.name} (${entry.cost}g${essence})`;
         btn.onclick = () => onSelect(entry.unit_kind);
         container.appendChild(btn);
     }
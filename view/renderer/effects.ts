@@ -1,5 +1,5 @@
 import { Container, Graphics, Text, TextStyle } from 'pixi.js';
-import type { CombatEvent, DamageType, Position } from '../types';
+import type { CombatEvent, DamageType, PingKind, Position } from '../types';
 import { theme, hexNum } from '../theme';
 import { CANVAS_WIDTH, CANVAS_HEIGHT } from '../layout';
 
@@ -11,6 +11,13 @@ function attackColor(t: DamageType): number {
     return hexNum(C.fxBasic);
 }
 
+function pingColor(kind: PingKind): number {
+    if (kind === 'Attack') return hexNum(C.kingHostile);
+    if (kind === 'Defend') return hexNum(C.friendly);
+    if (kind === 'Danger') return hexNum(C.accentGold);
+    return hexNum(C.accent);
+}
+
 const easeOutQuad = (t: number) => 1 - (1 - t) * (1 - t);
 const easeOutCubic = (t: number) => 1 - Math.pow(1 - t, 3);
 
@@ -95,6 +102,22 @@ export class EffectsLayer {
         }, this.overlay);
     }
 
+    // Three rings pulsing out of a map ping, drawn above the units.
+    pingMarker(x: number, y: number, kind: PingKind): void {
+        const color = pingColor(kind);
+        const g = new Graphics();
+        g.position.set(x, y);
+        this.add(g, 1500, (t, node) => {
+            const gg = node as Graphics;
+            gg.clear();
+            for (let i = 0; i < 3; i++) {
+                const k = (t * 3 - i * 0.5 + 3) % 1;
+                gg.circle(0, 0, 6 + 30 * easeOutCubic(k)).stroke({ width: 3, color, alpha: (1 - k) * (1 - t) });
+            }
+            gg.circle(0, 0, 5).fill({ color, alpha: 1 - t });
+        }, this.overlay);
+    }
+
     errorVignette(): void {
         const g = new Graphics();
         g.rect(0, 0, CANVAS_WIDTH, CANVAS_HEIGHT)
@@ -1,5 +1,5 @@
 import { FederatedPointerEvent, Rectangle } from 'pixi.js';
-import type { Unit, Player, CombatEvent } from '../types';
+import type { Unit, Player, CombatEvent, PingKind } from '../types';
 import { diffVisualState, snapshotOf, VisualSnapshot } from '../visual_diff';
 import {
     SQUARE_SIZE, LEFT_BOARD_END, RIGHT_BOARD_START, TOTAL_HEIGHT,
@@ -21,6 +21,8 @@ export interface RendererHandle {
     syncState(units: Map<number, Unit>, players: Player[], phase: string, myPlayerId: number | null): void;
     playCombatEvents(events: CombatEvent[]): void;
     onClick(cb: (hit: ClickHit) => void): void;
+    onPing(cb: (x: number, y: number) => void): void;
+    showPing(x: number, y: number, kind: PingKind): void;
     flashError(msg: string): void;
     reset(): void;
     destroy(): void;
@@ -77,6 +79,7 @@ export async function initRenderer(container: HTMLElement): Promise<RendererHand
     let players: Player[] = [];
     let myPlayerId: number | null = null;
     let clickCb: (hit: ClickHit) => void = () => {};
+    let pingCb: (x: number, y: number) => void = () => {};
 
     scene.app.stage.eventMode = 'static';
     scene.app.stage.hitArea = new Rectangle(0, 0, CANVAS_WIDTH, CANVAS_HEIGHT);
@@ -84,6 +87,12 @@ export async function initRenderer(container: HTMLElement): Promise<RendererHand
         const pos = e.getLocalPosition(scene.app.stage);
         clickCb(resolveClick(pos.x, pos.y, (x, y) => unitLayer.hitTest(x, y), players, myPlayerId));
     });
+    // Right-click pings instead of opening the browser menu.
+    scene.app.canvas.addEventListener('contextmenu', (e) => e.preventDefault());
+    scene.app.stage.on('rightclick', (e: FederatedPointerEvent) => {
+        const pos = e.getLocalPosition(scene.app.stage);
+        pingCb(pos.x, pos.y);
+    });
 
     scene.app.ticker.add((ticker) => {
         const dt = ticker.deltaMS;
@@ -111,6 +120,12 @@ export async function initRenderer(container: HTMLElement): Promise<RendererHand
         onClick(cb) {
             clickCb = cb;
         },
+        onPing(cb) {
+            pingCb = cb;
+        },
+        showPing(x, y, kind) {
+            effects.pingMarker(x, y, kind);
+        },
         flashError(_msg) {
             effects.errorVignette();
         },
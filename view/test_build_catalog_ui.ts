@@ -60,8 +60,8 @@ function makeContainer(): FakeContainer {
 test('renderBuildShop creates one button per catalog entry with cost label', () => {
     const container = makeContainer();
     const catalog: BuildCatalogEntry[] = [
-        { unit_kind: 'Square', name: 'Square', cost: 25 },
-        { unit_kind: 'Circle', name: 'Circle', cost: 75 },
+        { unit_kind: 'Square', name: 'Square', cost: 25, essence_cost: 0 },
+        { unit_kind: 'Circle', name: 'Circle', cost: 75, essence_cost: 25 },
     ];
     let selected: string | null = null;
     renderBuildShop(container as unknown as HTMLElement, catalog, (kind) => { selected = kind; });
@@ -69,6 +69,8 @@ test('renderBuildShop creates one button per catalog entry with cost label', ()
     assert.strictEqual(buttons.length, 2);
     assert.ok(buttons[0].textContent!.includes('Square'));
     assert.ok(buttons[0].textContent!.includes('25'));
+    assert.ok(!buttons[0].textContent!.includes('e)'));
+    assert.ok(buttons[1].textContent!.includes('+ 25e'));
     buttons[1].click();
     assert.strictEqual(selected, 'Circle');
 });
@@ -1,10 +1,54 @@
+// Must match `PROTOCOL_VERSION` in server/src/model/messages.rs; the full
+// wire contract is generated into protocol.schema.json.
+export const PROTOCOL_VERSION = 1;
+
 export type UnitKind = 'Square' | 'Circle' | 'Triangle';
 export type Family = 'Basic';
+export type GameMode = 'Classic' | 'Essence';
+export type Mineral = 'Gold' | 'Essence';
+export type WorkerUpgrade = 'Speed' | 'MiningTime' | 'Carry';
+// Mirrors `PauseInfo` (server/src/model/pause.rs).
+export interface PauseInfo {
+    paused_by: number;
+    seconds_left: number;
+}
+
+// Mirrors `PingKind` (server/src/model/chat.rs).
+export type PingKind = 'Look' | 'Attack' | 'Defend' | 'Danger';
+
+// Mirrors `GoldTransaction` (server/src/model/gold_ledger.rs).
+export interface GoldTransaction {
+    tick: number;
+    amount: number;
+    reason: 'Mining' | 'Bounty' | 'WaveReward' | 'Income' | 'PerfectClear' | 'Interest' | 'Refund'
+        | 'LeakPenalty' | 'Tower' | 'HireWorker' | 'WorkerUpgrade' | 'Send' | 'KingUpgrade';
+}
+
+// Mirrors `PlayerStats` (server/src/model/match_stats.rs).
+export interface PlayerStats {
+    player_id: number;
+    username: string;
+    damage_by_tower: { kind: UnitKind; damage: number }[];
+    creeps_killed: number;
+    leaks_per_wave: { wave: number; leaks: number }[];
+    gold_earned: {
+        workers: number;
+        bounties: number;
+        wave_reward: number;
+        income: number;
+        perfect_clear: number;
+        interest: number;
+        refunds: number;
+    };
+    gold_spent: { towers: number; workers: number; worker_upgrades: number; sends: number; king_upgrades: number; leak_penalties: number };
+    sends_made: number;
+}
 
 export interface BuildCatalogEntry {
     unit_kind: UnitKind;
     name: string;
     cost: number;
+    essence_cost: number;
 }
 
 export interface Unit {
@@ -27,13 +71,104 @@ export interface Player {
     id: number;
     username: string;
     gold: number;
+    essence: number;
     income: number;
     king_tier: number;
+    worker_tiers: Record<'speed' | 'mining_time' | 'carry', number>;
+    next_worker_cost: number;
     family: Family | null;
     spawning_queue: UnitKind[];
     next_send_costs: number[];
 }
 
+// Mirrors `GameError` (server/src/model/game_error.rs): `code` is the stable
+// variant name, extra fields depend on the code, `message` is display text.
+export type ServerError =
+    | { code: 'InsufficientGold'; needed: number; have: number; message: string }
+    | { code: 'InsufficientEssence'; needed: number; have: number; message: string }
+    | { code: 'WrongPhase'; required: string; message: string }
+    | { code: 'WorkerCap'; max: number; message: string }
+    | { code: 'ProtocolMismatch'; server: number; client: number; message: string }
+    | { code: 'RateLimited'; action: string; message: string }
+    | { code: 'Kicked'; reason: string; message: string }
+    | { code: 'InvalidMatchSettings'; reason: string; message: string }
+    | { code: 'QueuePenalty'; seconds_remaining: number; message: string }
+    | { code: 'InvalidChat'; reason: string; message: string }
+    | {
+          code:
+              | 'CellOccupied'
+              | 'InvalidPlacement'
+              | 'PathBlocked'
+              | 'FamilyNotPicked'
+              | 'FamilyAlreadyLocked'
+              | 'NotInFamily'
+              | 'MaxTier'
+              | 'KingNotFound'
+              | 'SessionReplaced'
+              | 'HandshakeRequired'
+              | 'TooManyInvalidMessages'
+              | 'ServerShuttingDown'
+              | 'AccountDeleted'
+              | 'SessionRevoked'
+              | 'AlreadyConnected'
+              | 'PrivateLobbyNotFound'
+              | 'PrivateLobbyFull'
+              | 'NotLobbyHost'
+              | 'NotInPrivateLobby'
+              | 'PrivateLobbyClosed'
+              | 'NotFriends'
+              | 'FriendOffline'
+              | 'FriendBusy'
+              | 'ChallengeNotFound'
+              | 'ChallengeDeclined'
+              | 'ChallengeExpired'
+              | 'InvalidPing'
+              | 'AlreadyPaused'
+              | 'NoPausesLeft'
+              | 'NotPaused'
+              | 'GamePaused'
+              | 'EssenceDisabled'
+              | 'WaitingForPlayers'
+              | 'MatchOver';
+          message: string;
+      };
+
+// Mirrors `MatchSettings` (server/src/model/match_settings.rs).
+export interface MatchSettings {
+    starting_gold: number;
+    build_time_secs: number;
+    starting_wave: number;
+    mode: GameMode;
+    interest_percent: number;
+    interest_cap: number;
+}
+
+export interface PrivateLobbyInfo {
+    code: string;
+    host: string;
+    guest: string | null;
+    host_ready: boolean;
+    guest_ready: boolean;
+    settings: MatchSettings;
+}
+
+// Mirrors `ChallengeInfo` (server/src/model/messages.rs).
+export interface ChallengeInfo {
+    challenge_id: number;
+    from_id: number;
+    from_username: string;
+    to_id: number;
+    to_username: string;
+    timeout_secs: number;
+}
+
+// Body of `GET /api/friends` (server/src/model/friend.rs).
+export interface FriendsList {
+    friends: { id: number; username: string; online: boolean; in_match: boolean }[];
+    incoming: { id: number; username: string }[];
+    outgoing: { id: number; username: string }[];
+}
+
 export interface Position {
     x: number;
     y: number;